@@ -0,0 +1,452 @@
+use std::borrow::Cow;
+use std::collections::hash_map::RandomState;
+use std::collections::{HashMap, HashSet};
+use std::hash::{BuildHasher, Hasher};
+use std::net::Ipv6Addr;
+use std::sync::{Arc, LazyLock, Mutex};
+
+use regex::{Captures, Regex};
+use siphasher::sip::SipHasher24;
+use tracing::warn;
+
+use crate::types::{decode_bom_text, ParsedSipMessage};
+
+static IPV4: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"\b(\d{1,3})\.(\d{1,3})\.(\d{1,3})\.(\d{1,3})\b").unwrap());
+/// Candidate IPv6 addresses: a run of address characters with a colon,
+/// checked by `Ipv6Addr` parsing before masking.
+static IPV6_CANDIDATE: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"[0-9A-Za-z.]*:[0-9A-Za-z:.]*").unwrap());
+static REPLACES_PARAM: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"(?i)[?&]Replaces=").unwrap());
+static URI_USER: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"(?i)\b(sips?:)([^@\s<>;,]+)@").unwrap());
+static TEL_URI: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"(?i)\b(tel:)([^\s<>;,]+)").unwrap());
+
+/// Rewrites messages so traces can be shared without customer data.
+///
+/// - digits in the user part of `sip:`/`sips:` URIs and in `tel:` URIs
+///   become `X`, keeping their length;
+/// - Call-IDs are replaced by a keyed hash, including those referenced by
+///   Replaces headers and `Replaces=` URI parameters;
+/// - IP addresses in the message address, request URI, headers (Via,
+///   Contact, Record-Route, ...) and bodies such as SDP or PIDF, even with
+///   Latin-1 bytes or in UTF-16, are replaced by addresses from
+///   `10.0.0.0/8` / `fd00::/8` derived from the same hash, so distinct
+///   hosts stay distinct. With only 24 bits for IPv4, two hosts may share
+///   a masked address; each such collision is logged.
+///
+/// The mapping depends only on the salt: the same salt gives the same output
+/// across files and runs, and without it the originals can't be recovered
+/// by hashing guesses. [`Anonymizer::random`] picks a salt nobody knows.
+#[derive(Clone)]
+pub struct Anonymizer {
+    hasher: SipHasher24,
+    ipv4: Arc<Mutex<Ipv4Seen>>,
+}
+
+/// Masked IPv4 addresses handed out, to notice two hosts sharing one.
+#[derive(Default)]
+struct Ipv4Seen {
+    /// Masked address to the hash of the first original seen for it.
+    first: HashMap<[u8; 3], u64>,
+    /// Hashes of the later originals that landed on a taken address.
+    collided: HashSet<u64>,
+}
+
+impl Anonymizer {
+    pub fn new(salt: &str) -> Self {
+        let k0 = SipHasher24::new_with_keys(0, 0).hash(salt.as_bytes());
+        let k1 = SipHasher24::new_with_keys(0, 1).hash(salt.as_bytes());
+        Self::with_keys(k0, k1)
+    }
+
+    /// Anonymizer keyed by a random salt: the mapping is consistent within
+    /// this instance (and its clones) but differs on every run.
+    pub fn random() -> Self {
+        let key = || RandomState::new().build_hasher().finish();
+        Self::with_keys(key(), key())
+    }
+
+    fn with_keys(k0: u64, k1: u64) -> Self {
+        Anonymizer {
+            hasher: SipHasher24::new_with_keys(k0, k1),
+            ipv4: Arc::default(),
+        }
+    }
+
+    fn hash(&self, value: &str) -> u64 {
+        self.hasher.hash(value.as_bytes())
+    }
+
+    /// Anonymized copy of `msg`; Content-Length follows body changes.
+    pub fn apply(&self, msg: &ParsedSipMessage) -> ParsedSipMessage {
+        let mut out = msg.clone();
+        out.address = self.mask_ips(&msg.address).into_owned();
+
+        if let crate::types::SipMessageType::Request { uri, .. } = &mut out.message_type {
+            *uri = self.mask_ips(&mask_uri_numbers(uri)).into_owned();
+        }
+
+        for (name, value) in &mut out.headers {
+            if name.eq_ignore_ascii_case("Call-ID") || name == "i" {
+                *value = self.mask_call_id(value);
+                continue;
+            }
+            if name.eq_ignore_ascii_case("Replaces") {
+                let (call_id, params) = value.split_once(';').unwrap_or((value, ""));
+                let sep = if params.is_empty() { "" } else { ";" };
+                *value = format!("{}{sep}{params}", self.mask_call_id(call_id.trim()));
+                continue;
+            }
+            let masked = self.mask_replaces_params(value);
+            *value = self.mask_ips(&mask_uri_numbers(&masked)).into_owned();
+        }
+
+        let masked = self.mask_body(&msg.body);
+        if masked.len() != msg.body.len() {
+            let length = masked.len().to_string();
+            for (name, value) in &mut out.headers {
+                if name.eq_ignore_ascii_case("Content-Length") || name == "l" {
+                    *value = length.clone();
+                }
+            }
+        }
+        out.body = masked;
+        out
+    }
+
+    /// Mask a body whatever its encoding: UTF-16 text (by its BOM) is
+    /// decoded and encoded back; anything else is masked run by run around
+    /// bytes that are not UTF-8, such as a Latin-1 `s=` line in SDP, which
+    /// are kept as they are.
+    fn mask_body(&self, body: &[u8]) -> Vec<u8> {
+        let utf16: Option<fn(u16) -> [u8; 2]> = match body.get(..2) {
+            Some(b"\xFF\xFE") => Some(u16::to_le_bytes),
+            Some(b"\xFE\xFF") => Some(u16::to_be_bytes),
+            _ => None,
+        };
+        let mask = |text: &str| self.mask_ips(&mask_uri_numbers(text)).into_owned();
+        let mut out = Vec::with_capacity(body.len());
+        match utf16 {
+            Some(encode) => {
+                out.extend_from_slice(&body[..2]);
+                let masked = mask(&decode_bom_text(body));
+                out.extend(masked.encode_utf16().flat_map(encode));
+            }
+            None => {
+                for chunk in body.utf8_chunks() {
+                    out.extend_from_slice(mask(chunk.valid()).as_bytes());
+                    out.extend_from_slice(chunk.invalid());
+                }
+            }
+        }
+        out
+    }
+
+    fn mask_call_id(&self, call_id: &str) -> String {
+        format!("{:016x}", self.hash(call_id))
+    }
+
+    /// Hash the Call-ID of each `Replaces=` URI parameter (as in Refer-To),
+    /// percent-decoded so it maps like the Call-ID header it refers to.
+    fn mask_replaces_params<'a>(&self, value: &'a str) -> Cow<'a, str> {
+        let mut out = String::new();
+        let mut rest = value;
+        while let Some(m) = REPLACES_PARAM.find(rest) {
+            let after = &rest[m.end()..];
+            let end = after.find(['&', '>', ';', '"', ' ']).unwrap_or(after.len());
+            let param = &after[..end];
+            let call_id_len = param
+                .to_ascii_lowercase()
+                .find("%3b")
+                .unwrap_or(param.len());
+            out.push_str(&rest[..m.end()]);
+            out.push_str(&self.mask_call_id(&percent_decode(&param[..call_id_len])));
+            rest = &after[call_id_len..];
+        }
+        if out.is_empty() {
+            return Cow::Borrowed(value);
+        }
+        out.push_str(rest);
+        Cow::Owned(out)
+    }
+
+    /// Replace IPv4 and IPv6 addresses, keeping ports, brackets and syntax.
+    pub fn mask_ips<'a>(&self, text: &'a str) -> Cow<'a, str> {
+        let text = IPV4.replace_all(text, |caps: &Captures| {
+            if caps
+                .iter()
+                .skip(1)
+                .flatten()
+                .any(|o| o.as_str().parse::<u8>().is_err())
+            {
+                return caps[0].to_string();
+            }
+            self.mask_ipv4(&caps[0])
+        });
+        match IPV6_CANDIDATE.replace_all(&text, |caps: &Captures| {
+            // A sentence may end right after an address
+            let candidate = caps[0].trim_end_matches('.');
+            match candidate.parse::<Ipv6Addr>() {
+                Ok(addr) => {
+                    let h = self.hash(&addr.to_string());
+                    let masked = format!(
+                        "fd00::{:x}:{:x}:{:x}:{:x}",
+                        h >> 48,
+                        (h >> 32) & 0xffff,
+                        (h >> 16) & 0xffff,
+                        h & 0xffff
+                    );
+                    format!("{masked}{}", &caps[0][candidate.len()..])
+                }
+                Err(_) => caps[0].to_string(),
+            }
+        }) {
+            Cow::Borrowed(_) => text,
+            Cow::Owned(s) => Cow::Owned(s),
+        }
+    }
+
+    fn mask_ipv4(&self, addr: &str) -> String {
+        let hash = self.hash(addr);
+        let h = hash.to_be_bytes();
+        let masked = format!("10.{}.{}.{}", h[0], h[1], h[2]);
+        let mut seen = self.ipv4.lock().unwrap_or_else(|e| e.into_inner());
+        let first = *seen.first.entry([h[0], h[1], h[2]]).or_insert(hash);
+        if first != hash && seen.collided.insert(hash) {
+            warn!(
+                masked,
+                "anonymized IPv4 collision: two addresses share this address"
+            );
+        }
+        masked
+    }
+
+    /// IPv4 addresses masked to an address already given to another one.
+    pub fn ipv4_collisions(&self) -> usize {
+        self.ipv4
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .collided
+            .len()
+    }
+}
+
+/// Decode `%XX` escapes; malformed escapes are kept as-is.
+fn percent_decode(s: &str) -> String {
+    let bytes = s.as_bytes();
+    let mut out = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        let hex = bytes
+            .get(i + 1..i + 3)
+            .and_then(|h| std::str::from_utf8(h).ok())
+            .and_then(|h| u8::from_str_radix(h, 16).ok());
+        match (bytes[i], hex) {
+            (b'%', Some(b)) => {
+                out.push(b);
+                i += 3;
+            }
+            (b, _) => {
+                out.push(b);
+                i += 1;
+            }
+        }
+    }
+    String::from_utf8_lossy(&out).into_owned()
+}
+
+/// Replace digits in URI user parts and `tel:` numbers with `X`.
+fn mask_uri_numbers(text: &str) -> Cow<'_, str> {
+    let mask = |caps: &Captures| {
+        let masked: String = caps[2]
+            .chars()
+            .map(|c| if c.is_ascii_digit() { 'X' } else { c })
+            .collect();
+        format!(
+            "{}{masked}{}",
+            &caps[1],
+            &caps[0][caps[1].len() + caps[2].len()..]
+        )
+    };
+    let text = URI_USER.replace_all(text, mask);
+    match TEL_URI.replace_all(&text, mask) {
+        Cow::Borrowed(_) => text,
+        Cow::Owned(s) => Cow::Owned(s),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::types::{Direction, SipMessage, Timestamp, Transport};
+
+    const INVITE: &[u8] = b"INVITE sip:15551234567@192.168.1.10:5060 SIP/2.0\r\n\
+        Via: SIP/2.0/UDP 192.168.1.20:5060;branch=z9hG4bK1;received=[2001:db8::1]\r\n\
+        From: \"Alice\" <sip:+15557654321@carrier.example>;tag=1\r\n\
+        To: <tel:+1-555-123-4567>\r\n\
+        Contact: <sip:alice@192.168.1.20:5060>\r\n\
+        Call-ID: abc123@192.168.1.20\r\n\
+        CSeq: 1 INVITE\r\n\
+        Content-Type: application/sdp\r\n\
+        Content-Length: 23\r\n\r\n\
+        c=IN IP4 192.168.1.20\r\n";
+
+    fn parsed(content: &[u8]) -> ParsedSipMessage {
+        SipMessage {
+            direction: Direction::Recv,
+            transport: Transport::Udp,
+            address: "192.168.1.20:5060".into(),
+            timestamp: Timestamp::TimeOnly {
+                hour: 12,
+                min: 0,
+                sec: 0,
+                usec: 0,
+            },
+            last_timestamp: Timestamp::TimeOnly {
+                hour: 12,
+                min: 0,
+                sec: 0,
+                usec: 0,
+            },
+            content: content.to_vec(),
+            frame_count: 1,
+        }
+        .parse()
+        .unwrap()
+    }
+
+    #[test]
+    fn masks_numbers_call_id_and_ips() {
+        let anon = Anonymizer::new("salt").apply(&parsed(INVITE));
+        let text = String::from_utf8(anon.to_bytes()).unwrap();
+        assert!(text.starts_with("INVITE sip:XXXXXXXXXXX@10."));
+        assert!(text.contains("<sip:+XXXXXXXXXXX@carrier.example>;tag=1"));
+        assert!(text.contains("<tel:+X-XXX-XXX-XXXX>"));
+        assert!(text.contains("received=[fd00::"));
+        assert!(!text.contains("192.168."));
+        assert!(!text.contains("abc123"));
+        assert!(!text.contains("2001:db8"));
+        assert!(anon.address.starts_with("10.") && anon.address.ends_with(":5060"));
+    }
+
+    #[test]
+    fn mapping_is_consistent() {
+        let a = Anonymizer::new("salt");
+        let first = a.apply(&parsed(INVITE));
+        let second = Anonymizer::new("salt").apply(&parsed(INVITE));
+        assert_eq!(first.to_bytes(), second.to_bytes());
+        // The same host maps to the same address in every place it appears
+        let host = first.address.trim_end_matches(":5060");
+        let via = first.headers.iter().find(|(k, _)| k == "Via").unwrap();
+        assert!(via.1.contains(host));
+        assert!(String::from_utf8_lossy(&first.body).contains(host));
+
+        let other = Anonymizer::new("pepper").apply(&parsed(INVITE));
+        assert_ne!(first.call_id(), other.call_id());
+    }
+
+    #[test]
+    fn output_stays_parseable() {
+        let anon = Anonymizer::new("").apply(&parsed(INVITE));
+        let reparsed = parsed(&anon.to_bytes());
+        assert_eq!(reparsed.call_id(), anon.call_id());
+        assert_eq!(reparsed.content_length(), Some(reparsed.body.len()));
+        assert_eq!(reparsed.method(), Some("INVITE"));
+    }
+
+    #[test]
+    fn masks_bodies_that_are_not_utf8() {
+        let a = Anonymizer::new("salt");
+        let sdp = b"v=0\r\ns=caf\xe9\r\nc=IN IP4 192.168.1.77\r\n";
+        let mut content = b"INVITE sip:a@host SIP/2.0\r\nCall-ID: x\r\n\r\n".to_vec();
+        content.extend_from_slice(sdp);
+        let body = a.apply(&parsed(&content)).body;
+        assert!(body.starts_with(b"v=0\r\ns=caf\xe9\r\nc=IN IP4 10."));
+        assert!(!String::from_utf8_lossy(&body).contains("192.168."));
+
+        // UTF-16 PIDF, by its BOM, is masked and kept UTF-16
+        let pidf: Vec<u8> = "<location>192.168.1.77</location>"
+            .encode_utf16()
+            .flat_map(u16::to_le_bytes)
+            .collect();
+        let masked = a.mask_body(&[&b"\xFF\xFE"[..], &pidf].concat());
+        let text = decode_bom_text(&masked);
+        assert!(text.starts_with("<location>10.") && !text.contains("192.168."));
+    }
+
+    #[test]
+    fn masks_unbracketed_ipv6() {
+        let a = Anonymizer::new("salt");
+        let sdp = "o=- 1 1 IN IP6 2001:db8::1\r\nc=IN IP6 2001:DB8:0:0::1\r\n";
+        let masked = a.mask_ips(sdp);
+        assert!(
+            !masked.to_ascii_lowercase().contains("2001:db8"),
+            "{masked}"
+        );
+        // Same address, however written, masks the same
+        let lines: Vec<_> = masked.lines().map(|l| l.rsplit(' ').next()).collect();
+        assert_eq!(lines[0], lines[1]);
+        let via = a.mask_ips("SIP/2.0/UDP [fe80::2]:5060;received=fe80::2;maddr=::1.");
+        assert!(!via.contains("fe80") && !via.contains("::1."), "{via}");
+        assert!(
+            via.starts_with("SIP/2.0/UDP [fd00::") && via.ends_with("."),
+            "{via}"
+        );
+        assert_eq!(
+            a.mask_ips("12:00:00 a:b sip:alice"),
+            "12:00:00 a:b sip:alice"
+        );
+    }
+
+    #[test]
+    fn hashes_call_ids_in_replaces() {
+        let content = b"REFER sip:bob@host SIP/2.0\r\n\
+            Call-ID: other@host\r\n\
+            Refer-To: <sip:carol@host?Replaces=abc123%40192.168.1.20%3Bto-tag%3D1%3Bfrom-tag%3D2>\r\n\
+            Replaces: abc123@192.168.1.20;to-tag=1;from-tag=2\r\n\r\n";
+        let a = Anonymizer::new("salt");
+        let call_id = a.apply(&parsed(INVITE)).call_id().unwrap().to_string();
+        let refer = a.apply(&parsed(content));
+        let header = |name: &str| {
+            refer
+                .headers
+                .iter()
+                .find(|(k, _)| k == name)
+                .unwrap()
+                .1
+                .clone()
+        };
+        assert_eq!(header("Replaces"), format!("{call_id};to-tag=1;from-tag=2"));
+        assert_eq!(
+            header("Refer-To"),
+            format!("<sip:carol@host?Replaces={call_id}%3Bto-tag%3D1%3Bfrom-tag%3D2>")
+        );
+    }
+
+    #[test]
+    fn reports_ipv4_collisions() {
+        let a = Anonymizer::new("salt");
+        // 2^16 addresses into a 2^24 space collide with near certainty
+        for i in 0..=u16::MAX {
+            a.mask_ips(&format!("172.16.{}.{}", i >> 8, i & 0xff));
+        }
+        assert!(a.ipv4_collisions() > 0);
+        assert_eq!(Anonymizer::new("salt").ipv4_collisions(), 0);
+    }
+
+    #[test]
+    fn random_salts_differ() {
+        let (a, b) = (Anonymizer::random(), Anonymizer::random());
+        assert_ne!(a.mask_ips("192.168.1.1"), b.mask_ips("192.168.1.1"));
+    }
+
+    #[test]
+    fn ignores_non_addresses() {
+        let a = Anonymizer::new("salt");
+        assert_eq!(a.mask_ips("version 1.2.3.400"), "version 1.2.3.400");
+        assert_eq!(mask_uri_numbers("Max-Forwards: 70"), "Max-Forwards: 70");
+    }
+}
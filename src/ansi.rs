@@ -0,0 +1,144 @@
+use std::io::Read;
+
+const ESC: u8 = 0x1B;
+
+const READ_CHUNK: usize = 8 * 1024;
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum State {
+    Text,
+    /// After an ESC that may start a CSI sequence.
+    Escape,
+    /// Inside `ESC [`, up to the final byte.
+    Csi,
+}
+
+/// Removes ANSI CSI escape sequences (`ESC [ ... m`, `ESC [ K`, ...) from the
+/// byte stream, such as those `grep --color=always` wraps around matches and
+/// prefixes. Sequences split across reads are handled; an ESC not followed by
+/// `[` passes through unchanged.
+pub struct AnsiStripFilter<R> {
+    inner: R,
+    buf: Vec<u8>,
+    pos: usize,
+    state: State,
+}
+
+impl<R: Read> AnsiStripFilter<R> {
+    pub fn new(reader: R) -> Self {
+        Self {
+            inner: reader,
+            buf: Vec::new(),
+            pos: 0,
+            state: State::Text,
+        }
+    }
+
+    fn strip(&mut self, data: &[u8]) {
+        for &b in data {
+            match self.state {
+                State::Text if b == ESC => self.state = State::Escape,
+                State::Text => self.buf.push(b),
+                State::Escape if b == b'[' => self.state = State::Csi,
+                // Not a CSI sequence: keep the ESC, which may start another
+                State::Escape if b == ESC => self.buf.push(ESC),
+                State::Escape => {
+                    self.buf.extend_from_slice(&[ESC, b]);
+                    self.state = State::Text;
+                }
+                // Parameter and intermediate bytes
+                State::Csi if (0x20..=0x3F).contains(&b) => {}
+                State::Csi if (0x40..=0x7E).contains(&b) => self.state = State::Text,
+                // Malformed sequence: drop what was read of it, keep the byte
+                State::Csi => {
+                    self.buf.push(b);
+                    self.state = State::Text;
+                }
+            }
+        }
+    }
+}
+
+impl<R: Read> Read for AnsiStripFilter<R> {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        if self.pos == self.buf.len() {
+            self.buf.clear();
+            self.pos = 0;
+            let mut chunk = [0u8; READ_CHUNK];
+            while self.buf.is_empty() {
+                let n = self.inner.read(&mut chunk)?;
+                if n == 0 {
+                    if self.state == State::Escape {
+                        self.buf.push(ESC);
+                    }
+                    self.state = State::Text;
+                    break;
+                }
+                self.strip(&chunk[..n]);
+            }
+        }
+
+        let available = &self.buf[self.pos..];
+        let n = buf.len().min(available.len());
+        buf[..n].copy_from_slice(&available[..n]);
+        self.pos += n;
+        Ok(n)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn strip(input: &[u8]) -> Vec<u8> {
+        let mut out = Vec::new();
+        AnsiStripFilter::new(input).read_to_end(&mut out).unwrap();
+        out
+    }
+
+    #[test]
+    fn strip_colored_header_line() {
+        // grep --color=always -n: colored path, line number, separators and match
+        let input = b"\x1b[35m\x1b[Ka.dump\x1b[m\x1b[K\x1b[36m\x1b[K:\x1b[m\x1b[K\
+            \x1b[32m\x1b[K12\x1b[m\x1b[K\x1b[36m\x1b[K:\x1b[m\x1b[K\
+            \x1b[01;31m\x1b[Krecv\x1b[m\x1b[K 4 bytes from udp/1.1.1.1:5060 at 00:00:00.000000:\n";
+        assert_eq!(
+            strip(input),
+            b"a.dump:12:recv 4 bytes from udp/1.1.1.1:5060 at 00:00:00.000000:\n"
+        );
+    }
+
+    #[test]
+    fn plain_input_unchanged() {
+        let input = b"INVITE sip:a@b SIP/2.0\r\nSubject: [test] m\r\n\x0B\n";
+        assert_eq!(strip(input), input);
+    }
+
+    #[test]
+    fn lone_escape_kept() {
+        assert_eq!(strip(b"a\x1bb"), b"a\x1bb");
+        assert_eq!(strip(b"a\x1b\x1b[mb"), b"a\x1bb");
+        assert_eq!(strip(b"a\x1b"), b"a\x1b");
+    }
+
+    /// Hands out one byte per `read`, like a slow pipe.
+    struct OneByte<'a>(&'a [u8]);
+
+    impl Read for OneByte<'_> {
+        fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+            let n = buf.len().min(self.0.len()).min(1);
+            buf[..n].copy_from_slice(&self.0[..n]);
+            self.0 = &self.0[n..];
+            Ok(n)
+        }
+    }
+
+    #[test]
+    fn sequence_split_across_reads() {
+        let mut out = Vec::new();
+        AnsiStripFilter::new(OneByte(b"\x1b[01;31mrecv\x1b[m 4"))
+            .read_to_end(&mut out)
+            .unwrap();
+        assert_eq!(out, b"recv 4");
+    }
+}
@@ -1,22 +1,130 @@
-use std::collections::HashMap;
+use std::borrow::Cow;
+use std::cell::RefCell;
+use std::collections::{BTreeMap, HashMap, HashSet, VecDeque};
 use std::fs::File;
-use std::io::{self, Read};
+use std::io::{self, IsTerminal, Read, Write};
+use std::net::{IpAddr, SocketAddr, TcpStream};
+use std::path::{Path, PathBuf};
 use std::process;
+use std::rc::Rc;
+use std::time::{Duration, Instant};
 
-use clap::Parser;
+use clap::{Parser, ValueEnum};
+use memchr::memmem;
 use regex::Regex;
-use tracing::info;
+use tracing::{debug, info};
 
-use freeswitch_sofia_trace_parser::types::{Direction, SipMessageType};
+use freeswitch_sofia_trace_parser::anonymize::Anonymizer;
+use freeswitch_sofia_trace_parser::message::{
+    RecentMessages, DEFAULT_DEDUP_WINDOW, DEFAULT_MAX_MESSAGE_SIZE, DEFAULT_REORDER_WINDOW,
+};
+use freeswitch_sofia_trace_parser::types::{
+    decode_bom_text, ipv4_mapped, split_address, Direction, Frame, SipMessageType, Timestamp,
+    Transport,
+};
 use freeswitch_sofia_trace_parser::{
-    FrameIterator, GrepFilter, MessageIterator, ParsedMessageIterator, ParsedSipMessage, SipMessage,
+    AnsiStripFilter, FrameIterator, GrepFilter, MessageFilter, MessageIterator, ParseError,
+    ParseOptions, ParsedSipMessage, PcapFrameIterator, SipMessage,
 };
 
 enum OutputMode {
     Summary,
     Full,
-    Headers,
-    Body,
+    /// Start line and headers; values longer than `wrap` columns are folded.
+    Headers {
+        wrap: Option<usize>,
+    },
+    Body {
+        part: Option<String>,
+    },
+    ExportDump,
+    Hex,
+    Csv,
+    /// Each match of `re` in the body (`--body-grep`) or the whole message
+    /// (`--grep`), one per line.
+    OnlyMatching {
+        re: Regex,
+        body: bool,
+    },
+}
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Limit {
+    None,
+    Head(usize),
+    Tail(usize),
+}
+
+struct OutputOptions {
+    mode: OutputMode,
+    color: bool,
+    limit: Limit,
+    keepalives: bool,
+    canonical_reason: bool,
+    tz_offset: Option<i32>,
+    swap_direction: bool,
+    anonymizer: Option<Anonymizer>,
+    replace_headers: Vec<(String, String)>,
+    format: Option<SummaryTemplate>,
+    align: bool,
+    show_frames: bool,
+    show_bytes: bool,
+}
+
+/// A `{...}` placeholder of a `--format` template.
+#[derive(Clone, Debug, PartialEq)]
+enum Placeholder {
+    Timestamp,
+    Direction,
+    Transport,
+    Address,
+    Method,
+    Status,
+    CallId,
+    CSeq,
+    Uri,
+    Header(String),
+}
+
+#[derive(Clone, Debug, PartialEq)]
+enum TemplatePart {
+    Literal(String),
+    Field(Placeholder),
+}
+
+/// `--format` summary line, parsed once at startup.
+#[derive(Clone, Debug, PartialEq)]
+struct SummaryTemplate(Vec<TemplatePart>);
+
+#[derive(Clone, Copy, PartialEq, Eq, ValueEnum)]
+enum InputFormat {
+    Dump,
+    Pcap,
+}
+
+/// `--pcap-local` endpoint; either part may be left out.
+#[derive(Clone, Copy)]
+struct LocalEndpoint {
+    host: Option<IpAddr>,
+    port: Option<u16>,
+}
+
+/// `--dialog-order`: how `-D` output orders messages of different dialogs.
+#[derive(Clone, Copy, PartialEq, Eq, ValueEnum)]
+enum DialogOrder {
+    /// All messages by timestamp, interleaving dialogs
+    Timestamp,
+    /// All messages in the order they were read
+    Input,
+    /// Each dialog's messages together, dialogs by first timestamp
+    Grouped,
+}
+
+#[derive(Clone, Copy, ValueEnum)]
+enum ColorChoice {
+    Auto,
+    Always,
+    Never,
 }
 
 #[derive(Parser)]
@@ -25,10 +133,12 @@ enum OutputMode {
     about = "Parse and filter FreeSWITCH mod_sofia SIP trace dump files"
 )]
 struct Cli {
-    /// Dump files to parse (- for stdin, default: stdin)
+    /// Dump files to parse (- for stdin, default: stdin); tcp://HOST:PORT
+    /// reads from a socket until it closes
     files: Vec<String>,
 
-    /// Include SIP method (request + responses via CSeq), repeatable
+    /// Include SIP method (request + responses via CSeq), repeatable; -m OPTIONS
+    /// also lifts the default OPTIONS exclusion
     #[arg(short, long = "method", value_name = "VERB")]
     method: Vec<String>,
 
@@ -40,6 +150,18 @@ struct Cli {
     #[arg(short = 'c', long = "call-id", value_name = "REGEX")]
     call_id: Option<String>,
 
+    /// Only match requests
+    #[arg(long = "requests-only", conflicts_with = "responses_only")]
+    requests_only: bool,
+
+    /// Only match responses
+    #[arg(long = "responses-only")]
+    responses_only: bool,
+
+    /// Drop messages (whole dialogs with -D) whose Call-ID matches, repeatable
+    #[arg(long = "exclude-call-id", value_name = "REGEX")]
+    exclude_call_id: Vec<String>,
+
     /// Filter by direction (recv/sent)
     #[arg(short, long, value_name = "DIR")]
     direction: Option<String>,
@@ -48,14 +170,55 @@ struct Cli {
     #[arg(short, long, value_name = "REGEX")]
     address: Option<String>,
 
+    /// Drop messages (whole dialogs with -D) whose address matches, repeatable
+    #[arg(long = "exclude-address", value_name = "REGEX")]
+    exclude_address: Vec<String>,
+
+    /// Match address host exactly (IPv6 with or without brackets)
+    #[arg(long, value_name = "HOST")]
+    host: Option<String>,
+
+    /// Match address port exactly
+    #[arg(long, value_name = "N")]
+    port: Option<u16>,
+
+    /// Match the Q.850 cause code of the Reason header, e.g. 16 (normal clearing)
+    #[arg(long, value_name = "N")]
+    cause: Option<u16>,
+
+    /// Match the realm of an authentication challenge or credentials by regex
+    #[arg(long, value_name = "REGEX")]
+    realm: Option<String>,
+
+    /// Match messages reassembled from at least N frames (UDP is always 1)
+    #[arg(long = "min-frames", value_name = "N")]
+    min_frames: Option<usize>,
+
     /// Match header value by regex (NAME=REGEX), repeatable
     #[arg(short = 'H', long = "header", value_name = "NAME=REGEX")]
     header: Vec<String>,
 
+    /// Require a header to be present (case-insensitive name), repeatable
+    #[arg(long = "has-header", value_name = "NAME")]
+    has_header: Vec<String>,
+
+    /// Require a header to be absent (case-insensitive name), repeatable
+    #[arg(long = "missing-header", value_name = "NAME")]
+    missing_header: Vec<String>,
+
     /// Match regex against message body
     #[arg(short = 'b', long = "body-grep", value_name = "REGEX")]
     body_grep: Option<String>,
 
+    /// Match regex against each decoded multipart part (base64 undone)
+    #[arg(long = "part-grep", value_name = "REGEX")]
+    part_grep: Option<String>,
+
+    /// Match Content-Type media type by substring (case-insensitive),
+    /// including the type of any multipart part, e.g. `pidf` or `sdp`
+    #[arg(long = "body-type", value_name = "SUBSTR")]
+    body_type: Option<String>,
+
     /// Match regex against full reconstructed SIP message
     #[arg(short = 'g', long = "grep", value_name = "REGEX")]
     grep: Option<String>,
@@ -64,8 +227,47 @@ struct Cli {
     #[arg(short = 'D', long = "dialog")]
     dialog: bool,
 
-    /// Include OPTIONS messages (excluded by default)
-    #[arg(long = "all-methods")]
+    /// With --dialog, keep OPTIONS sharing a Call-ID with a matched dialog
+    #[arg(long = "keep-options-in-dialog", requires = "dialog")]
+    keep_options_in_dialog: bool,
+
+    /// With --dialog, order output by timestamp, input order, or grouped per dialog
+    #[arg(
+        long = "dialog-order",
+        value_enum,
+        value_name = "ORDER",
+        default_value_t = DialogOrder::Timestamp,
+        requires = "dialog"
+    )]
+    dialog_order: DialogOrder,
+
+    /// Write each matched dialog to DIR/<Call-ID>.dump in dump format (implies --dialog)
+    #[arg(
+        long = "split-by-call-id",
+        value_name = "DIR",
+        conflicts_with_all = ["full", "headers", "body", "raw", "frames", "export_dump", "hex", "stats", "validate", "list_call_ids", "verify_roundtrip", "format", "align", "keepalives"]
+    )]
+    split_by_call_id: Option<PathBuf>,
+
+    /// Suppress UDP retransmissions (same fingerprint and direction within 32s)
+    #[arg(long)]
+    dedup: bool,
+
+    /// Drop messages repeated by overlapping rotated dumps (same time, direction,
+    /// address and leading bytes)
+    #[arg(long = "dedup-frames")]
+    dedup_frames: bool,
+
+    /// Only output the first matched message of each Call-ID
+    #[arg(long = "first-per-call-id")]
+    first_per_call_id: bool,
+
+    /// Show CRLF keepalive pings (dropped by default)
+    #[arg(long)]
+    keepalives: bool,
+
+    /// Include OPTIONS messages, excluded by default (-m OPTIONS includes them too)
+    #[arg(long = "all-methods", visible_alias = "include-options")]
     all_methods: bool,
 
     /// Show full SIP message content
@@ -80,6 +282,15 @@ struct Cli {
     #[arg(long, group = "output_mode")]
     body: bool,
 
+    /// With --headers, fold header values to fit COLS columns, continuing on
+    /// indented lines (display only)
+    #[arg(long, value_name = "COLS", requires = "headers", value_parser = clap::value_parser!(u16).range(20..))]
+    wrap: Option<u16>,
+
+    /// With --body, print only the multipart part whose Content-Type contains this
+    #[arg(long, value_name = "CONTENT-TYPE", requires = "body")]
+    part: Option<String>,
+
     /// Show raw reassembled bytes (level 2)
     #[arg(long, group = "output_mode")]
     raw: bool,
@@ -88,41 +299,382 @@ struct Cli {
     #[arg(long, group = "output_mode")]
     frames: bool,
 
+    /// With --frames, also hexdump the N bytes following each frame's boundary
+    #[arg(long = "with-trailer", value_name = "N", requires = "frames")]
+    with_trailer: Option<usize>,
+
+    /// Write matched messages back in dump format (re-parseable)
+    #[arg(long = "export-dump", group = "output_mode")]
+    export_dump: bool,
+
+    /// Hexdump each matched message (or frame/message with --frames/--raw)
+    #[arg(
+        long,
+        conflicts_with_all = ["full", "headers", "body", "export_dump", "stats", "validate", "list_call_ids"]
+    )]
+    hex: bool,
+
+    /// One CSV row per matched message, after a header row
+    #[arg(
+        long,
+        group = "output_mode",
+        conflicts_with_all = ["hex", "list_call_ids", "verify_roundtrip", "split_by_call_id", "format", "align", "show_frames", "show_bytes", "keepalives"]
+    )]
+    csv: bool,
+
+    /// Print only the --body-grep (or --grep) matches, one per line after the timestamp
+    #[arg(
+        long = "only-matching",
+        group = "output_mode",
+        conflicts_with_all = ["hex", "list_call_ids", "verify_roundtrip", "split_by_call_id", "format", "align", "show_frames", "show_bytes", "keepalives"]
+    )]
+    only_matching: bool,
+
     /// Show statistics summary
     #[arg(long, group = "output_mode")]
     stats: bool,
 
+    /// With --stats, add a message size histogram (power-of-two buckets, or WIDTH bytes)
+    #[arg(
+        long = "size-histogram",
+        value_name = "WIDTH",
+        num_args = 0..=1,
+        require_equals = true,
+        requires = "stats",
+        value_parser = clap::value_parser!(u64).range(1..)
+    )]
+    size_histogram: Option<Option<u64>>,
+
+    /// With --stats, count messages whose Via stack repeats a branch or lists
+    /// a sent-by host more than N times (default 2)
+    #[arg(
+        long,
+        value_name = "N",
+        num_args = 0..=1,
+        require_equals = true,
+        requires = "stats"
+    )]
+    loops: Option<Option<usize>>,
+
+    /// With --stats, print a single JSON object instead of text
+    #[arg(long, requires = "stats", conflicts_with = "size_histogram")]
+    json: bool,
+
+    /// Print the JSON Schema of the --stats --json object and exit
+    #[arg(long = "print-json-schema", hide = true)]
+    print_json_schema: bool,
+
+    /// Only print the distinct Call-IDs of matched messages, in first-seen order
+    #[arg(
+        long = "list-call-ids",
+        alias = "unique-call-ids",
+        conflicts_with_all = ["full", "headers", "body", "raw", "frames", "export_dump", "validate"]
+    )]
+    list_call_ids: bool,
+
+    /// Check messages against RFC 3261 rules, exit 1 if any fail
+    #[arg(long, group = "output_mode")]
+    validate: bool,
+
+    /// Report messages whose rebuilt form differs from the dump bytes, exit 1 if any
+    /// (with --stats, only count them)
+    #[arg(
+        long = "verify-roundtrip",
+        conflicts_with_all = [
+            "full", "headers", "body", "raw", "frames", "export_dump", "hex", "validate",
+            "list_call_ids", "dialog", "anonymize", "keepalives", "head", "tail"
+        ]
+    )]
+    verify_roundtrip: bool,
+
+    /// Stop after N matched messages (dialogs with -D)
+    #[arg(long, value_name = "N", conflicts_with = "tail")]
+    head: Option<usize>,
+
+    /// Only output the last N matched messages (dialogs with -D)
+    #[arg(long, value_name = "N")]
+    tail: Option<usize>,
+
+    /// Write output to FILE instead of stdout
+    #[arg(short, long, value_name = "FILE")]
+    output: Option<String>,
+
+    /// Print nothing; exit 0 when a message matches (stopping there), 1 when none does
+    #[arg(
+        short,
+        long,
+        conflicts_with_all = ["raw", "frames", "stats", "list_call_ids", "validate", "verify_roundtrip", "split_by_call_id", "output"]
+    )]
+    quiet: bool,
+
+    /// Summary line template: {timestamp} {direction} {transport} {address} {method} {status} {call_id} {cseq} {uri} {header:NAME}
+    #[arg(
+        long,
+        value_name = "TEMPLATE",
+        value_parser = parse_summary_template,
+        conflicts_with_all = ["full", "headers", "body", "raw", "frames", "export_dump", "hex", "stats", "validate", "list_call_ids", "verify_roundtrip"]
+    )]
+    format: Option<SummaryTemplate>,
+
+    /// Pad summary columns to line up (buffers all output until the end of input)
+    #[arg(
+        long,
+        conflicts_with_all = ["full", "headers", "body", "raw", "frames", "export_dump", "hex", "stats", "validate", "list_call_ids", "verify_roundtrip", "format"]
+    )]
+    align: bool,
+
+    /// Append a frames=N column with the number of frames each message was reassembled from
+    #[arg(
+        long = "show-frames",
+        alias = "show-frame-count",
+        conflicts_with_all = ["full", "headers", "body", "raw", "frames", "export_dump", "hex", "stats", "validate", "list_call_ids", "verify_roundtrip", "format"]
+    )]
+    show_frames: bool,
+
+    /// Append a bytes=N column with each message's reassembled size
+    #[arg(
+        long = "show-bytes",
+        conflicts_with_all = ["full", "headers", "body", "raw", "frames", "export_dump", "hex", "stats", "validate", "list_call_ids", "verify_roundtrip", "format"]
+    )]
+    show_bytes: bool,
+
+    /// Colorize direction and response codes
+    #[arg(long, value_enum, value_name = "WHEN", default_value = "auto")]
+    color: ColorChoice,
+
+    /// Mask phone numbers, Call-IDs and IP addresses in the output
+    #[arg(long)]
+    anonymize: bool,
+
+    /// Secret that keys the --anonymize mapping (same salt, same mapping);
+    /// without it a random salt is used, so each run maps differently
+    #[arg(long = "anonymize-salt", value_name = "SALT", requires = "anonymize")]
+    anonymize_salt: Option<String>,
+
+    /// Rewrite every NAME header (case-insensitive, compact forms included)
+    /// present in the output to VALUE, in place, repeatable
+    #[arg(
+        long = "replace-header",
+        value_name = "NAME=VALUE",
+        conflicts_with_all = ["raw", "frames"]
+    )]
+    replace_header: Vec<String>,
+
+    /// Replace response reasons with the RFC 3261 phrase for known codes
+    #[arg(long = "canonical-reason")]
+    canonical_reason: bool,
+
+    /// Shift displayed timestamps by a fixed offset, e.g. -04:00 (no DST handling)
+    #[arg(
+        long = "tz-offset",
+        value_name = "±HH:MM",
+        value_parser = parse_tz_offset,
+        allow_hyphen_values = true
+    )]
+    tz_offset: Option<i32>,
+
+    /// Print recv as sent and from as to (and back), reading the trace from the
+    /// remote side; -d still filters on the dump's own direction
+    #[arg(long = "swap-direction")]
+    swap_direction: bool,
+
+    /// Stop waiting for a TCP message body once headers plus Content-Length exceed BYTES
+    #[arg(long = "max-message-size", value_name = "BYTES", default_value_t = DEFAULT_MAX_MESSAGE_SIZE)]
+    max_message_size: usize,
+
+    /// Warn on stderr when consecutive frames on one connection are more than SECONDS apart
+    #[arg(long = "gap-warn", value_name = "SECONDS", value_parser = parse_seconds)]
+    gap_warn: Option<Duration>,
+
+    /// Order messages by first frame instead of completion, holding them up to SECS (default 10)
+    #[arg(
+        long = "order-by-start",
+        alias = "deinterleave",
+        value_name = "SECS",
+        num_args = 0..=1,
+        require_equals = true
+    )]
+    order_by_start: Option<Option<u64>>,
+
+    /// Stamp reassembled TCP messages with their first frame's time (default)
+    #[arg(
+        long = "first-frame-timestamp",
+        overrides_with = "last_frame_timestamp"
+    )]
+    first_frame_timestamp: bool,
+
+    /// Stamp reassembled TCP messages with their last frame's time, when they fully arrived
+    #[arg(
+        long = "last-frame-timestamp",
+        overrides_with = "first_frame_timestamp"
+    )]
+    last_frame_timestamp: bool,
+
+    /// Decode WebSocket frame headers logged in WSS content, splitting messages
+    /// on WebSocket lengths (most dumps log decoded text; leave off for those)
+    #[arg(long = "ws-framing")]
+    ws_framing: bool,
+
+    /// Treat messages that break RFC 3261 syntax (e.g. status code outside
+    /// 100-699) as parse errors instead of showing them
+    #[arg(long)]
+    strict: bool,
+
+    /// Input file format
+    #[arg(long = "input-format", value_name = "FORMAT", value_enum, default_value_t = InputFormat::Dump)]
+    input_format: InputFormat,
+
+    /// With --input-format pcap, read SIP on this UDP/TCP port, repeatable (default: 5060)
+    #[arg(long = "pcap-port", value_name = "N")]
+    pcap_port: Vec<u16>,
+
+    /// With --input-format pcap, packets from HOST[:PORT] (or :PORT) are sent, others received
+    #[arg(long = "pcap-local", value_name = "ADDR", value_parser = parse_local_endpoint)]
+    pcap_local: Option<LocalEndpoint>,
+
+    /// Reassemble each input file on its own and interleave their messages by
+    /// timestamp, instead of reading the files one after the other
+    #[arg(long, conflicts_with = "frames")]
+    merge: bool,
+
+    /// Log bytes read and frames seen to stderr every second
+    #[arg(long)]
+    progress: bool,
+
+    /// Remove ANSI color codes from dump input, e.g. from `grep --color=always`
+    #[arg(long = "strip-ansi")]
+    strip_ansi: bool,
+
+    /// Remove `grep -n`/`grep -rn` line prefixes (`path:NNN:`, `path-NNN-`) from dump input
+    #[arg(long = "strip-grep-prefix")]
+    strip_grep_prefix: bool,
+
     /// Increase verbosity (-v info, -vv debug, -vvv trace)
     #[arg(short, long, action = clap::ArgAction::Count)]
     verbose: u8,
 }
 
 struct CompiledFilters {
-    methods: Vec<String>,
-    excludes: Vec<String>,
-    exclude_options: bool,
+    method_filter: MessageFilter,
     call_id: Option<Regex>,
+    exclude_call_ids: Vec<Regex>,
+    requests_only: bool,
+    responses_only: bool,
     direction: Option<Direction>,
     address: Option<Regex>,
+    exclude_addresses: Vec<Regex>,
+    host: Option<String>,
+    port: Option<u16>,
+    min_frames: Option<usize>,
+    cause: Option<u16>,
+    realm: Option<Regex>,
     headers: Vec<(String, Regex)>,
+    has_headers: Vec<String>,
+    missing_headers: Vec<String>,
     body_grep: Option<Regex>,
+    part_grep: Option<Regex>,
+    body_type: Option<String>,
     grep: Option<Regex>,
+    strict: bool,
 }
 
 impl CompiledFilters {
+    fn is_options_excluded(&self, msg: &ParsedSipMessage) -> bool {
+        msg.method()
+            .is_some_and(|m| self.method_filter.is_options_excluded(m))
+    }
+
+    fn is_method_excluded(&self, msg: &ParsedSipMessage) -> bool {
+        self.method_filter
+            .is_method_excluded(msg.method().unwrap_or(""))
+    }
+
+    fn is_call_id_excluded(&self, call_id: &str) -> bool {
+        self.exclude_call_ids.iter().any(|re| re.is_match(call_id))
+    }
+
+    fn is_address_excluded(&self, address: &str) -> bool {
+        self.exclude_addresses.iter().any(|re| re.is_match(address))
+    }
+
     fn is_excluded(&self, msg: &ParsedSipMessage) -> bool {
-        let method = msg.method().unwrap_or("");
+        self.is_options_excluded(msg)
+            || self.is_method_excluded(msg)
+            || msg
+                .call_id()
+                .is_some_and(|cid| self.is_call_id_excluded(cid))
+    }
 
-        if self.exclude_options && method.eq_ignore_ascii_case("OPTIONS") {
-            return true;
+    fn matches_endpoint(&self, direction: Direction, address: &str) -> bool {
+        if let Some(dir) = self.direction {
+            if direction != dir {
+                return false;
+            }
         }
 
-        if !self.excludes.is_empty() && self.excludes.iter().any(|m| m.eq_ignore_ascii_case(method))
-        {
+        if let Some(ref re) = self.address {
+            if !re.is_match(address) {
+                return false;
+            }
+        }
+        if self.is_address_excluded(address) {
+            return false;
+        }
+
+        if self.host.is_some() || self.port.is_some() {
+            let Some((host, port)) = split_address(address) else {
+                return false;
+            };
+            // An IPv4 --host also matches its IPv4-mapped IPv6 form
+            let mapped = ipv4_mapped(host).map(|v4| v4.to_string());
+            if self.host.as_ref().is_some_and(|h| {
+                !h.eq_ignore_ascii_case(host) && mapped.as_deref() != Some(h.as_str())
+            }) {
+                return false;
+            }
+            if self.port.is_some_and(|p| p != port) {
+                return false;
+            }
+        }
+
+        true
+    }
+
+    /// Reject on method, message type and endpoint from the raw message,
+    /// before paying for a full parse. Never rejects a message `matches`
+    /// would accept; header, value and body filters still need the parse.
+    fn rejects_unparsed(&self, msg: &SipMessage) -> bool {
+        if !self.matches_endpoint(msg.direction, &msg.address) {
+            return true;
+        }
+        if self.min_frames.is_some_and(|n| msg.frame_count < n) {
             return true;
         }
+        let is_response = msg.peek_is_response();
+        if (self.requests_only && is_response) || (self.responses_only && !is_response) {
+            return true;
+        }
+        if self.method_filter.is_pass_all() {
+            return false;
+        }
+        !self
+            .method_filter
+            .matches_method(msg.peek_method().unwrap_or(""))
+    }
+
+    /// Whether any filter looks at the message body.
+    fn needs_body(&self) -> bool {
+        self.body_grep.is_some()
+            || self.part_grep.is_some()
+            || self.body_type.is_some()
+            || self.grep.is_some()
+    }
 
-        false
+    fn parse_options(&self) -> ParseOptions {
+        ParseOptions {
+            strict: self.strict,
+            ..ParseOptions::default()
+        }
     }
 
     fn matches(&self, msg: &ParsedSipMessage) -> bool {
@@ -130,11 +682,18 @@ impl CompiledFilters {
             return false;
         }
 
-        if !self.methods.is_empty() {
-            let method = msg.method().unwrap_or("");
-            if !self.methods.iter().any(|m| m.eq_ignore_ascii_case(method)) {
-                return false;
-            }
+        if !self
+            .method_filter
+            .matches_method(msg.method().unwrap_or(""))
+        {
+            return false;
+        }
+
+        if self.requests_only && !msg.is_request() {
+            return false;
+        }
+        if self.responses_only && !msg.is_response() {
+            return false;
         }
 
         if let Some(ref re) = self.call_id {
@@ -144,14 +703,24 @@ impl CompiledFilters {
             }
         }
 
-        if let Some(dir) = self.direction {
-            if msg.direction != dir {
-                return false;
-            }
+        if !self.matches_endpoint(msg.direction, &msg.address) {
+            return false;
         }
 
-        if let Some(ref re) = self.address {
-            if !re.is_match(&msg.address) {
+        if self.min_frames.is_some_and(|n| msg.frame_count < n) {
+            return false;
+        }
+
+        if self.cause.is_some() && msg.q850_cause() != self.cause {
+            return false;
+        }
+
+        if let Some(ref re) = self.realm {
+            let mut auth = msg
+                .auth_challenges()
+                .into_iter()
+                .chain(msg.auth_credentials());
+            if !auth.any(|a| a.realm().is_some_and(|r| re.is_match(r))) {
                 return false;
             }
         }
@@ -167,6 +736,18 @@ impl CompiledFilters {
             }
         }
 
+        let has = |name: &String| {
+            msg.headers
+                .iter()
+                .any(|(k, _)| k.eq_ignore_ascii_case(name))
+        };
+        if !self.has_headers.iter().all(has) {
+            return false;
+        }
+        if self.missing_headers.iter().any(has) {
+            return false;
+        }
+
         if let Some(ref re) = self.body_grep {
             let body_str = msg.body_text();
             if !re.is_match(&body_str) {
@@ -174,6 +755,18 @@ impl CompiledFilters {
             }
         }
 
+        if let Some(ref re) = self.part_grep {
+            if !part_matches(msg, re) {
+                return false;
+            }
+        }
+
+        if let Some(ref substr) = self.body_type {
+            if !body_type_matches(msg, substr) {
+                return false;
+            }
+        }
+
         if let Some(ref re) = self.grep {
             let full = msg.to_bytes();
             let full_str = String::from_utf8_lossy(&full);
@@ -186,6 +779,147 @@ impl CompiledFilters {
     }
 }
 
+/// Match `re` against any decoded multipart part, or the body of a single-part message.
+fn part_matches(msg: &ParsedSipMessage, re: &Regex) -> bool {
+    match msg.body_parts() {
+        Some(parts) => parts
+            .iter()
+            .any(|p| re.is_match(&String::from_utf8_lossy(&p.decoded_body()))),
+        None => re.is_match(&msg.body_text()),
+    }
+}
+
+/// Match `substr` (lowercase) against the Content-Type media type, or that of
+/// any multipart part.
+fn body_type_matches(msg: &ParsedSipMessage, substr: &str) -> bool {
+    let matches = |ct: &str| {
+        let mime = ct.split_once(';').map_or(ct, |(mime, _)| mime);
+        mime.trim().to_ascii_lowercase().contains(substr)
+    };
+    if msg.content_type_mime().is_some_and(matches) {
+        return true;
+    }
+    msg.body_parts()
+        .is_some_and(|parts| parts.iter().filter_map(|p| p.content_type()).any(matches))
+}
+
+/// Retransmissions of the same request/response seen within this window are suppressed
+/// (64*T1, the RFC 3261 transaction timeout).
+const RETRANSMIT_WINDOW: Duration = Duration::from_secs(32);
+
+#[derive(Default)]
+struct RetransmitFilter {
+    last_seen: HashMap<(u64, Direction), Timestamp>,
+    /// Insertion order of `last_seen`, so entries past the window can be evicted.
+    order: VecDeque<(Timestamp, (u64, Direction))>,
+    suppressed: usize,
+}
+
+impl RetransmitFilter {
+    /// Returns true if `msg` repeats a UDP message seen within the retransmit window.
+    fn is_retransmission(&mut self, msg: &ParsedSipMessage) -> bool {
+        if msg.transport != Transport::Udp {
+            return false;
+        }
+        self.evict(msg.timestamp);
+        let key = (msg.fingerprint(), msg.direction);
+        let repeated = self
+            .last_seen
+            .get(&key)
+            .and_then(|prev| msg.timestamp.duration_since(prev))
+            .is_some_and(|gap| gap <= RETRANSMIT_WINDOW);
+        self.last_seen.insert(key, msg.timestamp);
+        self.order.push_back((msg.timestamp, key));
+        if repeated {
+            self.suppressed += 1;
+        }
+        repeated
+    }
+
+    /// Forget messages seen more than `RETRANSMIT_WINDOW` before `now`.
+    fn evict(&mut self, now: Timestamp) {
+        while let Some((seen, key)) = self.order.front() {
+            if now
+                .duration_since(seen)
+                .is_none_or(|gap| gap <= RETRANSMIT_WINDOW)
+            {
+                break;
+            }
+            // A later repeat refreshed the entry; its own queue slot evicts it
+            if self.last_seen.get(key) == Some(seen) {
+                self.last_seen.remove(key);
+            }
+            self.order.pop_front();
+        }
+    }
+}
+
+/// Tracks Call-IDs already emitted for `--first-per-call-id`.
+#[derive(Default)]
+struct FirstPerCallId {
+    seen: HashSet<String>,
+}
+
+impl FirstPerCallId {
+    /// Returns true the first time a Call-ID is seen; messages without one never pass.
+    fn is_first(&mut self, msg: &ParsedSipMessage) -> bool {
+        match msg.call_id() {
+            Some(cid) if !self.seen.contains(cid) => {
+                self.seen.insert(cid.to_string());
+                true
+            }
+            _ => false,
+        }
+    }
+}
+
+/// Distinct Call-IDs of matched messages, in first-seen order, for `--list-call-ids`.
+#[derive(Default)]
+struct CallIdList {
+    seen: HashSet<String>,
+    order: Vec<String>,
+}
+
+impl CallIdList {
+    fn add(&mut self, msg: &ParsedSipMessage) {
+        if let Some(cid) = msg.call_id() {
+            if self.seen.insert(cid.to_string()) {
+                self.order.push(cid.to_string());
+            }
+        }
+    }
+}
+
+/// Stateful per-message selection applied after `CompiledFilters::matches`.
+#[derive(Default)]
+struct Selectors {
+    retransmits: Option<RetransmitFilter>,
+    first_per_call_id: Option<FirstPerCallId>,
+}
+
+impl Selectors {
+    fn new(cli: &Cli) -> Self {
+        Selectors {
+            retransmits: cli.dedup.then(RetransmitFilter::default),
+            first_per_call_id: cli.first_per_call_id.then(FirstPerCallId::default),
+        }
+    }
+
+    fn accept(&mut self, msg: &ParsedSipMessage) -> bool {
+        if let Some(ref mut rt) = self.retransmits {
+            if rt.is_retransmission(msg) {
+                return false;
+            }
+        }
+        if let Some(ref mut first) = self.first_per_call_id {
+            if !first.is_first(msg) {
+                return false;
+            }
+        }
+        true
+    }
+}
+
 fn compile_regex(pattern: &str, label: &str) -> Regex {
     match Regex::new(pattern) {
         Ok(re) => re,
@@ -197,12 +931,20 @@ fn compile_regex(pattern: &str, label: &str) -> Regex {
 }
 
 fn compile_filters(cli: &Cli) -> CompiledFilters {
-    let methods: Vec<String> = cli.method.iter().map(|m| m.to_uppercase()).collect();
-    let excludes: Vec<String> = cli.exclude.iter().map(|m| m.to_uppercase()).collect();
-
-    let exclude_options = !cli.all_methods && !methods.iter().any(|m| m == "OPTIONS");
+    let mut method_filter = MessageFilter::new().include_options(cli.all_methods);
+    for method in &cli.method {
+        method_filter = method_filter.method(method);
+    }
+    for method in &cli.exclude {
+        method_filter = method_filter.exclude(method);
+    }
 
     let call_id = cli.call_id.as_ref().map(|p| compile_regex(p, "call-id"));
+    let exclude_call_ids = cli
+        .exclude_call_id
+        .iter()
+        .map(|p| compile_regex(p, "exclude-call-id"))
+        .collect();
 
     let direction = cli.direction.as_ref().map(|d| match d.as_str() {
         "recv" => Direction::Recv,
@@ -214,6 +956,18 @@ fn compile_filters(cli: &Cli) -> CompiledFilters {
     });
 
     let address = cli.address.as_ref().map(|p| compile_regex(p, "address"));
+    let exclude_addresses = cli
+        .exclude_address
+        .iter()
+        .map(|p| compile_regex(p, "exclude-address"))
+        .collect();
+    let host = cli.host.as_ref().map(|h| {
+        let h = h
+            .strip_prefix('[')
+            .and_then(|h| h.strip_suffix(']'))
+            .unwrap_or(h);
+        h.split_once('%').map_or(h, |(h, _)| h).to_string()
+    });
 
     let mut headers = Vec::new();
     for spec in &cli.header {
@@ -233,401 +987,3798 @@ fn compile_filters(cli: &Cli) -> CompiledFilters {
         .body_grep
         .as_ref()
         .map(|p| compile_regex(p, "body-grep"));
+    let part_grep = cli
+        .part_grep
+        .as_ref()
+        .map(|p| compile_regex(p, "part-grep"));
     let grep = cli.grep.as_ref().map(|p| compile_regex(p, "grep"));
 
     CompiledFilters {
-        methods,
-        excludes,
-        exclude_options,
+        method_filter,
         call_id,
+        exclude_call_ids,
+        requests_only: cli.requests_only,
+        responses_only: cli.responses_only,
         direction,
         address,
+        exclude_addresses,
+        host,
+        port: cli.port,
+        min_frames: cli.min_frames,
+        cause: cli.cause,
+        realm: cli.realm.as_ref().map(|p| compile_regex(p, "realm")),
         headers,
+        has_headers: cli.has_header.clone(),
+        missing_headers: cli.missing_header.clone(),
         body_grep,
+        part_grep,
+        body_type: cli.body_type.as_ref().map(|t| t.to_ascii_lowercase()),
         grep,
+        strict: cli.strict,
     }
 }
 
-fn output_mode(cli: &Cli) -> OutputMode {
-    if cli.full {
-        OutputMode::Full
-    } else if cli.headers {
-        OutputMode::Headers
-    } else if cli.body {
-        OutputMode::Body
-    } else {
-        OutputMode::Summary
+fn output_options(cli: &Cli) -> OutputOptions {
+    let color = match cli.color {
+        ColorChoice::Always => true,
+        ColorChoice::Never => false,
+        ColorChoice::Auto => cli.output.is_none() && io::stdout().is_terminal(),
+    };
+    let limit = match (cli.head, cli.tail) {
+        (Some(n), _) => Limit::Head(n),
+        (None, Some(n)) => Limit::Tail(n),
+        (None, None) => Limit::None,
+    };
+    OutputOptions {
+        mode: output_mode(cli),
+        color,
+        limit,
+        keepalives: cli.keepalives,
+        canonical_reason: cli.canonical_reason,
+        tz_offset: cli.tz_offset,
+        swap_direction: cli.swap_direction,
+        anonymizer: cli.anonymize.then(|| match &cli.anonymize_salt {
+            Some(salt) => Anonymizer::new(salt),
+            None => Anonymizer::random(),
+        }),
+        replace_headers: cli
+            .replace_header
+            .iter()
+            .map(|spec| match spec.split_once('=') {
+                Some((name, value)) if !name.is_empty() => (name.to_string(), value.to_string()),
+                _ => {
+                    eprintln!("invalid header replacement '{spec}': expected NAME=VALUE");
+                    process::exit(2);
+                }
+            })
+            .collect(),
+        format: cli.format.clone(),
+        align: cli.align,
+        show_frames: cli.show_frames,
+        show_bytes: cli.show_bytes,
     }
 }
 
-fn open_input(files: &[String]) -> Box<dyn Read> {
-    let raw: Box<dyn Read> = if files.is_empty() || (files.len() == 1 && files[0] == "-") {
-        Box::new(io::stdin().lock())
-    } else {
-        let mut readers: Vec<Box<dyn Read>> = Vec::new();
-        for path in files {
-            if path == "-" {
-                readers.push(Box::new(io::stdin().lock()));
-            } else {
-                match File::open(path) {
-                    Ok(f) => readers.push(Box::new(f)),
-                    Err(e) => {
-                        eprintln!("{path}: {e}");
-                        process::exit(1);
-                    }
-                }
-            }
-        }
-
-        if readers.len() == 1 {
-            readers.remove(0)
-        } else {
-            let mut chain: Box<dyn Read> = readers.remove(0);
-            for r in readers {
-                chain = Box::new(chain.chain(r));
-            }
-            chain
-        }
+/// Parse `±HH:MM` (or `±HHMM`, `±HH`) into minutes.
+fn parse_tz_offset(s: &str) -> Result<i32, String> {
+    let (sign, rest) = match s.as_bytes().first() {
+        Some(b'+') => (1, &s[1..]),
+        Some(b'-') => (-1, &s[1..]),
+        _ => return Err("expected a leading + or -".into()),
     };
-    Box::new(GrepFilter::new(raw))
-}
-
-fn init_tracing(verbose: u8) {
-    let level = match verbose {
-        0 => "warn",
-        1 => "info",
-        2 => "debug",
-        _ => "trace",
+    let (hours, minutes) = match rest.split_once(':') {
+        Some((h, m)) => (h, m),
+        None if rest.len() == 4 => rest.split_at(2),
+        None => (rest, "0"),
     };
-    tracing_subscriber::fmt()
-        .with_env_filter(
-            tracing_subscriber::EnvFilter::try_from_default_env().unwrap_or_else(|_| level.into()),
-        )
-        .with_writer(io::stderr)
-        .init();
-}
-
-fn print_lossy(bytes: &[u8]) {
-    let s = String::from_utf8_lossy(bytes);
-    print!("{s}");
-    if !s.ends_with('\n') {
-        println!();
+    let hours: i32 = hours
+        .parse()
+        .map_err(|_| format!("invalid hours {hours:?}"))?;
+    let minutes: i32 = minutes
+        .parse()
+        .map_err(|_| format!("invalid minutes {minutes:?}"))?;
+    if hours > 23 || minutes > 59 {
+        return Err("offset out of range".into());
     }
+    Ok(sign * (hours * 60 + minutes))
 }
 
-fn format_summary(msg: &ParsedSipMessage) -> String {
-    let call_id = msg.call_id().unwrap_or("-");
-    format!(
-        "{} {} {}/{} {} {}",
-        msg.timestamp,
-        msg.direction,
-        msg.transport,
-        msg.address,
-        msg.message_type.summary(),
-        call_id
-    )
+/// Parse a non-negative number of seconds, fractions allowed.
+fn parse_seconds(s: &str) -> Result<Duration, String> {
+    let secs: f64 = s.parse().map_err(|_| format!("invalid seconds {s:?}"))?;
+    Duration::try_from_secs_f64(secs).map_err(|_| format!("seconds out of range: {s}"))
 }
 
-fn format_frame_header(msg: &ParsedSipMessage) -> String {
-    format!(
-        "{} {} {}/{} at {} ({} frames) {}",
-        msg.direction,
-        msg.direction.preposition(),
-        msg.transport,
-        msg.address,
-        msg.timestamp,
-        msg.frame_count,
-        msg.message_type.summary(),
-    )
+/// Parse a `--format` template; `{{` and `}}` are literal braces.
+fn parse_summary_template(s: &str) -> Result<SummaryTemplate, String> {
+    let mut parts = Vec::new();
+    let mut literal = String::new();
+    let mut chars = s.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '{' if chars.peek() == Some(&'{') => {
+                chars.next();
+                literal.push('{');
+            }
+            '}' if chars.peek() == Some(&'}') => {
+                chars.next();
+                literal.push('}');
+            }
+            '{' => {
+                let mut name = String::new();
+                loop {
+                    match chars.next() {
+                        Some('}') => break,
+                        Some(c) => name.push(c),
+                        None => return Err(format!("unterminated placeholder {{{name}")),
+                    }
+                }
+                let placeholder = match name.as_str() {
+                    "timestamp" => Placeholder::Timestamp,
+                    "direction" => Placeholder::Direction,
+                    "transport" => Placeholder::Transport,
+                    "address" => Placeholder::Address,
+                    "method" => Placeholder::Method,
+                    "status" => Placeholder::Status,
+                    "call_id" => Placeholder::CallId,
+                    "cseq" => Placeholder::CSeq,
+                    "uri" => Placeholder::Uri,
+                    _ => match name.strip_prefix("header:") {
+                        Some(header) if !header.is_empty() => Placeholder::Header(header.into()),
+                        _ => return Err(format!("unknown placeholder {{{name}}}")),
+                    },
+                };
+                if !literal.is_empty() {
+                    parts.push(TemplatePart::Literal(std::mem::take(&mut literal)));
+                }
+                parts.push(TemplatePart::Field(placeholder));
+            }
+            '}' => return Err("unmatched '}' (use '}}' for a literal brace)".into()),
+            _ => literal.push(c),
+        }
+    }
+    if !literal.is_empty() {
+        parts.push(TemplatePart::Literal(literal));
+    }
+    Ok(SummaryTemplate(parts))
 }
 
-fn output_full(msg: &ParsedSipMessage) {
-    println!("{}", format_frame_header(msg));
-    print_lossy(&msg.to_bytes());
+/// Parse `HOST`, `HOST:PORT`, `[V6]:PORT` or `:PORT`.
+fn parse_local_endpoint(s: &str) -> Result<LocalEndpoint, String> {
+    if let Ok(host) = s.trim_start_matches('[').trim_end_matches(']').parse() {
+        return Ok(LocalEndpoint {
+            host: Some(host),
+            port: None,
+        });
+    }
+    if let Ok(addr) = s.parse::<SocketAddr>() {
+        return Ok(LocalEndpoint {
+            host: Some(addr.ip()),
+            port: Some(addr.port()),
+        });
+    }
+    match s.strip_prefix(':').map(str::parse) {
+        Some(Ok(port)) => Ok(LocalEndpoint {
+            host: None,
+            port: Some(port),
+        }),
+        _ => Err("expected HOST, HOST:PORT or :PORT".into()),
+    }
 }
 
-fn output_headers(msg: &ParsedSipMessage) {
-    println!("{}", format_frame_header(msg));
-    match &msg.message_type {
-        SipMessageType::Request { method, uri } => {
-            println!("{method} {uri} SIP/2.0");
+fn output_mode(cli: &Cli) -> OutputMode {
+    if cli.full {
+        OutputMode::Full
+    } else if cli.headers {
+        OutputMode::Headers {
+            wrap: cli.wrap.map(usize::from),
         }
-        SipMessageType::Response { code, reason } => {
-            println!("SIP/2.0 {code} {reason}");
+    } else if cli.body {
+        OutputMode::Body {
+            part: cli.part.as_ref().map(|p| p.to_ascii_lowercase()),
         }
-    }
-    for (name, value) in &msg.headers {
-        println!("{name}: {value}");
+    } else if cli.export_dump || cli.split_by_call_id.is_some() {
+        OutputMode::ExportDump
+    } else if cli.hex {
+        OutputMode::Hex
+    } else if cli.csv {
+        OutputMode::Csv
+    } else if cli.only_matching {
+        match (&cli.body_grep, &cli.grep) {
+            (Some(p), _) => OutputMode::OnlyMatching {
+                re: compile_regex(p, "body-grep"),
+                body: true,
+            },
+            (None, Some(p)) => OutputMode::OnlyMatching {
+                re: compile_regex(p, "grep"),
+                body: false,
+            },
+            (None, None) => {
+                eprintln!("--only-matching requires --grep or --body-grep");
+                process::exit(2);
+            }
+        }
+    } else {
+        OutputMode::Summary
     }
 }
 
-fn output_body(msg: &ParsedSipMessage) {
-    if !msg.body.is_empty() {
-        print_lossy(&msg.body);
-    }
+/// How often `--progress` reports.
+const PROGRESS_INTERVAL: Duration = Duration::from_secs(1);
+
+/// `--progress` counts over all inputs, shared by their `ProgressReader`s so
+/// that `--merge` reports once for the whole run rather than once per file.
+struct Progress {
+    total: Option<u64>,
+    bytes: u64,
+    frames: u64,
+    last_report: Instant,
+    /// Readers not yet at end of input.
+    open: usize,
 }
 
-fn output_message(mode: &OutputMode, msg: &ParsedSipMessage) {
-    match mode {
-        OutputMode::Summary => println!("{}", format_summary(msg)),
-        OutputMode::Full => output_full(msg),
-        OutputMode::Headers => output_headers(msg),
-        OutputMode::Body => output_body(msg),
+impl Progress {
+    fn shared(total: Option<u64>) -> Rc<RefCell<Progress>> {
+        Rc::new(RefCell::new(Progress {
+            total,
+            bytes: 0,
+            frames: 0,
+            last_report: Instant::now(),
+            open: 0,
+        }))
+    }
+
+    fn report(&mut self) {
+        self.last_report = Instant::now();
+        match self.total {
+            Some(total) if total > 0 => info!(
+                target: "progress",
+                "{} of {total} bytes ({}%), {} frames",
+                self.bytes,
+                self.bytes * 100 / total,
+                self.frames
+            ),
+            _ => info!(target: "progress", "{} bytes, {} frames", self.bytes, self.frames),
+        }
     }
 }
 
-fn run_frames(reader: Box<dyn Read>) {
-    for result in FrameIterator::new(reader) {
-        match result {
-            Ok(frame) => {
-                println!(
-                    "{} {} bytes {} {}/{} at {}",
-                    frame.direction,
-                    frame.byte_count,
-                    frame.direction.preposition(),
-                    frame.transport,
-                    frame.address,
-                    frame.timestamp,
-                );
-                print_lossy(&frame.content);
-            }
-            Err(e) => info!("frame error: {e}"),
+/// Reader adapter counting bytes and dump frames into a `Progress`.
+struct ProgressReader<R> {
+    inner: R,
+    progress: Rc<RefCell<Progress>>,
+    last_byte: u8,
+    finished: bool,
+}
+
+impl<R> ProgressReader<R> {
+    fn new(inner: R, progress: &Rc<RefCell<Progress>>) -> Self {
+        progress.borrow_mut().open += 1;
+        ProgressReader {
+            inner,
+            progress: Rc::clone(progress),
+            last_byte: 0,
+            finished: false,
         }
     }
+
+    /// Count `data` and the `\x0B\n` frame terminators in it, including one
+    /// split across two reads.
+    fn account(&mut self, data: &[u8]) {
+        let Some(&last) = data.last() else {
+            return;
+        };
+        let mut progress = self.progress.borrow_mut();
+        progress.bytes += data.len() as u64;
+        if self.last_byte == 0x0B && data[0] == b'\n' {
+            progress.frames += 1;
+        }
+        progress.frames += memmem::find_iter(data, b"\x0B\n").count() as u64;
+        self.last_byte = last;
+    }
 }
 
-fn run_raw(reader: Box<dyn Read>) {
-    for result in MessageIterator::new(reader) {
-        match result {
-            Ok(msg) => {
-                println!(
-                    "{} {} {}/{} at {} ({} frames, {} bytes)",
-                    msg.direction,
-                    msg.direction.preposition(),
-                    msg.transport,
-                    msg.address,
-                    msg.timestamp,
-                    msg.frame_count,
-                    msg.content.len(),
-                );
-                print_lossy(&msg.content);
+impl<R: Read> Read for ProgressReader<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let n = self.inner.read(buf)?;
+        self.account(&buf[..n]);
+        let mut progress = self.progress.borrow_mut();
+        if n == 0 {
+            // Readers may be polled again after EOF; report the total once,
+            // when the last input ends
+            if !std::mem::replace(&mut self.finished, true) {
+                progress.open -= 1;
+                if progress.open == 0 {
+                    progress.report();
+                }
             }
-            Err(e) => info!("message error: {e}"),
+        } else if progress.last_report.elapsed() >= PROGRESS_INTERVAL {
+            progress.report();
         }
+        Ok(n)
     }
 }
 
-fn run_stats(reader: Box<dyn Read>, filters: &CompiledFilters) {
-    let mut method_counts: HashMap<String, usize> = HashMap::new();
-    let mut status_counts: HashMap<u16, usize> = HashMap::new();
-    let mut direction_counts: HashMap<Direction, usize> = HashMap::new();
-    let mut total: usize = 0;
-    let mut matched: usize = 0;
-    let mut errors: usize = 0;
+/// Open one input: `tcp://HOST:PORT` connects and reads until the peer
+/// closes, anything else is opened as a file (a FIFO blocks until a writer
+/// appears).
+fn open_path(path: &str) -> io::Result<Box<dyn Read>> {
+    match path.strip_prefix("tcp://") {
+        Some(addr) => Ok(Box::new(TcpStream::connect(addr)?)),
+        None => Ok(Box::new(File::open(path)?)),
+    }
+}
 
-    for result in ParsedMessageIterator::new(reader) {
-        total += 1;
-        match result {
-            Ok(msg) => {
-                if !filters.matches(&msg) {
-                    continue;
-                }
-                matched += 1;
-                *direction_counts.entry(msg.direction).or_default() += 1;
-                match &msg.message_type {
-                    SipMessageType::Request { method, .. } => {
-                        *method_counts.entry(method.clone()).or_default() += 1;
-                    }
-                    SipMessageType::Response { code, .. } => {
-                        *status_counts.entry(*code).or_default() += 1;
-                        if let Some(method) = msg.method() {
-                            *method_counts.entry(method.to_string()).or_default() += 1;
-                        }
-                    }
-                }
-            }
-            Err(_) => errors += 1,
+/// Open one input: stdin for `-`, else a file or `tcp://` socket. Exits on error.
+fn open_file(path: &str) -> Box<dyn Read> {
+    if path == "-" {
+        return Box::new(io::stdin().lock());
+    }
+    match open_path(path) {
+        Ok(r) => r,
+        Err(e) => {
+            eprintln!("{path}: {e}");
+            process::exit(2);
         }
     }
+}
 
-    println!("total: {total}");
-    println!("matched: {matched}");
-    if errors > 0 {
-        println!("parse errors: {errors}");
+/// Total size of `paths`, only known when every one is a regular file.
+fn input_size(paths: &[String]) -> Option<u64> {
+    paths
+        .iter()
+        .map(|path| {
+            (path != "-")
+                .then(|| std::fs::metadata(path).ok())
+                .flatten()
+                .filter(|m| m.is_file())
+                .map(|m| m.len())
+        })
+        .sum::<Option<u64>>()
+        .filter(|_| !paths.is_empty())
+}
+
+/// Add `--progress` reporting and the dump prefix/ANSI stripping to a raw input.
+fn wrap_input(cli: &Cli, raw: Box<dyn Read>, progress: &Rc<RefCell<Progress>>) -> Box<dyn Read> {
+    let raw: Box<dyn Read> = if cli.progress {
+        Box::new(ProgressReader::new(raw, progress))
+    } else {
+        raw
+    };
+    if cli.input_format == InputFormat::Pcap {
+        return raw;
+    }
+    let raw: Box<dyn Read> = if cli.strip_ansi {
+        Box::new(AnsiStripFilter::new(raw))
+    } else {
+        raw
+    };
+    if cli.strip_grep_prefix {
+        Box::new(GrepFilter::with_prefix_stripping(raw))
+    } else {
+        Box::new(GrepFilter::new(raw))
+    }
+}
+
+/// All inputs concatenated in command-line order.
+fn open_input(cli: &Cli) -> Box<dyn Read> {
+    let files = &cli.files;
+    let raw: Box<dyn Read> = if files.is_empty() {
+        Box::new(io::stdin().lock())
+    } else {
+        let mut readers = files.iter().map(|path| open_file(path));
+        let first = readers.next().unwrap();
+        readers.fold(first, |chain, r| Box::new(chain.chain(r)))
+    };
+    wrap_input(cli, raw, &Progress::shared(input_size(files)))
+}
+
+/// One reader per input file with `--merge`, else the concatenated input.
+fn open_inputs(cli: &Cli) -> Vec<Box<dyn Read>> {
+    if !cli.merge || cli.files.len() < 2 {
+        return vec![open_input(cli)];
     }
+    let progress = Progress::shared(input_size(&cli.files));
+    cli.files
+        .iter()
+        .map(|path| wrap_input(cli, open_file(path), &progress))
+        .collect()
+}
 
-    if let Some(&n) = direction_counts.get(&Direction::Recv) {
-        println!("recv: {n}");
+/// The messages of every input. Normally a single stream over the inputs
+/// concatenated; with `--merge`, one stream per file, each reassembled on its
+/// own, yielding the earliest head message first (the earlier file on ties).
+struct Messages {
+    streams: Vec<MessageIterator<Box<dyn Read>>>,
+    heads: Vec<Option<Result<SipMessage, ParseError>>>,
+    /// `--dedup-frames` over the merged streams.
+    dedup: Option<RecentMessages>,
+}
+
+impl Messages {
+    fn new(streams: Vec<MessageIterator<Box<dyn Read>>>) -> Self {
+        let heads = streams.iter().map(|_| None).collect();
+        Messages {
+            streams,
+            heads,
+            dedup: None,
+        }
     }
-    if let Some(&n) = direction_counts.get(&Direction::Sent) {
-        println!("sent: {n}");
+
+    /// Apply `MessageIterator::keepalives` to every stream.
+    fn keepalives(mut self, emit: bool) -> Self {
+        self.streams = self
+            .streams
+            .into_iter()
+            .map(|s| s.keepalives(emit))
+            .collect();
+        self
     }
 
-    let mut methods: Vec<_> = method_counts.into_iter().collect();
-    methods.sort_by(|a, b| b.1.cmp(&a.1));
-    if !methods.is_empty() {
-        println!("\nmethods:");
-        for (method, count) in &methods {
-            println!("  {method}: {count}");
+    /// Apply `MessageIterator::dedup_frames`. Several streams are deduplicated
+    /// once merged, so a message repeated across files is dropped as well.
+    fn dedup_frames(mut self, window: usize) -> Self {
+        if self.streams.len() == 1 {
+            self.streams = self
+                .streams
+                .into_iter()
+                .map(|s| s.dedup_frames(window))
+                .collect();
+        } else {
+            self.dedup = (window > 0).then(|| RecentMessages::new(window));
         }
+        self
     }
 
-    let mut statuses: Vec<_> = status_counts.into_iter().collect();
-    statuses.sort_by_key(|&(code, _)| code);
-    if !statuses.is_empty() {
-        println!("\nresponse codes:");
-        for (code, count) in &statuses {
-            println!("  {code}: {count}");
+    fn next_merged(&mut self) -> Option<Result<SipMessage, ParseError>> {
+        if let [stream] = &mut self.streams[..] {
+            return stream.next();
+        }
+        for (stream, head) in self.streams.iter_mut().zip(&mut self.heads) {
+            if head.is_none() {
+                *head = stream.next();
+            }
         }
+        // Errors sort first, so they are reported where they occur
+        let (index, _) = self
+            .heads
+            .iter()
+            .enumerate()
+            .filter_map(|(i, head)| Some((i, head.as_ref()?)))
+            .min_by_key(|(_, head)| head.as_ref().ok().map(|m| m.timestamp.sort_key()))?;
+        self.heads[index].take()
     }
 }
 
-fn run_filtered(reader: Box<dyn Read>, mode: &OutputMode, filters: &CompiledFilters) {
-    for result in ParsedMessageIterator::new(reader) {
-        match result {
-            Ok(msg) => {
-                if !filters.matches(&msg) {
+impl Iterator for Messages {
+    type Item = Result<SipMessage, ParseError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            let item = self.next_merged()?;
+            if let (Ok(msg), Some(recent)) = (&item, &mut self.dedup) {
+                if !recent.insert(msg) {
+                    debug!(address = %msg.address, "dropped duplicate message");
                     continue;
                 }
-                output_message(mode, &msg);
             }
-            Err(e) => info!("parse error: {e}"),
+            return Some(item);
         }
     }
 }
 
-struct DialogState {
-    messages: Vec<SipMessage>,
-    matched: bool,
-    saw_bye: bool,
-    saw_bye_response: bool,
+fn pcap_frames(cli: &Cli, input: Box<dyn Read>) -> PcapFrameIterator<Box<dyn Read>> {
+    let mut frames = PcapFrameIterator::new(input);
+    if !cli.pcap_port.is_empty() {
+        frames = frames.sip_ports(&cli.pcap_port);
+    }
+    if let Some(local) = cli.pcap_local {
+        if let Some(host) = local.host {
+            frames = frames.local_host(host);
+        }
+        if let Some(port) = local.port {
+            frames = frames.local_port(port);
+        }
+    }
+    frames
 }
 
-fn run_dialog(reader: Box<dyn Read>, mode: &OutputMode, filters: &CompiledFilters) {
-    let mut dialogs: HashMap<String, DialogState> = HashMap::new();
+fn init_tracing(verbose: u8, progress: bool) {
+    let level = match verbose {
+        0 => "warn",
+        1 => "info",
+        2 => "debug",
+        _ => "trace",
+    };
+    let level = if progress {
+        format!("{level},progress=info")
+    } else {
+        level.to_string()
+    };
+    tracing_subscriber::fmt()
+        .with_env_filter(
+            tracing_subscriber::EnvFilter::try_from_default_env().unwrap_or_else(|_| level.into()),
+        )
+        .with_writer(io::stderr)
+        .init();
+}
 
-    // Single pass: collect messages by Call-ID, track matches
-    for result in MessageIterator::new(reader) {
-        let sip_msg = match result {
-            Ok(m) => m,
-            Err(e) => {
-                info!("message error: {e}");
-                continue;
-            }
-        };
+fn print_lossy(w: &mut dyn Write, bytes: &[u8]) -> io::Result<()> {
+    print_text(w, &String::from_utf8_lossy(bytes))
+}
 
-        let parsed = match sip_msg.parse() {
-            Ok(p) => p,
-            Err(e) => {
-                info!("parse error: {e}");
-                continue;
-            }
-        };
+fn print_text(w: &mut dyn Write, s: &str) -> io::Result<()> {
+    write!(w, "{s}")?;
+    if !s.ends_with('\n') {
+        writeln!(w)?;
+    }
+    Ok(())
+}
 
-        if filters.is_excluded(&parsed) {
-            continue;
-        }
+const ANSI_RESET: &str = "\x1b[0m";
 
-        let call_id = match parsed.call_id() {
-            Some(cid) => cid.to_string(),
-            None => continue,
-        };
+fn paint(text: &str, ansi: &str, color: bool) -> String {
+    if color {
+        format!("{ansi}{text}{ANSI_RESET}")
+    } else {
+        text.to_string()
+    }
+}
 
-        let is_match = filters.matches(&parsed);
+fn direction_color(direction: Direction) -> &'static str {
+    match direction {
+        Direction::Recv => "\x1b[36m",
+        Direction::Sent => "\x1b[35m",
+    }
+}
 
-        // Detect BYE and BYE responses for pruning
-        let is_bye_request = matches!(
-            &parsed.message_type,
-            SipMessageType::Request { method, .. } if method.eq_ignore_ascii_case("BYE")
-        );
-        let is_bye_response = matches!(&parsed.message_type, SipMessageType::Response { .. })
-            && parsed
-                .method()
-                .map(|m| m.eq_ignore_ascii_case("BYE"))
-                .unwrap_or(false);
+fn status_color(code: u16) -> &'static str {
+    match code {
+        100..=199 => "\x1b[33m",
+        200..=299 => "\x1b[32m",
+        300..=399 => "\x1b[34m",
+        _ => "\x1b[31m",
+    }
+}
 
-        let state = dialogs.entry(call_id).or_insert_with(|| DialogState {
-            messages: Vec::new(),
-            matched: false,
-            saw_bye: false,
-            saw_bye_response: false,
-        });
+fn painted_direction(msg: &ParsedSipMessage, color: bool) -> String {
+    paint(
+        &msg.direction.to_string(),
+        direction_color(msg.direction),
+        color,
+    )
+}
 
-        if is_match {
-            state.matched = true;
-        }
-        if is_bye_request {
-            state.saw_bye = true;
-        }
-        if is_bye_response {
-            state.saw_bye_response = true;
-        }
+fn painted_type(msg: &ParsedSipMessage, color: bool) -> String {
+    let summary = msg.message_type.summary();
+    match msg.status_code() {
+        Some(code) => paint(&summary, status_color(code), color),
+        None => summary.into_owned(),
+    }
+}
+
+/// Summary fields: timestamp, direction, transport/address, message type, Call-ID.
+fn summary_columns(msg: &ParsedSipMessage, color: bool) -> [String; 5] {
+    [
+        msg.timestamp.to_string(),
+        painted_direction(msg, color),
+        format!("{}/{}", msg.transport, msg.address),
+        painted_type(msg, color),
+        msg.call_id().unwrap_or("-").to_string(),
+    ]
+}
 
-        state.messages.push(sip_msg);
+fn format_summary(msg: &ParsedSipMessage, color: bool) -> String {
+    summary_columns(msg, color).join(" ")
+}
 
-        // Prune: dialog terminated and never matched
-        if state.saw_bye && state.saw_bye_response && !state.matched {
-            dialogs.remove(parsed.call_id().unwrap());
-        }
+/// `--csv` columns; a stable interface, extend at the end, don't reorder.
+const CSV_HEADER: &str = "timestamp,direction,transport,address,type,method,status,reason,\
+    call_id,cseq,content_length,frame_count";
+
+/// One `--csv` row, fields quoted per RFC 4180 when needed.
+fn format_csv(msg: &ParsedSipMessage) -> String {
+    let (kind, status, reason) = match &msg.message_type {
+        SipMessageType::Request { .. } => ("request", String::new(), ""),
+        SipMessageType::Response { code, reason } => ("response", code.to_string(), &reason[..]),
+    };
+    let fields = [
+        &msg.timestamp.to_string(),
+        &msg.direction.to_string(),
+        &msg.transport.to_string(),
+        &msg.address,
+        kind,
+        msg.method().unwrap_or(""),
+        &status,
+        reason,
+        msg.call_id().unwrap_or(""),
+        msg.cseq().unwrap_or(""),
+        &msg.content_length()
+            .map_or(String::new(), |n| n.to_string()),
+        &msg.frame_count.to_string(),
+    ];
+    let fields: Vec<Cow<str>> = fields.into_iter().map(csv_field).collect();
+    fields.join(",")
+}
+
+fn csv_field(field: &str) -> Cow<'_, str> {
+    if field.contains([',', '"', '\r', '\n']) {
+        Cow::Owned(format!("\"{}\"", field.replace('"', "\"\"")))
+    } else {
+        Cow::Borrowed(field)
     }
+}
+
+/// Separates `--align` summary columns until `AlignedColumns` pads them.
+const COLUMN_SEP: &str = "\x1f";
+
+/// Collects `--align` output and pads each `COLUMN_SEP`-separated column to
+/// its widest value in `finish()`. Every line is held in memory until then;
+/// lines without separators (keepalives) are written as-is.
+struct AlignedColumns<'a> {
+    inner: &'a mut dyn Write,
+    buf: Vec<u8>,
+}
 
-    // Output matched dialogs in chronological order
-    let mut matched_messages: Vec<SipMessage> = Vec::new();
-    for (_, state) in dialogs {
-        if state.matched {
-            matched_messages.extend(state.messages);
+impl<'a> AlignedColumns<'a> {
+    fn new(inner: &'a mut dyn Write) -> Self {
+        AlignedColumns {
+            inner,
+            buf: Vec::new(),
         }
     }
 
-    // Sort by timestamp for chronological output across Call-IDs
-    matched_messages.sort_by_key(|m| m.timestamp.sort_key());
-
-    for sip_msg in &matched_messages {
-        match sip_msg.parse() {
-            Ok(parsed) => output_message(mode, &parsed),
-            Err(e) => info!("parse error on output: {e}"),
+    fn finish(self) -> io::Result<()> {
+        let text = String::from_utf8_lossy(&self.buf);
+        let mut widths: Vec<usize> = Vec::new();
+        for line in text.lines() {
+            let columns: Vec<&str> = line.split(COLUMN_SEP).collect();
+            for (i, column) in columns[..columns.len() - 1].iter().enumerate() {
+                let width = visible_width(column);
+                match widths.get_mut(i) {
+                    Some(max) => *max = (*max).max(width),
+                    None => widths.push(width),
+                }
+            }
+        }
+        for line in text.lines() {
+            let columns: Vec<&str> = line.split(COLUMN_SEP).collect();
+            let (last, padded) = columns.split_last().unwrap_or((&"", &[]));
+            for (column, width) in padded.iter().zip(&widths) {
+                let pad = width - visible_width(column) + 1;
+                write!(self.inner, "{column}{:pad$}", "")?;
+            }
+            writeln!(self.inner, "{last}")?;
         }
+        Ok(())
     }
 }
 
-fn main() {
-    let cli = Cli::parse();
-    init_tracing(cli.verbose);
-
-    if cli.dialog && (cli.raw || cli.frames) {
-        eprintln!("--dialog is incompatible with --raw and --frames");
-        process::exit(2);
+impl Write for AlignedColumns<'_> {
+    fn write(&mut self, data: &[u8]) -> io::Result<usize> {
+        self.buf.extend_from_slice(data);
+        Ok(data.len())
     }
 
-    if cli.dialog && cli.stats {
-        eprintln!("--dialog is incompatible with --stats");
-        process::exit(2);
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
     }
+}
 
-    if cli.frames {
-        run_frames(open_input(&cli.files));
-        return;
+/// Displayed width in characters, not counting ANSI color sequences.
+fn visible_width(text: &str) -> usize {
+    let mut width = 0;
+    let mut chars = text.chars();
+    while let Some(c) = chars.next() {
+        if c == '\x1b' {
+            chars.by_ref().find(|&c| c == 'm');
+        } else {
+            width += 1;
+        }
     }
+    width
+}
 
-    if cli.raw {
-        run_raw(open_input(&cli.files));
-        return;
+impl SummaryTemplate {
+    /// Render for one message; missing values show as `-`.
+    fn render(&self, msg: &ParsedSipMessage, color: bool) -> String {
+        let mut out = String::new();
+        for part in &self.0 {
+            let field = match part {
+                TemplatePart::Literal(text) => {
+                    out.push_str(text);
+                    continue;
+                }
+                TemplatePart::Field(field) => field,
+            };
+            let value = match field {
+                Placeholder::Timestamp => msg.timestamp.to_string(),
+                Placeholder::Direction => painted_direction(msg, color),
+                Placeholder::Transport => msg.transport.to_string(),
+                Placeholder::Address => msg.address.clone(),
+                Placeholder::Method => msg.method().unwrap_or("-").to_string(),
+                Placeholder::Status => match msg.status_code() {
+                    Some(code) => paint(&code.to_string(), status_color(code), color),
+                    None => "-".into(),
+                },
+                Placeholder::CallId => msg.call_id().unwrap_or("-").to_string(),
+                Placeholder::CSeq => msg.cseq().unwrap_or("-").to_string(),
+                Placeholder::Uri => match &msg.message_type {
+                    SipMessageType::Request { uri, .. } => uri.clone(),
+                    SipMessageType::Response { .. } => "-".into(),
+                },
+                Placeholder::Header(name) => msg
+                    .headers
+                    .iter()
+                    .find(|(k, _)| k.eq_ignore_ascii_case(name))
+                    .map_or("-", |(_, v)| v.as_str())
+                    .to_string(),
+            };
+            out.push_str(&value);
+        }
+        out
     }
+}
+
+fn format_keepalive(raw: &SipMessage, color: bool) -> String {
+    let direction = paint(
+        &raw.direction.to_string(),
+        direction_color(raw.direction),
+        color,
+    );
+    format!(
+        "{} {} CRLF-keepalive {} {}/{}",
+        raw.timestamp,
+        direction,
+        raw.direction.preposition(),
+        raw.transport,
+        raw.address
+    )
+}
+
+fn format_frame_header(msg: &ParsedSipMessage, color: bool) -> String {
+    format!(
+        "{} {} {}/{} at {} ({} frames) {}",
+        painted_direction(msg, color),
+        msg.direction.preposition(),
+        msg.transport,
+        msg.address,
+        msg.timestamp,
+        msg.frame_count,
+        painted_type(msg, color),
+    )
+}
 
-    let filters = compile_filters(&cli);
-    let mode = output_mode(&cli);
+fn output_full(w: &mut dyn Write, msg: &ParsedSipMessage, color: bool) -> io::Result<()> {
+    writeln!(w, "{}", format_frame_header(msg, color))?;
+    print_lossy(w, &msg.to_bytes())
+}
 
-    if cli.dialog {
-        run_dialog(open_input(&cli.files), &mode, &filters);
-        return;
+fn output_headers(
+    w: &mut dyn Write,
+    msg: &ParsedSipMessage,
+    color: bool,
+    wrap: Option<usize>,
+) -> io::Result<()> {
+    writeln!(w, "{}", format_frame_header(msg, color))?;
+    match &msg.message_type {
+        SipMessageType::Request { method, uri } => {
+            writeln!(w, "{method} {uri} SIP/2.0")?;
+        }
+        SipMessageType::Response { code, reason } => {
+            writeln!(w, "SIP/2.0 {code} {reason}")?;
+        }
+    }
+    for (name, value) in &msg.headers {
+        match wrap {
+            Some(cols) => {
+                for line in fold_header(name, value, cols) {
+                    writeln!(w, "{line}")?;
+                }
+            }
+            None => writeln!(w, "{name}: {value}")?,
+        }
     }
+    Ok(())
+}
 
-    let reader = open_input(&cli.files);
+/// Indent of a folded header's continuation lines.
+const FOLD_INDENT: &str = "    ";
 
-    if cli.stats {
-        run_stats(reader, &filters);
-        return;
+/// Lay out `name: value` in lines of at most `cols` characters, breaking
+/// after a `,` or `;` or at whitespace outside quoted strings, with
+/// continuation lines indented as in an RFC 3261 folded header. A run
+/// without a break point stays overlong.
+fn fold_header(name: &str, value: &str, cols: usize) -> Vec<String> {
+    let width = |s: &str| s.chars().count();
+    let mut lines = Vec::new();
+    let mut line = format!("{name}: ");
+    let mut rest = value.trim();
+    while width(&line) + width(rest) > cols {
+        let room = cols.saturating_sub(width(&line));
+        let mut breaks = fold_points(rest);
+        let Some(first) = breaks.next() else {
+            break;
+        };
+        let (end, next, _) = std::iter::once(first)
+            .chain(breaks)
+            .take_while(|&(_, _, end_width)| end_width <= room)
+            .last()
+            .unwrap_or(first);
+        if rest[next..].trim().is_empty() {
+            break;
+        }
+        line.push_str(rest[..end].trim_end());
+        lines.push(std::mem::replace(&mut line, FOLD_INDENT.to_string()));
+        rest = rest[next..].trim_start();
     }
+    line.push_str(rest);
+    lines.push(line);
+    lines
+}
 
-    run_filtered(reader, &mode, &filters);
+/// Break points of a header value for `fold_header`, skipping quoted strings
+/// the way `split_header_list` does: (end of this line, start of the next,
+/// characters up to the end), with byte offsets into `value`.
+fn fold_points(value: &str) -> impl Iterator<Item = (usize, usize, usize)> + '_ {
+    let mut in_quotes = false;
+    let mut escaped = false;
+    value
+        .char_indices()
+        .enumerate()
+        .filter_map(move |(n, (i, c))| {
+            if escaped {
+                escaped = false;
+                return None;
+            }
+            match c {
+                '\\' if in_quotes => escaped = true,
+                '"' => in_quotes = !in_quotes,
+                _ if in_quotes => {}
+                ',' | ';' => return Some((i + 1, i + 1, n + 1)),
+                ' ' | '\t' if i > 0 => return Some((i, i + 1, n)),
+                _ => {}
+            }
+            None
+        })
+}
+
+fn output_body(w: &mut dyn Write, msg: &ParsedSipMessage, part: Option<&str>) -> io::Result<()> {
+    match part {
+        Some(content_type) => {
+            for body in select_parts(msg, content_type) {
+                print_text(w, &decode_bom_text(&body))?;
+            }
+            Ok(())
+        }
+        None if !msg.body.is_empty() => print_text(w, &msg.body_text_decoded()),
+        None => Ok(()),
+    }
+}
+
+/// Canonical hexdump: offset, 16 bytes in two groups of 8, ASCII gutter,
+/// then the total length on its own line (`hexdump -C` layout).
+fn hexdump(data: &[u8], w: &mut dyn Write) -> io::Result<()> {
+    for (i, chunk) in data.chunks(16).enumerate() {
+        let mut line = format!("{:08x}  ", i * 16);
+        for j in 0..16 {
+            match chunk.get(j) {
+                Some(b) => line.push_str(&format!("{b:02x} ")),
+                None => line.push_str("   "),
+            }
+            if j == 7 {
+                line.push(' ');
+            }
+        }
+        let ascii: String = chunk
+            .iter()
+            .map(|&b| {
+                if b.is_ascii_graphic() || b == b' ' {
+                    b as char
+                } else {
+                    '.'
+                }
+            })
+            .collect();
+        writeln!(w, "{line} |{ascii}|")?;
+    }
+    writeln!(w, "{:08x}", data.len())
+}
+
+/// Decoded bodies of the parts whose Content-Type contains `content_type` (lowercase).
+/// Non-multipart messages are matched against their top-level Content-Type.
+fn select_parts(msg: &ParsedSipMessage, content_type: &str) -> Vec<Vec<u8>> {
+    let matches =
+        |ct: Option<&str>| ct.is_some_and(|ct| ct.to_ascii_lowercase().contains(content_type));
+    match msg.body_parts() {
+        Some(parts) => parts
+            .into_iter()
+            .filter(|p| matches(p.content_type()))
+            .map(|p| p.decoded_body().into_owned())
+            .filter(|b| !b.is_empty())
+            .collect(),
+        None if matches(msg.content_type()) && !msg.body.is_empty() => vec![msg.body.clone()],
+        None => Vec::new(),
+    }
+}
+
+/// Write a message as a single dump frame, parseable by `FrameIterator`.
+fn write_dump_frame(w: &mut dyn Write, msg: &SipMessage) -> io::Result<()> {
+    w.write_all(msg.header_line().as_bytes())?;
+    w.write_all(b"\n")?;
+    w.write_all(&msg.content)?;
+    w.write_all(b"\x0B\n")
+}
+
+/// The message as it should be displayed, with `--canonical-reason`,
+/// `--anonymize`, `--replace-header`, `--tz-offset` and `--swap-direction`
+/// applied.
+fn displayed<'a>(out: &OutputOptions, msg: &'a ParsedSipMessage) -> Cow<'a, ParsedSipMessage> {
+    let mut msg = Cow::Borrowed(msg);
+    if out.canonical_reason && msg.is_response() {
+        let mut canonical = msg.as_ref().clone();
+        if canonical.canonicalize_reason() {
+            msg = Cow::Owned(canonical);
+        }
+    }
+    if let Some(ref anonymizer) = out.anonymizer {
+        msg = Cow::Owned(anonymizer.apply(&msg));
+    }
+    for (name, value) in &out.replace_headers {
+        if msg.has_header(name) {
+            msg.to_mut().replace_header(name, value);
+        }
+    }
+    if let Some(offset) = out.tz_offset {
+        msg.to_mut().timestamp = msg.timestamp.shifted(offset);
+    }
+    if out.swap_direction {
+        msg.to_mut().direction = msg.direction.reversed();
+    }
+    msg
+}
+
+fn output_message(
+    w: &mut dyn Write,
+    out: &OutputOptions,
+    raw: &SipMessage,
+    msg: &ParsedSipMessage,
+) -> io::Result<()> {
+    let msg = &*displayed(out, msg);
+    match &out.mode {
+        OutputMode::Summary => match &out.format {
+            Some(template) => writeln!(w, "{}", template.render(msg, out.color)),
+            None => {
+                let mut columns = summary_columns(msg, out.color).to_vec();
+                if out.show_frames {
+                    columns.push(format!("frames={}", msg.frame_count));
+                }
+                if out.show_bytes {
+                    columns.push(format!("bytes={}", raw.content.len()));
+                }
+                let sep = if out.align { COLUMN_SEP } else { " " };
+                writeln!(w, "{}", columns.join(sep))
+            }
+        },
+        OutputMode::Full => output_full(w, msg, out.color),
+        OutputMode::Headers { wrap } => output_headers(w, msg, out.color, *wrap),
+        OutputMode::Body { part } => output_body(w, msg, part.as_deref()),
+        OutputMode::ExportDump if out.anonymizer.is_some() || !out.replace_headers.is_empty() => {
+            write_dump_frame(
+                w,
+                &SipMessage {
+                    address: msg.address.clone(),
+                    content: msg.to_bytes(),
+                    ..raw.clone()
+                },
+            )
+        }
+        OutputMode::ExportDump => write_dump_frame(w, raw),
+        OutputMode::Hex => {
+            writeln!(w, "{}", format_frame_header(msg, out.color))?;
+            hexdump(&msg.to_bytes(), w)
+        }
+        OutputMode::Csv => writeln!(w, "{}", format_csv(msg)),
+        OutputMode::OnlyMatching { re, body } => {
+            let text = if *body {
+                msg.body_text()
+            } else {
+                Cow::Owned(msg.to_string_lossy())
+            };
+            for m in re.find_iter(&text) {
+                writeln!(w, "{} {}", msg.timestamp, m.as_str())?;
+            }
+            Ok(())
+        }
+    }
+}
+
+fn output_keepalive(w: &mut dyn Write, out: &OutputOptions, raw: &SipMessage) -> io::Result<()> {
+    let masked;
+    let raw = match &out.anonymizer {
+        Some(anonymizer) => {
+            masked = SipMessage {
+                address: anonymizer.mask_ips(&raw.address).into_owned(),
+                ..raw.clone()
+            };
+            &masked
+        }
+        None => raw,
+    };
+    match &out.mode {
+        OutputMode::ExportDump => write_dump_frame(w, raw),
+        _ if out.tz_offset.is_some() || out.swap_direction => {
+            let offset = out.tz_offset.unwrap_or(0);
+            let shown = SipMessage {
+                timestamp: raw.timestamp.shifted(offset),
+                last_timestamp: raw.last_timestamp.shifted(offset),
+                direction: if out.swap_direction {
+                    raw.direction.reversed()
+                } else {
+                    raw.direction
+                },
+                ..raw.clone()
+            };
+            writeln!(w, "{}", format_keepalive(&shown, out.color))
+        }
+        _ => writeln!(w, "{}", format_keepalive(raw, out.color)),
+    }
+}
+
+/// Log and skip a recoverable error; a fatal one (the input failing to read)
+/// ends the run instead of being retried on every iteration.
+fn skip_recoverable(e: ParseError, what: &str) -> io::Result<()> {
+    if !e.is_recoverable() {
+        return Err(io::Error::other(e));
+    }
+    info!("{what}: {e}");
+    Ok(())
+}
+
+fn run_frames(
+    frames: impl Iterator<Item = Result<Frame, ParseError>>,
+    w: &mut dyn Write,
+    hex: bool,
+    swap_direction: bool,
+) -> io::Result<()> {
+    for result in frames {
+        match result {
+            Ok(frame) => output_frame(w, frame, hex, swap_direction)?,
+            Err(e) => skip_recoverable(e, "frame error")?,
+        }
+    }
+    Ok(())
+}
+
+/// `run_frames` for dump input, also showing the `trailer` bytes read past
+/// each frame's boundary, for debugging how the next frame was found.
+fn run_frames_with_trailer(
+    mut frames: FrameIterator<Box<dyn Read>>,
+    w: &mut dyn Write,
+    hex: bool,
+    swap_direction: bool,
+    trailer: usize,
+) -> io::Result<()> {
+    while let Some(result) = frames.next() {
+        match result {
+            Ok(frame) => {
+                output_frame(w, frame, hex, swap_direction)?;
+                let after = frames
+                    .peek(trailer)
+                    .map_err(|e| io::Error::other(ParseError::Io(e)))?;
+                writeln!(w, "trailer:")?;
+                hexdump(after, w)?;
+            }
+            Err(e) => skip_recoverable(e, "frame error")?,
+        }
+    }
+    Ok(())
+}
+
+fn output_frame(
+    w: &mut dyn Write,
+    mut frame: Frame,
+    hex: bool,
+    swap_direction: bool,
+) -> io::Result<()> {
+    if swap_direction {
+        frame.direction = frame.direction.reversed();
+    }
+    // Shown without the dump format's trailing colon
+    let header = frame.header_line();
+    writeln!(w, "{}", header.strip_suffix(':').unwrap_or(&header))?;
+    if hex {
+        hexdump(&frame.content, w)
+    } else {
+        print_lossy(w, &frame.content)
+    }
+}
+
+fn run_raw(
+    messages: Messages,
+    w: &mut dyn Write,
+    hex: bool,
+    swap_direction: bool,
+) -> io::Result<()> {
+    for result in messages {
+        match result {
+            Ok(msg) => {
+                let direction = if swap_direction {
+                    msg.direction.reversed()
+                } else {
+                    msg.direction
+                };
+                writeln!(
+                    w,
+                    "{} {} {}/{} at {} ({} frames, {} bytes)",
+                    direction,
+                    direction.preposition(),
+                    msg.transport,
+                    msg.address,
+                    msg.timestamp,
+                    msg.frame_count,
+                    msg.content.len(),
+                )?;
+                if hex {
+                    hexdump(&msg.content, w)?;
+                } else {
+                    print_lossy(w, &msg.content)?;
+                }
+            }
+            Err(e) => skip_recoverable(e, "message error")?,
+        }
+    }
+    Ok(())
+}
+
+fn run_validate(
+    messages: Messages,
+    w: &mut dyn Write,
+    filters: &CompiledFilters,
+    mut selectors: Selectors,
+    color: bool,
+) -> io::Result<bool> {
+    let mut checked = 0usize;
+    let mut invalid = 0usize;
+
+    let options = filters.parse_options();
+    for result in messages.map(|r| r.and_then(|raw| raw.parse_with(options))) {
+        let msg = match result {
+            Ok(m) => m,
+            Err(e) => {
+                skip_recoverable(e, "parse error")?;
+                continue;
+            }
+        };
+        if !filters.matches(&msg) || !selectors.accept(&msg) {
+            continue;
+        }
+        checked += 1;
+        let violations = msg.validate();
+        if violations.is_empty() {
+            continue;
+        }
+        invalid += 1;
+        writeln!(w, "{}", format_summary(&msg, color))?;
+        for v in &violations {
+            writeln!(w, "  {v}")?;
+        }
+    }
+
+    eprintln!("{invalid} of {checked} messages with violations");
+    Ok(invalid == 0)
+}
+
+/// Differing lines shown per message by `--verify-roundtrip`.
+const MAX_ROUNDTRIP_DIFF_LINES: usize = 5;
+
+/// Line-by-line comparison of dump bytes against the rebuilt message, as
+/// `@@ line N @@` hunks with `-original` and `+rebuilt` lines. Empty when
+/// identical.
+fn roundtrip_diff(original: &[u8], rebuilt: &[u8]) -> Vec<String> {
+    if original == rebuilt {
+        return Vec::new();
+    }
+    let original: Vec<&[u8]> = original.split(|&b| b == b'\n').collect();
+    let rebuilt: Vec<&[u8]> = rebuilt.split(|&b| b == b'\n').collect();
+    // Line endings are only shown when they are the difference
+    let show = |line: &[u8], other: Option<&&[u8]>| {
+        let trimmed = line.strip_suffix(b"\r").unwrap_or(line);
+        let other = other.map(|o| o.strip_suffix(b"\r").unwrap_or(o));
+        let line = if other == Some(trimmed) {
+            line
+        } else {
+            trimmed
+        };
+        String::from_utf8_lossy(line).escape_debug().to_string()
+    };
+
+    let mut out = Vec::new();
+    let mut differing = 0;
+    for i in 0..original.len().max(rebuilt.len()) {
+        let (a, b) = (original.get(i), rebuilt.get(i));
+        if a == b {
+            continue;
+        }
+        differing += 1;
+        if differing > MAX_ROUNDTRIP_DIFF_LINES {
+            continue;
+        }
+        out.push(format!("@@ line {} @@", i + 1));
+        if let Some(a) = a {
+            out.push(format!("-{}", show(a, b)));
+        }
+        if let Some(b) = b {
+            out.push(format!("+{}", show(b, a)));
+        }
+    }
+    if differing > MAX_ROUNDTRIP_DIFF_LINES {
+        out.push(format!(
+            "... {} more differing lines",
+            differing - MAX_ROUNDTRIP_DIFF_LINES
+        ));
+    }
+    out
+}
+
+fn run_verify_roundtrip(
+    messages: Messages,
+    w: &mut dyn Write,
+    filters: &CompiledFilters,
+    mut selectors: Selectors,
+    color: bool,
+) -> io::Result<bool> {
+    let mut checked = 0usize;
+    let mut mismatched = 0usize;
+
+    for result in messages {
+        let (msg, raw) =
+            match result.and_then(|raw| Ok((raw.parse_with(filters.parse_options())?, raw))) {
+                Ok(m) => m,
+                Err(e) => {
+                    skip_recoverable(e, "parse error")?;
+                    continue;
+                }
+            };
+        if !filters.matches(&msg) || !selectors.accept(&msg) {
+            continue;
+        }
+        checked += 1;
+        let diff = roundtrip_diff(&raw.content, &msg.to_bytes());
+        if diff.is_empty() {
+            continue;
+        }
+        mismatched += 1;
+        writeln!(w, "{}", format_summary(&msg, color))?;
+        for line in &diff {
+            writeln!(w, "  {line}")?;
+        }
+    }
+
+    eprintln!("{mismatched} of {checked} messages differ when rebuilt");
+    Ok(mismatched == 0)
+}
+
+fn run_list_call_ids(
+    messages: Messages,
+    w: &mut dyn Write,
+    filters: &CompiledFilters,
+    mut selectors: Selectors,
+) -> io::Result<()> {
+    let mut call_ids = CallIdList::default();
+    let options = filters.parse_options();
+    for result in messages.map(|r| r.and_then(|raw| raw.parse_with(options))) {
+        match result {
+            Ok(msg) => {
+                if filters.matches(&msg) && selectors.accept(&msg) {
+                    call_ids.add(&msg);
+                }
+            }
+            Err(e) => skip_recoverable(e, "parse error")?,
+        }
+    }
+    for cid in &call_ids.order {
+        writeln!(w, "{cid}")?;
+    }
+    Ok(())
+}
+
+/// Message size distribution, in power-of-two or fixed-width buckets.
+struct SizeHistogram {
+    width: Option<usize>,
+    buckets: BTreeMap<usize, usize>,
+}
+
+impl SizeHistogram {
+    /// Smallest power-of-two bucket; everything below lands in `[0-512)`.
+    const MIN_POW2: usize = 512;
+
+    fn new(width: Option<usize>) -> Self {
+        SizeHistogram {
+            width,
+            buckets: BTreeMap::new(),
+        }
+    }
+
+    fn bucket_range(&self, size: usize) -> (usize, usize) {
+        match self.width {
+            Some(w) => (size / w * w, size / w * w + w),
+            None if size < Self::MIN_POW2 => (0, Self::MIN_POW2),
+            None => {
+                let low = 1 << size.ilog2();
+                (low, low * 2)
+            }
+        }
+    }
+
+    fn add(&mut self, size: usize) {
+        let (low, _) = self.bucket_range(size);
+        *self.buckets.entry(low).or_default() += 1;
+    }
+
+    /// One `[low-high): count` line per non-empty bucket, smallest first.
+    fn lines(&self) -> Vec<String> {
+        self.buckets
+            .iter()
+            .map(|(&low, count)| {
+                let (_, high) = self.bucket_range(low);
+                format!("[{low}-{high}): {count}")
+            })
+            .collect()
+    }
+}
+
+/// JSON Schema of the `--stats --json` object; keep in step with `run_stats`.
+const STATS_JSON_SCHEMA: &str = r##"{
+  "$schema": "https://json-schema.org/draft/2020-12/schema",
+  "title": "freeswitch-sofia-trace-parser --stats --json",
+  "type": "object",
+  "required": ["total", "matched", "errors", "sigcomp", "recv", "sent", "methods", "response_codes", "transports"],
+  "properties": {
+    "total": {"$ref": "#/$defs/count", "description": "Messages read"},
+    "matched": {"$ref": "#/$defs/count", "description": "Messages passing the filters"},
+    "errors": {"$ref": "#/$defs/count", "description": "Messages that failed to parse"},
+    "sigcomp": {"$ref": "#/$defs/count", "description": "SigComp compressed messages, not decoded nor counted in errors"},
+    "recv": {"$ref": "#/$defs/count", "description": "Matched messages received"},
+    "sent": {"$ref": "#/$defs/count", "description": "Matched messages sent"},
+    "call_ids": {"$ref": "#/$defs/count", "description": "Distinct Call-IDs, with --list-call-ids"},
+    "retransmissions_suppressed": {"$ref": "#/$defs/count", "description": "With --dedup"},
+    "roundtrip_mismatches": {"$ref": "#/$defs/count", "description": "With --verify-roundtrip"},
+    "via_loop_suspects": {"$ref": "#/$defs/count", "description": "With --loops"},
+    "methods": {
+      "type": "object",
+      "description": "Matched messages by method (CSeq method for responses)",
+      "additionalProperties": {"$ref": "#/$defs/count"}
+    },
+    "response_codes": {
+      "type": "object",
+      "description": "Matched responses by status code",
+      "propertyNames": {"pattern": "^[0-9]+$"},
+      "additionalProperties": {"$ref": "#/$defs/count"}
+    },
+    "transports": {
+      "type": "object",
+      "description": "Frames reassembled into matched messages, by transport",
+      "additionalProperties": {
+        "type": "object",
+        "required": ["frames", "messages"],
+        "properties": {
+          "frames": {"$ref": "#/$defs/count"},
+          "messages": {"$ref": "#/$defs/count"}
+        }
+      }
+    }
+  },
+  "$defs": {
+    "count": {"type": "integer", "minimum": 0}
+  }
+}
+"##;
+
+/// Sent-by host repetitions in one Via stack tolerated by `--loops`.
+const DEFAULT_VIA_LOOP_THRESHOLD: usize = 2;
+
+/// Optional extras of `--stats`.
+struct StatsOptions {
+    sizes: Option<SizeHistogram>,
+    call_ids: Option<CallIdList>,
+    /// Count `--verify-roundtrip` mismatches.
+    roundtrip: bool,
+    /// Count Via loop suspects with this host threshold.
+    loops: Option<usize>,
+    json: bool,
+}
+
+/// `s` as a quoted JSON string.
+fn json_string(s: &str) -> String {
+    let mut out = String::with_capacity(s.len() + 2);
+    out.push('"');
+    for c in s.chars() {
+        match c {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            '\n' => out.push_str("\\n"),
+            '\r' => out.push_str("\\r"),
+            '\t' => out.push_str("\\t"),
+            c if u32::from(c) < 0x20 => out.push_str(&format!("\\u{:04x}", u32::from(c))),
+            c => out.push(c),
+        }
+    }
+    out.push('"');
+    out
+}
+
+fn run_stats(
+    messages: Messages,
+    w: &mut dyn Write,
+    filters: &CompiledFilters,
+    mut selectors: Selectors,
+    opts: StatsOptions,
+) -> io::Result<()> {
+    let StatsOptions {
+        mut sizes,
+        mut call_ids,
+        roundtrip,
+        loops,
+        json,
+    } = opts;
+    let mut method_counts: HashMap<String, usize> = HashMap::new();
+    let mut status_counts: HashMap<u16, usize> = HashMap::new();
+    let mut direction_counts: HashMap<Direction, usize> = HashMap::new();
+    // Frames reassembled into messages, per transport
+    let mut reassembly: BTreeMap<String, (usize, usize)> = BTreeMap::new();
+    let mut total: usize = 0;
+    let mut matched: usize = 0;
+    let mut errors: usize = 0;
+    let mut sigcomp: usize = 0;
+    let mut mismatches: usize = 0;
+    let mut loop_suspects: usize = 0;
+
+    for result in messages {
+        total += 1;
+        if result.as_ref().is_ok_and(SipMessage::is_sigcomp) {
+            sigcomp += 1;
+            continue;
+        }
+        match result.and_then(|raw| Ok((raw.parse_with(filters.parse_options())?, raw))) {
+            Ok((msg, raw)) => {
+                if !filters.matches(&msg) {
+                    continue;
+                }
+                if !selectors.accept(&msg) {
+                    continue;
+                }
+                matched += 1;
+                if let Some(ref mut hist) = sizes {
+                    hist.add(raw.content.len());
+                }
+                if roundtrip && raw.content != msg.to_bytes() {
+                    mismatches += 1;
+                }
+                if loops.is_some_and(|threshold| msg.via_loop_suspect(threshold)) {
+                    loop_suspects += 1;
+                }
+                if let Some(ref mut ids) = call_ids {
+                    ids.add(&msg);
+                }
+                *direction_counts.entry(msg.direction).or_default() += 1;
+                let (frames, messages) = reassembly.entry(msg.transport.to_string()).or_default();
+                *frames += msg.frame_count;
+                *messages += 1;
+                if let Some(code) = msg.status_code() {
+                    *status_counts.entry(code).or_default() += 1;
+                }
+                if let Some(method) = msg.method() {
+                    *method_counts.entry(method.to_string()).or_default() += 1;
+                }
+            }
+            Err(e) if e.is_recoverable() => errors += 1,
+            Err(e) => return Err(io::Error::other(e)),
+        }
+    }
+
+    if json {
+        // Key names are a stable interface; extend, don't rename
+        let recv = direction_counts.get(&Direction::Recv).copied().unwrap_or(0);
+        let sent = direction_counts.get(&Direction::Sent).copied().unwrap_or(0);
+        write!(
+            w,
+            "{{\"total\":{total},\"matched\":{matched},\"errors\":{errors},\"sigcomp\":{sigcomp},\"recv\":{recv},\"sent\":{sent}"
+        )?;
+        if let Some(ref ids) = call_ids {
+            write!(w, ",\"call_ids\":{}", ids.order.len())?;
+        }
+        if let Some(ref rt) = selectors.retransmits {
+            write!(w, ",\"retransmissions_suppressed\":{}", rt.suppressed)?;
+        }
+        if roundtrip {
+            write!(w, ",\"roundtrip_mismatches\":{mismatches}")?;
+        }
+        if loops.is_some() {
+            write!(w, ",\"via_loop_suspects\":{loop_suspects}")?;
+        }
+        // A response's method is the raw CSeq word, so it may need escaping
+        let methods: BTreeMap<_, _> = method_counts.into_iter().collect();
+        let methods: Vec<_> = methods
+            .iter()
+            .map(|(m, n)| format!("{}:{n}", json_string(m)))
+            .collect();
+        let codes: BTreeMap<_, _> = status_counts.into_iter().collect();
+        let codes: Vec<_> = codes.iter().map(|(c, n)| format!("\"{c}\":{n}")).collect();
+        let transports: Vec<_> = reassembly
+            .iter()
+            .map(|(t, (frames, messages))| {
+                format!("\"{t}\":{{\"frames\":{frames},\"messages\":{messages}}}")
+            })
+            .collect();
+        return writeln!(
+            w,
+            ",\"methods\":{{{}}},\"response_codes\":{{{}}},\"transports\":{{{}}}}}",
+            methods.join(","),
+            codes.join(","),
+            transports.join(",")
+        );
+    }
+
+    writeln!(w, "total: {total}")?;
+    writeln!(w, "matched: {matched}")?;
+    if let Some(ref ids) = call_ids {
+        writeln!(w, "call-ids: {}", ids.order.len())?;
+    }
+    if errors > 0 {
+        writeln!(w, "parse errors: {errors}")?;
+    }
+    if sigcomp > 0 {
+        writeln!(w, "sigcomp (not decoded): {sigcomp}")?;
+    }
+    if let Some(ref rt) = selectors.retransmits {
+        writeln!(w, "retransmissions suppressed: {}", rt.suppressed)?;
+    }
+    if roundtrip {
+        writeln!(w, "roundtrip mismatches: {mismatches}")?;
+    }
+    if loops.is_some() {
+        writeln!(w, "via loop suspects: {loop_suspects}")?;
+    }
+
+    if let Some(&n) = direction_counts.get(&Direction::Recv) {
+        writeln!(w, "recv: {n}")?;
+    }
+    if let Some(&n) = direction_counts.get(&Direction::Sent) {
+        writeln!(w, "sent: {n}")?;
+    }
+
+    let mut methods: Vec<_> = method_counts.into_iter().collect();
+    // Ties by name, so the order doesn't depend on hashing
+    methods.sort_by(|(a, a_count), (b, b_count)| b_count.cmp(a_count).then_with(|| a.cmp(b)));
+    if !methods.is_empty() {
+        writeln!(w, "\nmethods:")?;
+        for (method, count) in &methods {
+            writeln!(w, "  {method}: {count}")?;
+        }
+    }
+
+    let mut statuses: Vec<_> = status_counts.into_iter().collect();
+    statuses.sort_by_key(|&(code, _)| code);
+    if !statuses.is_empty() {
+        writeln!(w, "\nresponse codes:")?;
+        for (code, count) in &statuses {
+            writeln!(w, "  {code}: {count}")?;
+        }
+    }
+
+    if !reassembly.is_empty() {
+        writeln!(w, "\nreassembly:")?;
+        for (transport, (frames, messages)) in &reassembly {
+            writeln!(w, "  {transport}: {frames} frames -> {messages} messages")?;
+        }
+    }
+
+    if let Some(hist) = sizes {
+        writeln!(w, "\nmessage sizes (bytes):")?;
+        for line in hist.lines() {
+            writeln!(w, "  {line}")?;
+        }
+    }
+    Ok(())
+}
+
+/// Whether any message passes the filters, for `--quiet`; stops at the
+/// first one. With `-D` (`dialog` holding `keep_options`), whether any dialog
+/// matched, which takes the whole input.
+fn any_match(
+    messages: Messages,
+    filters: &CompiledFilters,
+    mut selectors: Selectors,
+    dialog: Option<bool>,
+) -> io::Result<bool> {
+    if let Some(keep_options) = dialog {
+        let dedup = selectors.retransmits.is_some();
+        let dialogs = collect_dialogs(messages, filters, keep_options, dedup)?;
+        return Ok(!dialogs.into_matched().is_empty());
+    }
+    let options = ParseOptions {
+        headers_only: !filters.needs_body(),
+        ..filters.parse_options()
+    };
+    for result in messages {
+        let sip_msg = match result {
+            Ok(m) => m,
+            Err(e) => {
+                skip_recoverable(e, "message error")?;
+                continue;
+            }
+        };
+        if filters.rejects_unparsed(&sip_msg) {
+            continue;
+        }
+        match sip_msg.parse_with(options) {
+            Ok(msg) if filters.matches(&msg) && selectors.accept(&msg) => return Ok(true),
+            Ok(_) => {}
+            Err(e) => skip_recoverable(e, "parse error")?,
+        }
+    }
+    Ok(false)
+}
+
+fn run_filtered(
+    messages: Messages,
+    w: &mut dyn Write,
+    out: &OutputOptions,
+    filters: &CompiledFilters,
+    mut selectors: Selectors,
+) -> io::Result<()> {
+    let mut emitted = 0usize;
+    let mut tail: VecDeque<SipMessage> = VecDeque::new();
+    if out.limit == Limit::Head(0) {
+        return Ok(());
+    }
+    // Summary and header output never show the body; skip copying it
+    let with_body = filters.needs_body()
+        || !matches!(
+            out.mode,
+            OutputMode::Summary | OutputMode::Headers { .. } | OutputMode::Csv
+        );
+    let options = ParseOptions {
+        headers_only: !with_body,
+        ..filters.parse_options()
+    };
+    for result in messages.keepalives(out.keepalives) {
+        let sip_msg = match result {
+            Ok(m) => m,
+            Err(e) => {
+                skip_recoverable(e, "message error")?;
+                continue;
+            }
+        };
+        // Keepalive pings count towards --head/--tail like messages
+        let parsed = if sip_msg.is_keepalive() {
+            if !filters.matches_endpoint(sip_msg.direction, &sip_msg.address) {
+                continue;
+            }
+            None
+        } else {
+            if filters.rejects_unparsed(&sip_msg) {
+                continue;
+            }
+            match sip_msg.parse_with(options) {
+                Ok(msg) if filters.matches(&msg) && selectors.accept(&msg) => Some(msg),
+                Ok(_) => continue,
+                Err(e) => {
+                    info!("parse error: {e}");
+                    continue;
+                }
+            }
+        };
+        match out.limit {
+            Limit::Tail(n) => push_bounded(&mut tail, sip_msg, n),
+            Limit::Head(n) => {
+                output_selected(w, out, &sip_msg, parsed.as_ref())?;
+                emitted += 1;
+                if emitted >= n {
+                    break;
+                }
+            }
+            Limit::None => output_selected(w, out, &sip_msg, parsed.as_ref())?,
+        }
+    }
+
+    for sip_msg in &tail {
+        if sip_msg.is_keepalive() {
+            output_keepalive(w, out, sip_msg)?;
+            continue;
+        }
+        match sip_msg.parse_with(options) {
+            Ok(parsed) => output_message(w, out, sip_msg, &parsed)?,
+            Err(e) => info!("parse error on output: {e}"),
+        }
+    }
+    Ok(())
+}
+
+/// Output a message passing `run_filtered`: `parsed`, or without it a
+/// keepalive ping.
+fn output_selected(
+    w: &mut dyn Write,
+    out: &OutputOptions,
+    raw: &SipMessage,
+    parsed: Option<&ParsedSipMessage>,
+) -> io::Result<()> {
+    match parsed {
+        Some(parsed) => output_message(w, out, raw, parsed),
+        None => output_keepalive(w, out, raw),
+    }
+}
+
+/// Append to a ring buffer holding at most `n` items, dropping the oldest.
+fn push_bounded<T>(buf: &mut VecDeque<T>, item: T, n: usize) {
+    if n == 0 {
+        return;
+    }
+    if buf.len() == n {
+        buf.pop_front();
+    }
+    buf.push_back(item);
+}
+
+/// Apply `--head`/`--tail` to matched dialogs, ordered by their first message.
+fn limit_dialogs(mut dialogs: Vec<DialogState>, limit: Limit) -> Vec<DialogState> {
+    if limit == Limit::None {
+        return dialogs;
+    }
+    dialogs.sort_by_key(DialogState::first_timestamp);
+    match limit {
+        Limit::Head(n) => dialogs.truncate(n),
+        Limit::Tail(n) => {
+            let skip = dialogs.len().saturating_sub(n);
+            dialogs.drain(..skip);
+        }
+        Limit::None => {}
+    }
+    dialogs
+}
+
+#[derive(Default)]
+struct DialogState {
+    call_id: String,
+    /// Kept parsed so output does not parse each message a second time.
+    messages: Vec<(SipMessage, ParsedSipMessage)>,
+    /// Input position of each of `messages`, for `--dialog-order input`.
+    input_order: Vec<u64>,
+    matched: bool,
+    /// Exchanged a message with an `--exclude-address` peer; never output.
+    excluded: bool,
+    saw_bye: bool,
+    saw_bye_response: bool,
+    /// CSeq number of the first INVITE, to recognize its final response.
+    invite_cseq: Option<u32>,
+    saw_cancel: bool,
+    saw_cancel_ok: bool,
+    /// Non-2xx final response to the initial INVITE.
+    saw_invite_failure: bool,
+}
+
+impl DialogState {
+    fn first_timestamp(&self) -> Option<(u16, u8, u8, u8, u8, u8, u32)> {
+        self.messages.first().map(|(m, _)| m.timestamp.sort_key())
+    }
+
+    /// Torn down by BYE, by an answered CANCEL, or by a failed initial INVITE.
+    fn is_terminated(&self) -> bool {
+        (self.saw_bye && self.saw_bye_response)
+            || (self.saw_cancel && self.saw_cancel_ok)
+            || self.saw_invite_failure
+    }
+
+    fn track(&mut self, parsed: &ParsedSipMessage) {
+        let mut cseq = parsed.cseq().unwrap_or("").split_whitespace();
+        let number = cseq.next().and_then(|n| n.parse::<u32>().ok());
+        let method = cseq.next().unwrap_or("");
+        match parsed.status_code() {
+            None => match parsed.request_method() {
+                Some(m) if m.eq_ignore_ascii_case("BYE") => self.saw_bye = true,
+                Some(m) if m.eq_ignore_ascii_case("CANCEL") => self.saw_cancel = true,
+                Some(m) if m.eq_ignore_ascii_case("INVITE") && self.invite_cseq.is_none() => {
+                    self.invite_cseq = number;
+                }
+                _ => {}
+            },
+            Some(_) if method.eq_ignore_ascii_case("BYE") => self.saw_bye_response = true,
+            Some(200) if method.eq_ignore_ascii_case("CANCEL") => self.saw_cancel_ok = true,
+            // 401/407 challenges are retried on the same Call-ID
+            Some(code)
+                if code >= 300
+                    && code != 401
+                    && code != 407
+                    && method.eq_ignore_ascii_case("INVITE")
+                    && number.is_some()
+                    && number == self.invite_cseq =>
+            {
+                self.saw_invite_failure = true;
+            }
+            Some(_) => {}
+        }
+    }
+}
+
+/// Single-pass collection of messages by Call-ID, tracking which dialogs matched.
+struct DialogCollector<'a> {
+    filters: &'a CompiledFilters,
+    keep_options: bool,
+    /// With `--dedup`, retransmissions are dropped before joining a dialog.
+    retransmits: Option<RetransmitFilter>,
+    dialogs: HashMap<String, DialogState>,
+    /// Messages pushed so far.
+    seq: u64,
+}
+
+impl<'a> DialogCollector<'a> {
+    fn new(filters: &'a CompiledFilters, keep_options: bool) -> Self {
+        DialogCollector {
+            filters,
+            keep_options,
+            retransmits: None,
+            dialogs: HashMap::new(),
+            seq: 0,
+        }
+    }
+
+    fn push(&mut self, sip_msg: SipMessage, parsed: ParsedSipMessage) {
+        let seq = self.seq;
+        self.seq += 1;
+        if self.filters.is_method_excluded(&parsed) {
+            return;
+        }
+
+        let call_id = match parsed.call_id() {
+            Some(cid) if !self.filters.is_call_id_excluded(cid) => cid.to_string(),
+            _ => return,
+        };
+
+        // Excluded OPTIONS never start a dialog, so OPTIONS-only Call-IDs
+        // are still never buffered
+        if self.filters.is_options_excluded(&parsed)
+            && !(self.keep_options && self.dialogs.contains_key(&call_id))
+        {
+            return;
+        }
+
+        // Remember the Call-ID so the rest of the dialog is dropped too
+        if self.filters.is_address_excluded(&parsed.address) {
+            let state = self
+                .dialogs
+                .entry(call_id.clone())
+                .or_insert_with(|| DialogState {
+                    call_id: call_id.clone(),
+                    ..Default::default()
+                });
+            state.excluded = true;
+            state.messages.clear();
+            state.input_order.clear();
+            return;
+        }
+        if self.dialogs.get(&call_id).is_some_and(|s| s.excluded) {
+            return;
+        }
+        if let Some(ref mut rt) = self.retransmits {
+            if rt.is_retransmission(&parsed) {
+                return;
+            }
+        }
+
+        let is_match = self.filters.matches(&parsed);
+
+        // An ACK never starts a dialog: an unmatched one with no dialog is
+        // usually the hop-by-hop ACK of a failure that was already pruned
+        if parsed.is_ack() && !is_match && !self.dialogs.contains_key(&call_id) {
+            return;
+        }
+
+        let state = self
+            .dialogs
+            .entry(call_id.clone())
+            .or_insert_with(|| DialogState {
+                call_id: call_id.clone(),
+                ..Default::default()
+            });
+
+        if is_match {
+            state.matched = true;
+        }
+        state.track(&parsed);
+
+        state.messages.push((sip_msg, parsed));
+        state.input_order.push(seq);
+
+        // Prune: dialog terminated and never matched
+        if state.is_terminated() && !state.matched {
+            self.dialogs.remove(&call_id);
+        }
+    }
+
+    fn into_matched(self) -> Vec<DialogState> {
+        self.dialogs
+            .into_values()
+            .filter(|s| s.matched && !s.excluded)
+            .collect()
+    }
+}
+
+fn collect_dialogs<'a>(
+    messages: Messages,
+    filters: &'a CompiledFilters,
+    keep_options: bool,
+    dedup: bool,
+) -> io::Result<DialogCollector<'a>> {
+    let mut collector = DialogCollector::new(filters, keep_options);
+    collector.retransmits = dedup.then(RetransmitFilter::default);
+    let options = filters.parse_options();
+    for result in messages {
+        let result = result.and_then(|raw| Ok((raw.parse_with(options)?, raw)));
+        match result {
+            Ok((parsed, sip_msg)) => collector.push(sip_msg, parsed),
+            Err(e) => skip_recoverable(e, "parse error")?,
+        }
+    }
+    Ok(collector)
+}
+
+fn run_dialog(
+    messages: Messages,
+    w: &mut dyn Write,
+    out: &OutputOptions,
+    filters: &CompiledFilters,
+    keep_options: bool,
+    dedup: bool,
+    order: DialogOrder,
+) -> io::Result<()> {
+    let collector = collect_dialogs(messages, filters, keep_options, dedup)?;
+
+    let mut dialogs = limit_dialogs(collector.into_matched(), out.limit);
+    if order == DialogOrder::Grouped {
+        sort_dialogs_by_start(&mut dialogs);
+    }
+    let mut matched_messages: Vec<(u64, SipMessage, ParsedSipMessage)> = Vec::new();
+    for state in dialogs {
+        let input_order = state.input_order.into_iter();
+        matched_messages.extend(
+            input_order
+                .zip(state.messages)
+                .map(|(seq, (sip_msg, parsed))| (seq, sip_msg, parsed)),
+        );
+    }
+    match order {
+        // Chronological across Call-IDs
+        DialogOrder::Timestamp => matched_messages.sort_by_key(|(_, m, _)| m.timestamp.sort_key()),
+        DialogOrder::Input => matched_messages.sort_by_key(|(seq, _, _)| *seq),
+        DialogOrder::Grouped => {}
+    }
+
+    for (_, sip_msg, parsed) in &matched_messages {
+        output_message(w, out, sip_msg, parsed)?;
+    }
+    Ok(())
+}
+
+/// Order dialogs by their first message, then by Call-ID so equal start
+/// times do not come out in hash order.
+fn sort_dialogs_by_start(dialogs: &mut [DialogState]) {
+    dialogs.sort_by(|a, b| {
+        a.first_timestamp()
+            .cmp(&b.first_timestamp())
+            .then_with(|| a.call_id.cmp(&b.call_id))
+    });
+}
+
+/// Longest file name stem `--split-by-call-id` derives from a Call-ID.
+const MAX_CALL_ID_FILE_STEM: usize = 200;
+
+/// File name stem for a Call-ID: characters other than ASCII letters, digits
+/// and `-._@+` become `_`, and a leading dot or empty name is prefixed
+/// with `_` so names are never hidden, empty or `..`.
+fn call_id_file_stem(call_id: &str) -> String {
+    let mut stem: String = call_id
+        .chars()
+        .take(MAX_CALL_ID_FILE_STEM)
+        .map(|c| {
+            if c.is_ascii_alphanumeric() || "-._@+".contains(c) {
+                c
+            } else {
+                '_'
+            }
+        })
+        .collect();
+    if stem.is_empty() || stem.starts_with('.') {
+        stem.insert(0, '_');
+    }
+    stem
+}
+
+/// Collect dialogs like `--dialog`, then write each one to its own dump file
+/// in `dir`, printing the paths. Call-IDs that sanitize to the same name get
+/// `-2`, `-3`... suffixes; existing files are overwritten.
+fn run_split_by_call_id(
+    messages: Messages,
+    w: &mut dyn Write,
+    dir: &Path,
+    out: &OutputOptions,
+    filters: &CompiledFilters,
+    keep_options: bool,
+    dedup: bool,
+) -> io::Result<()> {
+    let collector = collect_dialogs(messages, filters, keep_options, dedup)?;
+    let mut dialogs = limit_dialogs(collector.into_matched(), out.limit);
+    // Call-ID breaks ties so suffixes do not depend on hash order
+    sort_dialogs_by_start(&mut dialogs);
+
+    let with_path =
+        |e: io::Error, path: &Path| io::Error::new(e.kind(), format!("{}: {e}", path.display()));
+    std::fs::create_dir_all(dir).map_err(|e| with_path(e, dir))?;
+    let mut used: HashSet<String> = HashSet::new();
+    for dialog in dialogs {
+        let stem = call_id_file_stem(&dialog.call_id);
+        // Case-insensitive file systems would merge names differing in case
+        let name = (1..)
+            .map(|n| match n {
+                1 => format!("{stem}.dump"),
+                n => format!("{stem}-{n}.dump"),
+            })
+            .find(|name| used.insert(name.to_ascii_lowercase()))
+            .unwrap_or_default();
+        let path = dir.join(name);
+        let file = File::create(&path).map_err(|e| with_path(e, &path))?;
+        let mut file = io::BufWriter::new(file);
+        for (sip_msg, parsed) in &dialog.messages {
+            output_message(&mut file, out, sip_msg, parsed).map_err(|e| with_path(e, &path))?;
+        }
+        file.flush().map_err(|e| with_path(e, &path))?;
+        writeln!(w, "{}", path.display())?;
+    }
+    Ok(())
+}
+
+fn main() {
+    let cli = Cli::parse();
+    init_tracing(cli.verbose, cli.progress);
+
+    if cli.dialog && (cli.raw || cli.frames) {
+        eprintln!("--dialog is incompatible with --raw and --frames");
+        process::exit(2);
+    }
+
+    if cli.first_per_call_id && (cli.dialog || cli.raw || cli.frames) {
+        eprintln!("--first-per-call-id is incompatible with --dialog, --raw and --frames");
+        process::exit(2);
+    }
+
+    if cli.dialog && (cli.stats || cli.validate || cli.list_call_ids) {
+        eprintln!("--dialog is incompatible with --stats, --validate and --list-call-ids");
+        process::exit(2);
+    }
+
+    if (cli.head.is_some() || cli.tail.is_some())
+        && (cli.raw || cli.frames || cli.stats || cli.validate || cli.list_call_ids)
+    {
+        eprintln!(
+            "--head/--tail are incompatible with --raw, --frames, --stats, --validate and --list-call-ids"
+        );
+        process::exit(2);
+    }
+
+    if cli.keepalives
+        && (cli.dialog
+            || cli.stats
+            || cli.validate
+            || cli.list_call_ids
+            || cli.full
+            || cli.headers
+            || cli.body)
+    {
+        eprintln!("--keepalives only works with the summary and --export-dump outputs");
+        process::exit(2);
+    }
+
+    if cli.anonymize && (cli.raw || cli.frames || cli.validate || cli.list_call_ids) {
+        eprintln!(
+            "--anonymize is incompatible with --raw, --frames, --validate and --list-call-ids"
+        );
+        process::exit(2);
+    }
+
+    if cli.input_format != InputFormat::Pcap
+        && (!cli.pcap_port.is_empty() || cli.pcap_local.is_some())
+    {
+        eprintln!("--pcap-port and --pcap-local require --input-format pcap");
+        process::exit(2);
+    }
+
+    if cli.input_format == InputFormat::Pcap && cli.files.len() > 1 {
+        eprintln!("--input-format pcap reads a single capture file");
+        process::exit(2);
+    }
+    if cli.input_format == InputFormat::Pcap && cli.strip_ansi {
+        eprintln!("--strip-ansi only applies to dump input");
+        process::exit(2);
+    }
+    if cli.input_format == InputFormat::Pcap && cli.strip_grep_prefix {
+        eprintln!("--strip-grep-prefix only applies to dump input");
+        process::exit(2);
+    }
+    if cli.input_format == InputFormat::Pcap && cli.with_trailer.is_some() {
+        eprintln!("--with-trailer only applies to dump input");
+        process::exit(2);
+    }
+
+    let mut w: Box<dyn Write> = match &cli.output {
+        Some(path) => match File::create(path) {
+            Ok(f) => Box::new(io::BufWriter::new(f)),
+            Err(e) => {
+                eprintln!("{path}: {e}");
+                process::exit(2);
+            }
+        },
+        None => Box::new(io::stdout().lock()),
+    };
+
+    let result = if cli.align {
+        let mut aligned = AlignedColumns::new(&mut *w);
+        run(&cli, &mut aligned).and_then(|ok| aligned.finish().map(|()| ok))
+    } else {
+        run(&cli, &mut *w)
+    };
+
+    match result.and_then(|ok| w.flush().map(|()| ok)) {
+        Ok(true) => {}
+        Ok(false) => process::exit(1),
+        Err(e) if e.kind() == io::ErrorKind::BrokenPipe => {}
+        // 1 means "no match" to scripts using --quiet; errors are told apart
+        Err(e) if e.get_ref().is_some_and(|inner| inner.is::<ParseError>()) => {
+            eprintln!("read error: {e}");
+            process::exit(2);
+        }
+        Err(e) => {
+            eprintln!("write error: {e}");
+            process::exit(2);
+        }
+    }
+}
+
+/// Dispatch to the selected output mode; `Ok(false)` means `--validate` found
+/// violations, `--verify-roundtrip` found mismatches or `--quiet` found no
+/// match.
+fn run(cli: &Cli, w: &mut dyn Write) -> io::Result<bool> {
+    if cli.print_json_schema {
+        w.write_all(STATS_JSON_SCHEMA.as_bytes())?;
+        return Ok(true);
+    }
+
+    if cli.frames {
+        match cli.input_format {
+            InputFormat::Dump => {
+                let frames = FrameIterator::new(open_input(cli));
+                match cli.with_trailer {
+                    Some(n) => run_frames_with_trailer(frames, w, cli.hex, cli.swap_direction, n)?,
+                    None => run_frames(frames, w, cli.hex, cli.swap_direction)?,
+                }
+            }
+            InputFormat::Pcap => run_frames(
+                pcap_frames(cli, open_input(cli)),
+                w,
+                cli.hex,
+                cli.swap_direction,
+            )?,
+        }
+        return Ok(true);
+    }
+
+    let configure = |messages: MessageIterator<Box<dyn Read>>| {
+        let messages = messages
+            .with_max_message_size(cli.max_message_size)
+            .stamp_last_frame(cli.last_frame_timestamp)
+            .ws_framing(cli.ws_framing);
+        let messages = match cli.gap_warn {
+            Some(threshold) => messages.gap_warn(threshold),
+            None => messages,
+        };
+        match cli.order_by_start {
+            Some(secs) => {
+                messages.order_by_start(secs.map_or(DEFAULT_REORDER_WINDOW, Duration::from_secs))
+            }
+            None => messages,
+        }
+    };
+    let messages = || {
+        let streams = open_inputs(cli)
+            .into_iter()
+            .map(|input| match cli.input_format {
+                InputFormat::Dump => MessageIterator::new(input),
+                InputFormat::Pcap => MessageIterator::from(pcap_frames(cli, input)),
+            });
+        let window = if cli.dedup_frames {
+            DEFAULT_DEDUP_WINDOW
+        } else {
+            0
+        };
+        Messages::new(streams.map(configure).collect()).dedup_frames(window)
+    };
+
+    if cli.raw {
+        run_raw(messages(), w, cli.hex, cli.swap_direction)?;
+        return Ok(true);
+    }
+
+    let filters = compile_filters(cli);
+    let out = output_options(cli);
+
+    if cli.quiet {
+        let dialog = cli.dialog.then_some(cli.keep_options_in_dialog);
+        return any_match(messages(), &filters, Selectors::new(cli), dialog);
+    }
+
+    if let Some(dir) = &cli.split_by_call_id {
+        let keep_options = cli.keep_options_in_dialog;
+        run_split_by_call_id(messages(), w, dir, &out, &filters, keep_options, cli.dedup)?;
+        return Ok(true);
+    }
+
+    if matches!(out.mode, OutputMode::Csv) {
+        writeln!(w, "{CSV_HEADER}")?;
+    }
+
+    if cli.dialog {
+        let keep_options = cli.keep_options_in_dialog;
+        run_dialog(
+            messages(),
+            w,
+            &out,
+            &filters,
+            keep_options,
+            cli.dedup,
+            cli.dialog_order,
+        )?;
+        return Ok(true);
+    }
+
+    let messages = messages();
+
+    if cli.stats {
+        let opts = StatsOptions {
+            sizes: cli.size_histogram.map(|width| {
+                SizeHistogram::new(width.map(|w| usize::try_from(w).unwrap_or(usize::MAX)))
+            }),
+            call_ids: cli.list_call_ids.then(CallIdList::default),
+            roundtrip: cli.verify_roundtrip,
+            loops: cli
+                .loops
+                .map(|threshold| threshold.unwrap_or(DEFAULT_VIA_LOOP_THRESHOLD)),
+            json: cli.json,
+        };
+        run_stats(messages, w, &filters, Selectors::new(cli), opts)?;
+        return Ok(true);
+    }
+
+    if cli.list_call_ids {
+        run_list_call_ids(messages, w, &filters, Selectors::new(cli))?;
+        return Ok(true);
+    }
+
+    if cli.validate {
+        return run_validate(messages, w, &filters, Selectors::new(cli), out.color);
+    }
+
+    if cli.verify_roundtrip {
+        return run_verify_roundtrip(messages, w, &filters, Selectors::new(cli), out.color);
+    }
+
+    run_filtered(messages, w, &out, &filters, Selectors::new(cli))?;
+    Ok(true)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn udp_message(content: &[u8], sec: u8) -> ParsedSipMessage {
+        udp_raw(content, sec).parse().unwrap()
+    }
+
+    fn udp_raw(content: &[u8], sec: u8) -> SipMessage {
+        SipMessage {
+            direction: Direction::Recv,
+            transport: Transport::Udp,
+            address: "10.0.0.1:5060".into(),
+            timestamp: Timestamp::TimeOnly {
+                hour: 12,
+                min: 0,
+                sec,
+                usec: 0,
+            },
+            last_timestamp: Timestamp::TimeOnly {
+                hour: 12,
+                min: 0,
+                sec,
+                usec: 0,
+            },
+            content: content.to_vec(),
+            frame_count: 1,
+        }
+    }
+
+    /// Single-stream `Messages` over an in-memory dump.
+    fn messages_from(dump: &[u8]) -> Messages {
+        Messages::new(vec![MessageIterator::new(Box::new(io::Cursor::new(
+            dump.to_vec(),
+        )))])
+    }
+
+    /// Serves the wrapped dump, then fails every read after it.
+    struct FailAfter(io::Cursor<Vec<u8>>);
+
+    impl Read for FailAfter {
+        fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+            match self.0.read(buf)? {
+                0 => Err(io::Error::other("disk went away")),
+                n => Ok(n),
+            }
+        }
+    }
+
+    fn filters(args: &[&str]) -> CompiledFilters {
+        let argv = std::iter::once("freeswitch-sofia-trace-parser").chain(args.iter().copied());
+        compile_filters(&Cli::parse_from(argv))
+    }
+
+    /// CLI output for `args`, as `main` would write it to stdout.
+    fn run_to_string(args: &[&str]) -> String {
+        let argv = std::iter::once("freeswitch-sofia-trace-parser").chain(args.iter().copied());
+        let mut out = Vec::new();
+        run(&Cli::parse_from(argv), &mut out).unwrap();
+        String::from_utf8(out).unwrap()
+    }
+
+    /// Options every golden run uses: anonymized so the checked-in output
+    /// carries no addresses, numbers or Call-IDs from the samples.
+    const GOLDEN_ARGS: &[&str] = &["--anonymize", "--anonymize-salt", "golden"];
+
+    /// (input, extra options, golden file under `tests/golden/`). Inputs under
+    /// `tests/golden/` are synthetic and always checked; those under `samples/`
+    /// are skipped when the sample or its golden file is missing.
+    const GOLDEN_CASES: &[(&str, &[&str], &str)] = &[
+        (
+            "tests/golden/synthetic.dump",
+            &["--all-methods"],
+            "synthetic-summary.txt",
+        ),
+        (
+            "tests/golden/synthetic.dump",
+            &["--full"],
+            "synthetic-full.txt",
+        ),
+        (
+            "tests/golden/synthetic.dump",
+            &["--all-methods", "--stats"],
+            "synthetic-stats.txt",
+        ),
+        ("tests/golden/synthetic.dump", &["--csv"], "synthetic.csv"),
+        (
+            "samples/esinet1-v4-udp.dump.20",
+            &[],
+            "esinet1-v4-udp-summary.txt",
+        ),
+        (
+            "samples/esinet1-v4-tcp.dump.20",
+            &["--all-methods", "--show-frames", "--show-bytes"],
+            "esinet1-v4-tcp-summary.txt",
+        ),
+        (
+            "samples/esinet1-v4-tcp.dump.20",
+            &["--stats"],
+            "esinet1-v4-tcp-stats.txt",
+        ),
+        (
+            "samples/esinet1-v6-tls.dump.180",
+            &["--csv"],
+            "esinet1-v6-tls.csv",
+        ),
+    ];
+
+    #[test]
+    fn frames_header_has_no_trailing_colon() {
+        let out = run_to_string(&["--frames", "tests/golden/synthetic.dump"]);
+        assert_eq!(
+            out.lines().next(),
+            Some("recv 426 bytes from udp/192.0.2.10:5060 at 2024-03-01 10:00:00.000100")
+        );
+    }
+
+    /// Compare each case with its golden output. With `UPDATE_GOLDEN=1`,
+    /// write the current output instead, for a deliberate output change.
+    #[test]
+    fn golden_outputs() {
+        let root = std::path::Path::new(env!("CARGO_MANIFEST_DIR"));
+        let golden_dir = root.join("tests/golden");
+        let update = std::env::var_os("UPDATE_GOLDEN").is_some();
+        for &(input, args, golden) in GOLDEN_CASES {
+            let input_path = root.join(input);
+            let golden_path = golden_dir.join(golden);
+            let sample = input.starts_with("samples/");
+            if sample && !input_path.exists() {
+                eprintln!("skipping {golden}: {input} not found");
+                continue;
+            }
+            let input_arg = input_path.to_str().unwrap();
+            let argv: Vec<&str> = GOLDEN_ARGS
+                .iter()
+                .chain(args)
+                .copied()
+                .chain([input_arg])
+                .collect();
+            let actual = run_to_string(&argv);
+            if update {
+                std::fs::write(&golden_path, &actual).unwrap();
+                eprintln!("wrote {}", golden_path.display());
+                continue;
+            }
+            let expected = match std::fs::read_to_string(&golden_path) {
+                Ok(expected) => expected,
+                Err(_) if sample => {
+                    eprintln!(
+                        "skipping {golden}: golden file missing, \
+                         create it with UPDATE_GOLDEN=1"
+                    );
+                    continue;
+                }
+                Err(e) => panic!("{}: {e}", golden_path.display()),
+            };
+            if actual != expected {
+                let line = actual
+                    .lines()
+                    .zip(expected.lines())
+                    .position(|(a, e)| a != e)
+                    .unwrap_or_else(|| actual.lines().count().min(expected.lines().count()));
+                panic!(
+                    "{input} {args:?} differs from {} at line {}; \
+                     rerun with UPDATE_GOLDEN=1 if the change is intended",
+                    golden_path.display(),
+                    line + 1
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn roundtrip_reports_dropped_header_line() {
+        let lossy = b"OPTIONS sip:host SIP/2.0\r\nno colon here\r\nCall-ID: rt1\r\n\r\n";
+        let rebuilt = udp_raw(lossy, 0).parse().unwrap().to_bytes();
+        assert_eq!(
+            roundtrip_diff(lossy, &rebuilt)[..3],
+            ["@@ line 2 @@", "-no colon here", "+Call-ID: rt1"]
+        );
+
+        let exact = b"OPTIONS sip:host SIP/2.0\r\nCall-ID:  rt1\r\nContent-Length: 0\r\n\r\n";
+        let rebuilt = udp_raw(exact, 0).parse().unwrap().to_bytes();
+        assert!(roundtrip_diff(exact, &rebuilt).is_empty());
+    }
+
+    #[test]
+    fn roundtrip_shows_line_ending_differences() {
+        let lf_only = b"OPTIONS sip:host SIP/2.0\nContent-Length: 0\n\n";
+        let rebuilt = udp_raw(lf_only, 0).parse().unwrap().to_bytes();
+        let diff = roundtrip_diff(lf_only, &rebuilt);
+        assert_eq!(
+            diff[..3],
+            [
+                "@@ line 1 @@",
+                "-OPTIONS sip:host SIP/2.0",
+                "+OPTIONS sip:host SIP/2.0\\r"
+            ]
+        );
+    }
+
+    #[test]
+    fn host_and_port_match_ipv4_exactly() {
+        let f = filters(&["--host", "10.0.0.1"]);
+        assert!(f.matches_endpoint(Direction::Recv, "10.0.0.1:5060"));
+        assert!(!f.matches_endpoint(Direction::Recv, "10.0.0.10:5060"));
+        assert!(!f.matches_endpoint(Direction::Recv, "10.0.0.1x:5060"));
+        let f = filters(&["--host", "10.0.0.1", "--port", "5080"]);
+        assert!(!f.matches_endpoint(Direction::Recv, "10.0.0.1:5060"));
+        assert!(f.matches_endpoint(Direction::Recv, "10.0.0.1:5080"));
+        let f = filters(&["--port", "5060", "-d", "sent"]);
+        assert!(f.matches_endpoint(Direction::Sent, "[::1]:5060"));
+        assert!(!f.matches_endpoint(Direction::Recv, "[::1]:5060"));
+    }
+
+    #[test]
+    fn host_matches_ipv6_with_or_without_brackets() {
+        for host in ["2001:db8::1", "[2001:db8::1]", "2001:DB8::1"] {
+            let f = filters(&["--host", host]);
+            assert!(
+                f.matches_endpoint(Direction::Recv, "[2001:db8::1]:5061"),
+                "{host}"
+            );
+            assert!(
+                !f.matches_endpoint(Direction::Recv, "[2001:db8::10]:5061"),
+                "{host}"
+            );
+        }
+        let f = filters(&["--host", "fe80::1"]);
+        assert!(f.matches_endpoint(Direction::Recv, "[fe80::1%eth0]:5060"));
+    }
+
+    #[test]
+    fn ipv4_host_matches_ipv4_mapped_address() {
+        let f = filters(&["--host", "10.0.0.1"]);
+        assert!(f.matches_endpoint(Direction::Recv, "[::ffff:10.0.0.1]:5060"));
+        assert!(!f.matches_endpoint(Direction::Recv, "[::ffff:10.0.0.2]:5060"));
+        let f = filters(&["--host", "::ffff:10.0.0.1"]);
+        assert!(f.matches_endpoint(Direction::Recv, "[::ffff:10.0.0.1]:5060"));
+    }
+
+    const INVITE_WITH_TIMER: &[u8] = b"INVITE sip:bob@host SIP/2.0\r\n\
+        Call-ID: hdr1\r\n\
+        CSeq: 1 INVITE\r\n\
+        Supported: timer\r\n\
+        Content-Length: 0\r\n\r\n";
+
+    const INVITE_WITHOUT_TIMER: &[u8] = b"INVITE sip:bob@host SIP/2.0\r\n\
+        Call-ID: hdr2\r\n\
+        CSeq: 1 INVITE\r\n\
+        Content-Length: 0\r\n\r\n";
+
+    #[test]
+    fn requests_and_responses_only() {
+        let request = udp_message(b"INVITE sip:host SIP/2.0\r\nCSeq: 1 INVITE\r\n\r\n", 0);
+        let response = udp_message(b"SIP/2.0 200 OK\r\nCSeq: 1 INVITE\r\n\r\n", 1);
+
+        let f = filters(&["--requests-only"]);
+        assert!(f.matches(&request));
+        assert!(!f.matches(&response));
+
+        let f = filters(&["--responses-only", "-m", "INVITE"]);
+        assert!(!f.matches(&request));
+        assert!(f.matches(&response));
+
+        let f = filters(&["--responses-only", "-m", "BYE"]);
+        assert!(!f.matches(&response));
+
+        assert!(Cli::try_parse_from(["parser", "--requests-only", "--responses-only"]).is_err());
+    }
+
+    #[test]
+    fn exclude_call_id_drops_matching() {
+        let f = filters(&[
+            "--exclude-call-id",
+            "^noisy",
+            "--exclude-call-id",
+            "chatty$",
+        ]);
+        let msg = |cid: &str| {
+            udp_message(
+                format!("MESSAGE sip:host SIP/2.0\r\nCall-ID: {cid}\r\n\r\n").as_bytes(),
+                0,
+            )
+        };
+        assert!(!f.matches(&msg("noisy-1@host")));
+        assert!(!f.matches(&msg("very-chatty")));
+        assert!(f.matches(&msg("wanted@host")));
+    }
+
+    #[test]
+    fn unparsed_rejection_agrees_with_matches() {
+        let contents: [&[u8]; 6] = [
+            b"INVITE sip:host SIP/2.0\r\nCSeq: 1 INVITE\r\n\r\n",
+            b"OPTIONS sip:host SIP/2.0\r\nCSeq: 1 OPTIONS\r\n\r\n",
+            b"SIP/2.0 200 OK\r\ncseq: 1 INVITE\r\n\r\n",
+            b"SIP/2.0 200 OK\r\nCSeq: 2\r\n BYE\r\n\r\n",
+            b"SIP/2.0 500 Error\r\nCall-ID: x\r\n\r\n",
+            b"ACK sip:host SIP/2.0\r\nCSeq: 1 INVITE\r\n\r\n",
+        ];
+        for args in [
+            &[][..],
+            &["-m", "INVITE"],
+            &["-m", "bye", "-x", "INVITE"],
+            &["-x", "ack", "--all-methods"],
+            &["--responses-only", "--all-methods"],
+            &["--requests-only", "-d", "sent"],
+        ] {
+            let f = filters(args);
+            for content in contents {
+                let raw = udp_raw(content, 0);
+                if f.rejects_unparsed(&raw) {
+                    let parsed = raw.parse().unwrap();
+                    assert!(!f.matches(&parsed), "{args:?} {parsed:?}");
+                }
+            }
+        }
+        let f = filters(&["-m", "INVITE"]);
+        assert!(f.rejects_unparsed(&udp_raw(contents[1], 0)));
+        assert!(!f.rejects_unparsed(&udp_raw(contents[2], 0)));
+    }
+
+    #[test]
+    fn dialog_skips_excluded_call_id() {
+        let f = filters(&["--exclude-call-id", "noisy"]);
+        let mut collector = DialogCollector::new(&f, false);
+        for (i, cid) in ["noisy", "other"].iter().enumerate() {
+            let raw = udp_raw(
+                format!("INVITE sip:host SIP/2.0\r\nCall-ID: {cid}\r\nCSeq: 1 INVITE\r\n\r\n")
+                    .as_bytes(),
+                i as u8,
+            );
+            let parsed = raw.parse().unwrap();
+            collector.push(raw, parsed);
+        }
+        assert_eq!(collector.dialogs.len(), 1);
+        assert!(collector.dialogs.contains_key("other"));
+    }
+
+    #[test]
+    fn exclude_address_drops_matching() {
+        let f = filters(&["--exclude-address", r"^10\.0\.0\.9:"]);
+        let mut probe = udp_raw(INVITE_WITH_TIMER, 0);
+        probe.address = "10.0.0.9:5060".into();
+        assert!(!f.matches(&probe.parse().unwrap()));
+        assert!(f.rejects_unparsed(&probe));
+        assert!(f.matches(&udp_message(INVITE_WITH_TIMER, 0)));
+    }
+
+    #[test]
+    fn dialog_dropped_when_any_message_excluded_by_address() {
+        let f = filters(&["--exclude-address", "10.0.0.9"]);
+        let mut collector = DialogCollector::new(&f, false);
+        for (i, (cid, address)) in [
+            ("probed", "10.0.0.1:5060"),
+            ("probed", "10.0.0.9:5060"),
+            ("probed", "10.0.0.1:5060"),
+            ("other", "10.0.0.1:5060"),
+        ]
+        .iter()
+        .enumerate()
+        {
+            let mut raw = udp_raw(
+                format!("INVITE sip:host SIP/2.0\r\nCall-ID: {cid}\r\nCSeq: 1 INVITE\r\n\r\n")
+                    .as_bytes(),
+                i as u8,
+            );
+            raw.address = address.to_string();
+            let parsed = raw.parse().unwrap();
+            collector.push(raw, parsed);
+        }
+        let matched = collector.into_matched();
+        assert_eq!(matched.len(), 1);
+        assert_eq!(matched[0].call_id, "other");
+    }
+
+    #[test]
+    fn min_frames_excludes_single_frame_messages() {
+        let f = filters(&["--min-frames", "2"]);
+        let single = udp_raw(INVITE_WITH_TIMER, 0);
+        assert!(!f.matches(&single.parse().unwrap()));
+        assert!(f.rejects_unparsed(&single));
+
+        let mut reassembled = udp_raw(INVITE_WITH_TIMER, 0);
+        reassembled.transport = Transport::Tcp;
+        reassembled.frame_count = 2;
+        assert!(f.matches(&reassembled.parse().unwrap()));
+        assert!(!f.rejects_unparsed(&reassembled));
+    }
+
+    #[test]
+    fn cause_matches_q850_reason() {
+        let f = filters(&["--cause", "16"]);
+        let bye = |reason: &str| {
+            let content = format!("BYE sip:host SIP/2.0\r\nCall-ID: r1\r\n{reason}\r\n");
+            udp_message(content.as_bytes(), 0)
+        };
+        assert!(f.matches(&bye(
+            "Reason: Q.850;cause=16;text=\"Normal call clearing\"\r\n"
+        )));
+        assert!(f.matches(&bye("Reason: SIP;cause=200, Q.850;cause=16\r\n")));
+        assert!(!f.matches(&bye("Reason: Q.850;cause=17\r\n")));
+        assert!(!f.matches(&bye("Reason: SIP;cause=16\r\n")));
+        assert!(!f.matches(&bye("")));
+    }
+
+    #[test]
+    fn wrap_folds_long_via_into_indented_lines() {
+        let via = "SIP/2.0/TCP 192.168.100.200:5060;branch=z9hG4bK-524287-1---abcdef;rport;received=10.0.0.1";
+        let content = format!("INFO sip:b@host SIP/2.0\r\nVia: {via}\r\nCall-ID: w1\r\n\r\n");
+        let mut out = Vec::new();
+        output_headers(
+            &mut out,
+            &udp_message(content.as_bytes(), 0),
+            false,
+            Some(40),
+        )
+        .unwrap();
+        let out = String::from_utf8(out).unwrap();
+        let lines: Vec<&str> = out.lines().skip(2).collect();
+        assert_eq!(
+            lines,
+            [
+                "Via: SIP/2.0/TCP 192.168.100.200:5060;",
+                "    branch=z9hG4bK-524287-1---abcdef;",
+                "    rport;received=10.0.0.1",
+                "Call-ID: w1",
+            ]
+        );
+    }
+
+    #[test]
+    fn wrap_keeps_quoted_strings_and_counts_chars() {
+        let from = "\"Smith, John; \\\"Jr\\\" Sr\" <sip:john@example.com>;tag=abc123";
+        assert_eq!(
+            fold_header("From", from, 30),
+            [
+                "From: \"Smith, John; \\\"Jr\\\" Sr\"",
+                "    <sip:john@example.com>;",
+                "    tag=abc123",
+            ]
+        );
+
+        // 29 characters but 32 bytes: fits on one line
+        let to = "\"Élodie Bérubé\" <sip:e@h>";
+        assert_eq!(fold_header("To", to, 30), [format!("To: {to}")]);
+    }
+
+    #[test]
+    fn options_excluded_by_default_include_options_or_method() {
+        let options = udp_message(
+            b"OPTIONS sip:host SIP/2.0\r\nCall-ID: o1\r\nCSeq: 1 OPTIONS\r\n\r\n",
+            0,
+        );
+        let raw = udp_raw(
+            b"OPTIONS sip:host SIP/2.0\r\nCall-ID: o1\r\nCSeq: 1 OPTIONS\r\n\r\n",
+            0,
+        );
+        let f = filters(&[]);
+        assert!(!f.matches(&options));
+        assert!(f.rejects_unparsed(&raw));
+        for args in [
+            &["--include-options"][..],
+            &["--all-methods"],
+            &["-m", "OPTIONS"],
+        ] {
+            let f = filters(args);
+            assert!(f.matches(&options), "{args:?}");
+            assert!(!f.rejects_unparsed(&raw), "{args:?}");
+        }
+        assert!(!filters(&["-m", "INVITE"]).matches(&options));
+    }
+
+    #[test]
+    fn realm_matches_challenge_or_credentials() {
+        let f = filters(&["--realm", "^sip\\.example\\.com$"]);
+        let msg = |first_line: &str, auth: &str| {
+            let content = format!("{first_line}\r\nCall-ID: a1\r\nCSeq: 1 INVITE\r\n{auth}\r\n");
+            udp_message(content.as_bytes(), 0)
+        };
+        let challenge = "Proxy-Authenticate: Digest realm=\"sip.example.com\", nonce=\"n1\"\r\n";
+        assert!(f.matches(&msg("SIP/2.0 407 Proxy Authentication Required", challenge)));
+        let credentials = "Authorization: Digest username=\"a\", realm=\"sip.example.com\"\r\n";
+        assert!(f.matches(&msg("INVITE sip:b@host SIP/2.0", credentials)));
+        let other = "WWW-Authenticate: Digest realm=\"other.example.com\"\r\n";
+        assert!(!f.matches(&msg("SIP/2.0 401 Unauthorized", other)));
+        assert!(!f.matches(&msg("INVITE sip:b@host SIP/2.0", "")));
+    }
+
+    #[test]
+    fn has_header_requires_presence() {
+        let f = filters(&["--has-header", "supported"]);
+        assert!(f.matches(&udp_message(INVITE_WITH_TIMER, 0)));
+        assert!(!f.matches(&udp_message(INVITE_WITHOUT_TIMER, 0)));
+    }
+
+    #[test]
+    fn missing_header_requires_absence() {
+        let f = filters(&["--missing-header", "SUPPORTED"]);
+        assert!(!f.matches(&udp_message(INVITE_WITH_TIMER, 0)));
+        assert!(f.matches(&udp_message(INVITE_WITHOUT_TIMER, 0)));
+    }
+
+    #[test]
+    fn header_presence_composes_with_value_filter() {
+        let f = filters(&["--has-header", "Supported", "-H", "Call-ID=hdr2"]);
+        assert!(!f.matches(&udp_message(INVITE_WITH_TIMER, 0)));
+        assert!(!f.matches(&udp_message(INVITE_WITHOUT_TIMER, 0)));
+        let f = filters(&["--missing-header", "Supported", "-H", "Call-ID=hdr2"]);
+        assert!(f.matches(&udp_message(INVITE_WITHOUT_TIMER, 0)));
+    }
+
+    #[test]
+    fn canonical_reason_only_under_flag() {
+        let msg = udp_message(
+            b"SIP/2.0 486 Subscriber Unreachable Today\r\n\
+            Call-ID: busy1\r\n\
+            CSeq: 1 INVITE\r\n\
+            Content-Length: 0\r\n\r\n",
+            0,
+        );
+        let mut out = output_options(&Cli::parse_from(["freeswitch-sofia-trace-parser"]));
+        assert_eq!(
+            displayed(&out, &msg).reason_phrase(),
+            Some("Subscriber Unreachable Today")
+        );
+        out.canonical_reason = true;
+        assert_eq!(displayed(&out, &msg).reason_phrase(), Some("Busy Here"));
+        assert!(format_summary(&displayed(&out, &msg), false).contains("486 Busy Here"));
+    }
+
+    #[test]
+    fn tz_offset_parsing() {
+        assert_eq!(parse_tz_offset("+05:30"), Ok(330));
+        assert_eq!(parse_tz_offset("-04:00"), Ok(-240));
+        assert_eq!(parse_tz_offset("-0400"), Ok(-240));
+        assert_eq!(parse_tz_offset("+2"), Ok(120));
+        assert!(parse_tz_offset("05:00").is_err());
+        assert!(parse_tz_offset("+24:00").is_err());
+        assert!(parse_tz_offset("+01:60").is_err());
+    }
+
+    #[test]
+    fn tz_offset_shifts_displayed_timestamp() {
+        let msg = udp_message(b"OPTIONS sip:host SIP/2.0\r\nCall-ID: tz1\r\n\r\n", 0);
+        let out = output_options(&Cli::parse_from([
+            "freeswitch-sofia-trace-parser",
+            "--tz-offset",
+            "-13:00",
+        ]));
+        assert!(format_summary(&displayed(&out, &msg), false).starts_with("23:00:00.000000 "));
+    }
+
+    #[test]
+    fn summary_template_renders_fields() {
+        let request = udp_message(
+            b"INVITE sip:bob@host SIP/2.0\r\n\
+            From: <sip:alice@host>;tag=a1\r\n\
+            Call-ID: fmt1\r\n\
+            CSeq: 1 INVITE\r\n\r\n",
+            5,
+        );
+        let response = udp_message(b"SIP/2.0 486 Busy Here\r\nCSeq: 1 INVITE\r\n\r\n", 6);
+
+        let template = parse_summary_template("{timestamp} {method} {uri} {header:from}").unwrap();
+        assert_eq!(
+            template.render(&request, false),
+            "12:00:05.000000 INVITE sip:bob@host <sip:alice@host>;tag=a1"
+        );
+        assert_eq!(
+            template.render(&response, false),
+            "12:00:06.000000 INVITE - -"
+        );
+
+        let template =
+            parse_summary_template("{{{status}}} {direction}@{address}|{call_id}").unwrap();
+        assert_eq!(
+            template.render(&response, false),
+            "{486} recv@10.0.0.1:5060|-"
+        );
+    }
+
+    #[test]
+    fn align_pads_summary_columns() {
+        let out = output_options(&Cli::parse_from([
+            "freeswitch-sofia-trace-parser",
+            "--align",
+            "--color",
+            "always",
+        ]));
+        let short = udp_raw(b"OPTIONS sip:host SIP/2.0\r\nCall-ID: a1\r\n\r\n", 0);
+        let long = SipMessage {
+            address: "[2001:db8::1234]:15060".into(),
+            ..udp_raw(
+                b"SIP/2.0 200 OK\r\nCall-ID: a2\r\nCSeq: 1 OPTIONS\r\n\r\n",
+                1,
+            )
+        };
+
+        let mut output = Vec::new();
+        let mut aligned = AlignedColumns::new(&mut output);
+        for raw in [&short, &long] {
+            output_message(&mut aligned, &out, raw, &raw.parse().unwrap()).unwrap();
+        }
+        aligned.finish().unwrap();
+
+        let plain = String::from_utf8(output)
+            .unwrap()
+            .replace("\x1b[36m", "")
+            .replace("\x1b[32m", "")
+            .replace(ANSI_RESET, "");
+        let lines: Vec<&str> = plain.lines().collect();
+        assert_eq!(
+            lines,
+            [
+                "12:00:00.000000 recv udp/10.0.0.1:5060          OPTIONS a1",
+                "12:00:01.000000 recv udp/[2001:db8::1234]:15060 200 OK  a2",
+            ]
+        );
+    }
+
+    #[test]
+    fn summary_template_rejects_unknown_placeholders() {
+        for bad in ["{methd}", "{header:}", "{status", "status}"] {
+            assert!(parse_summary_template(bad).is_err(), "{bad}");
+        }
+        assert!(Cli::try_parse_from(["parser", "--format", "{nope}"]).is_err());
+        assert!(Cli::try_parse_from(["parser", "--format", "{cseq}", "--full"]).is_err());
+    }
+
+    #[test]
+    fn canonical_reason_keeps_unknown_codes() {
+        let msg = udp_message(
+            b"SIP/2.0 299 Carrier Special\r\n\
+            Call-ID: odd1\r\n\
+            CSeq: 1 INVITE\r\n\
+            Content-Length: 0\r\n\r\n",
+            0,
+        );
+        let out = output_options(&Cli::parse_from([
+            "freeswitch-sofia-trace-parser",
+            "--canonical-reason",
+        ]));
+        assert_eq!(
+            displayed(&out, &msg).reason_phrase(),
+            Some("Carrier Special")
+        );
+    }
+
+    #[test]
+    fn select_parts_picks_pidf() {
+        let body = b"--b1\r\n\
+            Content-Type: application/sdp\r\n\r\n\
+            v=0\r\n\
+            --b1\r\n\
+            Content-Type: application/pidf+xml\r\n\r\n\
+            <presence/>\r\n\
+            --b1--";
+        let mut content = Vec::new();
+        content.extend_from_slice(b"INVITE sip:host SIP/2.0\r\n");
+        content.extend_from_slice(b"Content-Type: multipart/mixed;boundary=b1\r\n");
+        content.extend_from_slice(format!("Content-Length: {}\r\n\r\n", body.len()).as_bytes());
+        content.extend_from_slice(body);
+        let msg = udp_message(&content, 0);
+
+        assert_eq!(select_parts(&msg, "pidf"), vec![b"<presence/>".to_vec()]);
+        assert_eq!(select_parts(&msg, "application/sdp"), vec![b"v=0".to_vec()]);
+        assert!(select_parts(&msg, "application/json").is_empty());
+    }
+
+    fn eido_invite() -> ParsedSipMessage {
+        let body = b"--b1\r\n\
+            Content-Type: application/sdp\r\n\r\n\
+            v=0\r\n\
+            --b1\r\n\
+            Content-Type: application/emergencyCallData.eido+xml\r\n\
+            Content-Transfer-Encoding: base64\r\n\r\n\
+            PEVJRE8+SU5DLTQy\r\nPC9FSURPPg==\r\n\
+            --b1--";
+        let mut content = Vec::new();
+        content.extend_from_slice(b"INVITE sip:host SIP/2.0\r\n");
+        content.extend_from_slice(b"Content-Type: multipart/mixed;boundary=b1\r\n");
+        content.extend_from_slice(format!("Content-Length: {}\r\n\r\n", body.len()).as_bytes());
+        content.extend_from_slice(body);
+        udp_message(&content, 0)
+    }
+
+    #[test]
+    fn part_grep_searches_decoded_base64() {
+        let msg = eido_invite();
+        assert!(filters(&["--part-grep", "INC-42"]).matches(&msg));
+        assert!(!filters(&["--body-grep", "INC-42"]).matches(&msg));
+        assert!(!filters(&["--part-grep", "INC-43"]).matches(&msg));
+        assert_eq!(
+            select_parts(&msg, "eido"),
+            vec![b"<EIDO>INC-42</EIDO>".to_vec()]
+        );
+    }
+
+    #[test]
+    fn body_type_matches_multipart_part() {
+        let body = b"--b1\r\n\
+            Content-Type: application/sdp\r\n\r\n\
+            v=0\r\n\
+            --b1\r\n\
+            Content-Type: application/PIDF+xml; charset=utf-8\r\n\r\n\
+            <presence/>\r\n\
+            --b1--";
+        let mut content = Vec::new();
+        content.extend_from_slice(b"INVITE sip:host SIP/2.0\r\n");
+        content.extend_from_slice(b"Content-Type: multipart/mixed;boundary=b1\r\n");
+        content.extend_from_slice(format!("Content-Length: {}\r\n\r\n", body.len()).as_bytes());
+        content.extend_from_slice(body);
+        let msg = udp_message(&content, 0);
+
+        assert!(filters(&["--body-type", "application/pidf+xml"]).matches(&msg));
+        assert!(filters(&["--body-type", "Multipart"]).matches(&msg));
+        assert!(!filters(&["--body-type", "json"]).matches(&msg));
+        // Parameters are not part of the media type
+        assert!(!filters(&["--body-type", "charset"]).matches(&msg));
+        assert!(!filters(&["--body-type", "pidf"]).matches(&udp_message(INVITE_WITH_TIMER, 0)));
+    }
+
+    #[test]
+    fn part_grep_single_part_uses_body() {
+        let msg = udp_message(
+            b"MESSAGE sip:host SIP/2.0\r\nContent-Length: 5\r\n\r\nhello",
+            0,
+        );
+        assert!(filters(&["--part-grep", "hel+o"]).matches(&msg));
+    }
+
+    #[test]
+    fn size_histogram_power_of_two() {
+        let mut hist = SizeHistogram::new(None);
+        for size in [0, 300, 511, 512, 1500, 5000] {
+            hist.add(size);
+        }
+        assert_eq!(
+            hist.lines(),
+            [
+                "[0-512): 3",
+                "[512-1024): 1",
+                "[1024-2048): 1",
+                "[4096-8192): 1",
+            ]
+        );
+    }
+
+    #[test]
+    fn size_histogram_fixed_width() {
+        let mut hist = SizeHistogram::new(Some(1000));
+        for size in [999, 1000, 2500] {
+            hist.add(size);
+        }
+        assert_eq!(
+            hist.lines(),
+            ["[0-1000): 1", "[1000-2000): 1", "[2000-3000): 1"]
+        );
+        assert!(SizeHistogram::new(Some(1000)).lines().is_empty());
+    }
+
+    #[test]
+    fn size_histogram_rejects_zero_width() {
+        let argv = ["freeswitch-sofia-trace-parser", "--stats"];
+        assert!(Cli::try_parse_from(argv.iter().chain(&["--size-histogram=0"])).is_err());
+        let cli = Cli::try_parse_from(argv.iter().chain(&["--size-histogram=64"])).unwrap();
+        assert_eq!(cli.size_histogram, Some(Some(64)));
+    }
+
+    #[test]
+    fn select_parts_single_part() {
+        let msg = udp_message(
+            b"INVITE sip:host SIP/2.0\r\n\
+            Content-Type: application/sdp\r\n\
+            Content-Length: 5\r\n\r\n\
+            v=0\r\n",
+            0,
+        );
+        assert_eq!(select_parts(&msg, "sdp"), vec![b"v=0\r\n".to_vec()]);
+        assert!(select_parts(&msg, "pidf").is_empty());
+    }
+
+    #[test]
+    fn summary_color() {
+        let msg = udp_message(
+            b"SIP/2.0 486 Busy Here\r\nCall-ID: c1\r\nCSeq: 1 INVITE\r\n\r\n",
+            0,
+        );
+        let plain = format_summary(&msg, false);
+        assert_eq!(
+            plain,
+            "12:00:00.000000 recv udp/10.0.0.1:5060 486 Busy Here c1"
+        );
+        let colored = format_summary(&msg, true);
+        assert!(colored.contains("\x1b[36mrecv\x1b[0m"));
+        assert!(colored.contains("\x1b[31m486 Busy Here\x1b[0m"));
+    }
+
+    #[test]
+    fn export_dump_round_trip() {
+        let original = vec![
+            SipMessage {
+                direction: Direction::Recv,
+                transport: Transport::Tcp,
+                address: "[2001:db8::1]:5061".into(),
+                timestamp: Timestamp::TimeOnly {
+                    hour: 13,
+                    min: 3,
+                    sec: 21,
+                    usec: 674883,
+                },
+                last_timestamp: Timestamp::TimeOnly {
+                    hour: 13,
+                    min: 3,
+                    sec: 21,
+                    usec: 674883,
+                },
+                content: b"NOTIFY sip:a SIP/2.0\r\nContent-Length: 5\r\n\r\nhello".to_vec(),
+                frame_count: 1,
+            },
+            SipMessage {
+                direction: Direction::Sent,
+                transport: Transport::Udp,
+                address: "10.0.0.1:5060".into(),
+                timestamp: Timestamp::DateTime {
+                    year: 2026,
+                    month: 2,
+                    day: 1,
+                    hour: 10,
+                    min: 0,
+                    sec: 0,
+                    usec: 1,
+                },
+                last_timestamp: Timestamp::DateTime {
+                    year: 2026,
+                    month: 2,
+                    day: 1,
+                    hour: 10,
+                    min: 0,
+                    sec: 0,
+                    usec: 1,
+                },
+                content: b"SIP/2.0 200 OK\r\nContent-Length: 0\r\n\r\n".to_vec(),
+                frame_count: 1,
+            },
+        ];
+        let mut dump = Vec::new();
+        for msg in &original {
+            write_dump_frame(&mut dump, msg).unwrap();
+        }
+        let reparsed: Vec<SipMessage> = MessageIterator::new(&dump[..])
+            .collect::<Result<Vec<_>, _>>()
+            .unwrap();
+        assert_eq!(reparsed.len(), original.len());
+        for (a, b) in original.iter().zip(&reparsed) {
+            assert_eq!(a.direction, b.direction);
+            assert_eq!(a.transport, b.transport);
+            assert_eq!(a.address, b.address);
+            assert_eq!(a.timestamp, b.timestamp);
+            assert_eq!(a.content, b.content);
+        }
+    }
+
+    #[test]
+    fn progress_reader_counts_bytes_and_frames() {
+        let data = b"recv 2 bytes from udp/a:1 at 00:00:00.000000:\nAB\x0B\n\
+            recv 2 bytes from udp/a:1 at 00:00:01.000000:\nCD\x0B\n";
+        // Small reads split the second terminator across two calls
+        let split = data.len() - 1;
+        let progress = Progress::shared(Some(data.len() as u64));
+        let mut reader = ProgressReader::new(&data[..], &progress);
+        let mut buf = vec![0; split];
+        assert_eq!(reader.read(&mut buf).unwrap(), split);
+        assert_eq!(
+            (progress.borrow().bytes, progress.borrow().frames),
+            (split as u64, 1)
+        );
+        let mut rest = Vec::new();
+        reader.read_to_end(&mut rest).unwrap();
+        assert_eq!(rest, b"\n");
+        assert_eq!(
+            (progress.borrow().bytes, progress.borrow().frames),
+            (data.len() as u64, 2)
+        );
+    }
+
+    #[test]
+    fn progress_shared_across_merged_inputs() {
+        let data = b"recv 2 bytes from udp/a:1 at 00:00:00.000000:\nAB\x0B\n";
+        let progress = Progress::shared(Some(2 * data.len() as u64));
+        let mut first = ProgressReader::new(&data[..], &progress);
+        let mut second = ProgressReader::new(&data[..], &progress);
+        assert_eq!(progress.borrow().open, 2);
+        io::copy(&mut first, &mut io::sink()).unwrap();
+        assert_eq!(progress.borrow().open, 1);
+        io::copy(&mut second, &mut io::sink()).unwrap();
+        let progress = progress.borrow();
+        assert_eq!(
+            (progress.bytes, progress.frames, progress.open),
+            (2 * data.len() as u64, 2, 0)
+        );
+    }
+
+    #[test]
+    fn dedup_frames_applies_across_merged_inputs() {
+        let dump = |call_ids: &[&str]| {
+            let mut dump = Vec::new();
+            for (sec, cid) in call_ids.iter().enumerate() {
+                let content = format!("NOTIFY sip:a@host SIP/2.0\r\nCall-ID: {cid}\r\n\r\n");
+                write_dump_frame(&mut dump, &udp_raw(content.as_bytes(), sec as u8)).unwrap();
+            }
+            let stream: Box<dyn Read> = Box::new(io::Cursor::new(dump));
+            MessageIterator::new(stream)
+        };
+        let call_ids = |messages: Messages| -> Vec<String> {
+            messages
+                .map(|msg| msg.unwrap().parse().unwrap().call_id().unwrap().to_string())
+                .collect()
+        };
+        let merged = || Messages::new(vec![dump(&["a", "b"]), dump(&["a", "c"])]);
+        assert_eq!(call_ids(merged()), ["a", "a", "b", "c"]);
+        assert_eq!(call_ids(merged().dedup_frames(16)), ["a", "b", "c"]);
+    }
+
+    #[test]
+    fn hexdump_layout() {
+        let mut out = Vec::new();
+        hexdump(b"INVITE sip:a SIP/2.0\r\n\x00\xff", &mut out).unwrap();
+        assert_eq!(
+            String::from_utf8(out).unwrap(),
+            "00000000  49 4e 56 49 54 45 20 73  69 70 3a 61 20 53 49 50  |INVITE sip:a SIP|\n\
+             00000010  2f 32 2e 30 0d 0a 00 ff                           |/2.0....|\n\
+             00000018\n"
+        );
+    }
+
+    #[test]
+    fn push_bounded_keeps_last_n() {
+        let mut buf = VecDeque::new();
+        for i in 0..5 {
+            push_bounded(&mut buf, i, 3);
+        }
+        assert_eq!(buf, [2, 3, 4]);
+    }
+
+    fn dialog_at(call_id: &str, sec: u8) -> DialogState {
+        let content = format!("OPTIONS sip:host SIP/2.0\r\nCall-ID: {call_id}\r\n\r\n");
+        let raw = udp_raw(content.as_bytes(), sec);
+        let parsed = raw.parse().unwrap();
+        DialogState {
+            messages: vec![(raw, parsed)],
+            call_id: call_id.into(),
+            matched: true,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn limit_dialogs_counts_dialogs() {
+        let dialogs = || vec![dialog_at("c", 3), dialog_at("a", 1), dialog_at("b", 2)];
+        let first_sec = |ds: Vec<DialogState>| -> Vec<u8> {
+            ds.iter()
+                .map(|d| match d.messages[0].0.timestamp {
+                    Timestamp::TimeOnly { sec, .. } => sec,
+                    _ => unreachable!(),
+                })
+                .collect()
+        };
+        assert_eq!(first_sec(limit_dialogs(dialogs(), Limit::Head(2))), [1, 2]);
+        assert_eq!(first_sec(limit_dialogs(dialogs(), Limit::Tail(1))), [3]);
+        assert_eq!(limit_dialogs(dialogs(), Limit::None).len(), 3);
+    }
+
+    fn dialog_output(order: DialogOrder) -> String {
+        let mut dump = Vec::new();
+        for (cid, cseq, sec) in [("a", 1, 2), ("b", 1, 1), ("a", 2, 3), ("b", 2, 4)] {
+            let content = format!(
+                "MESSAGE sip:host SIP/2.0\r\nCall-ID: {cid}\r\nCSeq: {cseq} MESSAGE\r\n\r\n"
+            );
+            write_dump_frame(&mut dump, &udp_raw(content.as_bytes(), sec)).unwrap();
+        }
+        let argv = [
+            "freeswitch-sofia-trace-parser",
+            "-D",
+            "--format",
+            "{call_id}{cseq}",
+        ];
+        let out = output_options(&Cli::parse_from(argv));
+        let mut written = Vec::new();
+        run_dialog(
+            messages_from(&dump),
+            &mut written,
+            &out,
+            &filters(&[]),
+            false,
+            false,
+            order,
+        )
+        .unwrap();
+        String::from_utf8(written).unwrap().replace(" MESSAGE", "")
+    }
+
+    #[test]
+    fn dialog_order_grouped_keeps_dialogs_together() {
+        assert_eq!(dialog_output(DialogOrder::Grouped), "b1\nb2\na1\na2\n");
+        assert_eq!(dialog_output(DialogOrder::Timestamp), "b1\na1\na2\nb2\n");
+        assert_eq!(dialog_output(DialogOrder::Input), "a1\nb1\na2\nb2\n");
+    }
+
+    fn collect_dialog_sizes(args: &[&str], keep_options: bool) -> Vec<usize> {
+        let f = filters(args);
+        let mut collector = DialogCollector::new(&f, keep_options);
+        for (sec, content) in [
+            &b"OPTIONS sip:host SIP/2.0\r\nCall-ID: d1\r\nCSeq: 1 OPTIONS\r\n\r\n"[..],
+            b"INVITE sip:host SIP/2.0\r\nCall-ID: d1\r\nCSeq: 2 INVITE\r\n\r\n",
+            b"OPTIONS sip:host SIP/2.0\r\nCall-ID: d1\r\nCSeq: 3 OPTIONS\r\n\r\n",
+            b"SIP/2.0 200 OK\r\nCall-ID: d1\r\nCSeq: 3 OPTIONS\r\n\r\n",
+            b"OPTIONS sip:host SIP/2.0\r\nCall-ID: ping\r\nCSeq: 1 OPTIONS\r\n\r\n",
+        ]
+        .into_iter()
+        .enumerate()
+        {
+            let raw = udp_raw(content, sec as u8);
+            let parsed = raw.parse().unwrap();
+            collector.push(raw, parsed);
+        }
+        let mut sizes: Vec<usize> = collector
+            .into_matched()
+            .iter()
+            .map(|d| d.messages.len())
+            .collect();
+        sizes.sort_unstable();
+        sizes
+    }
+
+    fn pending_dialogs(args: &[&str], contents: &[&str]) -> Vec<String> {
+        let f = filters(args);
+        let mut collector = DialogCollector::new(&f, false);
+        for (sec, content) in contents.iter().enumerate() {
+            let raw = udp_raw(content.as_bytes(), sec as u8);
+            let parsed = raw.parse().unwrap();
+            collector.push(raw, parsed);
+        }
+        let mut call_ids: Vec<String> = collector.dialogs.into_keys().collect();
+        call_ids.sort_unstable();
+        call_ids
+    }
+
+    #[test]
+    fn dialog_pruned_after_failed_invite() {
+        let busy = [
+            "INVITE sip:b@host SIP/2.0\r\nCall-ID: busy\r\nCSeq: 1 INVITE\r\n\r\n",
+            "SIP/2.0 100 Trying\r\nCall-ID: busy\r\nCSeq: 1 INVITE\r\n\r\n",
+            "SIP/2.0 486 Busy Here\r\nCall-ID: busy\r\nCSeq: 1 INVITE\r\n\r\n",
+        ];
+        assert!(pending_dialogs(&["-m", "MESSAGE"], &busy).is_empty());
+        // Matched dialogs are kept for output
+        assert_eq!(pending_dialogs(&["-m", "INVITE"], &busy), ["busy"]);
+    }
+
+    #[test]
+    fn dialog_pruned_after_answered_cancel() {
+        let cancelled = [
+            "INVITE sip:b@host SIP/2.0\r\nCall-ID: cxl\r\nCSeq: 1 INVITE\r\n\r\n",
+            "CANCEL sip:b@host SIP/2.0\r\nCall-ID: cxl\r\nCSeq: 1 CANCEL\r\n\r\n",
+            "SIP/2.0 200 OK\r\nCall-ID: cxl\r\nCSeq: 1 CANCEL\r\n\r\n",
+        ];
+        assert!(pending_dialogs(&["-m", "MESSAGE"], &cancelled).is_empty());
+    }
+
+    #[test]
+    fn dialog_not_restarted_by_ack_after_pruning() {
+        let busy = [
+            "INVITE sip:b@host SIP/2.0\r\nCall-ID: busy\r\nCSeq: 1 INVITE\r\n\r\n",
+            "SIP/2.0 486 Busy Here\r\nCall-ID: busy\r\nCSeq: 1 INVITE\r\n\r\n",
+            "ACK sip:b@host SIP/2.0\r\nCall-ID: busy\r\nCSeq: 1 ACK\r\n\r\n",
+        ];
+        assert!(pending_dialogs(&["-m", "MESSAGE"], &busy).is_empty());
+        assert_eq!(pending_dialogs(&["-m", "ACK"], &busy[2..]), ["busy"]);
+    }
+
+    #[test]
+    fn dialog_kept_after_auth_challenge_or_other_failure() {
+        let challenged = [
+            "INVITE sip:b@host SIP/2.0\r\nCall-ID: auth\r\nCSeq: 1 INVITE\r\n\r\n",
+            "SIP/2.0 407 Proxy Authentication Required\r\nCall-ID: auth\r\nCSeq: 1 INVITE\r\n\r\n",
+            "INVITE sip:b@host SIP/2.0\r\nCall-ID: reinv\r\nCSeq: 1 INVITE\r\n\r\n",
+            "SIP/2.0 200 OK\r\nCall-ID: reinv\r\nCSeq: 1 INVITE\r\n\r\n",
+            "INVITE sip:b@host SIP/2.0\r\nCall-ID: reinv\r\nCSeq: 2 INVITE\r\n\r\n",
+            "SIP/2.0 491 Request Pending\r\nCall-ID: reinv\r\nCSeq: 2 INVITE\r\n\r\n",
+        ];
+        assert_eq!(
+            pending_dialogs(&["-m", "MESSAGE"], &challenged),
+            ["auth", "reinv"]
+        );
+    }
+
+    #[test]
+    fn dialog_drops_options_by_default() {
+        assert_eq!(collect_dialog_sizes(&["-m", "INVITE"], false), [1]);
+    }
+
+    #[test]
+    fn dialog_keeps_in_dialog_options() {
+        // The OPTIONS before the INVITE can't be attributed yet; the
+        // OPTIONS-only "ping" Call-ID is never buffered.
+        assert_eq!(collect_dialog_sizes(&["-m", "INVITE"], true), [3]);
+        assert_eq!(
+            collect_dialog_sizes(&["-m", "INVITE", "-x", "OPTIONS"], true),
+            [1]
+        );
+    }
+
+    #[test]
+    fn first_per_call_id_keeps_first_of_each() {
+        let msgs = [
+            udp_message(b"INVITE sip:host SIP/2.0\r\nCall-ID: a\r\n\r\n", 0),
+            udp_message(b"SIP/2.0 200 OK\r\nCall-ID: a\r\n\r\n", 1),
+            udp_message(b"BYE sip:host SIP/2.0\r\nCall-ID: b\r\n\r\n", 2),
+            udp_message(b"MESSAGE sip:host SIP/2.0\r\n\r\n", 3),
+        ];
+        let mut first = FirstPerCallId::default();
+        let kept: Vec<&str> = msgs
+            .iter()
+            .filter(|m| first.is_first(m))
+            .map(|m| m.call_id().unwrap())
+            .collect();
+        assert_eq!(kept, ["a", "b"]);
+    }
+
+    #[test]
+    fn read_error_stops_run() {
+        let mut dump = Vec::new();
+        let content = b"MESSAGE sip:host SIP/2.0\r\nCall-ID: io1\r\n\r\n";
+        write_dump_frame(&mut dump, &udp_raw(content, 0)).unwrap();
+        let reader: Box<dyn Read> = Box::new(FailAfter(io::Cursor::new(dump)));
+        let mut out = Vec::new();
+        let err = run_raw(
+            Messages::new(vec![MessageIterator::new(reader)]),
+            &mut out,
+            false,
+            false,
+        )
+        .unwrap_err();
+        assert!(err.get_ref().unwrap().is::<ParseError>());
+        assert!(out.starts_with(b"recv from udp/10.0.0.1:5060"));
+    }
+
+    #[test]
+    fn any_match_stops_at_first_match() {
+        // The reader fails past the matching message, so reaching it would error
+        let mut dump = Vec::new();
+        for (i, status) in ["200 OK", "503 Service Unavailable", "486 Busy Here"]
+            .iter()
+            .enumerate()
+        {
+            let content = format!("SIP/2.0 {status}\r\nCall-ID: q{i}\r\nCSeq: 1 INVITE\r\n\r\n");
+            write_dump_frame(&mut dump, &udp_raw(content.as_bytes(), i as u8)).unwrap();
+        }
+        let any = |args: &[&str], dump: &[u8]| {
+            let reader: Box<dyn Read> = Box::new(FailAfter(io::Cursor::new(dump.to_vec())));
+            any_match(
+                Messages::new(vec![MessageIterator::new(reader)]),
+                &filters(args),
+                Selectors::default(),
+                None,
+            )
+        };
+        assert!(any(&["-H", "CSeq=INVITE", "-c", "q0"], &dump).unwrap());
+        assert!(any(&["--responses-only", "-c", "q1"], &dump).unwrap());
+        // No match means reading to the end, which fails here
+        assert!(any(&["-c", "q9"], &dump).is_err());
+
+        let found = any_match(
+            messages_from(&dump),
+            &filters(&["-c", "q9"]),
+            Selectors::default(),
+            None,
+        );
+        assert!(!found.unwrap());
+    }
+
+    #[test]
+    fn replace_header_rewrites_export() {
+        let raw = udp_raw(
+            b"MESSAGE sip:host SIP/2.0\r\nCall-ID: secret@10.0.0.1\r\nCSeq: 1 MESSAGE\r\n\r\n",
+            0,
+        );
+        let msg = raw.parse().unwrap();
+        let out = output_options(&Cli::parse_from([
+            "freeswitch-sofia-trace-parser",
+            "--export-dump",
+            "--replace-header",
+            "call-id=templated",
+            "--replace-header",
+            "X-Absent=1",
+        ]));
+        assert_eq!(
+            displayed(&out, &msg).to_bytes(),
+            b"MESSAGE sip:host SIP/2.0\r\nCall-ID: templated\r\nCSeq: 1 MESSAGE\r\n\r\n"
+        );
+
+        let mut dump = Vec::new();
+        output_message(&mut dump, &out, &raw, &msg).unwrap();
+        let exported: Vec<_> = freeswitch_sofia_trace_parser::parse_all(&dump).unwrap();
+        assert_eq!(exported[0].call_id(), Some("templated"));
+    }
+
+    #[test]
+    fn replace_header_matches_compact_forms_in_place() {
+        let raw = udp_raw(
+            b"MESSAGE sip:host SIP/2.0\r\ni: secret@10.0.0.1\r\nf: <sip:a@a>\r\n\
+            CSeq: 1 MESSAGE\r\nFrom: <sip:b@b>\r\n\r\n",
+            0,
+        );
+        let msg = raw.parse().unwrap();
+        let out = output_options(&Cli::parse_from([
+            "freeswitch-sofia-trace-parser",
+            "--export-dump",
+            "--replace-header",
+            "Call-ID=templated",
+            "--replace-header",
+            "from=<sip:x@x>",
+        ]));
+        assert_eq!(
+            displayed(&out, &msg).to_bytes(),
+            b"MESSAGE sip:host SIP/2.0\r\ni: templated\r\nf: <sip:x@x>\r\n\
+            CSeq: 1 MESSAGE\r\nFrom: <sip:x@x>\r\n\r\n"
+        );
+    }
+
+    #[test]
+    fn only_matching_prints_each_body_match() {
+        let msg = udp_raw(
+            b"MESSAGE sip:host SIP/2.0\r\nCall-ID: om1\r\nContent-Length: 26\r\n\r\n\
+            INC-42 then INC-7 and INC-",
+            3,
+        );
+        let out = output_options(&Cli::parse_from([
+            "freeswitch-sofia-trace-parser",
+            "--only-matching",
+            "--body-grep",
+            "INC-[0-9]+",
+        ]));
+        let mut buf = Vec::new();
+        output_message(&mut buf, &out, &msg, &msg.parse().unwrap()).unwrap();
+        assert_eq!(
+            String::from_utf8(buf).unwrap(),
+            "12:00:03.000000 INC-42\n12:00:03.000000 INC-7\n"
+        );
+    }
+
+    #[test]
+    fn tcp_input_reads_until_close() {
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = format!("tcp://{}", listener.local_addr().unwrap());
+        let feeder = std::thread::spawn(move || {
+            let (mut conn, _) = listener.accept().unwrap();
+            for (i, cid) in ["n1", "n2"].iter().enumerate() {
+                let content = format!("MESSAGE sip:host SIP/2.0\r\nCall-ID: {cid}\r\n\r\n");
+                write_dump_frame(&mut conn, &udp_raw(content.as_bytes(), i as u8)).unwrap();
+            }
+        });
+
+        let cli = Cli::parse_from(["freeswitch-sofia-trace-parser", &url]);
+        let call_ids: Vec<String> = freeswitch_sofia_trace_parser::ParsedMessageIterator::from(
+            MessageIterator::new(open_input(&cli)),
+        )
+        .map(|m| m.unwrap().call_id().unwrap().to_string())
+        .collect();
+        feeder.join().unwrap();
+        assert_eq!(call_ids, ["n1", "n2"]);
+    }
+
+    #[test]
+    fn list_call_ids_dedups_in_first_seen_order() {
+        let mut dump = Vec::new();
+        for (i, cid) in ["b", "a", "b", "c", "a"].iter().enumerate() {
+            let content = format!("MESSAGE sip:host SIP/2.0\r\nCall-ID: {cid}\r\n\r\n");
+            write_dump_frame(&mut dump, &udp_raw(content.as_bytes(), i as u8)).unwrap();
+        }
+        let mut out = Vec::new();
+        run_list_call_ids(
+            messages_from(&dump),
+            &mut out,
+            &filters(&[]),
+            Selectors::default(),
+        )
+        .unwrap();
+        assert_eq!(out, b"b\na\nc\n");
+    }
+
+    #[test]
+    fn stats_reports_frames_per_message_by_transport() {
+        let mut dump = Vec::new();
+        for (i, part) in [
+            "MESSAGE sip:host SIP/2.0\r\nCall-ID: t1\r\n",
+            "Content-Length: 0\r\n\r\n",
+            "INFO sip:host SIP/2.0\r\nCall-ID: t2\r\nContent-Length: 0\r\n\r\n",
+        ]
+        .iter()
+        .enumerate()
+        {
+            let header = format!(
+                "recv {} bytes from tcp/10.0.0.2:5060 at 00:00:00.00000{i}:\n",
+                part.len()
+            );
+            dump.extend_from_slice(header.as_bytes());
+            dump.extend_from_slice(part.as_bytes());
+            dump.extend_from_slice(b"\x0B\n");
+        }
+        let udp = b"MESSAGE sip:host SIP/2.0\r\nCall-ID: u1\r\n\r\n";
+        write_dump_frame(&mut dump, &udp_raw(udp, 1)).unwrap();
+
+        let opts = StatsOptions {
+            sizes: None,
+            call_ids: None,
+            roundtrip: false,
+            loops: None,
+            json: false,
+        };
+        let mut out = Vec::new();
+        run_stats(
+            messages_from(&dump),
+            &mut out,
+            &filters(&[]),
+            Selectors::default(),
+            opts,
+        )
+        .unwrap();
+        let out = String::from_utf8(out).unwrap();
+        assert!(
+            out.contains(
+                "\nreassembly:\n  tcp: 3 frames -> 2 messages\n  udp: 1 frames -> 1 messages\n"
+            ),
+            "{out}"
+        );
+    }
+
+    #[test]
+    fn stats_counts_sigcomp_apart_from_parse_errors() {
+        let mut dump = Vec::new();
+        for (i, content) in [
+            &b"MESSAGE sip:host SIP/2.0\r\nCall-ID: s1\r\n\r\n"[..],
+            b"\xF9\x10\x9a\x3c\x00\x01",
+            b"garbage without a line ending",
+        ]
+        .iter()
+        .enumerate()
+        {
+            write_dump_frame(&mut dump, &udp_raw(content, i as u8)).unwrap();
+        }
+        let opts = StatsOptions {
+            sizes: None,
+            call_ids: None,
+            roundtrip: false,
+            loops: None,
+            json: false,
+        };
+        let mut out = Vec::new();
+        run_stats(
+            messages_from(&dump),
+            &mut out,
+            &filters(&[]),
+            Selectors::default(),
+            opts,
+        )
+        .unwrap();
+        let out = String::from_utf8(out).unwrap();
+        assert!(out.contains("\nparse errors: 1\n"), "{out}");
+        assert!(out.contains("\nsigcomp (not decoded): 1\n"), "{out}");
+    }
+
+    #[test]
+    fn strict_skips_out_of_range_status_code() {
+        let mut dump = Vec::new();
+        for (i, line) in ["SIP/2.0 200 OK", "SIP/2.0 700 Nope", "SIP/2.0 99 Weird"]
+            .iter()
+            .enumerate()
+        {
+            let content = format!("{line}\r\nCall-ID: c{i}\r\n\r\n");
+            write_dump_frame(&mut dump, &udp_raw(content.as_bytes(), i as u8)).unwrap();
+        }
+        for (args, expected) in [
+            (&[][..], &b"c0\nc1\nc2\n"[..]),
+            (&["--strict"][..], &b"c0\n"[..]),
+        ] {
+            let mut out = Vec::new();
+            run_list_call_ids(
+                messages_from(&dump),
+                &mut out,
+                &filters(args),
+                Selectors::default(),
+            )
+            .unwrap();
+            assert_eq!(out, expected);
+        }
+    }
+
+    #[test]
+    fn dedup_suppresses_udp_retransmissions() {
+        let content = b"OPTIONS sip:host SIP/2.0\r\n\
+            Call-ID: retrans@host\r\n\
+            CSeq: 1 OPTIONS\r\n\
+            Content-Length: 0\r\n\
+            \r\n";
+        let mut rt = RetransmitFilter::default();
+        let passed = (0..3)
+            .map(|i| udp_message(content, i))
+            .filter(|msg| !rt.is_retransmission(msg))
+            .count();
+        assert_eq!(passed, 1);
+        assert_eq!(rt.suppressed, 2);
+    }
+
+    #[test]
+    fn dedup_ignores_tcp() {
+        let content = b"OPTIONS sip:host SIP/2.0\r\n\
+            Call-ID: tcp@host\r\n\
+            CSeq: 1 OPTIONS\r\n\
+            \r\n";
+        let mut rt = RetransmitFilter::default();
+        let mut msg = udp_message(content, 0);
+        msg.transport = Transport::Tcp;
+        assert!(!rt.is_retransmission(&msg));
+        assert!(!rt.is_retransmission(&msg));
+    }
+
+    #[test]
+    fn dedup_forgets_messages_past_the_window() {
+        let mut rt = RetransmitFilter::default();
+        for i in 0..10 {
+            let content = format!("OPTIONS sip:host SIP/2.0\r\nCall-ID: old{i}@host\r\n\r\n");
+            assert!(!rt.is_retransmission(&udp_message(content.as_bytes(), 0)));
+        }
+        assert_eq!(rt.last_seen.len(), 10);
+        let late = udp_message(b"OPTIONS sip:host SIP/2.0\r\nCall-ID: new@host\r\n\r\n", 40);
+        assert!(!rt.is_retransmission(&late));
+        assert_eq!(rt.last_seen.len(), 1);
+        assert_eq!(rt.order.len(), 1);
+    }
+
+    #[test]
+    fn keepalives_count_towards_head_and_tail() {
+        let mut dump = Vec::new();
+        for (sec, content) in [
+            &b"NOTIFY sip:a@host SIP/2.0\r\nCall-ID: k1\r\n\r\n"[..],
+            b"\r\n\r\n",
+            b"NOTIFY sip:a@host SIP/2.0\r\nCall-ID: k2\r\n\r\n",
+        ]
+        .into_iter()
+        .enumerate()
+        {
+            write_dump_frame(&mut dump, &udp_raw(content, sec as u8)).unwrap();
+        }
+        let run = |limit: &[&str]| {
+            let argv = [
+                "freeswitch-sofia-trace-parser",
+                "--keepalives",
+                "--format",
+                "{call_id}",
+            ];
+            let cli = Cli::parse_from(argv.iter().chain(limit));
+            let mut written = Vec::new();
+            run_filtered(
+                messages_from(&dump),
+                &mut written,
+                &output_options(&cli),
+                &compile_filters(&cli),
+                Selectors::new(&cli),
+            )
+            .unwrap();
+            let out = String::from_utf8(written).unwrap();
+            out.lines()
+                .map(|line| {
+                    if line.contains("keepalive") {
+                        "ping"
+                    } else {
+                        line
+                    }
+                })
+                .collect::<Vec<_>>()
+                .join(" ")
+        };
+        assert_eq!(run(&[]), "k1 ping k2");
+        assert_eq!(run(&["--head", "2"]), "k1 ping");
+        assert_eq!(run(&["--tail", "2"]), "ping k2");
+    }
+
+    #[test]
+    fn dedup_applies_within_dialogs() {
+        let invite = b"INVITE sip:host SIP/2.0\r\nCall-ID: rd1\r\nCSeq: 1 INVITE\r\n\r\n";
+        let f = filters(&[]);
+        let dialog_size = |dedup: bool| {
+            let mut collector = DialogCollector::new(&f, false);
+            collector.retransmits = dedup.then(RetransmitFilter::default);
+            for sec in 0..3 {
+                let raw = udp_raw(invite, sec);
+                let parsed = raw.parse().unwrap();
+                collector.push(raw, parsed);
+            }
+            collector.into_matched()[0].messages.len()
+        };
+        assert_eq!(dialog_size(false), 3);
+        assert_eq!(dialog_size(true), 1);
+    }
 }
@@ -0,0 +1,90 @@
+/// Selection of messages by SIP method: the request method, or for a
+/// response the CSeq method.
+///
+/// OPTIONS is excluded by default, as keepalive pings would otherwise drown
+/// everything else; `include_options(true)` or naming OPTIONS in `method()`
+/// keeps them.
+#[derive(Debug, Clone, Default)]
+pub struct MessageFilter {
+    methods: Vec<String>,
+    excludes: Vec<String>,
+    include_options: bool,
+}
+
+impl MessageFilter {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Keep only this method; repeat to keep several.
+    pub fn method(mut self, method: &str) -> Self {
+        self.methods.push(method.to_ascii_uppercase());
+        self
+    }
+
+    /// Drop this method, whatever else is kept.
+    pub fn exclude(mut self, method: &str) -> Self {
+        self.excludes.push(method.to_ascii_uppercase());
+        self
+    }
+
+    /// Keep OPTIONS, which are excluded by default.
+    pub fn include_options(mut self, include: bool) -> Self {
+        self.include_options = include;
+        self
+    }
+
+    /// True unless OPTIONS were included explicitly or named in `method()`.
+    pub fn excludes_options(&self) -> bool {
+        !self.include_options && !self.methods.iter().any(|m| m == "OPTIONS")
+    }
+
+    /// True for OPTIONS while they are excluded by default.
+    pub fn is_options_excluded(&self, method: &str) -> bool {
+        self.excludes_options() && method.eq_ignore_ascii_case("OPTIONS")
+    }
+
+    /// True for a method dropped with `exclude()`.
+    pub fn is_method_excluded(&self, method: &str) -> bool {
+        self.excludes.iter().any(|m| m.eq_ignore_ascii_case(method))
+    }
+
+    /// True when every method passes, so callers can skip finding it.
+    pub fn is_pass_all(&self) -> bool {
+        self.methods.is_empty() && self.excludes.is_empty() && !self.excludes_options()
+    }
+
+    /// Whether a message with this method is kept.
+    pub fn matches_method(&self, method: &str) -> bool {
+        !self.is_options_excluded(method)
+            && !self.is_method_excluded(method)
+            && (self.methods.is_empty()
+                || self.methods.iter().any(|m| m.eq_ignore_ascii_case(method)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn options_excluded_unless_included_or_selected() {
+        let default = MessageFilter::new();
+        assert!(!default.matches_method("OPTIONS"));
+        assert!(default.matches_method("INVITE"));
+        assert!(!default.is_pass_all());
+
+        let included = MessageFilter::new().include_options(true);
+        assert!(included.matches_method("options"));
+        assert!(included.is_pass_all());
+
+        let selected = MessageFilter::new().method("options");
+        assert!(selected.matches_method("OPTIONS"));
+        assert!(!selected.matches_method("INVITE"));
+
+        let excluded = MessageFilter::new()
+            .include_options(true)
+            .exclude("OPTIONS");
+        assert!(!excluded.matches_method("OPTIONS"));
+    }
+}
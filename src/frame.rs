@@ -1,4 +1,5 @@
 use std::io::Read;
+use std::iter::FusedIterator;
 
 use memchr::memmem;
 use tracing::{debug, info, trace, warn};
@@ -8,7 +9,12 @@ use crate::types::{Direction, Frame, Timestamp, Transport};
 #[derive(Debug)]
 pub enum ParseError {
     InvalidHeader(String),
-    InvalidMessage(String),
+    /// `offset` is the byte position in the message content where parsing
+    /// failed, when known.
+    InvalidMessage {
+        reason: String,
+        offset: Option<usize>,
+    },
     Io(std::io::Error),
 }
 
@@ -16,7 +22,14 @@ impl fmt::Display for ParseError {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         match self {
             ParseError::InvalidHeader(msg) => write!(f, "invalid frame header: {msg}"),
-            ParseError::InvalidMessage(msg) => write!(f, "invalid SIP message: {msg}"),
+            ParseError::InvalidMessage {
+                reason,
+                offset: Some(offset),
+            } => write!(f, "invalid SIP message at byte {offset}: {reason}"),
+            ParseError::InvalidMessage {
+                reason,
+                offset: None,
+            } => write!(f, "invalid SIP message: {reason}"),
             ParseError::Io(e) => write!(f, "I/O error: {e}"),
         }
     }
@@ -31,6 +44,15 @@ impl std::error::Error for ParseError {
     }
 }
 
+impl ParseError {
+    /// Whether iteration can usefully continue past this error. Malformed
+    /// frames and messages are skipped over; an I/O error means the input
+    /// itself failed and every further read will likely fail the same way.
+    pub fn is_recoverable(&self) -> bool {
+        !matches!(self, ParseError::Io(_))
+    }
+}
+
 impl From<std::io::Error> for ParseError {
     fn from(e: std::io::Error) -> Self {
         ParseError::Io(e)
@@ -92,45 +114,74 @@ fn parse_usize(bytes: &[u8]) -> Option<usize> {
 
 /// Parse timestamp from bytes: either `HH:MM:SS.usec` or `YYYY-MM-DD HH:MM:SS.usec`
 fn parse_timestamp(bytes: &[u8]) -> Option<Timestamp> {
-    // Try full datetime first: YYYY-MM-DD HH:MM:SS.usec (min 26 bytes)
-    if bytes.len() >= 26 && bytes[4] == b'-' && bytes[7] == b'-' && bytes[10] == b' ' {
+    parse_timestamp_prefix(bytes).map(|(ts, _)| ts)
+}
+
+/// Parse a timestamp at the start of `bytes`, returning it with the number
+/// of bytes consumed. Anything after the fractional digits is left unread.
+pub(crate) fn parse_timestamp_prefix(bytes: &[u8]) -> Option<(Timestamp, usize)> {
+    // Try full datetime first: YYYY-MM-DD HH:MM:SS[.frac] (min 19 bytes)
+    if bytes.len() >= 19 && bytes[4] == b'-' && bytes[7] == b'-' && bytes[10] == b' ' {
         let year = parse_u16(&bytes[0..4])?;
         let month = parse_u8(&bytes[5..7])?;
         let day = parse_u8(&bytes[8..10])?;
-        let ts = parse_time_part(&bytes[11..])?;
-        return Some(Timestamp::DateTime {
+        let (hour, min, sec, usec, len) = parse_time_part(&bytes[11..])?;
+        let ts = Timestamp::DateTime {
             year,
             month,
             day,
-            hour: ts.0,
-            min: ts.1,
-            sec: ts.2,
-            usec: ts.3,
-        });
-    }
-    // Time-only: HH:MM:SS.usec (min 15 bytes)
-    let (hour, min, sec, usec) = parse_time_part(bytes)?;
-    Some(Timestamp::TimeOnly {
+            hour,
+            min,
+            sec,
+            usec,
+        };
+        return Some((ts, 11 + len));
+    }
+    // Time-only: HH:MM:SS[.frac] (min 8 bytes)
+    let (hour, min, sec, usec, len) = parse_time_part(bytes)?;
+    let ts = Timestamp::TimeOnly {
         hour,
         min,
         sec,
         usec,
-    })
+    };
+    Some((ts, len))
 }
 
-/// Parse `HH:MM:SS.usec` from bytes, returns (hour, min, sec, usec)
-fn parse_time_part(bytes: &[u8]) -> Option<(u8, u8, u8, u32)> {
-    if bytes.len() < 15 {
+/// Parse `HH:MM:SS[.frac]` from bytes, returns (hour, min, sec, usec, consumed)
+///
+/// The fraction may have any precision; it is scaled (or truncated) to
+/// microseconds, so `.123` is 123000 usec.
+fn parse_time_part(bytes: &[u8]) -> Option<(u8, u8, u8, u32, usize)> {
+    if bytes.len() < 8 {
         return None;
     }
-    if bytes[2] != b':' || bytes[5] != b':' || bytes[8] != b'.' {
+    if bytes[2] != b':' || bytes[5] != b':' {
         return None;
     }
     let hour = parse_u8(&bytes[0..2])?;
     let min = parse_u8(&bytes[3..5])?;
     let sec = parse_u8(&bytes[6..8])?;
-    let usec = parse_u32(&bytes[9..15])?;
-    Some((hour, min, sec, usec))
+    match bytes.get(8) {
+        None => Some((hour, min, sec, 0, 8)),
+        Some(b'.') => {
+            let (usec, digits) = parse_fraction_usec(&bytes[9..])?;
+            Some((hour, min, sec, usec, 9 + digits))
+        }
+        Some(_) => None,
+    }
+}
+
+/// Scale the leading digits of a fractional second to microseconds.
+/// Returns the value and the number of digits consumed.
+fn parse_fraction_usec(bytes: &[u8]) -> Option<(u32, usize)> {
+    let digits = bytes.iter().take_while(|b| b.is_ascii_digit()).count();
+    if digits == 0 {
+        return None;
+    }
+    let precision = digits.min(6);
+    let value = parse_u32(&bytes[..precision])?;
+    Some((value * 10u32.pow((6 - precision) as u32), digits))
 }
 
 /// Parse a frame header line from `&[u8]`.
@@ -253,11 +304,23 @@ pub fn is_frame_header(data: &[u8]) -> bool {
 
 const READ_BUF_SIZE: usize = 32 * 1024;
 
+/// Bytes past a frame's declared end read before a lenient split is ruled
+/// out: blank-line padding plus enough of a header for `is_frame_header`.
+const LENIENT_LOOKAHEAD: usize = 64;
+
 pub struct FrameIterator<R> {
     reader: R,
     buf: Vec<u8>,
+    /// Set by `from_slice`: the whole input is the reader itself, viewed in
+    /// place instead of being read into `buf`.
+    in_place: Option<fn(&R) -> &[u8]>,
+    /// Start of the unconsumed bytes in `buf` (or the in-place input).
+    start: usize,
     eof: bool,
     frame_count: u64,
+    /// Input bytes consumed before `buf[start]`.
+    offset: u64,
+    lenient: bool,
 }
 
 impl<R: Read> FrameIterator<R> {
@@ -265,15 +328,56 @@ impl<R: Read> FrameIterator<R> {
         FrameIterator {
             reader,
             buf: Vec::with_capacity(READ_BUF_SIZE * 2),
+            in_place: None,
+            start: 0,
             eof: false,
             frame_count: 0,
+            offset: 0,
+            lenient: false,
+        }
+    }
+
+    /// Also end a frame where its byte count says it ends when a frame header
+    /// follows there, after optional blank lines, without a `\x0B\n`
+    /// boundary. For dumps whose `\x0B` bytes were stripped along the way;
+    /// content that happens to end in something header-like is misread.
+    pub fn lenient(mut self) -> Self {
+        self.lenient = true;
+        self
+    }
+
+    /// Up to `n` bytes following the last frame returned, past its `\x0B\n`
+    /// boundary, without consuming them: what the next call resyncs from.
+    /// Shorter than `n` only at end of input.
+    pub fn peek(&mut self, n: usize) -> Result<&[u8], std::io::Error> {
+        while self.pending().len() < n && self.fill_buf()? {}
+        let pending = self.pending();
+        Ok(&pending[..n.min(pending.len())])
+    }
+
+    /// Unconsumed bytes.
+    fn pending(&self) -> &[u8] {
+        match self.in_place {
+            Some(view) => &view(&self.reader)[self.start..],
+            None => &self.buf[self.start..],
         }
     }
 
+    /// Skip `n` bytes of the pending data, advancing the input offset.
+    fn consume(&mut self, n: usize) {
+        self.start += n;
+        self.offset += n as u64;
+    }
+
     fn fill_buf(&mut self) -> Result<bool, std::io::Error> {
         if self.eof {
             return Ok(false);
         }
+        // Only shift consumed bytes out when more room is needed
+        if self.start > 0 {
+            self.buf.drain(..self.start);
+            self.start = 0;
+        }
         let old_len = self.buf.len();
         self.buf.resize(old_len + READ_BUF_SIZE, 0);
         let n = self.reader.read(&mut self.buf[old_len..])?;
@@ -290,10 +394,10 @@ impl<R: Read> FrameIterator<R> {
         let finder = memmem::Finder::new(b"\x0B\n");
         let mut search_from = start;
         loop {
-            let pos = finder.find(&self.buf[search_from..])?;
+            let pos = finder.find(&self.pending()[search_from..])?;
             let abs_pos = search_from + pos;
             let after = abs_pos + 2;
-            if after >= self.buf.len() {
+            if after >= self.pending().len() {
                 // Boundary at very end — could be real, but we can't validate header yet
                 // If EOF, accept it as boundary (content ends at \x0B)
                 if self.eof {
@@ -301,7 +405,7 @@ impl<R: Read> FrameIterator<R> {
                 }
                 return None; // Need more data
             }
-            if is_frame_header(&self.buf[after..]) {
+            if is_frame_header(&self.pending()[after..]) {
                 return Some(abs_pos);
             }
             // \x0B\n in content, not a boundary — skip past it
@@ -313,20 +417,35 @@ impl<R: Read> FrameIterator<R> {
         }
     }
 
+    /// Start of a frame header at `end`, past any blank lines, for
+    /// `lenient` splitting.
+    fn header_after(&self, end: usize) -> Option<usize> {
+        let rest = self.pending().get(end..)?;
+        let padding = rest
+            .iter()
+            .take_while(|&&b| b == b'\r' || b == b'\n')
+            .count();
+        is_frame_header(&rest[padding..]).then_some(end + padding)
+    }
+
     /// Skip to the first valid frame header in the buffer (for partial first frames).
     fn skip_to_first_header(&mut self) -> Option<usize> {
-        if is_frame_header(&self.buf) {
+        if is_frame_header(self.pending()) {
             return Some(0);
         }
         // Look for \x0B\n followed by a valid header
         let finder = memmem::Finder::new(b"\x0B\n");
         let mut search_from = 0;
         loop {
-            if let Some(pos) = finder.find(&self.buf[search_from..]) {
+            if let Some(pos) = finder.find(&self.pending()[search_from..]) {
                 let abs_pos = search_from + pos;
                 let after = abs_pos + 2;
-                if after < self.buf.len() && is_frame_header(&self.buf[after..]) {
-                    warn!(skipped_bytes = after, "skipped partial first frame");
+                if after < self.pending().len() && is_frame_header(&self.pending()[after..]) {
+                    warn!(
+                        offset = self.offset,
+                        skipped_bytes = after,
+                        "skipped partial first frame"
+                    );
                     return Some(after);
                 }
                 search_from = abs_pos + 2;
@@ -337,18 +456,40 @@ impl<R: Read> FrameIterator<R> {
     }
 }
 
+impl<'a> FrameIterator<&'a [u8]> {
+    /// Iterate over a dump already in memory.
+    ///
+    /// The slice is read in place as the whole input, without being copied
+    /// into the internal buffer; only each frame's content is copied out.
+    pub fn from_slice(data: &'a [u8]) -> Self {
+        fn whole<'r>(data: &'r &[u8]) -> &'r [u8] {
+            data
+        }
+        FrameIterator {
+            reader: data,
+            buf: Vec::new(),
+            in_place: Some(whole),
+            start: 0,
+            eof: true,
+            frame_count: 0,
+            offset: 0,
+            lenient: false,
+        }
+    }
+}
+
 impl<R: Read> Iterator for FrameIterator<R> {
     type Item = Result<Frame, ParseError>;
 
     fn next(&mut self) -> Option<Self::Item> {
         // Ensure we have data
-        if self.buf.is_empty() && !self.eof {
+        if self.pending().is_empty() && !self.eof {
             if let Err(e) = self.fill_buf() {
                 return Some(Err(ParseError::Io(e)));
             }
         }
 
-        if self.buf.is_empty() {
+        if self.pending().is_empty() {
             return None;
         }
 
@@ -358,7 +499,7 @@ impl<R: Read> Iterator for FrameIterator<R> {
                 match self.skip_to_first_header() {
                     Some(offset) => {
                         if offset > 0 {
-                            self.buf.drain(..offset);
+                            self.consume(offset);
                         }
                         break;
                     }
@@ -375,18 +516,18 @@ impl<R: Read> Iterator for FrameIterator<R> {
             }
         }
 
-        if self.buf.is_empty() {
+        if self.pending().is_empty() {
             return None;
         }
 
         // Strip inter-frame newline padding (\n or \r\n between frames)
         let mut strip = 0;
-        while strip < self.buf.len() {
-            if self.buf[strip] == b'\n' {
+        while strip < self.pending().len() {
+            if self.pending()[strip] == b'\n' {
                 strip += 1;
-            } else if strip + 1 < self.buf.len()
-                && self.buf[strip] == b'\r'
-                && self.buf[strip + 1] == b'\n'
+            } else if strip + 1 < self.pending().len()
+                && self.pending()[strip] == b'\r'
+                && self.pending()[strip + 1] == b'\n'
             {
                 strip += 2;
             } else {
@@ -394,15 +535,17 @@ impl<R: Read> Iterator for FrameIterator<R> {
             }
         }
         if strip > 0 {
-            self.buf.drain(..strip);
-            if self.buf.is_empty() {
+            self.consume(strip);
+            if self.pending().is_empty() {
                 return self.next();
             }
         }
 
+        let frame_offset = self.offset;
+
         // Parse frame header — may need more data if header spans buffer boundary
         let (direction, byte_count, transport, address, timestamp, header_len) = loop {
-            match parse_frame_header(&self.buf) {
+            match parse_frame_header(self.pending()) {
                 Ok(h) => break h,
                 Err(ParseError::InvalidHeader(ref msg)) if msg == "no newline in header" => {
                     if self.eof {
@@ -427,32 +570,39 @@ impl<R: Read> Iterator for FrameIterator<R> {
                             }
                         })
                         .collect();
-                    if header_preview.starts_with("dump started at ") {
-                        let skip = memchr::memchr(b'\n', &self.buf)
+                    if is_dump_marker(self.pending()) {
+                        let skip = memchr::memchr(b'\n', self.pending())
                             .map(|p| {
                                 let mut end = p + 1;
-                                while end < self.buf.len() && self.buf[end] == b'\n' {
+                                while end < self.pending().len() && self.pending()[end] == b'\n' {
                                     end += 1;
                                 }
                                 end
                             })
-                            .unwrap_or(self.buf.len());
+                            .unwrap_or(self.pending().len());
                         info!(
+                            offset = frame_offset,
                             header = %header_preview,
                             skipped_bytes = skip,
-                            "skipped dump restart marker",
+                            "skipped dump marker",
                         );
-                        self.buf.drain(..skip);
+                        self.consume(skip);
                         return self.next();
                     }
                     let skip = if let Some(b) = self.find_boundary(0) {
                         b + 2
                     } else {
-                        memchr::memchr(b'\n', &self.buf)
+                        memchr::memchr(b'\n', self.pending())
                             .map(|p| p + 1)
-                            .unwrap_or(self.buf.len())
+                            .unwrap_or(self.pending().len())
                     };
-                    self.buf.drain(..skip);
+                    debug!(
+                        offset = frame_offset,
+                        header = %header_preview,
+                        skipped_bytes = skip,
+                        "invalid frame header, resyncing"
+                    );
+                    self.consume(skip);
                     return Some(Err(e));
                 }
             }
@@ -467,26 +617,27 @@ impl<R: Read> Iterator for FrameIterator<R> {
         // is followed by garbage from the next file's truncated first frame.
         loop {
             // Ensure we have enough data to check the expected position
-            while self.buf.len() <= expected_end + 1 && !self.eof {
+            let lookahead = if self.lenient { LENIENT_LOOKAHEAD } else { 1 };
+            while self.pending().len() <= expected_end + lookahead && !self.eof {
                 if let Err(e) = self.fill_buf() {
                     return Some(Err(ParseError::Io(e)));
                 }
             }
 
             // Check at expected position first (byte_count hint)
-            if expected_end < self.buf.len() && self.buf[expected_end] == 0x0B {
-                let has_newline =
-                    expected_end + 1 < self.buf.len() && self.buf[expected_end + 1] == b'\n';
-                let at_eof = expected_end + 1 >= self.buf.len() && self.eof;
+            if expected_end < self.pending().len() && self.pending()[expected_end] == 0x0B {
+                let has_newline = expected_end + 1 < self.pending().len()
+                    && self.pending()[expected_end + 1] == b'\n';
+                let at_eof = expected_end + 1 >= self.pending().len() && self.eof;
 
                 if has_newline || at_eof {
-                    let content = self.buf[content_start..expected_end].to_vec();
+                    let content = self.pending()[content_start..expected_end].to_vec();
                     let drain_to = if has_newline {
                         expected_end + 2
                     } else {
                         expected_end + 1
                     };
-                    self.buf.drain(..drain_to);
+                    self.consume(drain_to);
                     self.frame_count += 1;
                     return Some(Ok(Frame {
                         direction,
@@ -495,19 +646,51 @@ impl<R: Read> Iterator for FrameIterator<R> {
                         address,
                         timestamp,
                         content,
+                        offset: frame_offset,
+                    }));
+                }
+            }
+
+            // Lenient: a header right after the declared content, with no
+            // boundary before it, ends the frame
+            if let Some(next) = self
+                .lenient
+                .then(|| self.header_after(expected_end))
+                .flatten()
+            {
+                if self
+                    .find_boundary(content_start)
+                    .is_none_or(|b| b >= expected_end)
+                {
+                    trace!(
+                        offset = frame_offset,
+                        "frame split without \\x0B\\n boundary"
+                    );
+                    let content = self.pending()[content_start..expected_end].to_vec();
+                    self.consume(next);
+                    self.frame_count += 1;
+                    return Some(Ok(Frame {
+                        direction,
+                        byte_count,
+                        transport,
+                        address,
+                        timestamp,
+                        content,
+                        offset: frame_offset,
                     }));
                 }
             }
 
             // Fall back to scanning for \x0B\n + valid header
             if let Some(boundary_pos) = self.find_boundary(content_start) {
-                let content = self.buf[content_start..boundary_pos].to_vec();
-                self.buf.drain(..boundary_pos + 2);
+                let content = self.pending()[content_start..boundary_pos].to_vec();
+                self.consume(boundary_pos + 2);
                 self.frame_count += 1;
 
                 if content.len() != byte_count {
                     debug!(
                         frame = self.frame_count,
+                        offset = frame_offset,
                         expected = byte_count,
                         actual = content.len(),
                         "frame content size mismatch"
@@ -521,23 +704,25 @@ impl<R: Read> Iterator for FrameIterator<R> {
                     address,
                     timestamp,
                     content,
+                    offset: frame_offset,
                 }));
             }
 
             if self.eof {
                 // Last frame — no trailing \x0B\n
-                let end = if self.buf.last() == Some(&0x0B) {
-                    self.buf.len() - 1
+                let end = if self.pending().last() == Some(&0x0B) {
+                    self.pending().len() - 1
                 } else {
-                    self.buf.len()
+                    self.pending().len()
                 };
-                let content = self.buf[content_start..end].to_vec();
-                self.buf.clear();
+                let content = self.pending()[content_start..end].to_vec();
+                self.consume(self.pending().len());
                 self.frame_count += 1;
 
                 if content.len() != byte_count {
                     debug!(
                         frame = self.frame_count,
+                        offset = frame_offset,
                         expected = byte_count,
                         actual = content.len(),
                         "last frame content size mismatch"
@@ -551,6 +736,7 @@ impl<R: Read> Iterator for FrameIterator<R> {
                     address,
                     timestamp,
                     content,
+                    offset: frame_offset,
                 }));
             }
 
@@ -561,6 +747,18 @@ impl<R: Read> Iterator for FrameIterator<R> {
     }
 }
 
+/// `None` is only returned once the reader hit end of input, which is never
+/// read again.
+impl<R: Read> FusedIterator for FrameIterator<R> {}
+
+/// Banner lines mod_sofia writes when a dump is opened or closed.
+const DUMP_MARKERS: &[&[u8]] = &[b"dump started at ", b"dump ended at "];
+
+/// True when `line` starts with a dump banner rather than a frame header.
+fn is_dump_marker(line: &[u8]) -> bool {
+    DUMP_MARKERS.iter().any(|marker| line.starts_with(marker))
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -649,6 +847,60 @@ mod tests {
         );
     }
 
+    fn timestamp_of(ts: &str) -> Timestamp {
+        let header = format!("recv 1 bytes from udp/1.1.1.1:5060 at {ts}:\n");
+        parse_frame_header(header.as_bytes()).unwrap().4
+    }
+
+    fn time_only(usec: u32) -> Timestamp {
+        Timestamp::TimeOnly {
+            hour: 12,
+            min: 0,
+            sec: 0,
+            usec,
+        }
+    }
+
+    #[test]
+    fn parse_millisecond_timestamp() {
+        assert_eq!(timestamp_of("12:00:00.123"), time_only(123000));
+    }
+
+    #[test]
+    fn parse_timestamp_without_fraction() {
+        assert_eq!(timestamp_of("12:00:00"), time_only(0));
+    }
+
+    #[test]
+    fn parse_variable_precision_fractions() {
+        assert_eq!(timestamp_of("12:00:00.5"), time_only(500000));
+        assert_eq!(timestamp_of("12:00:00.000042"), time_only(42));
+        assert_eq!(timestamp_of("12:00:00.123456789"), time_only(123456));
+    }
+
+    #[test]
+    fn parse_datetime_variable_precision() {
+        let date_time = |usec| Timestamp::DateTime {
+            year: 2026,
+            month: 2,
+            day: 1,
+            hour: 10,
+            min: 0,
+            sec: 0,
+            usec,
+        };
+        assert_eq!(timestamp_of("2026-02-01 10:00:00"), date_time(0));
+        assert_eq!(timestamp_of("2026-02-01 10:00:00.25"), date_time(250000));
+    }
+
+    #[test]
+    fn parse_invalid_fraction() {
+        let header = b"recv 1 bytes from udp/1.1.1.1:5060 at 12:00:00.:\n";
+        assert!(parse_frame_header(header).is_err());
+        let header = b"recv 1 bytes from udp/1.1.1.1:5060 at 12:00:00,5:\n";
+        assert!(parse_frame_header(header).is_err());
+    }
+
     #[test]
     fn parse_invalid_header() {
         assert!(parse_frame_header(b"invalid header\n").is_err());
@@ -695,6 +947,24 @@ mod tests {
         assert_eq!(frames[1].direction, Direction::Sent);
     }
 
+    #[test]
+    fn frame_iterator_from_slice() {
+        let data = b"recv 5 bytes from tcp/1.1.1.1:5060 at 00:00:00.000000:\nhello\x0B\n\
+            sent 7 bytes to udp/1.1.1.1:5060 at 00:00:00.000001:\nwor\x0B\nld\x0B\n\
+            recv 3 bytes from tcp/1.1.1.1:5060 at 00:00:00.000002:\nend";
+        let mut iter = FrameIterator::from_slice(data);
+        let frames: Vec<Frame> = iter.by_ref().collect::<Result<Vec<_>, _>>().unwrap();
+        // Read in place: nothing was buffered
+        assert_eq!(iter.buf.capacity(), 0);
+        let contents: Vec<&[u8]> = frames.iter().map(|f| &f.content[..]).collect();
+        assert_eq!(contents, [&b"hello"[..], b"wor\x0B\nld", b"end"]);
+        assert_eq!(frames[1].offset, 62);
+        let from_reader: Vec<Frame> = FrameIterator::new(&data[..])
+            .collect::<Result<Vec<_>, _>>()
+            .unwrap();
+        assert_eq!(frames, from_reader);
+    }
+
     #[test]
     fn frame_iterator_vt_in_content() {
         // \x0B in content but not followed by valid header — should NOT split
@@ -761,6 +1031,23 @@ mod tests {
         assert_eq!(frames[1].content, b"bye");
     }
 
+    #[test]
+    fn byte_count_mismatch_flags_cut_frames() {
+        let mut data = Vec::new();
+        data.extend_from_slice(
+            b"recv 5 bytes from tcp/1.1.1.1:5060 at 00:00:00.000000:\nhello\x0B\n",
+        );
+        data.extend_from_slice(
+            b"recv 9 bytes from tcp/1.1.1.1:5060 at 00:00:01.000000:\nshort\x0B\n",
+        );
+        data.extend_from_slice(b"sent 8 bytes to tcp/1.1.1.1:5060 at 00:00:02.000000:\nbye");
+        let frames: Vec<Frame> = FrameIterator::new(&data[..])
+            .collect::<Result<Vec<_>, _>>()
+            .unwrap();
+        let mismatches: Vec<bool> = frames.iter().map(Frame::byte_count_mismatch).collect();
+        assert_eq!(mismatches, [false, true, true]);
+    }
+
     #[test]
     fn frame_iterator_file_concatenation() {
         // Simulates `cat dump.20 dump.21 | parser`
@@ -891,6 +1178,20 @@ mod tests {
         assert!(frames.is_empty());
     }
 
+    #[test]
+    fn frame_iterator_fused_after_truncated_header() {
+        let mut data = Vec::new();
+        data.extend_from_slice(
+            b"recv 5 bytes from tcp/1.1.1.1:5060 at 00:00:00.000000:\nhello\x0B\n",
+        );
+        data.extend_from_slice(b"sent 3 bytes to tcp/1.1.1.1:50");
+        let mut frames = FrameIterator::new(&data[..]);
+        assert_eq!(frames.next().unwrap().unwrap().content, b"hello");
+        for _ in 0..3 {
+            assert!(frames.next().is_none());
+        }
+    }
+
     #[test]
     fn frame_iterator_only_garbage() {
         let data = b"this is not a SIP trace dump at all, just garbage text";
@@ -931,6 +1232,81 @@ mod tests {
         assert_eq!(frames[1].as_ref().unwrap().content, b"bye");
     }
 
+    #[test]
+    fn frame_offsets() {
+        let first = b"recv 5 bytes from tcp/1.1.1.1:5060 at 00:00:00.000000:\nhello\x0B\n";
+        let mut data = first.to_vec();
+        data.extend_from_slice(b"sent 3 bytes to tcp/2.2.2.2:5060 at 00:00:01.000000:\nbye\x0B\n");
+
+        let frames: Vec<Frame> = FrameIterator::new(&data[..])
+            .collect::<Result<Vec<_>, _>>()
+            .unwrap();
+        assert_eq!(frames[0].offset, 0);
+        assert_eq!(frames[1].offset, first.len() as u64);
+        let start = frames[1].offset as usize;
+        assert!(data[start..].starts_with(b"sent 3 bytes"));
+    }
+
+    #[test]
+    fn peek_shows_bytes_after_boundary() {
+        let mut data =
+            b"recv 5 bytes from tcp/1.1.1.1:5060 at 00:00:00.000000:\nhello\x0B\n\n".to_vec();
+        data.extend_from_slice(b"sent 3 bytes to tcp/2.2.2.2:5060 at 00:00:01.000000:\nbye\x0B\n");
+
+        let mut frames = FrameIterator::new(&data[..]);
+        frames.next().unwrap().unwrap();
+        assert_eq!(frames.peek(8).unwrap(), b"\nsent 3 ");
+        // Peeking does not consume
+        assert_eq!(frames.next().unwrap().unwrap().content, b"bye");
+        assert_eq!(frames.peek(8).unwrap(), b"");
+    }
+
+    #[test]
+    fn frame_offsets_after_skipped_bytes() {
+        // Partial first frame and a dump marker are counted in the offset
+        let mut data = b"tail of a rotated frame\x0B\n".to_vec();
+        data.extend_from_slice(
+            b"recv 5 bytes from tcp/1.1.1.1:5060 at 00:00:00.000000:\nhello\x0B\n",
+        );
+        data.extend_from_slice(b"dump started at Thu Aug 22 11:38:11 2024\n\n");
+        let second = data.len() as u64;
+        data.extend_from_slice(b"sent 3 bytes to tcp/2.2.2.2:5060 at 00:00:01.000000:\nbye\x0B\n");
+
+        let frames: Vec<Frame> = FrameIterator::new(&data[..])
+            .collect::<Result<Vec<_>, _>>()
+            .unwrap();
+        assert_eq!(frames[0].offset, 25);
+        assert_eq!(frames[1].offset, second);
+    }
+
+    #[test]
+    fn frame_iterator_dump_ended_marker() {
+        let mut data = Vec::new();
+        data.extend_from_slice(
+            b"recv 5 bytes from tcp/1.1.1.1:5060 at 00:00:00.000000:\nhello\x0B\n",
+        );
+        data.extend_from_slice(b"dump ended at Thu Aug 22 11:38:10 2024\n\n");
+        data.extend_from_slice(b"dump started at Thu Aug 22 11:38:11 2024\n\n\n");
+        data.extend_from_slice(b"sent 3 bytes to tcp/2.2.2.2:5060 at 00:00:01.000000:\nbye\x0B\n");
+
+        let frames: Vec<Frame> = FrameIterator::new(&data[..])
+            .collect::<Result<Vec<_>, _>>()
+            .unwrap();
+        assert_eq!(frames.len(), 2);
+        assert_eq!(frames[1].content, b"bye");
+    }
+
+    #[test]
+    fn dump_marker_prefixes() {
+        assert!(is_dump_marker(
+            b"dump started at Thu Aug 22 11:38:11 2024\n"
+        ));
+        assert!(is_dump_marker(b"dump ended at Thu Aug 22 11:38:11 2024"));
+        assert!(!is_dump_marker(
+            b"recv 5 bytes from tcp/1.1.1.1:5060 at 00:00:00.000000:"
+        ));
+    }
+
     #[test]
     fn frame_iterator_extra_newline_after_boundary() {
         // Some dump files have \x0B\n\n between frames (extra \n after boundary).
@@ -987,4 +1363,49 @@ mod tests {
         assert_eq!(frames.len(), 1);
         assert_eq!(frames[0].content, b"hello");
     }
+
+    #[test]
+    fn lenient_splits_without_vertical_tab() {
+        let data = b"recv 5 bytes from udp/1.1.1.1:5060 at 00:00:00.000000:\nhello\n\
+            recv 5 bytes from udp/1.1.1.1:5060 at 00:00:01.000000:\nworld\x0B\n";
+
+        // Strict mode finds no boundary between them
+        let strict: Vec<Frame> = FrameIterator::new(&data[..])
+            .collect::<Result<_, _>>()
+            .unwrap();
+        assert_eq!(strict.len(), 1);
+
+        let frames: Vec<Frame> = FrameIterator::new(&data[..])
+            .lenient()
+            .collect::<Result<_, _>>()
+            .unwrap();
+        assert_eq!(frames.len(), 2);
+        assert_eq!(frames[0].content, b"hello");
+        assert_eq!(frames[1].content, b"world");
+        assert_eq!(frames[1].offset, 61);
+    }
+
+    /// Serves `data`, then fails every read after it.
+    struct FailAfter<'a>(&'a [u8]);
+
+    impl Read for FailAfter<'_> {
+        fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+            if self.0.is_empty() {
+                return Err(std::io::Error::other("disk went away"));
+            }
+            self.0.read(buf)
+        }
+    }
+
+    #[test]
+    fn io_error_mid_stream_is_fatal() {
+        let data = b"recv 5 bytes from udp/1.1.1.1:5060 at 00:00:00.000000:\nhello\x0B\n\
+            recv 5 bytes from udp/1.1.1.1:5060 at 00:00:01.000000:\nwor";
+        let mut it = FrameIterator::new(FailAfter(data));
+        assert_eq!(it.next().unwrap().unwrap().content, b"hello");
+        let err = it.next().unwrap().unwrap_err();
+        assert!(matches!(err, ParseError::Io(_)));
+        assert!(!err.is_recoverable());
+        assert!(ParseError::InvalidHeader("bad".into()).is_recoverable());
+    }
 }
@@ -4,6 +4,8 @@ pub struct GrepFilter<R> {
     inner: BufReader<R>,
     buf: Vec<u8>,
     pos: usize,
+    strip_prefixes: bool,
+    path: Option<Vec<u8>>,
 }
 
 impl<R: Read> GrepFilter<R> {
@@ -12,12 +14,67 @@ impl<R: Read> GrepFilter<R> {
             inner: BufReader::new(reader),
             buf: Vec::new(),
             pos: 0,
+            strip_prefixes: false,
+            path: None,
         }
     }
+
+    /// Also strip `grep -n` / `grep -rn` line prefixes (`path:NNN:` for
+    /// matches, `path-NNN-` for context lines).
+    ///
+    /// The path is learned from the first prefixed line whose remainder is
+    /// a frame header or boundary; lines without that prefix pass through.
+    pub fn with_prefix_stripping(reader: R) -> Self {
+        Self {
+            strip_prefixes: true,
+            ..Self::new(reader)
+        }
+    }
+}
+
+/// Length of the line number marker at the start of `rest`: `:NNN:` or
+/// `-NNN-` after a path, bare `NNN:` or `NNN-` when grep printed no path.
+fn line_number_len(rest: &[u8], after_path: bool) -> Option<usize> {
+    let lead = usize::from(after_path);
+    let digits = rest
+        .get(lead..)?
+        .iter()
+        .take_while(|b| b.is_ascii_digit())
+        .count();
+    let sep = *rest.get(lead + digits)?;
+    if digits == 0 || (sep != b':' && sep != b'-') || (after_path && rest[0] != sep) {
+        return None;
+    }
+    Some(lead + digits + 1)
+}
+
+fn looks_like_dump_line(line: &[u8]) -> bool {
+    line.starts_with(b"recv ") || line.starts_with(b"sent ") || line.starts_with(b"\x0B")
+}
+
+/// Length of the grep prefix on `line`, learning the path when needed.
+fn grep_prefix_len(line: &[u8], path: &mut Option<Vec<u8>>) -> Option<usize> {
+    if let Some(known) = path.as_deref() {
+        let rest = line.strip_prefix(known);
+        if let Some(n) = rest.and_then(|r| line_number_len(r, !known.is_empty())) {
+            return Some(known.len() + n);
+        }
+    }
+    for i in 0..line.len() {
+        if let Some(n) = line_number_len(&line[i..], i > 0) {
+            if looks_like_dump_line(&line[i + n..]) {
+                *path = Some(line[..i].to_vec());
+                return Some(i + n);
+            }
+        }
+    }
+    None
 }
 
+/// `line` as returned by `read_until`: only the last line before EOF can
+/// lack its `\n`, so a bare `--` or `--\r` is grep's final separator.
 fn is_grep_separator(line: &[u8]) -> bool {
-    line == b"--\n" || line == b"--\r\n"
+    matches!(line, b"--\n" | b"--\r\n" | b"--" | b"--\r")
 }
 
 impl<R: Read> Read for GrepFilter<R> {
@@ -45,6 +102,10 @@ impl<R: Read> Read for GrepFilter<R> {
             }
             if is_grep_separator(&self.buf[old_len..]) {
                 self.buf.truncate(old_len);
+            } else if self.strip_prefixes {
+                if let Some(n) = grep_prefix_len(&self.buf[old_len..], &mut self.path) {
+                    self.buf.drain(old_len..old_len + n);
+                }
             }
         }
 
@@ -70,6 +131,65 @@ mod tests {
         out
     }
 
+    fn filter_prefixed(input: &[u8]) -> Vec<u8> {
+        let mut out = Vec::new();
+        GrepFilter::with_prefix_stripping(input)
+            .read_to_end(&mut out)
+            .unwrap();
+        out
+    }
+
+    #[test]
+    fn strip_match_prefix() {
+        let input = b"dir/a.dump:12:\x0B\ndir/a.dump:13:recv 4 bytes from udp/1.1.1.1:5060 at 00:00:00.000000:\n";
+        assert_eq!(
+            filter_prefixed(input),
+            b"\x0B\nrecv 4 bytes from udp/1.1.1.1:5060 at 00:00:00.000000:\n"
+        );
+    }
+
+    #[test]
+    fn strip_context_prefix() {
+        let input = b"a.dump-7-recv 4 bytes from udp/1.1.1.1:5060 at 00:00:00.000000:\n\
+            a.dump-8-Via: SIP/2.0/UDP 1.1.1.1\r\n\
+            --\n\
+            a.dump:20:\x0B\n";
+        assert_eq!(
+            filter_prefixed(input),
+            b"recv 4 bytes from udp/1.1.1.1:5060 at 00:00:00.000000:\n\
+            Via: SIP/2.0/UDP 1.1.1.1\r\n\
+            \x0B\n"
+        );
+    }
+
+    #[test]
+    fn strip_prefix_path_with_dashes_and_colons() {
+        let input =
+            b"logs/esinet-2024-05:v4.dump:3:\x0B\nlogs/esinet-2024-05:v4.dump-4-CSeq: 1 INVITE\n";
+        assert_eq!(filter_prefixed(input), b"\x0B\nCSeq: 1 INVITE\n");
+    }
+
+    #[test]
+    fn strip_prefix_line_numbers_only() {
+        let input = b"41:\x0B\n42-sent 4 bytes to udp/1.1.1.1:5060 at 00:00:00.000000:\n";
+        assert_eq!(
+            filter_prefixed(input),
+            b"\x0B\nsent 4 bytes to udp/1.1.1.1:5060 at 00:00:00.000000:\n"
+        );
+    }
+
+    #[test]
+    fn prefix_stripping_keeps_colon_content() {
+        let input = b"Contact: <sip:1:2:3@host>\nX-Seq:10:recvd\n";
+        assert_eq!(filter_prefixed(input), input);
+    }
+
+    #[test]
+    fn prefix_stripping_off_by_default() {
+        let input = b"a.dump:1:\x0B\n";
+        assert_eq!(filter(input), input);
+    }
+
     #[test]
     fn strip_separator() {
         assert_eq!(filter(b"hello\n--\nworld\n"), b"hello\nworld\n");
@@ -102,6 +222,40 @@ mod tests {
         assert_eq!(filter(input), input);
     }
 
+    #[test]
+    fn separator_without_newline_at_eof() {
+        assert_eq!(filter(b"a\n--"), b"a\n");
+        assert_eq!(filter(b"a\n--\r"), b"a\n");
+        assert_eq!(filter(b"a\n---"), b"a\n---");
+    }
+
+    /// Hands out at most `chunk` bytes per `read`, like a pipe.
+    struct Trickle<'a> {
+        data: &'a [u8],
+        chunk: usize,
+    }
+
+    impl Read for Trickle<'_> {
+        fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+            let n = self.chunk.min(buf.len()).min(self.data.len());
+            buf[..n].copy_from_slice(&self.data[..n]);
+            self.data = &self.data[n..];
+            Ok(n)
+        }
+    }
+
+    #[test]
+    fn separator_split_across_reads() {
+        let input = b"hello\n--\r\nworld\n--\n";
+        for chunk in 1..input.len() {
+            let mut out = Vec::new();
+            GrepFilter::new(Trickle { data: input, chunk })
+                .read_to_end(&mut out)
+                .unwrap();
+            assert_eq!(out, b"hello\nworld\n", "chunk {chunk}");
+        }
+    }
+
     #[test]
     fn empty_input() {
         assert_eq!(filter(b""), b"");
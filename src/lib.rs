@@ -1,11 +1,25 @@
+pub mod anonymize;
+pub mod ansi;
+pub mod filter;
 pub mod frame;
 pub mod grep;
 pub mod message;
+pub mod pcap;
+pub mod reasons;
 pub mod sip;
+pub mod stream;
+pub mod transaction;
 pub mod types;
+pub mod validate;
 
+pub use ansi::AnsiStripFilter;
+pub use filter::MessageFilter;
 pub use frame::{FrameIterator, ParseError};
 pub use grep::GrepFilter;
 pub use message::MessageIterator;
-pub use sip::ParsedMessageIterator;
+pub use pcap::PcapFrameIterator;
+pub use sip::{parse_all, ParseOptions, ParsedMessageIterator, RawParsedMessageIterator};
+pub use stream::MessageStream;
+pub use transaction::{Transaction, TransactionIterator};
 pub use types::*;
+pub use validate::Violation;
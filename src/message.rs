@@ -1,61 +1,509 @@
-use std::collections::{HashMap, VecDeque};
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::iter::FusedIterator;
 use std::sync::LazyLock;
+use std::time::Duration;
 
 use memchr::memmem;
 use tracing::{debug, trace, warn};
 
 use crate::frame::{FrameIterator, ParseError};
-use crate::types::{Direction, SipMessage, Timestamp, Transport};
+use crate::pcap::PcapFrameIterator;
+use crate::types::{is_keepalive, is_sigcomp, Direction, Frame, SipMessage, Timestamp, Transport};
 
-static CRLF: LazyLock<memmem::Finder<'static>> = LazyLock::new(|| memmem::Finder::new(b"\r\n"));
 static CRLFCRLF: LazyLock<memmem::Finder<'static>> =
     LazyLock::new(|| memmem::Finder::new(b"\r\n\r\n"));
+static LFLF: LazyLock<memmem::Finder<'static>> = LazyLock::new(|| memmem::Finder::new(b"\n\n"));
+
+/// Default gap after which a partial TCP message is given up on.
+pub const DEFAULT_REASSEMBLY_TIMEOUT: Duration = Duration::from_secs(10);
+/// Default cap on a TCP message's declared size (headers plus Content-Length).
+pub const DEFAULT_MAX_MESSAGE_SIZE: usize = 4 * 1024 * 1024;
+/// Default cap on bytes buffered for one connection's partial message; no
+/// lower than `DEFAULT_MAX_MESSAGE_SIZE`, so a message within that cap fits.
+pub const DEFAULT_MAX_BUFFER_SIZE: usize = DEFAULT_MAX_MESSAGE_SIZE;
+/// Default time a message is held back for an earlier-started partial one.
+pub const DEFAULT_REORDER_WINDOW: Duration = Duration::from_secs(10);
+/// Default number of recent messages remembered to drop duplicates.
+pub const DEFAULT_DEDUP_WINDOW: usize = 1024;
+/// Leading content bytes that, with timestamp, direction and address,
+/// identify a duplicated message.
+const DEDUP_PREFIX_LEN: usize = 64;
 
 pub struct MessageIterator<R> {
-    frames: FrameIterator<R>,
+    frames: FrameSource<R>,
     buffers: HashMap<(Direction, String), ConnectionBuffer>,
     ready: VecDeque<SipMessage>,
     exhausted: bool,
+    emit_keepalives: bool,
+    reassembly_timeout: Duration,
+    max_buffer_size: usize,
+    max_message_size: usize,
+    reorder_window: Option<Duration>,
+    last_frame: Option<Timestamp>,
+    stamp_last_frame: bool,
+    dedup: Option<RecentMessages>,
+    ws_framing: bool,
+    ws_connections: HashMap<(Direction, String), WsConnection>,
+    gap_warn: Option<Duration>,
+    last_seen: HashMap<(Direction, String), Timestamp>,
+    gap_count: usize,
+}
+
+type DedupKey = (Timestamp, Direction, String, Vec<u8>);
+
+/// Sliding window of the last messages yielded, for `dedup_frames`; usable
+/// on its own to drop the same duplicates from messages of several inputs.
+pub struct RecentMessages {
+    window: usize,
+    order: VecDeque<DedupKey>,
+    seen: HashSet<DedupKey>,
+}
+
+impl RecentMessages {
+    /// Remember up to `window` messages; at least one.
+    pub fn new(window: usize) -> Self {
+        RecentMessages {
+            window: window.max(1),
+            order: VecDeque::new(),
+            seen: HashSet::new(),
+        }
+    }
+
+    /// Remember `msg`, returning false when it was already in the window.
+    pub fn insert(&mut self, msg: &SipMessage) -> bool {
+        let prefix = &msg.content[..msg.content.len().min(DEDUP_PREFIX_LEN)];
+        let key = (
+            msg.timestamp,
+            msg.direction,
+            msg.address.clone(),
+            prefix.to_vec(),
+        );
+        if self.seen.contains(&key) {
+            return false;
+        }
+        if self.order.len() == self.window {
+            if let Some(oldest) = self.order.pop_front() {
+                self.seen.remove(&oldest);
+            }
+        }
+        self.seen.insert(key.clone());
+        self.order.push_back(key);
+        true
+    }
 }
 
 struct ConnectionBuffer {
     transport: Transport,
     timestamp: Timestamp,
+    last_frame: Timestamp,
     content: Vec<u8>,
     frame_count: usize,
 }
 
+impl ConnectionBuffer {
+    /// Take whatever is buffered as a (possibly incomplete) message.
+    fn take_partial(&mut self, key: &(Direction, String)) -> Option<SipMessage> {
+        if self.content.is_empty() {
+            return None;
+        }
+        let frame_count = std::mem::take(&mut self.frame_count);
+        Some(SipMessage {
+            direction: key.0,
+            transport: self.transport,
+            address: key.1.clone(),
+            timestamp: self.timestamp,
+            last_timestamp: self.last_frame,
+            content: std::mem::take(&mut self.content),
+            frame_count,
+        })
+    }
+
+    /// True when the buffered partial should be flushed before appending `frame`.
+    fn is_stale(&self, frame: &Frame, timeout: Duration, max_size: usize) -> bool {
+        if self.content.is_empty() {
+            return false;
+        }
+        let gap = frame.timestamp.duration_since(&self.last_frame);
+        gap.is_some_and(|gap| gap > timeout) || self.content.len() + frame.content.len() > max_size
+    }
+}
+
+/// A WSS connection decoded with `ws_framing`: bytes not yet forming a whole
+/// WebSocket frame, and the payload of a message still being fragmented.
+struct WsConnection {
+    pending: Vec<u8>,
+    payload: Vec<u8>,
+    timestamp: Timestamp,
+    last_frame: Timestamp,
+    frame_count: usize,
+}
+
+impl WsConnection {
+    fn is_idle(&self) -> bool {
+        self.pending.is_empty() && self.payload.is_empty()
+    }
+
+    /// True when the message being decoded should be flushed before
+    /// appending `frame`, by the same rules as a TCP `ConnectionBuffer`.
+    fn is_stale(&self, frame: &Frame, timeout: Duration, max_size: usize) -> bool {
+        if self.is_idle() {
+            return false;
+        }
+        let gap = frame.timestamp.duration_since(&self.last_frame);
+        let buffered = self.pending.len() + self.payload.len();
+        gap.is_some_and(|gap| gap > timeout) || buffered + frame.content.len() > max_size
+    }
+
+    /// Take the message being decoded as it stands: the fragments already
+    /// complete, then whatever payload of a cut-short frame has arrived,
+    /// unmasked. Bytes too short to hold a frame header are kept raw.
+    fn take_partial(&mut self, key: &(Direction, String)) -> Option<SipMessage> {
+        if self.is_idle() {
+            return None;
+        }
+        let pending = std::mem::take(&mut self.pending);
+        match parse_ws_header(&pending) {
+            Some(header) if header.opcode >= 0x8 => {}
+            Some(header) => {
+                let start = header.header_len.min(pending.len());
+                let data = pending[start..]
+                    .iter()
+                    .enumerate()
+                    .map(|(i, b)| match header.mask {
+                        Some(mask) => b ^ mask[i % 4],
+                        None => *b,
+                    });
+                self.payload.extend(data);
+            }
+            None => self.payload.extend_from_slice(&pending),
+        }
+        if self.payload.is_empty() {
+            return None;
+        }
+        Some(SipMessage {
+            direction: key.0,
+            transport: Transport::Wss,
+            address: key.1.clone(),
+            timestamp: self.timestamp,
+            last_timestamp: self.last_frame,
+            content: std::mem::take(&mut self.payload),
+            frame_count: std::mem::take(&mut self.frame_count),
+        })
+    }
+
+    /// Append a dump frame, returning the SIP messages it completes: one per
+    /// WebSocket message (RFC 7118 Section 5), delimited by frame lengths.
+    fn push(&mut self, frame: &Frame, max_message_size: usize) -> Vec<SipMessage> {
+        if self.is_idle() {
+            self.timestamp = frame.timestamp;
+            self.frame_count = 0;
+        }
+        self.frame_count += 1;
+        self.last_frame = frame.timestamp;
+        self.pending.extend_from_slice(&frame.content);
+
+        let mut messages = Vec::new();
+        while !self.pending.is_empty() {
+            let header = match parse_ws_header(&self.pending) {
+                Some(h) if self.pending[0] & 0x70 == 0 && h.payload_len <= max_message_size => h,
+                Some(_) => {
+                    warn!(
+                        bytes = self.pending.len(),
+                        address = %frame.address,
+                        "discarding bytes that are not a WebSocket frame"
+                    );
+                    self.pending.clear();
+                    break;
+                }
+                None => break,
+            };
+            let end = header.header_len + header.payload_len;
+            if self.pending.len() < end {
+                break;
+            }
+            let mut data: Vec<u8> = self.pending.drain(..end).skip(header.header_len).collect();
+            if let Some(mask) = header.mask {
+                for (i, b) in data.iter_mut().enumerate() {
+                    *b ^= mask[i % 4];
+                }
+            }
+            // Control frames may arrive between the fragments of a message
+            if header.opcode >= 0x8 {
+                trace!(opcode = header.opcode, address = %frame.address, "WebSocket control frame");
+                continue;
+            }
+            self.payload.extend_from_slice(&data);
+            if header.fin {
+                messages.push(SipMessage {
+                    direction: frame.direction,
+                    transport: frame.transport,
+                    address: frame.address.clone(),
+                    timestamp: self.timestamp,
+                    last_timestamp: frame.timestamp,
+                    content: std::mem::take(&mut self.payload),
+                    frame_count: self.frame_count,
+                });
+                // Anything left started within this frame
+                self.timestamp = frame.timestamp;
+                self.frame_count = 1;
+            }
+        }
+        messages
+    }
+}
+
+enum FrameSource<R> {
+    Dump(FrameIterator<R>),
+    Pcap(PcapFrameIterator<R>),
+}
+
+impl<R: std::io::Read> FrameSource<R> {
+    fn next(&mut self) -> Option<Result<Frame, ParseError>> {
+        match self {
+            FrameSource::Dump(frames) => frames.next(),
+            FrameSource::Pcap(frames) => frames.next(),
+        }
+    }
+}
+
+impl<R: std::io::Read> From<FrameIterator<R>> for MessageIterator<R> {
+    fn from(frames: FrameIterator<R>) -> Self {
+        MessageIterator::with_frames(FrameSource::Dump(frames))
+    }
+}
+
+impl<R: std::io::Read> From<PcapFrameIterator<R>> for MessageIterator<R> {
+    fn from(frames: PcapFrameIterator<R>) -> Self {
+        MessageIterator::with_frames(FrameSource::Pcap(frames))
+    }
+}
+
 impl<R: std::io::Read> MessageIterator<R> {
-    pub fn new(reader: R) -> Self {
+    fn with_frames(frames: FrameSource<R>) -> Self {
         MessageIterator {
-            frames: FrameIterator::new(reader),
+            frames,
             buffers: HashMap::new(),
             ready: VecDeque::new(),
             exhausted: false,
+            emit_keepalives: false,
+            reassembly_timeout: DEFAULT_REASSEMBLY_TIMEOUT,
+            max_buffer_size: DEFAULT_MAX_BUFFER_SIZE,
+            max_message_size: DEFAULT_MAX_MESSAGE_SIZE,
+            reorder_window: None,
+            last_frame: None,
+            stamp_last_frame: false,
+            dedup: None,
+            ws_framing: false,
+            ws_connections: HashMap::new(),
+            gap_warn: None,
+            last_seen: HashMap::new(),
+            gap_count: 0,
         }
     }
 
+    pub fn new(reader: R) -> Self {
+        MessageIterator::from(FrameIterator::new(reader))
+    }
+
+    /// Give up on a partial TCP (or `ws_framing` WebSocket) message when the
+    /// next frame on its connection arrives more than `timeout` later; the
+    /// partial is yielded as-is.
+    pub fn reassembly_timeout(mut self, timeout: Duration) -> Self {
+        self.reassembly_timeout = timeout;
+        self
+    }
+
+    /// Give up on a partial TCP (or `ws_framing` WebSocket) message when
+    /// appending the next frame would buffer more than `max` bytes; the
+    /// partial is yielded as-is.
+    pub fn max_buffer_size(mut self, max: usize) -> Self {
+        self.max_buffer_size = max;
+        self
+    }
+
+    /// Don't wait for the body of a TCP message whose Content-Length would
+    /// make it larger than `max` bytes. The message is yielded truncated at
+    /// the next SIP start line, where reassembly resumes. The buffer cap of
+    /// `max_buffer_size` is raised to `max` if lower, so that a message the
+    /// cap allows is not flushed partway.
+    pub fn with_max_message_size(mut self, max: usize) -> Self {
+        self.max_message_size = max;
+        self.max_buffer_size = self.max_buffer_size.max(max);
+        self
+    }
+
+    /// Yield CRLF keepalive frames as messages instead of dropping them.
+    ///
+    /// Keepalives are only recognized between messages; a CRLF-only frame
+    /// arriving while a TCP message is partially buffered is kept as content.
+    pub fn keepalives(mut self, emit: bool) -> Self {
+        self.emit_keepalives = emit;
+        self
+    }
+
+    /// Yield messages in order of their first frame instead of completion.
+    ///
+    /// A completed message is held back while a partial TCP message that
+    /// started earlier is still buffered, until frames `window` past that
+    /// partial's start have been read.
+    pub fn order_by_start(mut self, window: Duration) -> Self {
+        self.reorder_window = Some(window);
+        self
+    }
+
+    /// Set `timestamp` to when the message fully arrived (its last frame)
+    /// rather than when its first frame was logged, for consumers that only
+    /// read `timestamp`, like `ParsedSipMessage`. `last_timestamp` always
+    /// holds the last frame's time either way.
+    pub fn stamp_last_frame(mut self, last: bool) -> Self {
+        self.stamp_last_frame = last;
+        self
+    }
+
+    /// Drop a message identical in timestamp, direction, address and first
+    /// 64 bytes to one of the last `window` messages, as seen when
+    /// concatenated rotated dumps overlap. A window of 0 turns this off.
+    pub fn dedup_frames(mut self, window: usize) -> Self {
+        self.dedup = (window > 0).then(|| RecentMessages::new(window));
+        self
+    }
+
+    /// Decode WSS frames whose content starts with a WebSocket frame header
+    /// (FIN, opcode, length, mask), delimiting and unmasking messages by the
+    /// WebSocket lengths rather than Content-Length. For dumps that log WSS
+    /// payloads before WebSocket decoding; frames of decoded text, the usual
+    /// case, are reassembled as before.
+    pub fn ws_framing(mut self, decode: bool) -> Self {
+        self.ws_framing = decode;
+        self
+    }
+
+    /// Warn when consecutive frames on one connection (direction and address)
+    /// are more than `threshold` apart, a hint that the capture lost data
+    /// between them. Off by default.
+    pub fn gap_warn(mut self, threshold: Duration) -> Self {
+        self.gap_warn = Some(threshold);
+        self
+    }
+
+    /// Number of gaps reported so far by `gap_warn`.
+    pub fn gap_count(&self) -> usize {
+        self.gap_count
+    }
+
+    /// Record the frame's time for its connection and warn when it follows
+    /// the previous frame there by more than the `gap_warn` threshold.
+    fn check_gap(&mut self, frame: &Frame) {
+        let Some(threshold) = self.gap_warn else {
+            return;
+        };
+        let key = (frame.direction, frame.address.clone());
+        if let Some(gap) = self
+            .last_seen
+            .insert(key, frame.timestamp)
+            .and_then(|prev| frame.timestamp.duration_since(&prev))
+            .filter(|gap| *gap > threshold)
+        {
+            self.gap_count += 1;
+            warn!(
+                address = %frame.address,
+                direction = %frame.direction,
+                gap_secs = gap.as_secs_f64(),
+                offset = frame.offset,
+                "possible capture loss: gap between frames"
+            );
+        }
+    }
+
+    /// Next message to yield, or `None` when nothing is ready or the earliest
+    /// ready message must wait for an earlier-started partial.
+    fn pop_ready(&mut self) -> Option<SipMessage> {
+        let Some(window) = self.reorder_window else {
+            return self.ready.pop_front();
+        };
+        let (index, first) = self
+            .ready
+            .iter()
+            .enumerate()
+            .min_by_key(|(_, msg)| msg.timestamp.sort_key())?;
+        let last_frame = self.last_frame.unwrap_or(first.timestamp);
+        let waiting = !self.exhausted
+            && self.buffers.values().any(|buf| {
+                !buf.content.is_empty()
+                    && buf.timestamp.sort_key() < first.timestamp.sort_key()
+                    && last_frame
+                        .duration_since(&buf.timestamp)
+                        .is_some_and(|age| age <= window)
+            });
+        if waiting {
+            return None;
+        }
+        self.ready.remove(index)
+    }
+
+    /// True when no partial TCP message is buffered for the frame's connection.
+    fn is_idle(&self, frame: &Frame) -> bool {
+        if frame.transport == Transport::Udp {
+            return true;
+        }
+        self.buffers
+            .get(&(frame.direction, frame.address.clone()))
+            .is_none_or(|buf| buf.content.is_empty())
+    }
+
+    /// Flush every buffered connection, as at end of input, and return the
+    /// messages that become ready, leaving the iterator usable for further
+    /// frames. For a live source that has gone quiet: a message still waiting
+    /// for bytes is returned as it stands, so it may be truncated.
+    pub fn flush_pending(&mut self) -> Vec<SipMessage> {
+        self.flush_all();
+        let mut ready: Vec<_> = self.ready.drain(..).collect();
+        if self.reorder_window.is_some() {
+            ready.sort_by_key(|msg| msg.timestamp.sort_key());
+        }
+        ready
+            .into_iter()
+            .filter_map(|msg| self.finish(msg))
+            .collect()
+    }
+
+    /// Apply duplicate dropping and last-frame stamping to a message about to
+    /// be yielded; `None` when it duplicates a recent one.
+    fn finish(&mut self, mut msg: SipMessage) -> Option<SipMessage> {
+        if self
+            .dedup
+            .as_mut()
+            .is_some_and(|recent| !recent.insert(&msg))
+        {
+            debug!(address = %msg.address, "dropped duplicate message");
+            return None;
+        }
+        if self.stamp_last_frame {
+            msg.timestamp = msg.last_timestamp;
+        }
+        Some(msg)
+    }
+
     fn flush_all(&mut self) {
         let keys: Vec<_> = self.buffers.keys().cloned().collect();
         for key in keys {
             if let Some(buf) = self.buffers.get_mut(&key) {
-                let msgs = extract_complete(buf, &key);
+                let msgs = extract_complete(buf, &key, self.max_message_size);
                 self.ready.extend(msgs);
-
-                if !buf.content.is_empty() {
-                    let content = std::mem::take(&mut buf.content);
-                    self.ready.push_back(SipMessage {
-                        direction: key.0,
-                        transport: buf.transport,
-                        address: key.1.clone(),
-                        timestamp: buf.timestamp,
-                        content,
-                        frame_count: buf.frame_count,
-                    });
-                    buf.frame_count = 0;
-                }
+                self.ready.extend(buf.take_partial(&key));
             }
         }
+        for (key, conn) in &mut self.ws_connections {
+            if conn.is_idle() {
+                continue;
+            }
+            warn!(
+                bytes = conn.pending.len() + conn.payload.len(),
+                address = %key.1,
+                "flushing incomplete WebSocket message"
+            );
+            self.ready.extend(conn.take_partial(key));
+        }
     }
 }
 
@@ -63,26 +511,93 @@ impl<R: std::io::Read> Iterator for MessageIterator<R> {
     type Item = Result<SipMessage, ParseError>;
 
     fn next(&mut self) -> Option<Self::Item> {
-        if let Some(msg) = self.ready.pop_front() {
-            return Some(Ok(msg));
-        }
+        loop {
+            if let Some(msg) = self.pop_ready() {
+                match self.finish(msg) {
+                    Some(msg) => return Some(Ok(msg)),
+                    None => continue,
+                }
+            }
 
-        if self.exhausted {
-            return None;
-        }
+            if self.exhausted {
+                return None;
+            }
 
-        loop {
             match self.frames.next() {
-                Some(Ok(frame)) => {
-                    if frame.transport == Transport::Udp {
-                        return Some(Ok(SipMessage {
+                Some(Ok(mut frame)) => {
+                    self.last_frame = Some(frame.timestamp);
+                    self.check_gap(&frame);
+                    if self.ws_framing && frame.transport == Transport::Wss {
+                        let key = (frame.direction, frame.address.clone());
+                        if let Some(conn) = self.ws_connections.get_mut(&key) {
+                            if conn.is_stale(&frame, self.reassembly_timeout, self.max_buffer_size)
+                            {
+                                warn!(
+                                    bytes = conn.pending.len() + conn.payload.len(),
+                                    address = %key.1,
+                                    "flushing incomplete WebSocket message"
+                                );
+                                self.ready.extend(conn.take_partial(&key));
+                            }
+                        }
+                        let decoding = self.ws_connections.get(&key).is_some_and(|c| !c.is_idle())
+                            || starts_with_ws_frame(&frame.content, self.max_message_size);
+                        if decoding {
+                            let conn =
+                                self.ws_connections
+                                    .entry(key)
+                                    .or_insert_with(|| WsConnection {
+                                        pending: Vec::new(),
+                                        payload: Vec::new(),
+                                        timestamp: frame.timestamp,
+                                        last_frame: frame.timestamp,
+                                        frame_count: 0,
+                                    });
+                            let msgs = conn.push(&frame, self.max_message_size);
+                            self.ready.extend(msgs);
+                            continue;
+                        }
+                    }
+                    if frame.transport == Transport::Wss && self.is_idle(&frame) {
+                        strip_ws_framing(&mut frame);
+                    }
+
+                    if is_keepalive(&frame.content) && self.is_idle(&frame) {
+                        trace!(address = %frame.address, "CRLF keepalive frame");
+                        if self.emit_keepalives {
+                            self.ready.push_back(SipMessage {
+                                direction: frame.direction,
+                                transport: frame.transport,
+                                address: frame.address,
+                                timestamp: frame.timestamp,
+                                last_timestamp: frame.timestamp,
+                                content: frame.content,
+                                frame_count: 1,
+                            });
+                        }
+                        continue;
+                    }
+
+                    // SigComp on a stream is delimited by 0xFFFF (RFC 3320
+                    // Section 4.2.2), which is not decoded: each frame is
+                    // taken as one message rather than glued to what follows
+                    let sigcomp = frame.transport != Transport::Udp
+                        && is_sigcomp(&frame.content)
+                        && self.is_idle(&frame);
+                    if sigcomp {
+                        debug!(address = %frame.address, "SigComp frame, not reassembled");
+                    }
+                    if frame.transport == Transport::Udp || sigcomp {
+                        self.ready.push_back(SipMessage {
                             direction: frame.direction,
                             transport: frame.transport,
                             address: frame.address,
                             timestamp: frame.timestamp,
+                            last_timestamp: frame.timestamp,
                             content: frame.content,
                             frame_count: 1,
-                        }));
+                        });
+                        continue;
                     }
 
                     let key = (frame.direction, frame.address.clone());
@@ -93,13 +608,24 @@ impl<R: std::io::Read> Iterator for MessageIterator<R> {
                         .or_insert_with(|| ConnectionBuffer {
                             transport: frame.transport,
                             timestamp: frame.timestamp,
+                            last_frame: frame.timestamp,
                             content: Vec::new(),
                             frame_count: 0,
                         });
 
+                    if buf.is_stale(&frame, self.reassembly_timeout, self.max_buffer_size) {
+                        warn!(
+                            bytes = buf.content.len(),
+                            address = %key.1,
+                            "flushing incomplete TCP message"
+                        );
+                        self.ready.extend(buf.take_partial(&key));
+                    }
+
                     if buf.content.is_empty() {
                         buf.timestamp = frame.timestamp;
                     }
+                    buf.last_frame = frame.timestamp;
 
                     trace!(
                         frame = buf.frame_count + 1,
@@ -111,28 +637,35 @@ impl<R: std::io::Read> Iterator for MessageIterator<R> {
                     buf.content.extend_from_slice(&frame.content);
                     buf.frame_count += 1;
 
-                    let msgs = extract_complete(buf, &key);
+                    let msgs = extract_complete(buf, &key, self.max_message_size);
                     self.ready.extend(msgs);
-
-                    if let Some(msg) = self.ready.pop_front() {
-                        return Some(Ok(msg));
-                    }
                 }
                 Some(Err(e)) => return Some(Err(e)),
                 None => {
                     self.exhausted = true;
                     self.flush_all();
-                    return self.ready.pop_front().map(Ok);
                 }
             }
         }
     }
 }
 
+/// Partial messages are flushed once, when the frames run out; after that
+/// nothing refills the ready queue.
+impl<R: std::io::Read> FusedIterator for MessageIterator<R> {}
+
 /// Extract complete SIP messages from a connection buffer.
 /// Messages are complete when we find headers (\r\n\r\n) and have
-/// Content-Length bytes of body available.
-fn extract_complete(buf: &mut ConnectionBuffer, key: &(Direction, String)) -> Vec<SipMessage> {
+/// Content-Length bytes of body available. A Content-Length past
+/// `max_message_size` is not waited for: the message ends at the next SIP
+/// start line, or at the end of the buffer. Without Content-Length, bytes
+/// after the headers that don't start a new message are taken as the body,
+/// up to the next SIP start line.
+fn extract_complete(
+    buf: &mut ConnectionBuffer,
+    key: &(Direction, String),
+    max_message_size: usize,
+) -> Vec<SipMessage> {
     let mut messages = Vec::new();
 
     loop {
@@ -143,13 +676,7 @@ fn extract_complete(buf: &mut ConnectionBuffer, key: &(Direction, String)) -> Ve
         // Skip non-SIP prefix (body fragments from incomplete prior messages)
         if !is_sip_start(&buf.content) {
             // Skip leading CRLF (inter-message padding)
-            let mut crlf_skip = 0;
-            while crlf_skip + 1 < buf.content.len()
-                && buf.content[crlf_skip] == b'\r'
-                && buf.content[crlf_skip + 1] == b'\n'
-            {
-                crlf_skip += 2;
-            }
+            let crlf_skip = line_ending_padding(&buf.content);
             if crlf_skip > 0
                 && crlf_skip < buf.content.len()
                 && is_sip_start(&buf.content[crlf_skip..])
@@ -177,13 +704,22 @@ fn extract_complete(buf: &mut ConnectionBuffer, key: &(Direction, String)) -> Ve
         }
 
         // Find header/body boundary
-        let header_end = match CRLFCRLF.find(&buf.content) {
-            Some(offset) => offset,
+        let body_start = match find_header_end(&buf.content) {
+            Some((offset, separator_len)) => offset + separator_len,
             None => break, // Headers incomplete, wait for more data
         };
-        let body_start = header_end + 4;
 
         let msg_end = match find_content_length(&buf.content) {
+            Some(cl) if body_start.saturating_add(cl) > max_message_size => {
+                let resync = find_sip_start(&buf.content[body_start..]);
+                warn!(
+                    content_length = cl,
+                    max_message_size,
+                    address = %key.1,
+                    "Content-Length exceeds max message size, truncating message"
+                );
+                resync.map_or(buf.content.len(), |offset| body_start + offset)
+            }
             Some(cl) => {
                 let end = body_start + cl;
                 if end > buf.content.len() {
@@ -191,15 +727,38 @@ fn extract_complete(buf: &mut ConnectionBuffer, key: &(Direction, String)) -> Ve
                 }
                 end
             }
-            None => body_start, // No CL = no body (RFC 3261 Section 18.3)
+            None => {
+                let rest = &buf.content[body_start..];
+                let padding = line_ending_padding(rest);
+                if padding == rest.len() {
+                    // A body may still follow in the next frame; at EOF
+                    // the message is flushed as-is
+                    break;
+                }
+                if is_sip_start(&rest[padding..]) {
+                    body_start // No CL = no body (RFC 3261 Section 18.3)
+                } else {
+                    // Buggy peers omit Content-Length over TCP too
+                    match find_sip_start(rest) {
+                        Some(offset) => {
+                            warn!(
+                                body_bytes = offset,
+                                address = %key.1,
+                                "no Content-Length on TCP message, body taken up to the next SIP start line"
+                            );
+                            body_start + offset
+                        }
+                        None => break, // Body end unknown, wait for the next message
+                    }
+                }
+            }
         };
 
         let msg_content: Vec<u8> = buf.content.drain(..msg_end).collect();
 
         // Skip trailing CRLF between messages
-        while buf.content.len() >= 2 && buf.content[0] == b'\r' && buf.content[1] == b'\n' {
-            buf.content.drain(..2);
-        }
+        let padding = line_ending_padding(&buf.content);
+        buf.content.drain(..padding);
 
         let frame_count = if messages.is_empty() {
             buf.frame_count
@@ -221,6 +780,7 @@ fn extract_complete(buf: &mut ConnectionBuffer, key: &(Direction, String)) -> Ve
             transport: buf.transport,
             address: key.1.clone(),
             timestamp: buf.timestamp,
+            last_timestamp: buf.last_frame,
             content: msg_content,
             frame_count,
         });
@@ -231,27 +791,137 @@ fn extract_complete(buf: &mut ConnectionBuffer, key: &(Direction, String)) -> Ve
     messages
 }
 
+/// Largest WebSocket frame header: 2 bytes + 8-byte extended length + 4-byte mask.
+const MAX_WS_HEADER_LEN: usize = 14;
+
+/// A WebSocket frame header (RFC 6455 Section 5.2).
+struct WsFrameHeader {
+    fin: bool,
+    opcode: u8,
+    mask: Option<[u8; 4]>,
+    header_len: usize,
+    payload_len: usize,
+}
+
+/// Parse the WebSocket frame header at the start of `data`; `None` until
+/// enough bytes are available.
+fn parse_ws_header(data: &[u8]) -> Option<WsFrameHeader> {
+    let (b0, b1) = (*data.first()?, *data.get(1)?);
+    let (payload_len, mut header_len) = match b1 & 0x7F {
+        126 => (
+            u16::from_be_bytes(data.get(2..4)?.try_into().ok()?) as usize,
+            4,
+        ),
+        127 => {
+            let len = u64::from_be_bytes(data.get(2..10)?.try_into().ok()?);
+            (usize::try_from(len).unwrap_or(usize::MAX), 10)
+        }
+        n => (n as usize, 2),
+    };
+    let mask = if b1 & 0x80 != 0 {
+        let key = data.get(header_len..header_len + 4)?.try_into().ok()?;
+        header_len += 4;
+        Some(key)
+    } else {
+        None
+    };
+    Some(WsFrameHeader {
+        fin: b0 & 0x80 != 0,
+        opcode: b0 & 0x0F,
+        mask,
+        header_len,
+        payload_len,
+    })
+}
+
+/// True when `data` opens with a plausible WebSocket frame: reserved bits
+/// clear, a text or binary opcode (or a final control frame), and a length
+/// within `max_len`. SIP text never passes: ASCII letters set a reserved bit.
+fn starts_with_ws_frame(data: &[u8], max_len: usize) -> bool {
+    let Some(&b0) = data.first() else {
+        return false;
+    };
+    let fin = b0 & 0x80 != 0;
+    let opcode_ok = match b0 & 0x0F {
+        0x1 | 0x2 => true,
+        0x8..=0xA => fin,
+        _ => false,
+    };
+    b0 & 0x70 == 0 && opcode_ok && parse_ws_header(data).is_none_or(|h| h.payload_len <= max_len)
+}
+
+/// Strip WebSocket opcode/length/mask bytes logged ahead of the SIP start line.
+fn strip_ws_framing(frame: &mut Frame) {
+    if frame.content.is_empty() || is_sip_start(&frame.content) {
+        return;
+    }
+    let window = frame.content.len().min(MAX_WS_HEADER_LEN + 1);
+    if let Some(offset) = (1..window).find(|&i| is_sip_start(&frame.content[i..])) {
+        debug!(
+            stripped_bytes = offset,
+            address = %frame.address,
+            "stripped WebSocket framing before SIP start line"
+        );
+        frame.content.drain(..offset);
+    }
+}
+
+/// Length of the run of CRLF or bare LF line endings at the start of data.
+fn line_ending_padding(data: &[u8]) -> usize {
+    let mut pos = 0;
+    loop {
+        match &data[pos..] {
+            [b'\r', b'\n', ..] => pos += 2,
+            [b'\n', ..] => pos += 1,
+            _ => return pos,
+        }
+    }
+}
+
+/// Find the blank line ending the SIP headers.
+///
+/// Returns its offset and length: 4 for `\r\n\r\n`, or 2 for `\n\n` when the
+/// sender uses bare LF line endings. Whichever comes first wins.
+pub(crate) fn find_header_end(data: &[u8]) -> Option<(usize, usize)> {
+    match (CRLFCRLF.find(data), LFLF.find(data)) {
+        (Some(crlf), Some(lf)) if lf < crlf => Some((lf, 2)),
+        (Some(crlf), _) => Some((crlf, 4)),
+        (None, Some(lf)) => Some((lf, 2)),
+        (None, None) => None,
+    }
+}
+
 /// Find Content-Length header value in SIP message bytes.
-/// Returns the value as usize if found.
-fn find_content_length(data: &[u8]) -> Option<usize> {
-    let header_end = CRLFCRLF.find(data)?;
+/// Returns the value as usize if found. Of conflicting values the smallest
+/// is used, so reassembly never reads into the next message.
+pub(crate) fn find_content_length(data: &[u8]) -> Option<usize> {
+    let (header_end, _) = find_header_end(data)?;
     let headers = &data[..header_end];
 
+    let mut values = Vec::new();
     let mut pos = 0;
     while pos < headers.len() {
-        let line_end = CRLF.find(&headers[pos..]).unwrap_or(headers.len() - pos);
-        let line = &headers[pos..pos + line_end];
-
-        if let Some(value) = extract_header_value(line, b"Content-Length") {
-            return parse_content_length(value);
-        }
-        if let Some(value) = extract_compact_header_value(line, b'l') {
-            return parse_content_length(value);
+        let line_end = memchr::memchr(b'\n', &headers[pos..]).unwrap_or(headers.len() - pos);
+        let line = headers[pos..pos + line_end]
+            .strip_suffix(b"\r")
+            .unwrap_or(&headers[pos..pos + line_end]);
+
+        if let Some(value) = extract_header_value(line, b"Content-Length")
+            .or_else(|| extract_compact_header_value(line, b'l'))
+        {
+            values.extend(parse_content_length(value));
         }
 
-        pos += line_end + 2; // skip \r\n
+        pos += line_end + 1; // skip \n
     }
-    None
+
+    let first = *values.first()?;
+    if values.iter().all(|&v| v == first) {
+        return Some(first);
+    }
+    let chosen = *values.iter().min()?;
+    warn!("conflicting Content-Length headers {values:?}, using {chosen}");
+    Some(chosen)
 }
 
 fn extract_header_value<'a>(line: &'a [u8], name: &[u8]) -> Option<&'a [u8]> {
@@ -294,7 +964,14 @@ fn parse_content_length(value: &[u8]) -> Option<usize> {
     s.parse().ok()
 }
 
+/// Longest request line `is_request_line()` looks at.
+const MAX_REQUEST_LINE: usize = 4096;
+
 /// Check if data at given position starts with a SIP request or response line.
+///
+/// Any method is accepted when the whole request line is there; the common
+/// methods are also recognized from their first word alone, for start lines
+/// cut short at the end of a frame.
 fn is_sip_start(data: &[u8]) -> bool {
     if data.starts_with(b"SIP/2.0 ") {
         return true;
@@ -320,17 +997,43 @@ fn is_sip_start(data: &[u8]) -> bool {
             return true;
         }
     }
-    false
+    is_request_line(data)
 }
 
-/// Scan for the first SIP message start at a CRLF boundary within data.
+/// `METHOD SP Request-URI SP SIP/2.0` and a line ending, for any token method
+/// (extensions such as `QUERY` or `MELD`).
+fn is_request_line(data: &[u8]) -> bool {
+    let window = &data[..data.len().min(MAX_REQUEST_LINE)];
+    let Some(end) = memchr::memchr(b'\n', window) else {
+        return false;
+    };
+    let line = window[..end].strip_suffix(b"\r").unwrap_or(&window[..end]);
+    let Some(rest) = line.strip_suffix(b" SIP/2.0") else {
+        return false;
+    };
+    let Some(space) = memchr::memchr(b' ', rest) else {
+        return false;
+    };
+    let (method, uri) = (&rest[..space], &rest[space + 1..]);
+    !method.is_empty()
+        && method.iter().all(|&c| is_token_char(c))
+        && uri.contains(&b':')
+        && !uri.iter().any(|c| c.is_ascii_whitespace())
+}
+
+/// RFC 3261 `token` characters (Section 25.1).
+fn is_token_char(c: u8) -> bool {
+    c.is_ascii_alphanumeric() || b"-.!%*_+`'~".contains(&c)
+}
+
+/// Scan for the first SIP message start at a line boundary within data.
 fn find_sip_start(data: &[u8]) -> Option<usize> {
     if is_sip_start(data) {
         return Some(0);
     }
     let mut pos = 0;
-    while let Some(offset) = CRLF.find(&data[pos..]) {
-        let candidate = pos + offset + 2;
+    while let Some(offset) = memchr::memchr(b'\n', &data[pos..]) {
+        let candidate = pos + offset + 1;
         if candidate >= data.len() {
             break;
         }
@@ -353,25 +1056,32 @@ mod tests {
         addr: &str,
         content: &[u8],
     ) -> Vec<u8> {
-        let dir_str = match direction {
-            Direction::Recv => "recv",
-            Direction::Sent => "sent",
-        };
-        let prep = match direction {
-            Direction::Recv => "from",
-            Direction::Sent => "to",
-        };
-        let transport_str = match transport {
-            Transport::Tcp => "tcp",
-            Transport::Udp => "udp",
-            Transport::Tls => "tls",
-            Transport::Wss => "wss",
+        make_frame_at(direction, transport, addr, content, 0)
+    }
+
+    fn make_frame_at(
+        direction: Direction,
+        transport: Transport,
+        addr: &str,
+        content: &[u8],
+        sec: u8,
+    ) -> Vec<u8> {
+        let frame = Frame {
+            direction,
+            byte_count: content.len(),
+            transport,
+            address: addr.into(),
+            timestamp: Timestamp::TimeOnly {
+                hour: 0,
+                min: 0,
+                sec,
+                usec: 0,
+            },
+            content: content.to_vec(),
+            offset: 0,
         };
-        let header = format!(
-            "{dir_str} {} bytes {prep} {transport_str}/{addr} at 00:00:00.000000:\n",
-            content.len()
-        );
-        let mut data = header.into_bytes();
+        let mut data = frame.header_line().into_bytes();
+        data.push(b'\n');
         data.extend_from_slice(content);
         data.extend_from_slice(b"\x0B\n");
         data
@@ -390,6 +1100,325 @@ mod tests {
         assert_eq!(msgs[0].transport, Transport::Udp);
     }
 
+    #[test]
+    fn fused_after_flushing_partial_at_eof() {
+        let partial = b"INVITE sip:bob@host SIP/2.0\r\nContent-Length: 100\r\n\r\nv=0";
+        let data = make_frame(Direction::Recv, Transport::Tcp, "1.1.1.1:5060", partial);
+        let mut msgs = MessageIterator::new(&data[..]);
+        assert_eq!(msgs.next().unwrap().unwrap().content, partial);
+        for _ in 0..3 {
+            assert!(msgs.next().is_none());
+        }
+    }
+
+    #[test]
+    fn flush_pending_returns_partial_and_keeps_reading() {
+        let partial = b"INVITE sip:bob@host SIP/2.0\r\nContent-Length: 100\r\n\r\nv=0";
+        let udp = b"INFO sip:a@host SIP/2.0\r\nContent-Length: 0\r\n\r\n";
+        let mut data = make_frame(Direction::Recv, Transport::Tcp, "1.1.1.1:5060", partial);
+        data.extend(make_frame(
+            Direction::Recv,
+            Transport::Udp,
+            "2.2.2.2:5060",
+            udp,
+        ));
+        data.extend(make_frame(
+            Direction::Recv,
+            Transport::Udp,
+            "3.3.3.3:5060",
+            udp,
+        ));
+        let mut msgs = MessageIterator::new(&data[..]);
+        assert_eq!(msgs.next().unwrap().unwrap().address, "2.2.2.2:5060");
+
+        let flushed = msgs.flush_pending();
+        assert_eq!(flushed.len(), 1);
+        assert_eq!(flushed[0].content, partial);
+        assert!(msgs.flush_pending().is_empty());
+
+        assert_eq!(msgs.next().unwrap().unwrap().address, "3.3.3.3:5060");
+        assert!(msgs.next().is_none());
+    }
+
+    #[test]
+    fn gap_warn_counts_gap_on_same_connection() {
+        let udp = b"INFO sip:a@host SIP/2.0\r\nContent-Length: 0\r\n\r\n";
+        let mut data = Vec::new();
+        for (addr, sec) in [
+            ("1.1.1.1:5060", 0),
+            ("2.2.2.2:5060", 5),
+            ("1.1.1.1:5060", 10),
+            ("1.1.1.1:5060", 12),
+        ] {
+            data.extend(make_frame_at(
+                Direction::Recv,
+                Transport::Udp,
+                addr,
+                udp,
+                sec,
+            ));
+        }
+        let mut msgs = MessageIterator::new(&data[..]).gap_warn(Duration::from_secs(5));
+        assert_eq!(msgs.by_ref().count(), 4);
+        assert_eq!(msgs.gap_count(), 1);
+    }
+
+    #[test]
+    fn wss_leading_framing_stripped() {
+        let sip = b"NOTIFY sip:a@host SIP/2.0\r\nContent-Length: 0\r\n\r\n";
+        let mut content = vec![0x81, 0x2f];
+        content.extend_from_slice(sip);
+        let data = make_frame(Direction::Recv, Transport::Wss, "10.0.0.1:443", &content);
+        let msgs: Vec<SipMessage> = MessageIterator::new(&data[..])
+            .collect::<Result<Vec<_>, _>>()
+            .unwrap();
+        assert_eq!(msgs.len(), 1);
+        assert_eq!(msgs[0].content, sip);
+    }
+
+    /// A single WebSocket frame around `payload`, masked when `mask` is set.
+    fn ws_frame(opcode: u8, payload: &[u8], mask: Option<[u8; 4]>) -> Vec<u8> {
+        let mask_bit = if mask.is_some() { 0x80 } else { 0 };
+        let mut frame = vec![0x80 | opcode];
+        match payload.len() {
+            n if n < 126 => frame.push(mask_bit | n as u8),
+            n => {
+                frame.push(mask_bit | 126);
+                frame.extend_from_slice(&(n as u16).to_be_bytes());
+            }
+        }
+        let mut payload = payload.to_vec();
+        if let Some(mask) = mask {
+            frame.extend_from_slice(&mask);
+            for (i, b) in payload.iter_mut().enumerate() {
+                *b ^= mask[i % 4];
+            }
+        }
+        frame.extend_from_slice(&payload);
+        frame
+    }
+
+    #[test]
+    fn ws_framing_masked_text_frame() {
+        let sip = b"REGISTER sip:host SIP/2.0\r\nCall-ID: ws1\r\nContent-Length: 0\r\n\r\n";
+        let content = ws_frame(0x1, sip, Some([0x37, 0xfa, 0x21, 0x3d]));
+        let data = make_frame(Direction::Recv, Transport::Wss, "10.0.0.1:443", &content);
+
+        let msgs: Vec<SipMessage> = MessageIterator::new(&data[..])
+            .ws_framing(true)
+            .collect::<Result<Vec<_>, _>>()
+            .unwrap();
+        assert_eq!(msgs.len(), 1);
+        assert_eq!(msgs[0].content, sip);
+        assert_eq!(msgs[0].transport, Transport::Wss);
+
+        // Off by default: masked bytes are not recognizable as SIP
+        let msgs: Vec<SipMessage> = MessageIterator::new(&data[..])
+            .collect::<Result<Vec<_>, _>>()
+            .unwrap();
+        assert_ne!(msgs[0].content, sip);
+    }
+
+    #[test]
+    fn ws_framing_delimits_by_frame_length() {
+        // Content-Length is wrong on purpose: the WebSocket length wins
+        let first = b"MESSAGE sip:a@host SIP/2.0\r\nContent-Length: 100\r\n\r\nshort";
+        let second = format!(
+            "MESSAGE sip:b@host SIP/2.0\r\nContent-Length: 200\r\n\r\n{}",
+            "x".repeat(200)
+        );
+        let mut stream = ws_frame(0x1, first, Some([1, 2, 3, 4]));
+        stream.extend(ws_frame(0x9, b"ping", Some([5, 6, 7, 8])));
+        stream.extend(ws_frame(0x1, second.as_bytes(), Some([9, 10, 11, 12])));
+        let split = stream.len() - 100;
+        let addr = "10.0.0.1:443";
+        let mut data = make_frame_at(Direction::Recv, Transport::Wss, addr, &stream[..split], 1);
+        data.extend(make_frame_at(
+            Direction::Recv,
+            Transport::Wss,
+            addr,
+            &stream[split..],
+            2,
+        ));
+
+        let msgs: Vec<SipMessage> = MessageIterator::new(&data[..])
+            .ws_framing(true)
+            .collect::<Result<Vec<_>, _>>()
+            .unwrap();
+        assert_eq!(msgs.len(), 2);
+        assert_eq!(msgs[0].content, first);
+        assert_eq!(msgs[0].frame_count, 1);
+        assert_eq!(msgs[1].content, second.as_bytes());
+        assert_eq!(msgs[1].frame_count, 2);
+        assert_eq!(msgs[1].timestamp.sort_key(), msgs[0].timestamp.sort_key());
+        assert_ne!(
+            msgs[1].last_timestamp.sort_key(),
+            msgs[1].timestamp.sort_key()
+        );
+    }
+
+    #[test]
+    fn ws_framing_flushes_cut_short_frame() {
+        let sip = b"MESSAGE sip:a@host SIP/2.0\r\nContent-Length: 4\r\n\r\nbody";
+        let stream = ws_frame(0x1, sip, Some([1, 2, 3, 4]));
+        let cut = &stream[..stream.len() - 4];
+        let data = make_frame(Direction::Recv, Transport::Wss, "10.0.0.1:443", cut);
+
+        let msgs: Vec<SipMessage> = MessageIterator::new(&data[..])
+            .ws_framing(true)
+            .collect::<Result<Vec<_>, _>>()
+            .unwrap();
+        assert_eq!(msgs.len(), 1);
+        assert_eq!(msgs[0].content, &sip[..sip.len() - 4]);
+
+        // flush_pending takes it too, while the input goes on
+        let udp = b"INFO sip:a@host SIP/2.0\r\nContent-Length: 0\r\n\r\n";
+        let mut data = data;
+        data.extend(make_frame(
+            Direction::Recv,
+            Transport::Udp,
+            "2.2.2.2:5060",
+            udp,
+        ));
+        let mut msgs = MessageIterator::new(&data[..]).ws_framing(true);
+        assert_eq!(msgs.next().unwrap().unwrap().address, "2.2.2.2:5060");
+        let flushed = msgs.flush_pending();
+        assert_eq!(flushed.len(), 1);
+        assert_eq!(flushed[0].content, &sip[..sip.len() - 4]);
+    }
+
+    #[test]
+    fn ws_framing_stale_partial_flushed() {
+        let first = b"MESSAGE sip:a@host SIP/2.0\r\nContent-Length: 4\r\n\r\nbody";
+        let second = b"NOTIFY sip:a@host SIP/2.0\r\nContent-Length: 0\r\n\r\n";
+        let partial = ws_frame(0x1, first, Some([1, 2, 3, 4]));
+        let partial = &partial[..partial.len() - 4];
+        let fresh = ws_frame(0x1, second, Some([5, 6, 7, 8]));
+        let addr = "10.0.0.1:443";
+        let mut data = make_frame_at(Direction::Recv, Transport::Wss, addr, partial, 0);
+        data.extend(make_frame_at(
+            Direction::Recv,
+            Transport::Wss,
+            addr,
+            &fresh,
+            30,
+        ));
+
+        let msgs: Vec<SipMessage> = MessageIterator::new(&data[..])
+            .ws_framing(true)
+            .collect::<Result<Vec<_>, _>>()
+            .unwrap();
+        assert_eq!(msgs.len(), 2);
+        assert_eq!(msgs[0].content, &first[..first.len() - 4]);
+        assert_eq!(msgs[1].content, second);
+
+        // Within the timeout, the same caps as TCP: the buffer size
+        let msgs: Vec<SipMessage> = MessageIterator::new(&data[..])
+            .ws_framing(true)
+            .reassembly_timeout(Duration::from_secs(60))
+            .max_buffer_size(64)
+            .collect::<Result<Vec<_>, _>>()
+            .unwrap();
+        assert_eq!(msgs.len(), 2);
+        assert_eq!(msgs[1].content, second);
+    }
+
+    #[test]
+    fn tcp_sigcomp_frame_not_glued_to_next_message() {
+        let sigcomp = [0xF8, 0x00, 0x12, 0x34, 0x0D, 0x0A, 0x0D, 0x0A, 0xAB];
+        let sip = b"NOTIFY sip:a@host SIP/2.0\r\nContent-Length: 0\r\n\r\n";
+        let addr = "10.0.0.1:5060";
+        let mut data = make_frame(Direction::Recv, Transport::Tcp, addr, &sigcomp);
+        data.extend(make_frame(Direction::Recv, Transport::Tcp, addr, sip));
+        let msgs: Vec<SipMessage> = MessageIterator::new(&data[..])
+            .collect::<Result<Vec<_>, _>>()
+            .unwrap();
+        assert_eq!(msgs.len(), 2);
+        assert!(msgs[0].is_sigcomp());
+        assert_eq!(msgs[0].content, sigcomp);
+        assert_eq!(msgs[1].content, sip);
+    }
+
+    #[test]
+    fn tcp_leading_bytes_not_treated_as_ws_framing() {
+        let mut content = b"xx".to_vec();
+        content.extend_from_slice(b"NOTIFY sip:a@host SIP/2.0\r\nContent-Length: 0\r\n\r\n");
+        let data = make_frame(Direction::Recv, Transport::Tcp, "10.0.0.1:5060", &content);
+        let msgs: Vec<SipMessage> = MessageIterator::new(&data[..])
+            .collect::<Result<Vec<_>, _>>()
+            .unwrap();
+        assert_eq!(msgs.len(), 1);
+        assert!(msgs[0].content.starts_with(b"xx"));
+    }
+
+    fn keepalive_between_messages() -> Vec<u8> {
+        let addr = "10.0.0.1:5060";
+        let mut data = make_frame(
+            Direction::Recv,
+            Transport::Tcp,
+            addr,
+            b"OPTIONS sip:a@host SIP/2.0\r\nContent-Length: 0\r\n\r\n",
+        );
+        data.extend_from_slice(&make_frame(
+            Direction::Recv,
+            Transport::Tcp,
+            addr,
+            b"\r\n\r\n",
+        ));
+        data.extend_from_slice(&make_frame(
+            Direction::Recv,
+            Transport::Tcp,
+            addr,
+            b"OPTIONS sip:b@host SIP/2.0\r\nContent-Length: 0\r\n\r\n",
+        ));
+        data
+    }
+
+    #[test]
+    fn tcp_keepalive_skipped() {
+        let data = keepalive_between_messages();
+        let msgs: Vec<SipMessage> = MessageIterator::new(&data[..])
+            .collect::<Result<Vec<_>, _>>()
+            .unwrap();
+        assert_eq!(msgs.len(), 2);
+        assert!(msgs[0].content.starts_with(b"OPTIONS sip:a@host"));
+        assert!(msgs[1].content.starts_with(b"OPTIONS sip:b@host"));
+        assert_eq!(msgs[1].frame_count, 1);
+    }
+
+    #[test]
+    fn tcp_keepalive_emitted() {
+        let data = keepalive_between_messages();
+        let msgs: Vec<SipMessage> = MessageIterator::new(&data[..])
+            .keepalives(true)
+            .collect::<Result<Vec<_>, _>>()
+            .unwrap();
+        assert_eq!(msgs.len(), 3);
+        assert!(!msgs[0].is_keepalive());
+        assert!(msgs[1].is_keepalive());
+        assert_eq!(msgs[1].content, b"\r\n\r\n");
+        assert!(!msgs[2].is_keepalive());
+    }
+
+    #[test]
+    fn crlf_frame_inside_body_is_not_keepalive() {
+        let addr = "10.0.0.1:5060";
+        let mut data = make_frame(
+            Direction::Recv,
+            Transport::Tcp,
+            addr,
+            b"MESSAGE sip:a@host SIP/2.0\r\nContent-Length: 4\r\n\r\nab",
+        );
+        data.extend_from_slice(&make_frame(Direction::Recv, Transport::Tcp, addr, b"\r\n"));
+        let msgs: Vec<SipMessage> = MessageIterator::new(&data[..])
+            .keepalives(true)
+            .collect::<Result<Vec<_>, _>>()
+            .unwrap();
+        assert_eq!(msgs.len(), 1);
+        assert!(msgs[0].content.ends_with(b"\r\n\r\nab\r\n"));
+        assert_eq!(msgs[0].frame_count, 2);
+    }
+
     #[test]
     fn tcp_reassembly_two_frames() {
         let part1 = b"NOTIFY sip:user@host SIP/2.0\r\n";
@@ -412,6 +1441,60 @@ mod tests {
         assert_eq!(msgs[0].content, expected);
     }
 
+    #[test]
+    fn tcp_body_without_content_length() {
+        let invite = b"INVITE sip:bob@host SIP/2.0\r\nCall-ID: nocl\r\n\
+            Content-Type: application/sdp\r\n\r\n";
+        let sdp = b"v=0\r\no=- 1 1 IN IP4 10.0.0.1\r\ns=-\r\n";
+        let bye = b"BYE sip:bob@host SIP/2.0\r\nCall-ID: nocl\r\nContent-Length: 0\r\n\r\n";
+        let mut data = make_frame(Direction::Recv, Transport::Tcp, "[::1]:5060", invite);
+        data.extend(make_frame(
+            Direction::Recv,
+            Transport::Tcp,
+            "[::1]:5060",
+            &[&sdp[..], bye].concat(),
+        ));
+        let msgs: Vec<SipMessage> = MessageIterator::new(&data[..])
+            .collect::<Result<Vec<_>, _>>()
+            .unwrap();
+        assert_eq!(msgs.len(), 2);
+        assert_eq!(msgs[0].content, [&invite[..], sdp].concat());
+        assert_eq!(msgs[0].parse().unwrap().body, sdp);
+        assert_eq!(msgs[1].content, bye);
+
+        // A body at the end of the dump is flushed with its message
+        let data = make_frame(
+            Direction::Recv,
+            Transport::Tcp,
+            "[::1]:5060",
+            &[&invite[..], sdp].concat(),
+        );
+        let msgs: Vec<SipMessage> = MessageIterator::new(&data[..])
+            .collect::<Result<Vec<_>, _>>()
+            .unwrap();
+        assert_eq!(msgs.len(), 1);
+        assert!(msgs[0].content.ends_with(sdp));
+    }
+
+    #[test]
+    fn tcp_reassembly_lf_only() {
+        let part1 = b"MESSAGE sip:user@host SIP/2.0\nContent-Length: 5\n\nhel";
+        let part2 = b"lo\nOPTIONS sip:user@host SIP/2.0\nContent-Length: 0\n\n";
+        let mut data = make_frame(Direction::Recv, Transport::Tcp, "[::1]:5060", part1);
+        data.extend_from_slice(&make_frame(
+            Direction::Recv,
+            Transport::Tcp,
+            "[::1]:5060",
+            part2,
+        ));
+        let msgs: Vec<SipMessage> = MessageIterator::new(&data[..])
+            .collect::<Result<Vec<_>, _>>()
+            .unwrap();
+        assert_eq!(msgs.len(), 2);
+        assert!(msgs[0].content.ends_with(b"\n\nhello"));
+        assert!(msgs[1].content.starts_with(b"OPTIONS "));
+    }
+
     #[test]
     fn tcp_reassembly_across_interleaved_frames() {
         // Frame 1: recv from A (partial INVITE)
@@ -455,6 +1538,128 @@ mod tests {
         assert_eq!(invite.content, expected_invite);
     }
 
+    #[test]
+    fn order_by_start_across_interleaved_frames() {
+        let part1 = b"INVITE sip:user@host SIP/2.0\r\n";
+        let part2 = b"Content-Length: 3\r\n\r\nSDP";
+        let response = b"SIP/2.0 100 Trying\r\nContent-Length: 0\r\n\r\n";
+        let options = b"OPTIONS sip:user@host SIP/2.0\r\nContent-Length: 0\r\n\r\n";
+
+        let mut data = make_frame_at(Direction::Recv, Transport::Tcp, "10.0.0.1:5060", part1, 1);
+        data.extend_from_slice(&make_frame_at(
+            Direction::Sent,
+            Transport::Tcp,
+            "10.0.0.1:5060",
+            response,
+            2,
+        ));
+        data.extend_from_slice(&make_frame_at(
+            Direction::Recv,
+            Transport::Udp,
+            "10.0.0.2:5060",
+            options,
+            3,
+        ));
+        data.extend_from_slice(&make_frame_at(
+            Direction::Recv,
+            Transport::Tcp,
+            "10.0.0.1:5060",
+            part2,
+            4,
+        ));
+
+        let starts = |msgs: MessageIterator<&[u8]>| -> Vec<u8> {
+            msgs.map(|m| match m.unwrap().timestamp {
+                Timestamp::TimeOnly { sec, .. } => sec,
+                _ => unreachable!(),
+            })
+            .collect()
+        };
+
+        assert_eq!(starts(MessageIterator::new(&data[..])), [2, 3, 1]);
+        let ordered = MessageIterator::new(&data[..]).order_by_start(Duration::from_secs(10));
+        assert_eq!(starts(ordered), [1, 2, 3]);
+
+        // Past the window, the partial INVITE no longer holds later messages
+        let ordered = MessageIterator::new(&data[..]).order_by_start(Duration::from_secs(1));
+        assert_eq!(starts(ordered), [2, 3, 1]);
+    }
+
+    #[test]
+    fn order_by_start_flushes_partial_at_eof() {
+        let partial = b"INVITE sip:user@host SIP/2.0\r\nContent-Length: 10\r\n\r\n";
+        let options = b"OPTIONS sip:user@host SIP/2.0\r\nContent-Length: 0\r\n\r\n";
+        let mut data = make_frame_at(Direction::Recv, Transport::Tcp, "10.0.0.1:5060", partial, 1);
+        data.extend_from_slice(&make_frame_at(
+            Direction::Recv,
+            Transport::Udp,
+            "10.0.0.2:5060",
+            options,
+            2,
+        ));
+
+        let msgs: Vec<SipMessage> = MessageIterator::new(&data[..])
+            .order_by_start(DEFAULT_REORDER_WINDOW)
+            .collect::<Result<Vec<_>, _>>()
+            .unwrap();
+        assert_eq!(msgs.len(), 2);
+        assert_eq!(msgs[0].content, partial);
+        assert_eq!(msgs[1].content, options);
+    }
+
+    #[test]
+    fn reassembled_message_first_and_last_timestamps() {
+        let part1 = b"INVITE sip:user@host SIP/2.0\r\nContent-Length: 3\r\n\r\n";
+        let mut data = make_frame_at(Direction::Recv, Transport::Tcp, "10.0.0.1:5060", part1, 1);
+        data.extend_from_slice(&make_frame_at(
+            Direction::Recv,
+            Transport::Tcp,
+            "10.0.0.1:5060",
+            b"SDP",
+            4,
+        ));
+        let at = |sec| Timestamp::TimeOnly {
+            hour: 0,
+            min: 0,
+            sec,
+            usec: 0,
+        };
+
+        let msgs: Vec<SipMessage> = MessageIterator::new(&data[..])
+            .collect::<Result<Vec<_>, _>>()
+            .unwrap();
+        assert_eq!(msgs.len(), 1);
+        assert_eq!(msgs[0].frame_count, 2);
+        assert_eq!(msgs[0].timestamp, at(1));
+        assert_eq!(msgs[0].last_timestamp, at(4));
+
+        let msgs: Vec<SipMessage> = MessageIterator::new(&data[..])
+            .stamp_last_frame(true)
+            .collect::<Result<Vec<_>, _>>()
+            .unwrap();
+        assert_eq!(msgs[0].timestamp, at(4));
+        assert_eq!(msgs[0].last_timestamp, at(4));
+    }
+
+    #[test]
+    fn dedup_frames_drops_repeated_message() {
+        let options = b"OPTIONS sip:user@host SIP/2.0\r\nContent-Length: 0\r\n\r\n";
+        let frame = make_frame_at(Direction::Recv, Transport::Udp, "10.0.0.1:5060", options, 1);
+        let later = make_frame_at(Direction::Recv, Transport::Udp, "10.0.0.1:5060", options, 2);
+        let data = [&frame[..], &frame, &later].concat();
+
+        let count = |msgs: MessageIterator<&[u8]>| msgs.filter_map(Result::ok).count();
+        assert_eq!(count(MessageIterator::new(&data[..])), 3);
+        // Same content at another time is a retransmission, not a duplicate
+        assert_eq!(count(MessageIterator::new(&data[..]).dedup_frames(16)), 2);
+        assert_eq!(count(MessageIterator::new(&data[..]).dedup_frames(0)), 3);
+
+        // Only the last `window` messages are remembered
+        let data = [&frame[..], &later, &frame].concat();
+        assert_eq!(count(MessageIterator::new(&data[..]).dedup_frames(1)), 3);
+        assert_eq!(count(MessageIterator::new(&data[..]).dedup_frames(2)), 2);
+    }
+
     #[test]
     fn direction_change_splits_messages() {
         let recv_content = b"OPTIONS sip:user@host SIP/2.0\r\nContent-Length: 0\r\n\r\n";
@@ -527,6 +1732,114 @@ mod tests {
         assert_eq!(msgs[1].content, msg2);
     }
 
+    #[test]
+    fn stale_partial_flushed_after_gap() {
+        let partial = b"INVITE sip:a SIP/2.0\r\nContent-Length: 100\r\n\r\nv=0\r\n";
+        let fresh = b"OPTIONS sip:a SIP/2.0\r\nContent-Length: 0\r\n\r\n";
+        let addr = "10.0.0.1:5060";
+        let mut data = make_frame_at(Direction::Recv, Transport::Tcp, addr, partial, 0);
+        data.extend(make_frame_at(
+            Direction::Recv,
+            Transport::Tcp,
+            addr,
+            fresh,
+            30,
+        ));
+
+        let msgs: Vec<_> = MessageIterator::new(&data[..])
+            .collect::<Result<_, _>>()
+            .unwrap();
+        assert_eq!(msgs.len(), 2);
+        assert_eq!(msgs[0].content, partial);
+        assert_eq!(msgs[1].content, fresh);
+        assert_eq!(
+            msgs[1].timestamp,
+            Timestamp::TimeOnly {
+                hour: 0,
+                min: 0,
+                sec: 30,
+                usec: 0
+            }
+        );
+
+        // Within the timeout the frames are still glued together
+        let msgs: Vec<_> = MessageIterator::new(&data[..])
+            .reassembly_timeout(Duration::from_secs(60))
+            .collect::<Result<_, _>>()
+            .unwrap();
+        assert_eq!(msgs.len(), 1);
+    }
+
+    #[test]
+    fn stale_partial_flushed_over_size_cap() {
+        let partial = b"INVITE sip:a SIP/2.0\r\nContent-Length: 100\r\n\r\nv=0\r\n";
+        let fresh = b"OPTIONS sip:a SIP/2.0\r\nContent-Length: 0\r\n\r\n";
+        let addr = "10.0.0.1:5060";
+        let mut data = make_frame(Direction::Recv, Transport::Tcp, addr, partial);
+        data.extend(make_frame(Direction::Recv, Transport::Tcp, addr, fresh));
+
+        let msgs: Vec<_> = MessageIterator::new(&data[..])
+            .max_buffer_size(64)
+            .collect::<Result<_, _>>()
+            .unwrap();
+        assert_eq!(msgs.len(), 2);
+        assert_eq!(msgs[1].content, fresh);
+    }
+
+    #[test]
+    fn absurd_content_length_truncated_at_next_message() {
+        let bogus = b"INVITE sip:a SIP/2.0\r\nContent-Length: 999999999\r\n\r\nv=0\r\n";
+        let next = b"BYE sip:a SIP/2.0\r\nContent-Length: 0\r\n\r\n";
+        let later = b"OPTIONS sip:a SIP/2.0\r\nContent-Length: 0\r\n\r\n";
+        let addr = "10.0.0.1:5060";
+        let mut data = make_frame(
+            Direction::Recv,
+            Transport::Tcp,
+            addr,
+            &[&bogus[..], next].concat(),
+        );
+        data.extend(make_frame(Direction::Recv, Transport::Tcp, addr, later));
+
+        let msgs: Vec<_> = MessageIterator::new(&data[..])
+            .with_max_message_size(1024)
+            .collect::<Result<_, _>>()
+            .unwrap();
+        assert_eq!(msgs.len(), 3);
+        assert_eq!(msgs[0].content, bogus);
+        assert_eq!(msgs[1].content, next);
+        assert_eq!(msgs[2].content, later);
+    }
+
+    #[test]
+    fn message_over_a_mebibyte_reassembled() {
+        let addr = "10.0.0.1:5060";
+        let body = "x".repeat(3 * 1024 * 1024);
+        let msg = format!(
+            "MESSAGE sip:a SIP/2.0\r\nContent-Length: {}\r\n\r\n{body}",
+            body.len()
+        );
+        let data: Vec<u8> = msg
+            .as_bytes()
+            .chunks(64 * 1024)
+            .flat_map(|chunk| make_frame(Direction::Recv, Transport::Tcp, addr, chunk))
+            .collect();
+
+        let msgs: Vec<_> = MessageIterator::new(&data[..])
+            .collect::<Result<_, _>>()
+            .unwrap();
+        assert_eq!(msgs.len(), 1);
+        assert_eq!(msgs[0].content, msg.as_bytes());
+
+        // A raised message cap raises the buffer cap with it
+        let msgs: Vec<_> = MessageIterator::new(&data[..])
+            .max_buffer_size(1024)
+            .with_max_message_size(8 * 1024 * 1024)
+            .collect::<Result<_, _>>()
+            .unwrap();
+        assert_eq!(msgs.len(), 1);
+        assert_eq!(msgs[0].content, msg.as_bytes());
+    }
+
     #[test]
     fn find_content_length_standard() {
         let data = b"NOTIFY sip:a SIP/2.0\r\nContent-Length: 42\r\n\r\n";
@@ -539,6 +1852,16 @@ mod tests {
         assert_eq!(find_content_length(data), Some(42));
     }
 
+    #[test]
+    fn find_content_length_conflicting() {
+        let data = b"NOTIFY sip:a SIP/2.0\r\nContent-Length: 120\r\nl: 5\r\n\r\n";
+        assert_eq!(find_content_length(data), Some(5));
+        let data = b"NOTIFY sip:a SIP/2.0\r\nContent-Length: 5\r\nContent-Length: 120\r\n\r\n";
+        assert_eq!(find_content_length(data), Some(5));
+        let data = b"NOTIFY sip:a SIP/2.0\r\nContent-Length: 7\r\nl: 7\r\n\r\n";
+        assert_eq!(find_content_length(data), Some(7));
+    }
+
     #[test]
     fn find_content_length_missing() {
         let data = b"NOTIFY sip:a SIP/2.0\r\nCSeq: 1 NOTIFY\r\n\r\n";
@@ -553,6 +1876,42 @@ mod tests {
         assert!(is_sip_start(b"ACK sip:user@host SIP/2.0\r\n"));
     }
 
+    #[test]
+    fn is_sip_start_extension_method() {
+        assert!(is_sip_start(b"QUERY sip:ecrf@host SIP/2.0\r\n"));
+        assert!(is_sip_start(b"MELD sip:ecrf@host SIP/2.0\n"));
+        // Unknown methods need the whole request line
+        assert!(!is_sip_start(b"QUERY sip:ecrf@host SIP/2.0"));
+        assert!(!is_sip_start(b"a=label x SIP/2.0\r\n"));
+        assert!(!is_sip_start(b"QUE(RY sip:ecrf@host SIP/2.0\r\n"));
+        assert!(!is_sip_start(b"QUERY sip:ecrf@host SIP/3.0\r\n"));
+    }
+
+    #[test]
+    fn tcp_reassembly_extension_method() {
+        let part1 = b"QUERY sip:ecrf@host SIP/2.0\r\nContent-Length: 5\r\n\r\nhel";
+        let part2 = b"lo";
+        let part3 = b"QUERY sip:ecrf@host SIP/2.0\r\nContent-Length: 0\r\n\r\n";
+        let mut data = Vec::new();
+        for part in [&part1[..], part2, part3] {
+            data.extend(make_frame(
+                Direction::Recv,
+                Transport::Tcp,
+                "[::1]:5060",
+                part,
+            ));
+        }
+        let msgs: Vec<SipMessage> = MessageIterator::new(&data[..])
+            .collect::<Result<Vec<_>, _>>()
+            .unwrap();
+        assert_eq!(msgs.len(), 2);
+        assert_eq!(msgs[0].frame_count, 2);
+        assert!(msgs[0].content.ends_with(b"\r\n\r\nhello"));
+        assert_eq!(msgs[1].content, part3);
+        let parsed = msgs[1].parse().unwrap();
+        assert_eq!(parsed.method(), Some("QUERY"));
+    }
+
     #[test]
     fn is_sip_start_response() {
         assert!(is_sip_start(b"SIP/2.0 200 OK\r\n"));
@@ -628,10 +1987,16 @@ mod tests {
                 sec: 0,
                 usec: 0,
             },
+            last_frame: Timestamp::TimeOnly {
+                hour: 0,
+                min: 0,
+                sec: 0,
+                usec: 0,
+            },
             content,
             frame_count: 1,
         };
-        let msgs = extract_complete(&mut buf, &key);
+        let msgs = extract_complete(&mut buf, &key, DEFAULT_MAX_MESSAGE_SIZE);
         assert_eq!(msgs.len(), 2);
         assert_eq!(msgs[0].content, msg1);
         assert_eq!(msgs[1].content, msg2);
@@ -654,10 +2019,16 @@ mod tests {
                 sec: 0,
                 usec: 0,
             },
+            last_frame: Timestamp::TimeOnly {
+                hour: 0,
+                min: 0,
+                sec: 0,
+                usec: 0,
+            },
             content,
             frame_count: 1,
         };
-        let msgs = extract_complete(&mut buf, &key);
+        let msgs = extract_complete(&mut buf, &key, DEFAULT_MAX_MESSAGE_SIZE);
         assert_eq!(msgs.len(), 1);
         assert_eq!(msgs[0].content, msg);
     }
@@ -676,10 +2047,16 @@ mod tests {
                 sec: 0,
                 usec: 0,
             },
+            last_frame: Timestamp::TimeOnly {
+                hour: 0,
+                min: 0,
+                sec: 0,
+                usec: 0,
+            },
             content,
             frame_count: 1,
         };
-        let msgs = extract_complete(&mut buf, &key);
+        let msgs = extract_complete(&mut buf, &key, DEFAULT_MAX_MESSAGE_SIZE);
         assert!(msgs.is_empty(), "should wait for body to complete");
         assert!(!buf.content.is_empty(), "buffer should retain data");
     }
@@ -698,10 +2075,16 @@ mod tests {
                 sec: 0,
                 usec: 0,
             },
+            last_frame: Timestamp::TimeOnly {
+                hour: 0,
+                min: 0,
+                sec: 0,
+                usec: 0,
+            },
             content,
             frame_count: 1,
         };
-        let msgs = extract_complete(&mut buf, &key);
+        let msgs = extract_complete(&mut buf, &key, DEFAULT_MAX_MESSAGE_SIZE);
         assert!(msgs.is_empty(), "should wait for headers to complete");
     }
 }
@@ -0,0 +1,786 @@
+use std::collections::HashMap;
+use std::io::{self, Read};
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr};
+
+use tracing::{debug, trace};
+
+use crate::frame::ParseError;
+use crate::types::{Direction, Frame, Timestamp, Transport};
+
+/// Ports whose traffic is read as SIP unless `sip_ports()` says otherwise.
+pub const DEFAULT_SIP_PORTS: &[u16] = &[5060];
+/// SIP over TLS can't be decrypted, so its packets are always skipped.
+const TLS_PORT: u16 = 5061;
+/// Sanity cap on a single captured packet.
+const MAX_PACKET_SIZE: usize = 16 * 1024 * 1024;
+
+const PCAPNG_SHB: [u8; 4] = [0x0a, 0x0d, 0x0d, 0x0a];
+const PCAPNG_IDB: u32 = 1;
+const PCAPNG_SPB: u32 = 3;
+const PCAPNG_EPB: u32 = 6;
+
+/// Reads SIP frames out of a pcap or pcapng capture.
+///
+/// UDP datagrams and TCP segments to or from one of the SIP ports become one
+/// `Frame` each, so `MessageIterator::from()` reassembles TCP messages the
+/// same way it does for dump files. TCP retransmissions and overlapping
+/// segments are trimmed by sequence number; fragmented IP packets are
+/// skipped.
+///
+/// Captures carry no direction, so it is inferred from `local_host()` and
+/// `local_port()`: packets from the local endpoint are `Sent` to their
+/// destination, everything else is `Recv` from its source.
+pub struct PcapFrameIterator<R> {
+    reader: R,
+    offset: u64,
+    format: Option<Format>,
+    sip_ports: Vec<u16>,
+    local_host: Option<IpAddr>,
+    local_port: Option<u16>,
+    /// Next expected sequence number per TCP flow (source, destination).
+    tcp_next_seq: HashMap<(SocketAddr, SocketAddr), u32>,
+    done: bool,
+}
+
+enum Format {
+    Pcap {
+        big_endian: bool,
+        units_per_sec: u64,
+        linktype: u32,
+    },
+    PcapNg {
+        big_endian: bool,
+        interfaces: Vec<Interface>,
+    },
+}
+
+struct Interface {
+    linktype: u32,
+    units_per_sec: u64,
+}
+
+struct Packet {
+    offset: u64,
+    linktype: u32,
+    units: u64,
+    units_per_sec: u64,
+    data: Vec<u8>,
+}
+
+struct Segment<'a> {
+    transport: Transport,
+    src: SocketAddr,
+    dst: SocketAddr,
+    tcp: Option<TcpHeader>,
+    payload: &'a [u8],
+}
+
+struct TcpHeader {
+    seq: u32,
+    syn: bool,
+    fin_or_rst: bool,
+}
+
+impl<R: Read> PcapFrameIterator<R> {
+    pub fn new(reader: R) -> Self {
+        PcapFrameIterator {
+            reader,
+            offset: 0,
+            format: None,
+            sip_ports: DEFAULT_SIP_PORTS.to_vec(),
+            local_host: None,
+            local_port: None,
+            tcp_next_seq: HashMap::new(),
+            done: false,
+        }
+    }
+
+    /// Only read packets with one of these as source or destination port.
+    pub fn sip_ports(mut self, ports: &[u16]) -> Self {
+        self.sip_ports = ports.to_vec();
+        self
+    }
+
+    /// Treat packets from this IP address as sent by us.
+    pub fn local_host(mut self, host: IpAddr) -> Self {
+        self.local_host = Some(host);
+        self
+    }
+
+    /// Treat packets from this port as sent by us.
+    pub fn local_port(mut self, port: u16) -> Self {
+        self.local_port = Some(port);
+        self
+    }
+
+    /// Read exactly `buf.len()` bytes; `Ok(false)` on a clean end of input.
+    fn read_or_eof(&mut self, buf: &mut [u8]) -> io::Result<bool> {
+        let mut filled = 0;
+        while filled < buf.len() {
+            match self.reader.read(&mut buf[filled..]) {
+                Ok(0) if filled == 0 => return Ok(false),
+                Ok(0) => {
+                    return Err(io::Error::new(
+                        io::ErrorKind::UnexpectedEof,
+                        format!("truncated capture at byte {}", self.offset),
+                    ))
+                }
+                Ok(n) => filled += n,
+                Err(e) if e.kind() == io::ErrorKind::Interrupted => {}
+                Err(e) => return Err(e),
+            }
+        }
+        self.offset += buf.len() as u64;
+        Ok(true)
+    }
+
+    fn read_vec(&mut self, len: usize) -> Result<Vec<u8>, ParseError> {
+        if len > MAX_PACKET_SIZE {
+            return Err(ParseError::InvalidHeader(format!(
+                "capture record of {len} bytes at byte {}",
+                self.offset
+            )));
+        }
+        let mut buf = vec![0; len];
+        if !self.read_or_eof(&mut buf)? && len > 0 {
+            return Err(io::Error::from(io::ErrorKind::UnexpectedEof).into());
+        }
+        Ok(buf)
+    }
+
+    /// Identify the capture format from its first four bytes.
+    fn read_magic(&mut self) -> Result<bool, ParseError> {
+        let mut magic = [0; 4];
+        if !self.read_or_eof(&mut magic)? {
+            return Ok(false);
+        }
+        let (big_endian, units_per_sec) = match magic {
+            PCAPNG_SHB => {
+                let len = self.read_vec(4)?;
+                self.start_section(&len)?;
+                return Ok(true);
+            }
+            [0xd4, 0xc3, 0xb2, 0xa1] => (false, 1_000_000),
+            [0xa1, 0xb2, 0xc3, 0xd4] => (true, 1_000_000),
+            [0x4d, 0x3c, 0xb2, 0xa1] => (false, 1_000_000_000),
+            [0xa1, 0xb2, 0x3c, 0x4d] => (true, 1_000_000_000),
+            _ => {
+                return Err(ParseError::InvalidHeader(format!(
+                    "not a pcap or pcapng file (magic {magic:02x?})"
+                )))
+            }
+        };
+        let header = self.read_vec(20)?;
+        self.format = Some(Format::Pcap {
+            big_endian,
+            units_per_sec,
+            linktype: read_u32(&header[16..20], big_endian),
+        });
+        Ok(true)
+    }
+
+    /// Read the rest of a pcapng section header block after its type and
+    /// length; the section's byte order applies to every following block.
+    fn start_section(&mut self, len_bytes: &[u8]) -> Result<(), ParseError> {
+        let bom = self.read_vec(4)?;
+        let big_endian = match bom[..] {
+            [0x1a, 0x2b, 0x3c, 0x4d] => true,
+            [0x4d, 0x3c, 0x2b, 0x1a] => false,
+            _ => {
+                return Err(ParseError::InvalidHeader(
+                    "pcapng section header with unknown byte order".into(),
+                ))
+            }
+        };
+        let len = read_u32(len_bytes, big_endian) as usize;
+        if len < 28 || !len.is_multiple_of(4) {
+            return Err(ParseError::InvalidHeader(format!(
+                "pcapng section header length {len}"
+            )));
+        }
+        self.read_vec(len - 12)?;
+        self.format = Some(Format::PcapNg {
+            big_endian,
+            interfaces: Vec::new(),
+        });
+        Ok(())
+    }
+
+    fn next_packet(&mut self) -> Result<Option<Packet>, ParseError> {
+        if self.format.is_none() && !self.read_magic()? {
+            return Ok(None);
+        }
+        match self.format {
+            Some(Format::Pcap {
+                big_endian,
+                units_per_sec,
+                linktype,
+            }) => {
+                let offset = self.offset;
+                let mut header = [0; 16];
+                if !self.read_or_eof(&mut header)? {
+                    return Ok(None);
+                }
+                let secs = u64::from(read_u32(&header[0..4], big_endian));
+                let frac = u64::from(read_u32(&header[4..8], big_endian));
+                let len = read_u32(&header[8..12], big_endian) as usize;
+                let data = self.read_vec(len)?;
+                Ok(Some(Packet {
+                    offset,
+                    linktype,
+                    units: secs * units_per_sec + frac,
+                    units_per_sec,
+                    data,
+                }))
+            }
+            Some(Format::PcapNg { .. }) => self.next_pcapng_packet(),
+            None => Ok(None),
+        }
+    }
+
+    fn next_pcapng_packet(&mut self) -> Result<Option<Packet>, ParseError> {
+        loop {
+            let offset = self.offset;
+            let mut head = [0; 8];
+            if !self.read_or_eof(&mut head)? {
+                return Ok(None);
+            }
+            if head[0..4] == PCAPNG_SHB {
+                // Concatenated captures start a new section
+                self.start_section(&head[4..8])?;
+                continue;
+            }
+            let Some(Format::PcapNg { big_endian, .. }) = self.format else {
+                return Ok(None);
+            };
+            let block_type = read_u32(&head[0..4], big_endian);
+            let len = read_u32(&head[4..8], big_endian) as usize;
+            if len < 12 || !len.is_multiple_of(4) {
+                return Err(ParseError::InvalidHeader(format!(
+                    "pcapng block length {len} at byte {offset}"
+                )));
+            }
+            let mut body = self.read_vec(len - 8)?;
+            body.truncate(len - 12);
+            let Some(Format::PcapNg { interfaces, .. }) = self.format.as_mut() else {
+                return Ok(None);
+            };
+            match block_type {
+                PCAPNG_IDB if body.len() >= 8 => {
+                    interfaces.push(Interface {
+                        linktype: u32::from(read_u16(&body[0..2], big_endian)),
+                        units_per_sec: interface_resolution(&body[8..], big_endian),
+                    });
+                }
+                PCAPNG_EPB if body.len() >= 20 => {
+                    let id = read_u32(&body[0..4], big_endian) as usize;
+                    let Some(iface) = interfaces.get(id) else {
+                        debug!(offset, id, "pcapng packet on unknown interface");
+                        continue;
+                    };
+                    let units = u64::from(read_u32(&body[4..8], big_endian)) << 32
+                        | u64::from(read_u32(&body[8..12], big_endian));
+                    let captured = read_u32(&body[12..16], big_endian) as usize;
+                    let end = (20 + captured).min(body.len());
+                    return Ok(Some(Packet {
+                        offset,
+                        linktype: iface.linktype,
+                        units,
+                        units_per_sec: iface.units_per_sec,
+                        data: body[20..end].to_vec(),
+                    }));
+                }
+                PCAPNG_SPB if body.len() >= 4 => {
+                    // Simple packets carry no timestamp
+                    let Some(iface) = interfaces.first() else {
+                        continue;
+                    };
+                    let original = read_u32(&body[0..4], big_endian) as usize;
+                    let end = (4 + original).min(body.len());
+                    return Ok(Some(Packet {
+                        offset,
+                        linktype: iface.linktype,
+                        units: 0,
+                        units_per_sec: iface.units_per_sec,
+                        data: body[4..end].to_vec(),
+                    }));
+                }
+                _ => trace!(offset, block_type, "skipping pcapng block"),
+            }
+        }
+    }
+
+    /// Decode one packet into a SIP frame, or `None` when it isn't SIP traffic.
+    fn frame(&mut self, packet: Packet) -> Option<Frame> {
+        let Some(ip) = link_payload(packet.linktype, &packet.data) else {
+            trace!(offset = packet.offset, "not an IP packet");
+            return None;
+        };
+        let segment = match ip_segment(ip) {
+            Ok(segment) => segment?,
+            Err(reason) => {
+                debug!(offset = packet.offset, "skipping packet: {reason}");
+                return None;
+            }
+        };
+        let (sport, dport) = (segment.src.port(), segment.dst.port());
+        if sport == TLS_PORT || dport == TLS_PORT {
+            return None;
+        }
+        if !self.sip_ports.contains(&sport) && !self.sip_ports.contains(&dport) {
+            return None;
+        }
+        let mut payload = segment.payload;
+        if let Some(tcp) = &segment.tcp {
+            payload = self.trim_tcp(segment.src, segment.dst, tcp, payload);
+        }
+        if payload.is_empty() {
+            return None;
+        }
+
+        let (direction, address) = if self.is_local(&segment.src) {
+            (Direction::Sent, segment.dst)
+        } else {
+            (Direction::Recv, segment.src)
+        };
+        let secs = packet.units / packet.units_per_sec;
+        let frac = u128::from(packet.units % packet.units_per_sec);
+        let usec = (frac * 1_000_000 / u128::from(packet.units_per_sec)) as u32;
+        Some(Frame {
+            direction,
+            byte_count: payload.len(),
+            transport: segment.transport,
+            address: address.to_string(),
+            timestamp: Timestamp::from_unix(secs, usec),
+            content: payload.to_vec(),
+            offset: packet.offset,
+        })
+    }
+
+    fn is_local(&self, addr: &SocketAddr) -> bool {
+        (self.local_host.is_some() || self.local_port.is_some())
+            && self.local_host.is_none_or(|host| host == addr.ip())
+            && self.local_port.is_none_or(|port| port == addr.port())
+    }
+
+    /// Drop the part of a TCP payload already seen on its flow.
+    fn trim_tcp<'a>(
+        &mut self,
+        src: SocketAddr,
+        dst: SocketAddr,
+        tcp: &TcpHeader,
+        payload: &'a [u8],
+    ) -> &'a [u8] {
+        let flow = (src, dst);
+        let seq = tcp.seq.wrapping_add(u32::from(tcp.syn));
+        let end = seq.wrapping_add(payload.len() as u32);
+        let payload = match self.tcp_next_seq.get(&flow) {
+            Some(&next) => {
+                let behind = next.wrapping_sub(seq) as i32;
+                if behind <= 0 {
+                    payload
+                } else if behind as usize >= payload.len() {
+                    trace!(%src, "TCP retransmission");
+                    &[]
+                } else {
+                    &payload[behind as usize..]
+                }
+            }
+            None => payload,
+        };
+        if tcp.fin_or_rst {
+            self.tcp_next_seq.remove(&flow);
+        } else {
+            let next = self.tcp_next_seq.entry(flow).or_insert(end);
+            if (end.wrapping_sub(*next) as i32) > 0 {
+                *next = end;
+            }
+        }
+        payload
+    }
+}
+
+impl<R: Read> Iterator for PcapFrameIterator<R> {
+    type Item = Result<Frame, ParseError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        while !self.done {
+            match self.next_packet() {
+                Ok(Some(packet)) => {
+                    if let Some(frame) = self.frame(packet) {
+                        return Some(Ok(frame));
+                    }
+                }
+                Ok(None) => self.done = true,
+                Err(e) => {
+                    self.done = true;
+                    return Some(Err(e));
+                }
+            }
+        }
+        None
+    }
+}
+
+fn read_u16(bytes: &[u8], big_endian: bool) -> u16 {
+    let bytes = [bytes[0], bytes[1]];
+    if big_endian {
+        u16::from_be_bytes(bytes)
+    } else {
+        u16::from_le_bytes(bytes)
+    }
+}
+
+fn read_u32(bytes: &[u8], big_endian: bool) -> u32 {
+    let bytes = [bytes[0], bytes[1], bytes[2], bytes[3]];
+    if big_endian {
+        u32::from_be_bytes(bytes)
+    } else {
+        u32::from_le_bytes(bytes)
+    }
+}
+
+/// Timestamp units per second from a pcapng interface's `if_tsresol` option.
+fn interface_resolution(mut options: &[u8], big_endian: bool) -> u64 {
+    while options.len() >= 4 {
+        let code = read_u16(&options[0..2], big_endian);
+        let len = read_u16(&options[2..4], big_endian) as usize;
+        if code == 0 {
+            break;
+        }
+        let Some(value) = options.get(4..4 + len) else {
+            break;
+        };
+        if let (9, [resol]) = (code, value) {
+            let exp = u32::from(resol & 0x7f);
+            return if resol & 0x80 != 0 {
+                1u64.checked_shl(exp).unwrap_or(1_000_000)
+            } else {
+                10u64.checked_pow(exp).unwrap_or(1_000_000)
+            };
+        }
+        options = options
+            .get(4 + len.next_multiple_of(4)..)
+            .unwrap_or_default();
+    }
+    1_000_000
+}
+
+/// `rest` when the ethertype is IPv4 or IPv6.
+fn ip_ethertype(ethertype: u16, rest: &[u8]) -> Option<&[u8]> {
+    matches!(ethertype, 0x0800 | 0x86dd).then_some(rest)
+}
+
+/// Strip the link-layer header, returning the IP packet.
+fn link_payload(linktype: u32, data: &[u8]) -> Option<&[u8]> {
+    match linktype {
+        // BSD loopback: 4-byte address family, then IP
+        0 | 108 => data.get(4..),
+        // Ethernet, skipping 802.1Q/802.1ad tags
+        1 => {
+            let mut pos = 12;
+            loop {
+                let ethertype = u16::from_be_bytes([*data.get(pos)?, *data.get(pos + 1)?]);
+                if ethertype == 0x8100 || ethertype == 0x88a8 {
+                    pos += 4;
+                    continue;
+                }
+                return ip_ethertype(ethertype, data.get(pos + 2..)?);
+            }
+        }
+        // Raw IP
+        12 | 14 | 101 | 228 | 229 => Some(data),
+        // Linux cooked capture v1 and v2
+        113 => ip_ethertype(
+            u16::from_be_bytes([*data.get(14)?, *data.get(15)?]),
+            data.get(16..)?,
+        ),
+        276 => ip_ethertype(
+            u16::from_be_bytes([*data.first()?, *data.get(1)?]),
+            data.get(20..)?,
+        ),
+        _ => None,
+    }
+}
+
+/// Decode the IP and UDP/TCP headers. `Ok(None)` for other protocols.
+fn ip_segment(ip: &[u8]) -> Result<Option<Segment<'_>>, &'static str> {
+    let version = ip.first().ok_or("empty IP packet")? >> 4;
+    let (src, dst, mut protocol, mut payload) = match version {
+        4 => {
+            if ip.len() < 20 {
+                return Err("truncated IPv4 header");
+            }
+            let header_len = usize::from(ip[0] & 0x0f) * 4;
+            let total = usize::from(u16::from_be_bytes([ip[2], ip[3]]));
+            // Offloaded captures may leave the total length at zero
+            let end = if total == 0 {
+                ip.len()
+            } else {
+                total.min(ip.len())
+            };
+            let fragment = u16::from_be_bytes([ip[6], ip[7]]);
+            if fragment & 0x3fff != 0 {
+                return Err("fragmented IPv4 packet");
+            }
+            let src = Ipv4Addr::new(ip[12], ip[13], ip[14], ip[15]);
+            let dst = Ipv4Addr::new(ip[16], ip[17], ip[18], ip[19]);
+            let payload = ip.get(header_len..end).ok_or("truncated IPv4 header")?;
+            (IpAddr::V4(src), IpAddr::V4(dst), ip[9], payload)
+        }
+        6 => {
+            if ip.len() < 40 {
+                return Err("truncated IPv6 header");
+            }
+            let len = usize::from(u16::from_be_bytes([ip[4], ip[5]]));
+            let end = if len == 0 {
+                ip.len()
+            } else {
+                (40 + len).min(ip.len())
+            };
+            let src: [u8; 16] = ip[8..24].try_into().unwrap_or_default();
+            let dst: [u8; 16] = ip[24..40].try_into().unwrap_or_default();
+            (
+                IpAddr::V6(Ipv6Addr::from(src)),
+                IpAddr::V6(Ipv6Addr::from(dst)),
+                ip[6],
+                &ip[40..end],
+            )
+        }
+        _ => return Err("unknown IP version"),
+    };
+
+    // IPv6 extension headers: hop-by-hop, routing, destination options
+    while matches!(protocol, 0 | 43 | 60) {
+        if payload.len() < 8 {
+            return Err("truncated IPv6 extension header");
+        }
+        let len = (usize::from(payload[1]) + 1) * 8;
+        protocol = payload[0];
+        payload = payload
+            .get(len..)
+            .ok_or("truncated IPv6 extension header")?;
+    }
+
+    match protocol {
+        17 => {
+            if payload.len() < 8 {
+                return Err("truncated UDP header");
+            }
+            let len = usize::from(u16::from_be_bytes([payload[4], payload[5]]));
+            let end = if len < 8 {
+                payload.len()
+            } else {
+                len.min(payload.len())
+            };
+            Ok(Some(Segment {
+                transport: Transport::Udp,
+                src: SocketAddr::new(src, u16::from_be_bytes([payload[0], payload[1]])),
+                dst: SocketAddr::new(dst, u16::from_be_bytes([payload[2], payload[3]])),
+                tcp: None,
+                payload: &payload[8..end],
+            }))
+        }
+        6 => {
+            if payload.len() < 20 {
+                return Err("truncated TCP header");
+            }
+            let header_len = usize::from(payload[12] >> 4) * 4;
+            let flags = payload[13];
+            Ok(Some(Segment {
+                transport: Transport::Tcp,
+                src: SocketAddr::new(src, u16::from_be_bytes([payload[0], payload[1]])),
+                dst: SocketAddr::new(dst, u16::from_be_bytes([payload[2], payload[3]])),
+                tcp: Some(TcpHeader {
+                    seq: u32::from_be_bytes([payload[4], payload[5], payload[6], payload[7]]),
+                    syn: flags & 0x02 != 0,
+                    fin_or_rst: flags & 0x05 != 0,
+                }),
+                payload: payload.get(header_len..).ok_or("truncated TCP header")?,
+            }))
+        }
+        44 => Err("fragmented IPv6 packet"),
+        _ => Ok(None),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::message::MessageIterator;
+
+    const OPTIONS: &[u8] = b"OPTIONS sip:a@host SIP/2.0\r\nContent-Length: 0\r\n\r\n";
+
+    #[test]
+    fn interface_resolution_truncated_option() {
+        // if_tsresol claims one byte of value but the block ends first
+        assert_eq!(interface_resolution(&[9, 0, 1, 0], false), 1_000_000);
+        assert_eq!(
+            interface_resolution(&[9, 0, 1, 0, 0x89, 0, 0, 0], false),
+            512
+        );
+        assert_eq!(
+            interface_resolution(&[9, 0, 1, 0, 6, 0, 0, 0], false),
+            1_000_000
+        );
+    }
+
+    fn ipv4(src: [u8; 4], dst: [u8; 4], protocol: u8, payload: &[u8]) -> Vec<u8> {
+        let mut ip = vec![0x45, 0, 0, 0, 0, 0, 0x40, 0, 64, protocol, 0, 0];
+        ip[2..4].copy_from_slice(&(20 + payload.len() as u16).to_be_bytes());
+        ip.extend_from_slice(&src);
+        ip.extend_from_slice(&dst);
+        ip.extend_from_slice(payload);
+        ip
+    }
+
+    fn udp(sport: u16, dport: u16, payload: &[u8]) -> Vec<u8> {
+        let mut udp = [sport.to_be_bytes(), dport.to_be_bytes()].concat();
+        udp.extend_from_slice(&(8 + payload.len() as u16).to_be_bytes());
+        udp.extend_from_slice(&[0, 0]);
+        udp.extend_from_slice(payload);
+        udp
+    }
+
+    fn tcp(sport: u16, dport: u16, seq: u32, payload: &[u8]) -> Vec<u8> {
+        let mut tcp = [sport.to_be_bytes(), dport.to_be_bytes()].concat();
+        tcp.extend_from_slice(&seq.to_be_bytes());
+        tcp.extend_from_slice(&[0, 0, 0, 0, 0x50, 0x18, 0xff, 0xff, 0, 0, 0, 0]);
+        tcp.extend_from_slice(payload);
+        tcp
+    }
+
+    fn ethernet(ip: &[u8]) -> Vec<u8> {
+        let mut frame = vec![0; 12];
+        frame.extend_from_slice(&[0x08, 0x00]);
+        frame.extend_from_slice(ip);
+        frame
+    }
+
+    /// Little-endian microsecond pcap with Ethernet link type.
+    fn pcap(packets: &[(u32, u32, Vec<u8>)]) -> Vec<u8> {
+        let mut out = vec![0xd4, 0xc3, 0xb2, 0xa1, 2, 0, 4, 0];
+        out.extend_from_slice(&[0; 8]);
+        out.extend_from_slice(&65535u32.to_le_bytes());
+        out.extend_from_slice(&1u32.to_le_bytes());
+        for (secs, usec, data) in packets {
+            out.extend_from_slice(&secs.to_le_bytes());
+            out.extend_from_slice(&usec.to_le_bytes());
+            out.extend_from_slice(&(data.len() as u32).to_le_bytes());
+            out.extend_from_slice(&(data.len() as u32).to_le_bytes());
+            out.extend_from_slice(data);
+        }
+        out
+    }
+
+    fn pcapng_block(block_type: u32, body: &[u8]) -> Vec<u8> {
+        let padded = body.len().next_multiple_of(4);
+        let len = (12 + padded) as u32;
+        let mut block = [block_type.to_le_bytes(), len.to_le_bytes()].concat();
+        block.extend_from_slice(body);
+        block.resize(8 + padded, 0);
+        block.extend_from_slice(&len.to_le_bytes());
+        block
+    }
+
+    /// Little-endian pcapng with one raw IPv4 interface at nanosecond resolution.
+    fn pcapng(packets: &[(u64, Vec<u8>)]) -> Vec<u8> {
+        let shb = [
+            0x4d, 0x3c, 0x2b, 0x1a, 1, 0, 0, 0, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff,
+        ];
+        let mut out = pcapng_block(0x0a0d0d0a, &shb);
+        let idb = [228, 0, 0, 0, 0, 0, 0, 0, 9, 0, 1, 0, 9, 0, 0, 0];
+        out.extend(pcapng_block(PCAPNG_IDB, &idb));
+        for (nanos, data) in packets {
+            let mut epb = 0u32.to_le_bytes().to_vec();
+            epb.extend_from_slice(&((nanos >> 32) as u32).to_le_bytes());
+            epb.extend_from_slice(&(*nanos as u32).to_le_bytes());
+            epb.extend_from_slice(&(data.len() as u32).to_le_bytes());
+            epb.extend_from_slice(&(data.len() as u32).to_le_bytes());
+            epb.extend_from_slice(data);
+            out.extend(pcapng_block(PCAPNG_EPB, &epb));
+        }
+        out
+    }
+
+    #[test]
+    fn udp_options_from_pcap() {
+        let (a, b) = ([10, 0, 0, 1], [10, 0, 0, 2]);
+        let data = pcap(&[
+            (
+                1_704_164_645,
+                6,
+                ethernet(&ipv4(a, b, 17, &udp(5060, 5060, OPTIONS))),
+            ),
+            // RTP and SIP over TLS are skipped
+            (
+                1_704_164_646,
+                0,
+                ethernet(&ipv4(a, b, 17, &udp(10000, 20000, b"rtp"))),
+            ),
+            (
+                1_704_164_646,
+                0,
+                ethernet(&ipv4(a, b, 6, &tcp(5061, 40000, 1, b"tls"))),
+            ),
+        ]);
+        let frames: Vec<Frame> = PcapFrameIterator::new(&data[..])
+            .local_host(IpAddr::from(b))
+            .collect::<Result<_, _>>()
+            .unwrap();
+        assert_eq!(frames.len(), 1);
+        let frame = &frames[0];
+        assert_eq!(frame.direction, Direction::Recv);
+        assert_eq!(frame.transport, Transport::Udp);
+        assert_eq!(frame.address, "10.0.0.1:5060");
+        assert_eq!(frame.content, OPTIONS);
+        assert_eq!(frame.byte_count, OPTIONS.len());
+        assert_eq!(frame.offset, 24);
+        assert_eq!(
+            frame.timestamp,
+            Timestamp::DateTime {
+                year: 2024,
+                month: 1,
+                day: 2,
+                hour: 3,
+                min: 4,
+                sec: 5,
+                usec: 6,
+            }
+        );
+    }
+
+    #[test]
+    fn pcapng_tcp_retransmission_trimmed() {
+        let (a, b) = ([192, 168, 0, 1], [192, 168, 0, 2]);
+        let (head, body) = OPTIONS.split_at(20);
+        let data = pcapng(&[
+            (1_000_000_000, ipv4(a, b, 6, &tcp(5060, 40000, 100, head))),
+            // Retransmission, then a segment overlapping the first
+            (1_000_000_500, ipv4(a, b, 6, &tcp(5060, 40000, 100, head))),
+            (
+                1_000_001_000,
+                ipv4(a, b, 6, &tcp(5060, 40000, 110, &OPTIONS[10..])),
+            ),
+        ]);
+        let frames = PcapFrameIterator::new(&data[..]).local_port(5060);
+        let messages: Vec<_> = MessageIterator::from(frames)
+            .collect::<Result<_, _>>()
+            .unwrap();
+        assert_eq!(messages.len(), 1);
+        assert_eq!(messages[0].direction, Direction::Sent);
+        assert_eq!(messages[0].address, "192.168.0.2:40000");
+        assert_eq!(messages[0].content, [head, body].concat());
+        assert_eq!(messages[0].frame_count, 2);
+    }
+
+    #[test]
+    fn rejects_unknown_format() {
+        let mut frames = PcapFrameIterator::new(&b"recv 1 bytes"[..]);
+        assert!(matches!(
+            frames.next(),
+            Some(Err(ParseError::InvalidHeader(_)))
+        ));
+        assert!(frames.next().is_none());
+    }
+}
@@ -1,29 +1,175 @@
-use std::sync::LazyLock;
+use std::collections::{HashMap, HashSet};
+use std::iter::FusedIterator;
 
 use memchr::memmem;
-
-use crate::frame::ParseError;
-use crate::message::MessageIterator;
-use crate::types::{MimePart, ParsedSipMessage, SipMessage, SipMessageType};
-
-static CRLF: LazyLock<memmem::Finder<'static>> = LazyLock::new(|| memmem::Finder::new(b"\r\n"));
-static CRLFCRLF: LazyLock<memmem::Finder<'static>> =
-    LazyLock::new(|| memmem::Finder::new(b"\r\n\r\n"));
+use tracing::debug;
+
+use crate::frame::{FrameIterator, ParseError};
+use crate::message::{find_content_length, find_header_end, MessageIterator};
+use crate::types::{
+    header_name_matches, AuthParams, MimePart, NameAddr, ParsedSipMessage, ReasonInfo, SipMessage,
+    SipMessageType, Via,
+};
+
+/// Options for [`SipMessage::parse_with`] and the parsed-message iterators.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct ParseOptions {
+    /// Leave `body` empty, as [`SipMessage::parse_headers_only`] does.
+    pub headers_only: bool,
+    /// Reject messages that parse but break RFC 3261 syntax, such as a
+    /// status code that is not three digits in 100-699, instead of keeping
+    /// them for [`crate::validate`] to flag.
+    pub strict: bool,
+}
 
 impl SipMessage {
     pub fn parse(&self) -> Result<ParsedSipMessage, ParseError> {
-        parse_sip_message(self)
+        parse_sip_message(self, ParseOptions::default())
+    }
+
+    pub fn parse_with(&self, options: ParseOptions) -> Result<ParsedSipMessage, ParseError> {
+        parse_sip_message(self, options)
+    }
+
+    /// Like [`parse`](Self::parse), but a status code outside 100-699 is an
+    /// `InvalidMessage` error.
+    pub fn parse_strict(&self) -> Result<ParsedSipMessage, ParseError> {
+        parse_sip_message(
+            self,
+            ParseOptions {
+                strict: true,
+                ..ParseOptions::default()
+            },
+        )
+    }
+
+    /// Parse the start line and headers but leave `body` empty, skipping the
+    /// body copy. `content_length()` still reports the declared length;
+    /// body accessors see an empty body.
+    pub fn parse_headers_only(&self) -> Result<ParsedSipMessage, ParseError> {
+        parse_sip_message(
+            self,
+            ParseOptions {
+                headers_only: true,
+                ..ParseOptions::default()
+            },
+        )
+    }
+
+    /// True when the content is a SigComp message (RFC 3320), whose first
+    /// byte has its five high bits set: compressed, so not parseable as SIP.
+    pub fn is_sigcomp(&self) -> bool {
+        crate::types::is_sigcomp(&self.content)
+    }
+
+    /// True when the content starts with a status line, as
+    /// [`ParsedSipMessage::is_response`] would report after parsing.
+    pub fn peek_is_response(&self) -> bool {
+        self.content.starts_with(b"SIP/2.0 ")
+    }
+
+    /// The method [`ParsedSipMessage::method`] would report, read from the
+    /// raw content without parsing: the request line's method, or the CSeq
+    /// method of a response. Allocation-free, for filtering before a parse.
+    pub fn peek_method(&self) -> Option<&str> {
+        let content = &self.content[..];
+        let first_line_end = memchr::memchr(b'\n', content)?;
+        if !self.peek_is_response() {
+            let first_space = memchr::memchr(b' ', &content[..first_line_end])?;
+            let method = &content[..first_space];
+            return is_sip_token(method)
+                .then(|| std::str::from_utf8(method).ok())
+                .flatten();
+        }
+
+        let header_start = first_line_end + 1;
+        let headers = match find_header_end(content) {
+            Some((pos, _)) if pos >= header_start => &content[header_start..pos],
+            Some(_) => return None,
+            None => &content[header_start..],
+        };
+        let cseq = raw_header_value(headers, "CSeq")?;
+        let method = cseq
+            .split(|c| c.is_ascii_whitespace())
+            .filter(|token| !token.is_empty())
+            .nth(1)?;
+        std::str::from_utf8(method).ok()
     }
 }
 
+/// Value of the first `name` header in a header block, folding included,
+/// without the trailing line ending. Mirrors `parse_headers_with_spacing`.
+fn raw_header_value<'a>(data: &'a [u8], name: &str) -> Option<&'a [u8]> {
+    let line_len = |from: usize| memchr::memchr(b'\n', &data[from..]).unwrap_or(data.len() - from);
+    let mut pos = 0;
+    while pos < data.len() {
+        let start = pos;
+        let mut end = pos + line_len(pos);
+        pos = end + 1;
+        while pos < data.len() && (data[pos] == b' ' || data[pos] == b'\t') {
+            end = pos + line_len(pos);
+            pos = end + 1;
+        }
+        let line = &data[start..end];
+        let line = line.strip_suffix(b"\r").unwrap_or(line);
+        if let Some(colon) = memchr::memchr(b':', line) {
+            if line[..colon].eq_ignore_ascii_case(name.as_bytes()) {
+                return Some(&line[colon + 1..]);
+            }
+        }
+    }
+    None
+}
+
 pub struct ParsedMessageIterator<R> {
     inner: MessageIterator<R>,
+    options: ParseOptions,
 }
 
 impl<R: std::io::Read> ParsedMessageIterator<R> {
     pub fn new(reader: R) -> Self {
         ParsedMessageIterator {
             inner: MessageIterator::new(reader),
+            options: ParseOptions::default(),
+        }
+    }
+
+    /// Parse with [`SipMessage::parse_headers_only`], leaving every body
+    /// empty. For header-based filtering of dumps with large bodies.
+    pub fn headers_only(mut self) -> Self {
+        self.options.headers_only = true;
+        self
+    }
+
+    /// Parse with [`SipMessage::parse_strict`], yielding an error for
+    /// messages with a status code outside 100-699.
+    pub fn strict(mut self) -> Self {
+        self.options.strict = true;
+        self
+    }
+
+    pub fn with_options(mut self, options: ParseOptions) -> Self {
+        self.options = options;
+        self
+    }
+
+    /// Also yield the `SipMessage` each message was parsed from, for callers
+    /// that need the reassembled bytes or frame count without parsing twice.
+    pub fn with_raw(self) -> RawParsedMessageIterator<R> {
+        RawParsedMessageIterator {
+            inner: self.inner,
+            options: self.options,
+        }
+    }
+}
+
+/// Parse messages from a configured `MessageIterator`, e.g. one with a
+/// non-default `with_max_message_size`.
+impl<R: std::io::Read> From<MessageIterator<R>> for ParsedMessageIterator<R> {
+    fn from(inner: MessageIterator<R>) -> Self {
+        ParsedMessageIterator {
+            inner,
+            options: ParseOptions::default(),
         }
     }
 }
@@ -36,10 +182,39 @@ impl<R: std::io::Read> Iterator for ParsedMessageIterator<R> {
             Ok(m) => m,
             Err(e) => return Some(Err(e)),
         };
-        Some(msg.parse())
+        Some(parse_sip_message(&msg, self.options))
+    }
+}
+
+impl<R: std::io::Read> FusedIterator for ParsedMessageIterator<R> {}
+
+/// Iterator over `(SipMessage, ParsedSipMessage)` pairs, created by
+/// [`ParsedMessageIterator::with_raw`].
+pub struct RawParsedMessageIterator<R> {
+    inner: MessageIterator<R>,
+    options: ParseOptions,
+}
+
+impl<R: std::io::Read> Iterator for RawParsedMessageIterator<R> {
+    type Item = Result<(SipMessage, ParsedSipMessage), ParseError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let msg = match self.inner.next()? {
+            Ok(m) => m,
+            Err(e) => return Some(Err(e)),
+        };
+        Some(parse_sip_message(&msg, self.options).map(|parsed| (msg, parsed)))
     }
 }
 
+impl<R: std::io::Read> FusedIterator for RawParsedMessageIterator<R> {}
+
+/// Parse every message of a dump held in memory, stopping at the first error.
+pub fn parse_all(data: &[u8]) -> Result<Vec<ParsedSipMessage>, ParseError> {
+    let messages = MessageIterator::from(FrameIterator::from_slice(data));
+    ParsedMessageIterator::from(messages).collect()
+}
+
 fn content_preview(content: &[u8], max_len: usize) -> String {
     use std::fmt::Write;
     let len = content.len().min(max_len);
@@ -63,56 +238,79 @@ fn content_preview(content: &[u8], max_len: usize) -> String {
     out
 }
 
-fn parse_sip_message(msg: &SipMessage) -> Result<ParsedSipMessage, ParseError> {
+fn parse_sip_message(
+    msg: &SipMessage,
+    options: ParseOptions,
+) -> Result<ParsedSipMessage, ParseError> {
     let content = &msg.content;
 
-    parse_sip_content(msg, content).map_err(|e| {
-        let reason = match e {
-            ParseError::InvalidMessage(reason) => reason,
+    parse_sip_content(msg, content, options).map_err(|e| {
+        let (reason, offset) = match e {
+            ParseError::InvalidMessage { reason, offset } => (reason, offset),
             other => return other,
         };
         let preview = content_preview(content, 200);
-        ParseError::InvalidMessage(format!(
-            "{} {}/{} at {} ({} frames, {} bytes): {reason}\n  {preview}",
-            msg.direction,
-            msg.transport,
-            msg.address,
-            msg.timestamp,
-            msg.frame_count,
-            content.len(),
-        ))
+        ParseError::InvalidMessage {
+            reason: format!(
+                "{} {}/{} at {} ({} frames, {} bytes): {reason}\n  {preview}",
+                msg.direction,
+                msg.transport,
+                msg.address,
+                msg.timestamp,
+                msg.frame_count,
+                content.len(),
+            ),
+            offset,
+        }
     })
 }
 
-fn parse_sip_content(msg: &SipMessage, content: &[u8]) -> Result<ParsedSipMessage, ParseError> {
-    // Find end of first line
-    let first_line_end = CRLF
-        .find(content)
-        .ok_or_else(|| ParseError::InvalidMessage("no CRLF found".into()))?;
-    let first_line = &content[..first_line_end];
+fn parse_sip_content(
+    msg: &SipMessage,
+    content: &[u8],
+    options: ParseOptions,
+) -> Result<ParsedSipMessage, ParseError> {
+    if msg.is_sigcomp() {
+        return Err(invalid("sigcomp compressed message, not decoded", Some(0)));
+    }
+
+    // Find end of first line; bare LF endings are accepted as well as CRLF
+    let first_line_end =
+        memchr::memchr(b'\n', content).ok_or_else(|| invalid("no CRLF found", None))?;
+    let first_line = content[..first_line_end]
+        .strip_suffix(b"\r")
+        .unwrap_or(&content[..first_line_end]);
 
-    let message_type = parse_first_line(first_line)?;
+    let message_type = parse_first_line(first_line, options.strict)?;
 
     // Find end of headers
-    let header_end = CRLFCRLF.find(content);
-    let (headers, body) = match header_end {
-        Some(pos) if pos > first_line_end + 1 => {
-            let header_bytes = &content[first_line_end + 2..pos];
-            let body = &content[pos + 4..];
-            (header_bytes, body)
-        }
-        Some(pos) => {
-            let body = &content[pos + 4..];
-            (&[][..], body)
+    let header_start = first_line_end + 1;
+    let (headers, body) = match find_header_end(content) {
+        Some((pos, separator_len)) if pos >= header_start => {
+            (&content[header_start..pos], &content[pos + separator_len..])
         }
+        Some((pos, separator_len)) => (&[][..], &content[pos + separator_len..]),
         None => {
             // No blank line — entire content after first line is headers, no body
-            let header_bytes = &content[first_line_end + 2..];
-            (header_bytes, &[][..])
+            (&content[header_start..], &[][..])
+        }
+    };
+
+    // UDP datagrams are not trimmed to Content-Length the way TCP
+    // reassembly is, and may carry padding past the declared body
+    let body = match find_content_length(content) {
+        Some(cl) if cl < body.len() => {
+            debug!(
+                dropped_bytes = body.len() - cl,
+                address = %msg.address,
+                "dropped bytes past Content-Length"
+            );
+            &body[..cl]
         }
+        _ => body,
     };
 
-    let headers = parse_headers(headers);
+    let (headers, header_spacing) = parse_headers_with_spacing(headers);
 
     Ok(ParsedSipMessage {
         direction: msg.direction,
@@ -121,29 +319,41 @@ fn parse_sip_content(msg: &SipMessage, content: &[u8]) -> Result<ParsedSipMessag
         timestamp: msg.timestamp,
         message_type,
         headers,
-        body: body.to_vec(),
+        header_spacing,
+        body: if options.headers_only {
+            Vec::new()
+        } else {
+            body.to_vec()
+        },
         frame_count: msg.frame_count,
     })
 }
 
-fn parse_first_line(line: &[u8]) -> Result<SipMessageType, ParseError> {
+fn parse_first_line(line: &[u8], strict: bool) -> Result<SipMessageType, ParseError> {
     if line.starts_with(b"SIP/2.0 ") {
-        return parse_status_line(line);
+        return parse_status_line(line, strict);
     }
     parse_request_line(line)
 }
 
-fn parse_status_line(line: &[u8]) -> Result<SipMessageType, ParseError> {
+fn parse_status_line(line: &[u8], strict: bool) -> Result<SipMessageType, ParseError> {
     // SIP/2.0 <code> <reason>
     let after_version = &line[8..]; // skip "SIP/2.0 "
 
     let space = memchr::memchr(b' ', after_version)
-        .ok_or_else(|| ParseError::InvalidMessage("no space after status code".into()))?;
+        .ok_or_else(|| invalid("no space after status code", Some(line.len())))?;
     let code_bytes = &after_version[..space];
     let code: u16 = std::str::from_utf8(code_bytes)
-        .map_err(|_| ParseError::InvalidMessage("non-UTF-8 status code".into()))?
+        .map_err(|_| invalid("non-UTF-8 status code", Some(8)))?
         .parse()
-        .map_err(|_| ParseError::InvalidMessage("invalid status code".into()))?;
+        .map_err(|_| invalid("invalid status code", Some(8)))?;
+    // RFC 3261 25.1: Status-Code is exactly three digits; 1xx-6xx are defined
+    if code_bytes.len() != 3 || !(100..=699).contains(&code) {
+        if strict {
+            return Err(invalid("status code not three digits in 100-699", Some(8)));
+        }
+        debug!(code, "status code outside 100-699");
+    }
 
     let reason = &after_version[space + 1..];
     let reason = bytes_to_string(reason);
@@ -160,25 +370,28 @@ fn is_sip_token(b: &[u8]) -> bool {
 fn parse_request_line(line: &[u8]) -> Result<SipMessageType, ParseError> {
     // <METHOD> <URI> SIP/2.0
     let first_space = memchr::memchr(b' ', line)
-        .ok_or_else(|| ParseError::InvalidMessage("no space in request line".into()))?;
+        .ok_or_else(|| invalid("no space in request line", Some(line.len())))?;
     let method = &line[..first_space];
 
     if !is_sip_token(method) {
-        return Err(ParseError::InvalidMessage(format!(
-            "invalid SIP method: {:?}",
-            String::from_utf8_lossy(method)
-        )));
+        return Err(invalid(
+            format!("invalid SIP method: {:?}", String::from_utf8_lossy(method)),
+            Some(0),
+        ));
     }
     let rest = &line[first_space + 1..];
 
     let last_space = memchr::memrchr(b' ', rest)
-        .ok_or_else(|| ParseError::InvalidMessage("no SIP version in request line".into()))?;
+        .ok_or_else(|| invalid("no SIP version in request line", Some(line.len())))?;
     let version = &rest[last_space + 1..];
     if version != b"SIP/2.0" {
-        return Err(ParseError::InvalidMessage(format!(
-            "expected SIP/2.0, got {:?}",
-            String::from_utf8_lossy(version)
-        )));
+        return Err(invalid(
+            format!(
+                "expected SIP/2.0, got {:?}",
+                String::from_utf8_lossy(version)
+            ),
+            Some(first_space + 1 + last_space + 1),
+        ));
     }
     let uri = &rest[..last_space];
 
@@ -188,6 +401,13 @@ fn parse_request_line(line: &[u8]) -> Result<SipMessageType, ParseError> {
     Ok(SipMessageType::Request { method, uri })
 }
 
+fn invalid(reason: impl Into<String>, offset: Option<usize>) -> ParseError {
+    ParseError::InvalidMessage {
+        reason: reason.into(),
+        offset,
+    }
+}
+
 fn bytes_to_string(b: &[u8]) -> String {
     match std::str::from_utf8(b) {
         Ok(s) => s.to_owned(),
@@ -195,25 +415,50 @@ fn bytes_to_string(b: &[u8]) -> String {
     }
 }
 
-fn parse_headers(data: &[u8]) -> Vec<(String, String)> {
+/// Parse a block of `Name: value` lines, as found between a start line and
+/// the blank line before a body. Folded continuation lines are joined to
+/// their header, lines without a colon are skipped, and names keep their
+/// original case.
+///
+/// ```
+/// use freeswitch_sofia_trace_parser::sip::parse_headers;
+///
+/// let headers = parse_headers(b"Call-ID: abc@host\r\nSubject: long\r\n  folded\r\nl: 0\r\n");
+/// assert_eq!(headers.len(), 3);
+/// assert_eq!(headers[0], ("Call-ID".to_string(), "abc@host".to_string()));
+/// assert_eq!(headers[1].0, "Subject");
+/// assert!(headers[1].1.ends_with("folded"));
+/// assert_eq!(headers[2], ("l".to_string(), "0".to_string()));
+/// ```
+pub fn parse_headers(data: &[u8]) -> Vec<(String, String)> {
+    parse_headers_with_spacing(data).0
+}
+
+/// Headers, plus the whitespace after each colon when any header deviates
+/// from a single space.
+fn parse_headers_with_spacing(data: &[u8]) -> (Vec<(String, String)>, Option<Vec<String>>) {
     let mut headers = Vec::new();
+    let mut spacing = Vec::new();
     if data.is_empty() {
-        return headers;
+        return (headers, None);
     }
 
+    // Lines end in \n; a preceding \r is stripped so LF-only senders parse too
+    let line_len = |from: usize| memchr::memchr(b'\n', &data[from..]).unwrap_or(data.len() - from);
     let mut pos = 0;
     while pos < data.len() {
-        let line_end = CRLF.find(&data[pos..]).unwrap_or(data.len() - pos);
+        let line_end = line_len(pos);
         let mut line = &data[pos..pos + line_end];
-        pos += line_end + 2; // skip \r\n
+        pos += line_end + 1; // skip \n
 
         // Handle header folding (continuation lines start with SP or HT)
         while pos < data.len() && (data[pos] == b' ' || data[pos] == b'\t') {
-            let next_end = CRLF.find(&data[pos..]).unwrap_or(data.len() - pos);
+            let next_end = line_len(pos);
             // Extend line to include continuation
             line = &data[line.as_ptr() as usize - data.as_ptr() as usize..pos + next_end];
-            pos += next_end + 2;
+            pos += next_end + 1;
         }
+        let line = line.strip_suffix(b"\r").unwrap_or(line);
 
         if line.is_empty() {
             continue;
@@ -221,16 +466,15 @@ fn parse_headers(data: &[u8]) -> Vec<(String, String)> {
 
         if let Some(colon) = memchr::memchr(b':', line) {
             let name = &line[..colon];
-            let value = if colon + 1 < line.len() {
-                trim_header_value(&line[colon + 1..])
-            } else {
-                &[]
-            };
+            let raw = &line[colon + 1..];
+            let value = trim_header_value(raw);
             headers.push((bytes_to_string(name), bytes_to_string(value)));
+            spacing.push(bytes_to_string(&raw[..raw.len() - value.len()]));
         }
     }
 
-    headers
+    let spacing = spacing.iter().any(|s| s != " ").then_some(spacing);
+    (headers, spacing)
 }
 
 fn trim_header_value(b: &[u8]) -> &[u8] {
@@ -243,11 +487,23 @@ fn trim_header_value(b: &[u8]) -> &[u8] {
 
 impl ParsedSipMessage {
     pub fn is_multipart(&self) -> bool {
-        self.content_type()
-            .map(|ct| ct.to_ascii_lowercase().starts_with("multipart/"))
+        self.content_type_mime()
+            .map(|mime| mime.to_ascii_lowercase().starts_with("multipart/"))
             .unwrap_or(false)
     }
 
+    /// Media type of Content-Type without its parameters, e.g. `application/sdp`.
+    pub fn content_type_mime(&self) -> Option<&str> {
+        let ct = self.content_type()?;
+        Some(ct.split_once(';').map_or(ct, |(mime, _)| mime).trim())
+    }
+
+    /// A Content-Type parameter such as `charset`, `boundary` or the
+    /// emergency-call `msgtype`; quotes around the value are removed.
+    pub fn content_type_param(&self, name: &str) -> Option<&str> {
+        media_type_param(self.content_type()?, name)
+    }
+
     pub fn multipart_boundary(&self) -> Option<&str> {
         let ct = self.content_type()?;
         extract_boundary(ct)
@@ -257,25 +513,391 @@ impl ParsedSipMessage {
         let boundary = self.multipart_boundary()?;
         Some(parse_multipart_body(&self.body, boundary))
     }
+
+    pub fn from_addr(&self) -> Option<NameAddr> {
+        self.header_value("From")
+            .or_else(|| self.header_value("f"))
+            .and_then(NameAddr::parse)
+    }
+
+    pub fn to_addr(&self) -> Option<NameAddr> {
+        self.header_value("To")
+            .or_else(|| self.header_value("t"))
+            .and_then(NameAddr::parse)
+    }
+
+    /// First P-Asserted-Identity; see `p_asserted_identities()` for all of them.
+    pub fn p_asserted_identity(&self) -> Option<NameAddr> {
+        self.p_asserted_identities().into_iter().next()
+    }
+
+    /// All P-Asserted-Identity values, across repeated headers and
+    /// comma-separated lists (RFC 3325 allows a SIP and a tel URI).
+    pub fn p_asserted_identities(&self) -> Vec<NameAddr> {
+        self.name_addr_list("P-Asserted-Identity")
+    }
+
+    /// Record-Route set in header order, across repeated headers and
+    /// comma-separated lists.
+    pub fn record_routes(&self) -> Vec<NameAddr> {
+        self.name_addr_list("Record-Route")
+    }
+
+    /// Route set in header order, across repeated headers and
+    /// comma-separated lists.
+    pub fn routes(&self) -> Vec<NameAddr> {
+        self.name_addr_list("Route")
+    }
+
+    fn name_addr_list(&self, name: &str) -> Vec<NameAddr> {
+        self.headers
+            .iter()
+            .filter(|(k, _)| k.eq_ignore_ascii_case(name))
+            .flat_map(|(_, v)| split_header_list(v))
+            .filter_map(NameAddr::parse)
+            .collect()
+    }
+
+    /// Contact header values, across repeated headers and comma-separated
+    /// lists. Empty for the `Contact: *` wildcard, see `is_contact_wildcard()`.
+    pub fn contacts(&self) -> Vec<NameAddr> {
+        self.contact_values()
+            .filter(|v| v.trim() != "*")
+            .filter_map(NameAddr::parse)
+            .collect()
+    }
+
+    /// True for `Contact: *`, which a REGISTER uses to remove all bindings.
+    pub fn is_contact_wildcard(&self) -> bool {
+        self.contact_values().any(|v| v.trim() == "*")
+    }
+
+    /// Registration lifetime in seconds: the Expires header, or failing that
+    /// the `expires` parameter of the first Contact.
+    pub fn expires(&self) -> Option<u32> {
+        if let Some(value) = self.header_value("Expires") {
+            return value.trim().parse().ok();
+        }
+        self.contact_values()
+            .find_map(|v| header_param(v, "expires"))
+            .and_then(|value| value.parse().ok())
+    }
+
+    /// Via header values, topmost first, across repeated headers and
+    /// comma-separated lists.
+    pub fn vias(&self) -> Vec<Via> {
+        self.headers
+            .iter()
+            .filter(|(k, _)| header_name_matches(k, "Via"))
+            .flat_map(|(_, v)| split_header_list(v))
+            .filter_map(Via::parse)
+            .collect()
+    }
+
+    /// Reason header values (RFC 3326), across repeated headers and
+    /// comma-separated lists; a BYE or CANCEL may carry one for SIP and one
+    /// for Q.850.
+    pub fn reason_headers(&self) -> Vec<ReasonInfo> {
+        self.headers
+            .iter()
+            .filter(|(k, _)| k.eq_ignore_ascii_case("Reason"))
+            .flat_map(|(_, v)| split_header_list(v))
+            .filter_map(ReasonInfo::parse)
+            .collect()
+    }
+
+    /// First Reason header value; see `reason_headers()` for all of them.
+    pub fn reason_header(&self) -> Option<ReasonInfo> {
+        self.reason_headers().into_iter().next()
+    }
+
+    /// `cause` of the Q.850 Reason: the ISDN hangup cause, e.g. 16 for
+    /// normal clearing.
+    pub fn q850_cause(&self) -> Option<u16> {
+        self.reason_headers()
+            .into_iter()
+            .find(|r| r.protocol.eq_ignore_ascii_case("Q.850"))
+            .and_then(|r| r.cause)
+    }
+
+    /// Challenges from WWW-Authenticate and Proxy-Authenticate, one per header.
+    pub fn auth_challenges(&self) -> Vec<AuthParams> {
+        self.auth_headers(&["WWW-Authenticate", "Proxy-Authenticate"])
+    }
+
+    /// Credentials from Authorization and Proxy-Authorization, one per header.
+    pub fn auth_credentials(&self) -> Vec<AuthParams> {
+        self.auth_headers(&["Authorization", "Proxy-Authorization"])
+    }
+
+    fn auth_headers(&self, names: &[&str]) -> Vec<AuthParams> {
+        self.headers
+            .iter()
+            .filter(|(k, _)| names.iter().any(|name| k.eq_ignore_ascii_case(name)))
+            .filter_map(|(_, v)| AuthParams::parse(v))
+            .collect()
+    }
+
+    /// `branch` parameter of the topmost Via: the transaction id. Reads only
+    /// the first Via value, without parsing the rest of the stack.
+    pub fn branch(&self) -> Option<&str> {
+        let via = self
+            .headers
+            .iter()
+            .find(|(k, _)| header_name_matches(k, "Via"))
+            .map(|(_, v)| v.as_str())?;
+        via_branch(via.split(',').next()?)
+    }
+
+    /// True when the Via stack looks like a routing loop: a sent-by host
+    /// appears more than `threshold` times, or a branch is repeated.
+    pub fn via_loop_suspect(&self, threshold: usize) -> bool {
+        let vias = self.vias();
+        let mut hosts: HashMap<String, usize> = HashMap::new();
+        let mut branches = HashSet::new();
+        for via in &vias {
+            let count = hosts.entry(via.host().to_ascii_lowercase()).or_default();
+            *count += 1;
+            if *count > threshold {
+                return true;
+            }
+            if let Some(branch) = &via.branch {
+                if !branches.insert(branch.as_str()) {
+                    return true;
+                }
+            }
+        }
+        false
+    }
+
+    fn contact_values(&self) -> impl Iterator<Item = &str> {
+        self.headers
+            .iter()
+            .filter(|(k, _)| header_name_matches(k, "Contact"))
+            .flat_map(|(_, v)| split_header_list(v))
+    }
 }
 
-fn extract_boundary(content_type: &str) -> Option<&str> {
-    let lower = content_type.to_ascii_lowercase();
-    let idx = lower.find("boundary=")?;
-    let after = &content_type[idx + 9..];
-
-    if let Some(after_quote) = after.strip_prefix('"') {
-        let end_quote = after_quote.find('"')?;
-        Some(&after_quote[..end_quote])
-    } else {
-        let end = after.find(';').unwrap_or(after.len());
-        let boundary = after[..end].trim();
-        if boundary.is_empty() {
-            None
+/// A header parameter (`;name=value` after the URI) of a single list item.
+/// Parameters inside `<...>` belong to the URI and are not considered.
+fn header_param<'a>(item: &'a str, name: &str) -> Option<&'a str> {
+    let params = match item.rfind('>') {
+        Some(gt) => &item[gt + 1..],
+        None => item.split_once(';').map_or("", |(_, rest)| rest),
+    };
+    params.split(';').find_map(|param| {
+        let (key, value) = param.split_once('=')?;
+        key.trim()
+            .eq_ignore_ascii_case(name)
+            .then(|| value.trim().trim_matches('"'))
+    })
+}
+
+impl NameAddr {
+    /// Parse `"Display" <uri>;params`, `Display <uri>` or a bare `uri;params`.
+    ///
+    /// Header parameters (`;tag=`) are not part of the URI and are dropped.
+    pub fn parse(value: &str) -> Option<NameAddr> {
+        let value = value.trim();
+        let (display_name, rest) = if let Some(quoted) = value.strip_prefix('"') {
+            let (name, len) = unquote(quoted)?;
+            (Some(name), quoted[len..].trim_start())
         } else {
-            Some(boundary)
+            match value.find('<') {
+                Some(lt) => {
+                    let name = value[..lt].trim();
+                    ((!name.is_empty()).then(|| name.to_string()), &value[lt..])
+                }
+                None => (None, value),
+            }
+        };
+
+        let uri = match rest.strip_prefix('<') {
+            Some(inner) => &inner[..inner.find('>')?],
+            None => {
+                if display_name.is_some() && rest.is_empty() {
+                    return None;
+                }
+                rest.split(';').next().unwrap_or(rest)
+            }
+        };
+        let uri = uri.trim();
+        if uri.is_empty() {
+            return None;
+        }
+        Some(NameAddr {
+            display_name,
+            uri: uri.to_string(),
+        })
+    }
+}
+
+impl Via {
+    /// Parse `SIP/2.0/UDP host[:port];params`. Only the `branch` parameter
+    /// is kept.
+    pub fn parse(value: &str) -> Option<Via> {
+        let (protocol, rest) = value.trim().split_once(|c: char| c.is_ascii_whitespace())?;
+        let mut protocol = protocol.split('/').map(str::trim);
+        let (_name, _version, transport) = (protocol.next()?, protocol.next()?, protocol.next()?);
+        let sent_by = rest.split(';').next()?.trim();
+        if transport.is_empty() || sent_by.is_empty() {
+            return None;
+        }
+        Some(Via {
+            transport: transport.to_string(),
+            sent_by: sent_by.to_string(),
+            branch: via_branch(value).map(str::to_string),
+        })
+    }
+
+    /// `sent_by` without its port; IPv6 references keep their brackets.
+    pub fn host(&self) -> &str {
+        let sent_by = self.sent_by.as_str();
+        if sent_by.starts_with('[') {
+            return sent_by.find(']').map_or(sent_by, |end| &sent_by[..=end]);
+        }
+        sent_by
+            .split_once(':')
+            .map_or(sent_by, |(host, _)| host)
+            .trim()
+    }
+}
+
+impl ReasonInfo {
+    /// Parse `protocol;cause=N;text="..."`; other parameters are ignored.
+    pub fn parse(value: &str) -> Option<ReasonInfo> {
+        let protocol = value.split(';').next()?.trim();
+        if protocol.is_empty() {
+            return None;
+        }
+        Some(ReasonInfo {
+            protocol: protocol.to_string(),
+            cause: media_type_param(value, "cause").and_then(|c| c.trim().parse().ok()),
+            text: media_type_param(value, "text").map(str::to_string),
+        })
+    }
+}
+
+impl AuthParams {
+    /// Parse `scheme name=value, name="quoted value", ...`. A header carrying
+    /// several challenges is read as one, its parameters merged.
+    pub fn parse(value: &str) -> Option<AuthParams> {
+        let value = value.trim();
+        let (scheme, rest) = value
+            .split_once(|c: char| c.is_ascii_whitespace())
+            .unwrap_or((value, ""));
+        if scheme.is_empty() {
+            return None;
+        }
+        let params = split_header_list(rest)
+            .into_iter()
+            .filter_map(|param| {
+                let (name, value) = param.split_once('=')?;
+                let value = value.trim();
+                let value = match value.strip_prefix('"') {
+                    Some(quoted) => unquote(quoted)?.0,
+                    None => value.to_string(),
+                };
+                Some((name.trim().to_string(), value))
+            })
+            .collect();
+        Some(AuthParams {
+            scheme: scheme.to_string(),
+            params,
+        })
+    }
+}
+
+/// `branch` parameter of a single Via value.
+fn via_branch(value: &str) -> Option<&str> {
+    value.split(';').skip(1).find_map(|param| {
+        let (name, value) = param.split_once('=')?;
+        name.trim()
+            .eq_ignore_ascii_case("branch")
+            .then(|| value.trim())
+    })
+}
+
+/// Unescape a quoted-string body (after the opening quote).
+/// Returns the text and the byte length consumed including the closing quote.
+fn unquote(s: &str) -> Option<(String, usize)> {
+    let mut out = String::new();
+    let mut chars = s.char_indices();
+    while let Some((i, c)) = chars.next() {
+        match c {
+            '"' => return Some((out, i + 1)),
+            '\\' => out.push(chars.next()?.1),
+            _ => out.push(c),
+        }
+    }
+    None
+}
+
+/// Split a comma-separated header list, ignoring commas inside quoted
+/// strings and angle-bracketed URIs.
+fn split_header_list(value: &str) -> Vec<&str> {
+    let mut items = Vec::new();
+    let mut start = 0;
+    let mut in_quotes = false;
+    let mut in_angle = false;
+    let mut escaped = false;
+    for (i, c) in value.char_indices() {
+        if escaped {
+            escaped = false;
+            continue;
+        }
+        match c {
+            '\\' if in_quotes => escaped = true,
+            '"' if !in_angle => in_quotes = !in_quotes,
+            '<' if !in_quotes => in_angle = true,
+            '>' if !in_quotes => in_angle = false,
+            ',' if !in_quotes && !in_angle => {
+                items.push(value[start..i].trim());
+                start = i + 1;
+            }
+            _ => {}
         }
     }
+    items.push(value[start..].trim());
+    items.retain(|item| !item.is_empty());
+    items
+}
+
+/// Value of parameter `name` (case-insensitive) of a media type like
+/// `multipart/mixed; boundary="a;b"`, or of a header value with the same
+/// `token;param=value` shape such as Reason. A quoted value may contain `;`.
+fn media_type_param<'a>(content_type: &'a str, name: &str) -> Option<&'a str> {
+    let mut rest = content_type.split_once(';')?.1;
+    loop {
+        let (key, after_key) = match rest.find(['=', ';']) {
+            Some(i) if rest.as_bytes()[i] == b'=' => (&rest[..i], &rest[i + 1..]),
+            Some(i) => {
+                rest = &rest[i + 1..];
+                continue;
+            }
+            None => return None,
+        };
+        let after_key = after_key.trim_start();
+        let (value, next) = match after_key.strip_prefix('"') {
+            Some(quoted) => {
+                let end = quoted.find('"')?;
+                let next = quoted[end + 1..].find(';').map(|i| &quoted[end + 2 + i..]);
+                (&quoted[..end], next)
+            }
+            None => match after_key.split_once(';') {
+                Some((value, next)) => (value.trim(), Some(next)),
+                None => (after_key.trim(), None),
+            },
+        };
+        if key.trim().eq_ignore_ascii_case(name) {
+            return Some(value);
+        }
+        rest = next?;
+    }
+}
+
+fn extract_boundary(content_type: &str) -> Option<&str> {
+    media_type_param(content_type, "boundary").filter(|b| !b.is_empty())
 }
 
 fn parse_multipart_body(body: &[u8], boundary: &str) -> Vec<MimePart> {
@@ -307,7 +929,7 @@ fn parse_multipart_body(body: &[u8], boundary: &str) -> Vec<MimePart> {
             end -= 2;
         }
 
-        parts.push(parse_mime_part(&body[pos..end]));
+        parts.push(MimePart::parse(&body[pos..end]));
 
         // Move past delimiter
         pos = pos + next + open_bytes.len();
@@ -326,31 +948,36 @@ fn parse_multipart_body(body: &[u8], boundary: &str) -> Vec<MimePart> {
     parts
 }
 
-fn parse_mime_part(data: &[u8]) -> MimePart {
-    match memmem::find(data, b"\r\n\r\n") {
-        Some(pos) => {
-            let header_bytes = &data[..pos];
-            let body = &data[pos + 4..];
-            let headers = parse_headers(header_bytes);
-            MimePart {
-                headers,
-                body: body.to_vec(),
-            }
-        }
-        None => {
-            // Could be headers-only or body-only.
-            // If first line has a colon, treat as headers with no body.
-            let first_line_end = memmem::find(data, b"\r\n").unwrap_or(data.len());
-            if memchr::memchr(b':', &data[..first_line_end]).is_some() {
-                let headers = parse_headers(data);
+impl MimePart {
+    /// Parse one MIME entity: headers, a blank line, then the body. Without
+    /// a blank line the data is taken as headers only when its first line
+    /// has a colon, and as a bare body otherwise.
+    pub fn parse(data: &[u8]) -> MimePart {
+        match memmem::find(data, b"\r\n\r\n") {
+            Some(pos) => {
+                let header_bytes = &data[..pos];
+                let body = &data[pos + 4..];
+                let headers = parse_headers(header_bytes);
                 MimePart {
                     headers,
-                    body: Vec::new(),
+                    body: body.to_vec(),
                 }
-            } else {
-                MimePart {
-                    headers: Vec::new(),
-                    body: data.to_vec(),
+            }
+            None => {
+                // Could be headers-only or body-only.
+                // If first line has a colon, treat as headers with no body.
+                let first_line_end = memmem::find(data, b"\r\n").unwrap_or(data.len());
+                if memchr::memchr(b':', &data[..first_line_end]).is_some() {
+                    let headers = parse_headers(data);
+                    MimePart {
+                        headers,
+                        body: Vec::new(),
+                    }
+                } else {
+                    MimePart {
+                        headers: Vec::new(),
+                        body: data.to_vec(),
+                    }
                 }
             }
         }
@@ -373,6 +1000,12 @@ mod tests {
                 sec: 0,
                 usec: 0,
             },
+            last_timestamp: Timestamp::TimeOnly {
+                hour: 12,
+                min: 0,
+                sec: 0,
+                usec: 0,
+            },
             content: content.to_vec(),
             frame_count: 1,
         }
@@ -488,6 +1121,28 @@ mod tests {
         assert_eq!(parsed.body, body);
     }
 
+    #[test]
+    fn udp_padding_past_content_length_dropped() {
+        let padded = b"MESSAGE sip:user@host SIP/2.0\r\n\
+            Content-Length: 5\r\n\
+            \r\n\
+            hello\0\0\0\r\n";
+        let parsed = make_sip_message(padded).parse().unwrap();
+        assert_eq!(parsed.body, b"hello");
+
+        let compact = b"MESSAGE sip:user@host SIP/2.0\r\nl: 2\r\n\r\nhi there";
+        assert_eq!(make_sip_message(compact).parse().unwrap().body, b"hi");
+
+        // Without Content-Length the whole datagram remains the body
+        let undeclared = b"MESSAGE sip:user@host SIP/2.0\r\n\r\nhello\0\0";
+        let parsed = make_sip_message(undeclared).parse().unwrap();
+        assert_eq!(parsed.body, b"hello\0\0");
+
+        // A short body is kept as-is
+        let short = b"MESSAGE sip:user@host SIP/2.0\r\nContent-Length: 10\r\n\r\nhello";
+        assert_eq!(make_sip_message(short).parse().unwrap().body, b"hello");
+    }
+
     #[test]
     fn compact_headers() {
         let content = b"NOTIFY sip:user@host SIP/2.0\r\n\
@@ -569,6 +1224,15 @@ mod tests {
                 sec: 0,
                 usec: 123456,
             },
+            last_timestamp: Timestamp::DateTime {
+                year: 2026,
+                month: 2,
+                day: 12,
+                hour: 10,
+                min: 30,
+                sec: 1,
+                usec: 0,
+            },
             content: content.to_vec(),
             frame_count: 3,
         };
@@ -699,6 +1363,110 @@ mod tests {
         assert!(result.is_err());
     }
 
+    #[test]
+    fn error_offset_malformed_status_line() {
+        let msg = make_sip_message(b"SIP/2.0 2OO OK\r\nContent-Length: 0\r\n\r\n");
+        let err = msg.parse().unwrap_err();
+        assert!(matches!(
+            err,
+            ParseError::InvalidMessage {
+                offset: Some(8),
+                ..
+            }
+        ));
+        let text = err.to_string();
+        assert!(
+            text.starts_with("invalid SIP message at byte 8: "),
+            "{text}"
+        );
+        assert!(text.contains("invalid status code"));
+        assert!(text.contains("SIP/2.0 2OO OK"));
+    }
+
+    #[test]
+    fn error_offset_bad_version() {
+        let err = make_sip_message(b"INVITE sip:a SIP/3.0\r\n\r\n")
+            .parse()
+            .unwrap_err();
+        assert!(matches!(
+            err,
+            ParseError::InvalidMessage {
+                offset: Some(13),
+                ..
+            }
+        ));
+    }
+
+    #[test]
+    fn to_bytes_preserves_header_spacing() {
+        let content = b"INVITE sip:bob@host SIP/2.0\r\n\
+            Via:SIP/2.0/UDP 10.0.0.1;branch=z9hG4bK1\r\n\
+            Call-ID:   spacing@host\r\n\
+            CSeq:\t1 INVITE\r\n\
+            Subject:\r\n\
+            Content-Length: 4\r\n\
+            \r\n\
+            body";
+        let parsed = make_sip_message(content).parse().unwrap();
+        assert_eq!(parsed.call_id(), Some("spacing@host"));
+        assert_eq!(parsed.cseq(), Some("1 INVITE"));
+        assert_eq!(parsed.to_bytes(), content);
+
+        let mut edited = parsed.clone();
+        edited.remove_header("Subject");
+        assert!(edited.header_spacing.is_none());
+        assert!(edited.to_string().contains("\r\nVia: SIP/2.0/UDP"));
+    }
+
+    #[test]
+    fn header_spacing_only_captured_when_irregular() {
+        let content = b"OPTIONS sip:host SIP/2.0\r\nCall-ID: a\r\nCSeq: 1 OPTIONS\r\n\r\n";
+        let parsed = make_sip_message(content).parse().unwrap();
+        assert!(parsed.header_spacing.is_none());
+        assert_eq!(parsed.to_bytes(), content);
+    }
+
+    #[test]
+    fn parse_lf_only_options() {
+        let content = b"OPTIONS sip:user@host SIP/2.0\n\
+            Via: SIP/2.0/UDP 10.0.0.1:5060;branch=z9hG4bK-1\n\
+            Call-ID: lf-only@host\n\
+            CSeq: 1 OPTIONS\n\
+            Content-Length: 0\n\
+            \n";
+        let parsed = make_sip_message(content).parse().unwrap();
+        assert_eq!(
+            parsed.message_type,
+            SipMessageType::Request {
+                method: "OPTIONS".into(),
+                uri: "sip:user@host".into()
+            }
+        );
+        assert_eq!(parsed.headers.len(), 4);
+        assert_eq!(parsed.call_id(), Some("lf-only@host"));
+        assert_eq!(parsed.cseq(), Some("1 OPTIONS"));
+        assert!(parsed.body.is_empty());
+    }
+
+    #[test]
+    fn parse_lf_only_with_body() {
+        let body = b"v=0\r\no=- 1 1 IN IP4 10.0.0.1\r\n";
+        let mut content = format!(
+            "SIP/2.0 200 OK\n\
+             Call-ID: lf-body@host\n\
+             Content-Type: application/sdp\n\
+             Content-Length: {}\n\
+             \n",
+            body.len()
+        )
+        .into_bytes();
+        content.extend_from_slice(body);
+        let parsed = make_sip_message(&content).parse().unwrap();
+        assert_eq!(parsed.status_code(), Some(200));
+        assert_eq!(parsed.content_type(), Some("application/sdp"));
+        assert_eq!(parsed.body, body);
+    }
+
     #[test]
     fn parse_request_rejects_xml_method() {
         let content =
@@ -779,6 +1547,124 @@ mod tests {
         assert_eq!(parsed[0].method(), Some("OPTIONS"));
     }
 
+    #[test]
+    fn parsed_message_iterator_fused() {
+        let data = b"recv 48 bytes from udp/10.0.0.1:5060 at 00:00:00.000000:\n\
+            OPTIONS sip:host SIP/2.0\r\nCall-ID: a\r\nl: 0\r\n\r\n\x0B\n";
+        let mut parsed = ParsedMessageIterator::new(&data[..]);
+        assert_eq!(parsed.next().unwrap().unwrap().call_id(), Some("a"));
+        for _ in 0..3 {
+            assert!(parsed.next().is_none());
+        }
+    }
+
+    #[test]
+    fn headers_only_skips_body() {
+        let data = b"recv 98 bytes from udp/10.0.0.1:5060 at 00:00:00.000000:\n\
+            MESSAGE sip:host SIP/2.0\r\nCall-ID: h1\r\n\
+            Content-Type: text/plain\r\nContent-Length: 11\r\n\r\nhello world\x0B\n";
+
+        let parsed: Vec<ParsedSipMessage> = ParsedMessageIterator::new(&data[..])
+            .headers_only()
+            .collect::<Result<_, _>>()
+            .unwrap();
+        assert_eq!(parsed.len(), 1);
+        assert_eq!(parsed[0].call_id(), Some("h1"));
+        assert_eq!(parsed[0].content_type(), Some("text/plain"));
+        assert!(parsed[0].body.is_empty());
+        assert_eq!(parsed[0].content_length(), Some(11));
+
+        let full = ParsedMessageIterator::new(&data[..])
+            .next()
+            .unwrap()
+            .unwrap();
+        assert_eq!(full.body, b"hello world");
+    }
+
+    #[test]
+    fn sigcomp_message_reported_as_such() {
+        let msg = make_sip_message(b"\xF8\x01\x2a\x9c\x00\x00\x8f\x07\r\nINVITE");
+        assert!(msg.is_sigcomp());
+        match msg.parse() {
+            Err(ParseError::InvalidMessage { reason, offset }) => {
+                assert!(
+                    reason.contains("sigcomp compressed message, not decoded"),
+                    "{reason}"
+                );
+                assert_eq!(offset, Some(0));
+            }
+            other => panic!("expected InvalidMessage, got {other:?}"),
+        }
+        assert!(!make_sip_message(b"INVITE sip:a@host SIP/2.0\r\n\r\n").is_sigcomp());
+    }
+
+    #[test]
+    fn status_code_range_strict() {
+        for (content, code) in [
+            (&b"SIP/2.0 99 Weird\r\nCall-ID: s1\r\n\r\n"[..], 99),
+            (b"SIP/2.0 700 Nope\r\nCall-ID: s2\r\n\r\n", 700),
+            (b"SIP/2.0 1000 Big\r\nCall-ID: s3\r\n\r\n", 1000),
+        ] {
+            let msg = make_sip_message(content);
+            assert_eq!(msg.parse().unwrap().status_code(), Some(code));
+            match msg.parse_strict() {
+                Err(ParseError::InvalidMessage { reason, .. }) => {
+                    assert!(reason.contains("status code not three digits"), "{reason}");
+                }
+                other => panic!("expected InvalidMessage for {code}, got {other:?}"),
+            }
+        }
+
+        let ok = make_sip_message(b"SIP/2.0 200 OK\r\nCall-ID: s4\r\n\r\n");
+        assert_eq!(ok.parse_strict().unwrap().status_code(), Some(200));
+    }
+
+    #[test]
+    fn parsed_message_iterator_with_raw() {
+        let data = b"recv 48 bytes from udp/10.0.0.1:5060 at 00:00:00.000000:\n\
+            OPTIONS sip:host SIP/2.0\r\nCall-ID: a\r\nl: 0\r\n\r\n\x0B\n\
+            sent 21 bytes to tcp/10.0.0.2:5060 at 00:00:00.100000:\n\
+            SIP/2.0 200 OK\r\nCall-\x0B\n\
+            sent 20 bytes to tcp/10.0.0.2:5060 at 00:00:00.100001:\n\
+            ID: b\r\nl: 0\r\n\r\n\x0B\n";
+
+        let pairs: Vec<(SipMessage, ParsedSipMessage)> = ParsedMessageIterator::new(&data[..])
+            .with_raw()
+            .collect::<Result<Vec<_>, _>>()
+            .unwrap();
+
+        assert_eq!(pairs.len(), 2);
+        for (raw, parsed) in &pairs {
+            assert_eq!(raw.parse().unwrap().call_id(), parsed.call_id());
+            assert_eq!(raw.address, parsed.address);
+            assert_eq!(raw.direction, parsed.direction);
+        }
+        assert_eq!(pairs[0].1.call_id(), Some("a"));
+        assert_eq!(pairs[1].1.call_id(), Some("b"));
+        assert_eq!(pairs[1].0.frame_count, 2);
+        assert!(pairs[1]
+            .0
+            .content
+            .starts_with(b"SIP/2.0 200 OK\r\nCall-ID: b"));
+    }
+
+    #[test]
+    fn parse_all_from_slice() {
+        let data = b"recv 48 bytes from udp/10.0.0.1:5060 at 00:00:00.000000:\n\
+            OPTIONS sip:host SIP/2.0\r\nCall-ID: a\r\nl: 0\r\n\r\n\x0B\n\
+            sent 21 bytes to tcp/10.0.0.1:5060 at 00:00:00.100000:\n\
+            SIP/2.0 200 OK\r\nCall-\x0B\n\
+            sent 20 bytes to tcp/10.0.0.1:5060 at 00:00:00.100001:\n\
+            ID: a\r\nl: 0\r\n\r\n\x0B\n";
+        let parsed = parse_all(data).unwrap();
+        assert_eq!(parsed.len(), 2);
+        assert_eq!(parsed[0].method(), Some("OPTIONS"));
+        assert_eq!(parsed[1].status_code(), Some(200));
+        assert_eq!(parsed[1].call_id(), Some("a"));
+        assert_eq!(parsed[1].frame_count, 2);
+        assert!(parse_all(b"").unwrap().is_empty());
+    }
+
     // --- Multipart tests ---
 
     fn make_multipart_invite(boundary: &str, parts: &[(&str, &[u8])]) -> SipMessage {
@@ -1042,6 +1928,29 @@ mod tests {
         );
     }
 
+    #[test]
+    fn content_type_mime_and_params() {
+        let content = b"INVITE sip:bob@host SIP/2.0\r\n\
+            Content-Type: application/sdp; charset=utf-8\r\n\r\n";
+        let parsed = make_sip_message(content).parse().unwrap();
+        assert_eq!(parsed.content_type_mime(), Some("application/sdp"));
+        assert_eq!(parsed.content_type_param("charset"), Some("utf-8"));
+        assert_eq!(parsed.content_type_param("CHARSET"), Some("utf-8"));
+        assert_eq!(parsed.content_type_param("boundary"), None);
+
+        let content = b"MESSAGE sip:psap@host SIP/2.0\r\n\
+            Content-Type: application/vnd.3gpp.sms;msgtype=\"ack;sms\"; charset=\"us-ascii\"\r\n\r\n";
+        let parsed = make_sip_message(content).parse().unwrap();
+        assert_eq!(parsed.content_type_mime(), Some("application/vnd.3gpp.sms"));
+        assert_eq!(parsed.content_type_param("msgtype"), Some("ack;sms"));
+        assert_eq!(parsed.content_type_param("charset"), Some("us-ascii"));
+
+        let content = b"INVITE sip:bob@host SIP/2.0\r\n\r\n";
+        let parsed = make_sip_message(content).parse().unwrap();
+        assert_eq!(parsed.content_type_mime(), None);
+        assert_eq!(parsed.content_type_param("charset"), None);
+    }
+
     #[test]
     fn extract_boundary_missing() {
         assert_eq!(extract_boundary("multipart/mixed"), None);
@@ -1072,4 +1981,311 @@ mod tests {
         assert!(parts[0].headers.is_empty());
         assert_eq!(parts[0].body, raw_body);
     }
+
+    fn name_addr(display_name: Option<&str>, uri: &str) -> NameAddr {
+        NameAddr {
+            display_name: display_name.map(String::from),
+            uri: uri.into(),
+        }
+    }
+
+    #[test]
+    fn name_addr_quoted_display_name() {
+        assert_eq!(
+            NameAddr::parse("\"Alice\" <sip:alice@x>;tag=1"),
+            Some(name_addr(Some("Alice"), "sip:alice@x"))
+        );
+    }
+
+    #[test]
+    fn name_addr_quoted_with_comma_and_escape() {
+        assert_eq!(
+            NameAddr::parse(r#""Smith, \"Bob\"" <sip:bob@x>"#),
+            Some(name_addr(Some("Smith, \"Bob\""), "sip:bob@x"))
+        );
+    }
+
+    #[test]
+    fn name_addr_token_display_name() {
+        assert_eq!(
+            NameAddr::parse("Extension 1583 <sip:1583@pbx>;tag=9"),
+            Some(name_addr(Some("Extension 1583"), "sip:1583@pbx"))
+        );
+    }
+
+    #[test]
+    fn name_addr_angle_brackets_only() {
+        assert_eq!(
+            NameAddr::parse("<sip:user@host;transport=tcp>;tag=abc"),
+            Some(name_addr(None, "sip:user@host;transport=tcp"))
+        );
+    }
+
+    #[test]
+    fn name_addr_bare_uri() {
+        assert_eq!(
+            NameAddr::parse("sip:user@host;tag=abc"),
+            Some(name_addr(None, "sip:user@host"))
+        );
+    }
+
+    #[test]
+    fn name_addr_invalid() {
+        assert_eq!(NameAddr::parse(""), None);
+        assert_eq!(NameAddr::parse("\"unterminated <sip:a@b>"), None);
+        assert_eq!(NameAddr::parse("\"No URI\""), None);
+        assert_eq!(NameAddr::parse("<sip:a@b"), None);
+    }
+
+    #[test]
+    fn register_contacts_and_expires() {
+        let content = b"REGISTER sip:pbx SIP/2.0\r\n\
+            Contact: \"Desk\" <sip:1001@10.0.0.5:5060;transport=udp>;expires=60\r\n\
+            m: <sip:1001@[2001:db8::5]:5060>;q=0.5, sip:1001@10.0.0.6\r\n\
+            Expires: 3600\r\n\
+            Content-Length: 0\r\n\r\n";
+        let parsed = make_sip_message(content).parse().unwrap();
+        assert_eq!(
+            parsed.contacts(),
+            [
+                name_addr(Some("Desk"), "sip:1001@10.0.0.5:5060;transport=udp"),
+                name_addr(None, "sip:1001@[2001:db8::5]:5060"),
+                name_addr(None, "sip:1001@10.0.0.6"),
+            ]
+        );
+        assert!(!parsed.is_contact_wildcard());
+        assert_eq!(parsed.expires(), Some(3600));
+    }
+
+    #[test]
+    fn record_route_set_in_order() {
+        let content = b"SIP/2.0 200 OK\r\n\
+            Record-Route: <sip:p1.example.com;lr>, <sip:p2.example.com;lr>\r\n\
+            Record-Route: <sip:p3.example.com;lr>,<sip:p4.example.com;lr;ftag=x>\r\n\
+            Route: <sip:edge.example.com;lr>\r\n\r\n";
+        let parsed = make_sip_message(content).parse().unwrap();
+        assert_eq!(
+            parsed.record_routes(),
+            [
+                name_addr(None, "sip:p1.example.com;lr"),
+                name_addr(None, "sip:p2.example.com;lr"),
+                name_addr(None, "sip:p3.example.com;lr"),
+                name_addr(None, "sip:p4.example.com;lr;ftag=x"),
+            ]
+        );
+        assert_eq!(
+            parsed.routes(),
+            [name_addr(None, "sip:edge.example.com;lr")]
+        );
+    }
+
+    #[test]
+    fn reason_header_sip_and_q850() {
+        let content = b"BYE sip:bob@host SIP/2.0\r\n\
+            Reason: SIP;cause=200;text=\"Call completed elsewhere\"\r\n\
+            Reason: Q.850;cause=16;text=\"Normal; call clearing\"\r\n\r\n";
+        let parsed = make_sip_message(content).parse().unwrap();
+        assert_eq!(
+            parsed.reason_headers(),
+            [
+                ReasonInfo {
+                    protocol: "SIP".into(),
+                    cause: Some(200),
+                    text: Some("Call completed elsewhere".into()),
+                },
+                ReasonInfo {
+                    protocol: "Q.850".into(),
+                    cause: Some(16),
+                    text: Some("Normal; call clearing".into()),
+                },
+            ]
+        );
+        assert_eq!(parsed.reason_header().unwrap().protocol, "SIP");
+        assert_eq!(parsed.q850_cause(), Some(16));
+
+        let listed = b"CANCEL sip:bob@host SIP/2.0\r\n\
+            Reason: Q.850 ; cause=31, SIP;cause=487\r\n\r\n";
+        let parsed = make_sip_message(listed).parse().unwrap();
+        assert_eq!(parsed.reason_headers().len(), 2);
+        assert_eq!(parsed.q850_cause(), Some(31));
+        assert_eq!(parsed.reason_headers()[0].text, None);
+    }
+
+    #[test]
+    fn auth_digest_challenge_and_credentials() {
+        let content = b"SIP/2.0 401 Unauthorized\r\n\
+            WWW-Authenticate: Digest realm=\"sip.example.com\", \
+            nonce=\"a1b2,c3\", algorithm=MD5, qop=\"auth\"\r\n\r\n";
+        let parsed = make_sip_message(content).parse().unwrap();
+        let challenges = parsed.auth_challenges();
+        assert_eq!(challenges.len(), 1);
+        assert_eq!(challenges[0].scheme, "Digest");
+        assert_eq!(challenges[0].realm(), Some("sip.example.com"));
+        assert_eq!(challenges[0].param("Nonce"), Some("a1b2,c3"));
+        assert_eq!(challenges[0].param("algorithm"), Some("MD5"));
+        assert_eq!(challenges[0].param("qop"), Some("auth"));
+        assert!(parsed.auth_credentials().is_empty());
+
+        let content = b"INVITE sip:bob@host SIP/2.0\r\n\
+            Proxy-Authorization: Digest username=\"alice\", realm=\"sip.example.com\", \
+            uri=\"sip:bob@host\", response=\"6629fae49393a05397450978507c4ef1\"\r\n\r\n";
+        let parsed = make_sip_message(content).parse().unwrap();
+        let credentials = parsed.auth_credentials();
+        assert_eq!(credentials.len(), 1);
+        assert_eq!(credentials[0].param("username"), Some("alice"));
+        assert_eq!(
+            credentials[0].param("response"),
+            Some("6629fae49393a05397450978507c4ef1")
+        );
+        assert!(parsed.auth_challenges().is_empty());
+    }
+
+    #[test]
+    fn expires_from_contact_param() {
+        let content = b"SIP/2.0 200 OK\r\n\
+            Contact: <sip:1001@10.0.0.5;expires=5>;expires=120\r\n\r\n";
+        let parsed = make_sip_message(content).parse().unwrap();
+        assert_eq!(parsed.expires(), Some(120));
+        let content = b"SIP/2.0 200 OK\r\nContact: sip:1001@10.0.0.5\r\n\r\n";
+        assert_eq!(make_sip_message(content).parse().unwrap().expires(), None);
+    }
+
+    #[test]
+    fn ack_method_from_request_line() {
+        for cseq in ["1 ACK", "1 INVITE"] {
+            let content = format!("ACK sip:bob@host SIP/2.0\r\nCSeq: {cseq}\r\n\r\n");
+            let parsed = make_sip_message(content.as_bytes()).parse().unwrap();
+            assert_eq!(parsed.method(), Some("ACK"), "{cseq}");
+            assert!(parsed.is_ack(), "{cseq}");
+        }
+        let invite = make_sip_message(b"INVITE sip:bob@host SIP/2.0\r\nCSeq: 1 INVITE\r\n\r\n");
+        assert!(!invite.parse().unwrap().is_ack());
+        // Only requests are ACKs, even if a broken response names one in CSeq
+        let response = make_sip_message(b"SIP/2.0 200 OK\r\nCSeq: 1 ACK\r\n\r\n");
+        let response = response.parse().unwrap();
+        assert_eq!(response.method(), Some("ACK"));
+        assert!(!response.is_ack());
+    }
+
+    #[test]
+    fn peek_method_matches_parsed_method() {
+        let contents: [&[u8]; 7] = [
+            b"INVITE sip:bob@host SIP/2.0\r\nCSeq: 1 INVITE\r\n\r\n",
+            b"ACK sip:bob@host SIP/2.0\r\nCSeq: 1 INVITE\r\n\r\n",
+            b"SIP/2.0 200 OK\r\ncseq:  7 BYE\r\nCSeq: 8 INFO\r\n\r\n",
+            b"SIP/2.0 200 OK\r\nCSeq: 2\r\n\tUPDATE\r\n\r\n",
+            b"SIP/2.0 200 OK\nCSeq: 3 PRACK\n\n",
+            b"SIP/2.0 500 Error\r\nCall-ID: x\r\n\r\nCSeq: 1 INVITE",
+            b"SIP/2.0 200 OK\r\nCSeq: 4\r\n\r\n",
+        ];
+        for content in contents {
+            let msg = make_sip_message(content);
+            let parsed = msg.parse().unwrap();
+            assert_eq!(msg.peek_method(), parsed.method(), "{parsed:?}");
+            assert_eq!(msg.peek_is_response(), parsed.is_response());
+        }
+    }
+
+    #[test]
+    fn via_stack_and_loop_suspect() {
+        let content = b"INVITE sip:bob@host SIP/2.0\r\n\
+            Via: SIP/2.0/UDP proxy.example.com:5060;branch=z9hG4bK3\r\n\
+            v: SIP/2.0/TCP Proxy.example.com;branch=z9hG4bK2, SIP/2.0/UDP [2001:db8::1]:5060;rport\r\n\
+            Via: SIP/2.0/UDP proxy.example.com:5080;received=10.0.0.9;branch=z9hG4bK1\r\n\
+            CSeq: 1 INVITE\r\n\r\n";
+        let parsed = make_sip_message(content).parse().unwrap();
+        let vias = parsed.vias();
+        assert_eq!(vias.len(), 4);
+        assert_eq!(vias[0].transport, "UDP");
+        assert_eq!(vias[0].sent_by, "proxy.example.com:5060");
+        assert_eq!(vias[0].branch.as_deref(), Some("z9hG4bK3"));
+        assert_eq!(vias[2].host(), "[2001:db8::1]");
+        assert_eq!(vias[2].branch, None);
+        assert_eq!(vias[3].branch.as_deref(), Some("z9hG4bK1"));
+        assert_eq!(parsed.branch(), Some("z9hG4bK3"));
+
+        // Three Vias from the same host, case and port aside
+        assert!(parsed.via_loop_suspect(2));
+        assert!(!parsed.via_loop_suspect(3));
+
+        let repeated = b"SIP/2.0 200 OK\r\n\
+            Via: SIP/2.0/UDP a;branch=z9hG4bK1\r\n\
+            Via: SIP/2.0/UDP b;branch=z9hG4bK1\r\n\
+            CSeq: 1 INVITE\r\n\r\n";
+        let parsed = make_sip_message(repeated).parse().unwrap();
+        assert!(parsed.via_loop_suspect(2));
+    }
+
+    #[test]
+    fn branch_is_topmost_via_only() {
+        let content = b"INVITE sip:bob@host SIP/2.0\r\n\
+            v: SIP/2.0/UDP a;rport;branch=z9hG4bKtop, SIP/2.0/UDP b;branch=z9hG4bKlow\r\n\
+            Via: SIP/2.0/UDP c;branch=z9hG4bKthird\r\n\
+            Content-Length: 0\r\n\r\n";
+        let parsed = make_sip_message(content).parse().unwrap();
+        assert_eq!(parsed.branch(), Some("z9hG4bKtop"));
+
+        let no_branch = b"INVITE sip:bob@host SIP/2.0\r\n\
+            Via: SIP/2.0/UDP a, SIP/2.0/UDP b;branch=z9hG4bKlow\r\n\r\n";
+        assert_eq!(make_sip_message(no_branch).parse().unwrap().branch(), None);
+
+        // Compact forms are case-insensitive like full names
+        let upper = b"INVITE sip:bob@host SIP/2.0\r\nV: SIP/2.0/UDP a;branch=z9hG4bKup\r\n\r\n";
+        let parsed = make_sip_message(upper).parse().unwrap();
+        assert_eq!(parsed.branch(), Some("z9hG4bKup"));
+        assert_eq!(parsed.vias().len(), 1);
+    }
+
+    #[test]
+    fn contact_wildcard_deregistration() {
+        let content = b"REGISTER sip:pbx SIP/2.0\r\nContact: *\r\nExpires: 0\r\n\r\n";
+        let parsed = make_sip_message(content).parse().unwrap();
+        assert!(parsed.contacts().is_empty());
+        assert!(parsed.is_contact_wildcard());
+        assert_eq!(parsed.expires(), Some(0));
+
+        let compact = b"REGISTER sip:pbx SIP/2.0\r\nM: *\r\nExpires: 0\r\n\r\n";
+        assert!(make_sip_message(compact)
+            .parse()
+            .unwrap()
+            .is_contact_wildcard());
+    }
+
+    #[test]
+    fn from_to_and_pai_accessors() {
+        let content = b"INVITE sip:bob@host SIP/2.0\r\n\
+            f: \"Alice\" <sip:alice@x>;tag=1\r\n\
+            To: <sip:bob@host>\r\n\
+            P-Asserted-Identity: \"Doe, Jane\" <sip:+15551234567@x>, <tel:+15551234567>\r\n\
+            P-Asserted-Identity: sip:backup@x\r\n\
+            Call-ID: pai@host\r\n\
+            Content-Length: 0\r\n\
+            \r\n";
+        let parsed = make_sip_message(content).parse().unwrap();
+        assert_eq!(
+            parsed.from_addr(),
+            Some(name_addr(Some("Alice"), "sip:alice@x"))
+        );
+        assert_eq!(parsed.to_addr(), Some(name_addr(None, "sip:bob@host")));
+        assert_eq!(
+            parsed.p_asserted_identity(),
+            Some(name_addr(Some("Doe, Jane"), "sip:+15551234567@x"))
+        );
+        assert_eq!(
+            parsed.p_asserted_identities(),
+            [
+                name_addr(Some("Doe, Jane"), "sip:+15551234567@x"),
+                name_addr(None, "tel:+15551234567"),
+                name_addr(None, "sip:backup@x"),
+            ]
+        );
+    }
+
+    #[test]
+    fn missing_name_addr_headers() {
+        let content = b"OPTIONS sip:host SIP/2.0\r\nCall-ID: none@host\r\n\r\n";
+        let parsed = make_sip_message(content).parse().unwrap();
+        assert_eq!(parsed.from_addr(), None);
+        assert!(parsed.p_asserted_identities().is_empty());
+    }
 }
@@ -0,0 +1,140 @@
+use std::io::Read;
+
+use crate::frame::{FrameIterator, ParseError};
+use crate::grep::GrepFilter;
+use crate::message::MessageIterator;
+use crate::sip::ParsedMessageIterator;
+use crate::transaction::TransactionIterator;
+use crate::types::ParsedSipMessage;
+
+/// Single entry point for reading a dump at any parsing level.
+///
+/// `MessageStream` takes ownership of the reader; each level method consumes
+/// the stream and returns an iterator that owns the reader in turn, so the
+/// result can be returned from functions or moved to another thread when `R`
+/// allows it. Pass `&mut reader` or `&data[..]` to keep ownership instead.
+///
+/// ```no_run
+/// use std::fs::File;
+/// use freeswitch_sofia_trace_parser::MessageStream;
+///
+/// let file = File::open("profile.dump")?;
+/// for msg in MessageStream::new(file).parsed().filter_map(Result::ok) {
+///     println!("{} {}", msg.timestamp, msg.message_type);
+/// }
+/// # Ok::<(), std::io::Error>(())
+/// ```
+pub struct MessageStream<R> {
+    reader: R,
+}
+
+impl<R: Read> MessageStream<R> {
+    pub fn new(reader: R) -> Self {
+        MessageStream { reader }
+    }
+
+    /// Strip `grep` group separators before parsing.
+    pub fn with_grep_filter(self) -> MessageStream<GrepFilter<R>> {
+        MessageStream {
+            reader: GrepFilter::new(self.reader),
+        }
+    }
+
+    /// Like [`with_grep_filter`](Self::with_grep_filter), and also strip
+    /// `grep -n` / `grep -rn` line prefixes.
+    pub fn with_grep_prefix_stripping(self) -> MessageStream<GrepFilter<R>> {
+        MessageStream {
+            reader: GrepFilter::with_prefix_stripping(self.reader),
+        }
+    }
+
+    /// Level 1: raw frames.
+    pub fn frames(self) -> FrameIterator<R> {
+        FrameIterator::new(self.reader)
+    }
+
+    /// Level 2: reassembled messages.
+    pub fn messages(self) -> MessageIterator<R> {
+        MessageIterator::new(self.reader)
+    }
+
+    /// Level 3: parsed SIP messages.
+    pub fn parsed(self) -> ParsedMessageIterator<R> {
+        ParsedMessageIterator::new(self.reader)
+    }
+
+    /// Level 4: requests grouped with their responses.
+    pub fn transactions(self) -> TransactionIterator<R> {
+        TransactionIterator::new(self.reader)
+    }
+
+    /// Level 3 messages for which `filter` returns true; errors pass through.
+    pub fn filtered<F>(
+        self,
+        mut filter: F,
+    ) -> impl Iterator<Item = Result<ParsedSipMessage, ParseError>>
+    where
+        F: FnMut(&ParsedSipMessage) -> bool,
+    {
+        self.parsed().filter(move |result| match result {
+            Ok(msg) => filter(msg),
+            Err(_) => true,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const DUMP: &[u8] = b"recv 52 bytes from udp/10.0.0.1:5060 at 00:00:00.000000:\n\
+        OPTIONS sip:a@host SIP/2.0\r\nContent-Length: 0\r\n\r\n\x0B\n\
+        recv 53 bytes from udp/10.0.0.1:5060 at 00:00:01.000000:\n\
+        NOTIFY sip:a@host SIP/2.0\r\nContent-Length: 0\r\n\r\n\x0B\n";
+
+    #[test]
+    fn all_levels() {
+        assert_eq!(MessageStream::new(DUMP).frames().count(), 2);
+        assert_eq!(MessageStream::new(DUMP).messages().count(), 2);
+        assert_eq!(MessageStream::new(DUMP).parsed().count(), 2);
+        assert_eq!(MessageStream::new(DUMP).transactions().count(), 2);
+    }
+
+    #[test]
+    fn filtered_keeps_matching() {
+        let methods: Vec<String> = MessageStream::new(DUMP)
+            .filtered(|m| m.method() == Some("NOTIFY"))
+            .map(|r| r.unwrap().method().unwrap().to_string())
+            .collect();
+        assert_eq!(methods, ["NOTIFY"]);
+    }
+
+    #[test]
+    fn grep_filter_strips_separators() {
+        let mut data = b"--\n".to_vec();
+        data.extend_from_slice(DUMP);
+        let parsed: Vec<_> = MessageStream::new(&data[..])
+            .with_grep_filter()
+            .parsed()
+            .collect::<Result<_, _>>()
+            .unwrap();
+        assert_eq!(parsed.len(), 2);
+    }
+
+    #[test]
+    fn grep_prefix_stripping_is_opt_in() {
+        let mut data = Vec::new();
+        for (i, line) in DUMP.split_inclusive(|&b| b == b'\n').enumerate() {
+            data.extend_from_slice(format!("profile.dump:{}:", i + 1).as_bytes());
+            data.extend_from_slice(line);
+        }
+        let parsed: Vec<_> = MessageStream::new(&data[..])
+            .with_grep_prefix_stripping()
+            .parsed()
+            .collect::<Result<_, _>>()
+            .unwrap();
+        assert_eq!(parsed.len(), 2);
+        let unstripped = MessageStream::new(&data[..]).with_grep_filter().parsed();
+        assert_eq!(unstripped.filter_map(Result::ok).count(), 0);
+    }
+}
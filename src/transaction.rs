@@ -0,0 +1,338 @@
+use std::collections::{HashMap, VecDeque};
+
+use tracing::{debug, trace};
+
+use crate::frame::ParseError;
+use crate::sip::ParsedMessageIterator;
+use crate::types::ParsedSipMessage;
+
+/// RFC 3261 branch parameters start with this magic cookie (Section 8.1.1.7).
+const BRANCH_COOKIE: &str = "z9hG4bK";
+
+/// A request with its provisional and final responses.
+#[derive(Debug, Clone)]
+pub struct Transaction {
+    /// The request, `None` when the dump starts after it was sent.
+    pub request: Option<ParsedSipMessage>,
+    /// Responses in dump order.
+    pub responses: Vec<ParsedSipMessage>,
+}
+
+impl Transaction {
+    /// The first final (>= 200) response, if one was seen.
+    pub fn final_response(&self) -> Option<&ParsedSipMessage> {
+        self.responses
+            .iter()
+            .find(|r| r.status_code().is_some_and(|code| code >= 200))
+    }
+
+    pub fn is_complete(&self) -> bool {
+        self.final_response().is_some()
+    }
+}
+
+/// Transaction identity: the topmost Via branch when RFC 3261 compliant,
+/// otherwise Call-ID and CSeq number. The CSeq method is always part of the
+/// key so a CANCEL is not merged with the INVITE it shares a branch with.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+enum TransactionKey {
+    Branch { branch: String, method: String },
+    CallIdCSeq { call_id: String, cseq: String },
+}
+
+impl TransactionKey {
+    fn of(msg: &ParsedSipMessage) -> Option<TransactionKey> {
+        let mut cseq = msg.cseq()?.split_whitespace();
+        let number = cseq.next()?;
+        let method = cseq.next()?.to_ascii_uppercase();
+        match msg.branch() {
+            Some(branch) if branch.starts_with(BRANCH_COOKIE) => Some(TransactionKey::Branch {
+                branch: branch.to_string(),
+                method,
+            }),
+            _ => Some(TransactionKey::CallIdCSeq {
+                call_id: msg.call_id()?.to_string(),
+                cseq: format!("{number} {method}"),
+            }),
+        }
+    }
+}
+
+struct Pending {
+    seq: u64,
+    transaction: Transaction,
+}
+
+/// Level 4: groups parsed messages into transactions.
+///
+/// A transaction is yielded once its final response is seen, or at EOF for
+/// those still waiting. ACK requests (see `ParsedSipMessage::is_ack()`) get
+/// no response and are yielded on their own immediately, whether they ACK a
+/// 2xx or share the branch of a failed INVITE, as are responses whose
+/// request is not in the dump.
+/// Retransmitted requests are dropped; responses are all kept. Parse errors
+/// pass through.
+pub struct TransactionIterator<R> {
+    inner: ParsedMessageIterator<R>,
+    pending: HashMap<TransactionKey, Pending>,
+    ready: VecDeque<Transaction>,
+    next_seq: u64,
+    exhausted: bool,
+}
+
+impl<R: std::io::Read> TransactionIterator<R> {
+    pub fn new(reader: R) -> Self {
+        TransactionIterator {
+            inner: ParsedMessageIterator::new(reader),
+            pending: HashMap::new(),
+            ready: VecDeque::new(),
+            next_seq: 0,
+            exhausted: false,
+        }
+    }
+
+    fn push(&mut self, msg: ParsedSipMessage) {
+        if msg.is_ack() {
+            self.ready.push_back(alone(msg));
+            return;
+        }
+
+        let Some(key) = TransactionKey::of(&msg) else {
+            debug!(message = %msg.message_type, "no transaction key, yielding alone");
+            self.ready.push_back(alone(msg));
+            return;
+        };
+
+        if msg.is_request() {
+            if self.pending.contains_key(&key) {
+                trace!(?key, "dropping retransmitted request");
+                return;
+            }
+            self.pending.insert(
+                key,
+                Pending {
+                    seq: self.next_seq,
+                    transaction: Transaction {
+                        request: Some(msg),
+                        responses: Vec::new(),
+                    },
+                },
+            );
+            self.next_seq += 1;
+            return;
+        }
+
+        let Some(pending) = self.pending.get_mut(&key) else {
+            trace!(?key, "response without a pending request");
+            self.ready.push_back(alone(msg));
+            return;
+        };
+        pending.transaction.responses.push(msg);
+        if pending.transaction.is_complete() {
+            if let Some(done) = self.pending.remove(&key) {
+                self.ready.push_back(done.transaction);
+            }
+        }
+    }
+
+    fn flush_pending(&mut self) {
+        let mut pending: Vec<Pending> = self.pending.drain().map(|(_, p)| p).collect();
+        pending.sort_by_key(|p| p.seq);
+        self.ready
+            .extend(pending.into_iter().map(|p| p.transaction));
+    }
+}
+
+/// A transaction holding a single message.
+fn alone(msg: ParsedSipMessage) -> Transaction {
+    if msg.is_request() {
+        Transaction {
+            request: Some(msg),
+            responses: Vec::new(),
+        }
+    } else {
+        Transaction {
+            request: None,
+            responses: vec![msg],
+        }
+    }
+}
+
+impl<R: std::io::Read> Iterator for TransactionIterator<R> {
+    type Item = Result<Transaction, ParseError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            if let Some(transaction) = self.ready.pop_front() {
+                return Some(Ok(transaction));
+            }
+            if self.exhausted {
+                return None;
+            }
+            match self.inner.next() {
+                Some(Ok(msg)) => self.push(msg),
+                Some(Err(e)) => return Some(Err(e)),
+                None => {
+                    self.exhausted = true;
+                    self.flush_pending();
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn frame(direction: &str, sec: u8, content: &str) -> Vec<u8> {
+        let prep = if direction == "recv" { "from" } else { "to" };
+        let mut data = format!(
+            "{direction} {} bytes {prep} udp/10.0.0.1:5060 at 00:00:{sec:02}.000000:\n",
+            content.len()
+        )
+        .into_bytes();
+        data.extend_from_slice(content.as_bytes());
+        data.extend_from_slice(b"\x0B\n");
+        data
+    }
+
+    fn request(method: &str, branch: &str, cseq: &str) -> String {
+        format!(
+            "{method} sip:bob@host SIP/2.0\r\n\
+             Via: SIP/2.0/UDP 10.0.0.1;branch={branch}\r\n\
+             Call-ID: tx@host\r\n\
+             CSeq: {cseq}\r\n\
+             Content-Length: 0\r\n\r\n"
+        )
+    }
+
+    fn response(code: &str, branch: &str, cseq: &str) -> String {
+        format!(
+            "SIP/2.0 {code}\r\n\
+             Via: SIP/2.0/UDP 10.0.0.1;branch={branch}\r\n\
+             Call-ID: tx@host\r\n\
+             CSeq: {cseq}\r\n\
+             Content-Length: 0\r\n\r\n"
+        )
+    }
+
+    fn transactions(data: &[u8]) -> Vec<Transaction> {
+        TransactionIterator::new(data)
+            .collect::<Result<_, _>>()
+            .unwrap()
+    }
+
+    #[test]
+    fn invite_with_provisionals_and_final() {
+        let mut data = frame("recv", 0, &request("INVITE", "z9hG4bK1", "1 INVITE"));
+        data.extend(frame(
+            "sent",
+            0,
+            &response("100 Trying", "z9hG4bK1", "1 INVITE"),
+        ));
+        data.extend(frame(
+            "sent",
+            1,
+            &response("180 Ringing", "z9hG4bK1", "1 INVITE"),
+        ));
+        data.extend(frame(
+            "sent",
+            2,
+            &response("200 OK", "z9hG4bK1", "1 INVITE"),
+        ));
+
+        let txs = transactions(&data);
+        assert_eq!(txs.len(), 1);
+        let tx = &txs[0];
+        assert_eq!(tx.request.as_ref().unwrap().method(), Some("INVITE"));
+        let codes: Vec<_> = tx
+            .responses
+            .iter()
+            .filter_map(|r| r.status_code())
+            .collect();
+        assert_eq!(codes, [100, 180, 200]);
+        assert_eq!(tx.final_response().unwrap().status_code(), Some(200));
+    }
+
+    #[test]
+    fn cancel_is_separate_from_invite() {
+        let mut data = frame("recv", 0, &request("INVITE", "z9hG4bK1", "1 INVITE"));
+        data.extend(frame("recv", 1, &request("CANCEL", "z9hG4bK1", "1 CANCEL")));
+        data.extend(frame(
+            "sent",
+            1,
+            &response("200 OK", "z9hG4bK1", "1 CANCEL"),
+        ));
+        data.extend(frame(
+            "sent",
+            1,
+            &response("487 Request Terminated", "z9hG4bK1", "1 INVITE"),
+        ));
+        data.extend(frame("recv", 2, &request("ACK", "z9hG4bK1", "1 ACK")));
+
+        let txs = transactions(&data);
+        let summary: Vec<_> = txs
+            .iter()
+            .map(|t| {
+                (
+                    t.request.as_ref().unwrap().method().unwrap().to_string(),
+                    t.responses.len(),
+                )
+            })
+            .collect();
+        assert_eq!(
+            summary,
+            [
+                ("CANCEL".to_string(), 1),
+                ("INVITE".to_string(), 1),
+                ("ACK".to_string(), 0)
+            ]
+        );
+    }
+
+    #[test]
+    fn fallback_key_and_eof_flush() {
+        // RFC 2543 branch: keyed by Call-ID and CSeq
+        let mut data = frame("recv", 0, &request("OPTIONS", "1", "7 OPTIONS"));
+        data.extend(frame("recv", 0, &request("INFO", "z9hG4bK2", "8 INFO")));
+        data.extend(frame("sent", 1, &response("200 OK", "1", "7 OPTIONS")));
+        data.extend(frame(
+            "sent",
+            1,
+            &response("100 Trying", "z9hG4bK2", "8 INFO"),
+        ));
+
+        let txs = transactions(&data);
+        assert_eq!(txs.len(), 2);
+        assert!(txs[0].is_complete());
+        assert_eq!(txs[1].request.as_ref().unwrap().method(), Some("INFO"));
+        assert!(!txs[1].is_complete());
+    }
+
+    #[test]
+    fn orphan_response_and_retransmission() {
+        let mut data = frame("sent", 0, &response("200 OK", "z9hG4bK0", "1 BYE"));
+        data.extend(frame("recv", 1, &request("BYE", "z9hG4bK3", "2 BYE")));
+        data.extend(frame("recv", 1, &request("BYE", "z9hG4bK3", "2 BYE")));
+        data.extend(frame("sent", 2, &response("200 OK", "z9hG4bK3", "2 BYE")));
+
+        let txs = transactions(&data);
+        assert_eq!(txs.len(), 2);
+        assert!(txs[0].request.is_none());
+        assert_eq!(txs[1].responses.len(), 1);
+    }
+
+    #[test]
+    fn top_via_branch_only() {
+        let data = "INVITE sip:bob@host SIP/2.0\r\n\
+            Via: SIP/2.0/UDP a;branch=z9hG4bKtop, SIP/2.0/UDP b;branch=z9hG4bKlow\r\n\
+            Via: SIP/2.0/UDP c;branch=z9hG4bKthird\r\n\
+            Content-Length: 0\r\n\r\n";
+        let msg = crate::ParsedMessageIterator::new(&frame("recv", 0, data)[..])
+            .next()
+            .unwrap()
+            .unwrap();
+        assert_eq!(msg.branch(), Some("z9hG4bKtop"));
+    }
+}
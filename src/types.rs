@@ -1,5 +1,15 @@
 use std::borrow::Cow;
 use std::fmt;
+use std::hash::{Hash, Hasher};
+use std::net::{Ipv4Addr, Ipv6Addr};
+use std::str::FromStr;
+use std::time::Duration;
+
+use base64::prelude::{Engine, BASE64_STANDARD};
+use siphasher::sip::SipHasher13;
+use tracing::debug;
+
+use crate::frame::ParseError;
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
 pub enum Direction {
@@ -23,9 +33,17 @@ impl Direction {
             Direction::Sent => "to",
         }
     }
+
+    /// The same hop seen from the remote end.
+    pub fn reversed(self) -> Direction {
+        match self {
+            Direction::Recv => Direction::Sent,
+            Direction::Sent => Direction::Recv,
+        }
+    }
 }
 
-#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
 pub enum Transport {
     Tcp,
     Udp,
@@ -44,7 +62,7 @@ impl fmt::Display for Transport {
     }
 }
 
-#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
 pub enum Timestamp {
     TimeOnly {
         hour: u8,
@@ -85,6 +103,200 @@ impl Timestamp {
     }
 }
 
+const USEC_PER_DAY: u64 = 86_400 * 1_000_000;
+
+fn usec_of_day(hour: u8, min: u8, sec: u8, usec: u32) -> u64 {
+    (u64::from(hour) * 3600 + u64::from(min) * 60 + u64::from(sec)) * 1_000_000 + u64::from(usec)
+}
+
+/// Days since 1970-01-01 for a proleptic Gregorian date.
+fn days_from_civil(year: u16, month: u8, day: u8) -> i64 {
+    let y = i64::from(year) - i64::from(month <= 2);
+    let era = y.div_euclid(400);
+    let yoe = y - era * 400;
+    let m = i64::from(month);
+    let doy = (153 * (if m > 2 { m - 3 } else { m + 9 }) + 2) / 5 + i64::from(day) - 1;
+    let doe = yoe * 365 + yoe / 4 - yoe / 100 + doy;
+    era * 146_097 + doe - 719_468
+}
+
+/// Proleptic Gregorian date for a count of days since 1970-01-01.
+fn civil_from_days(days: i64) -> (u16, u8, u8) {
+    let z = days + 719_468;
+    let era = z.div_euclid(146_097);
+    let doe = z - era * 146_097;
+    let yoe = (doe - doe / 1460 + doe / 36_524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + i64::from(month <= 2);
+    (
+        year.clamp(0, i64::from(u16::MAX)) as u16,
+        month as u8,
+        day as u8,
+    )
+}
+
+/// Hour, minute, second and microsecond of a microsecond-of-day count.
+fn time_of_day(usec: u64) -> (u8, u8, u8, u32) {
+    let secs = usec / 1_000_000;
+    (
+        (secs / 3600) as u8,
+        (secs / 60 % 60) as u8,
+        (secs % 60) as u8,
+        (usec % 1_000_000) as u32,
+    )
+}
+
+impl Timestamp {
+    /// Time elapsed from `earlier` to `self`.
+    ///
+    /// `TimeOnly` timestamps carry no date: a backward jump of more than 12 hours is
+    /// taken as a midnight wrap, smaller backward jumps return `None`. `DateTime`
+    /// backward jumps and mixed `TimeOnly`/`DateTime` comparisons return `None`.
+    pub fn duration_since(&self, earlier: &Timestamp) -> Option<Duration> {
+        let usec = match (self, earlier) {
+            (
+                Timestamp::TimeOnly {
+                    hour,
+                    min,
+                    sec,
+                    usec,
+                },
+                Timestamp::TimeOnly {
+                    hour: e_hour,
+                    min: e_min,
+                    sec: e_sec,
+                    usec: e_usec,
+                },
+            ) => {
+                let now = usec_of_day(*hour, *min, *sec, *usec);
+                let then = usec_of_day(*e_hour, *e_min, *e_sec, *e_usec);
+                if now >= then {
+                    now - then
+                } else if then - now > USEC_PER_DAY / 2 {
+                    now + USEC_PER_DAY - then
+                } else {
+                    return None;
+                }
+            }
+            (
+                Timestamp::DateTime {
+                    year,
+                    month,
+                    day,
+                    hour,
+                    min,
+                    sec,
+                    usec,
+                },
+                Timestamp::DateTime {
+                    year: e_year,
+                    month: e_month,
+                    day: e_day,
+                    hour: e_hour,
+                    min: e_min,
+                    sec: e_sec,
+                    usec: e_usec,
+                },
+            ) => {
+                let days = days_from_civil(*year, *month, *day)
+                    - days_from_civil(*e_year, *e_month, *e_day);
+                let now = days * USEC_PER_DAY as i64 + usec_of_day(*hour, *min, *sec, *usec) as i64;
+                let then = usec_of_day(*e_hour, *e_min, *e_sec, *e_usec) as i64;
+                u64::try_from(now - then).ok()?
+            }
+            _ => {
+                debug!(%self, %earlier, "cannot compare TimeOnly and DateTime timestamps");
+                return None;
+            }
+        };
+        Some(Duration::from_micros(usec))
+    }
+
+    /// UTC date and time of a Unix timestamp, as read from packet captures.
+    pub(crate) fn from_unix(secs: u64, usec: u32) -> Timestamp {
+        let days = (secs / 86_400) as i64;
+        let (year, month, day) = civil_from_days(days);
+        let (hour, min, sec, _) = time_of_day(secs % 86_400 * 1_000_000);
+        Timestamp::DateTime {
+            year,
+            month,
+            day,
+            hour,
+            min,
+            sec,
+            usec,
+        }
+    }
+
+    /// This timestamp moved by a fixed offset, e.g. to show local dump times in UTC.
+    ///
+    /// `DateTime` rolls over into the neighbouring day, month or year; `TimeOnly`
+    /// has no date and wraps around midnight. DST changes are not accounted for.
+    pub fn shifted(&self, offset_minutes: i32) -> Timestamp {
+        let offset = i64::from(offset_minutes) * 60_000_000;
+        let day = USEC_PER_DAY as i64;
+        match *self {
+            Timestamp::TimeOnly {
+                hour,
+                min,
+                sec,
+                usec,
+            } => {
+                let t = (usec_of_day(hour, min, sec, usec) as i64 + offset).rem_euclid(day);
+                let (hour, min, sec, usec) = time_of_day(t as u64);
+                Timestamp::TimeOnly {
+                    hour,
+                    min,
+                    sec,
+                    usec,
+                }
+            }
+            Timestamp::DateTime {
+                year,
+                month,
+                day: mday,
+                hour,
+                min,
+                sec,
+                usec,
+            } => {
+                let total = days_from_civil(year, month, mday) * day
+                    + usec_of_day(hour, min, sec, usec) as i64
+                    + offset;
+                let (year, month, mday) = civil_from_days(total.div_euclid(day));
+                let (hour, min, sec, usec) = time_of_day(total.rem_euclid(day) as u64);
+                Timestamp::DateTime {
+                    year,
+                    month,
+                    day: mday,
+                    hour,
+                    min,
+                    sec,
+                    usec,
+                }
+            }
+        }
+    }
+}
+
+/// Parses the `Display` form, `HH:MM:SS[.frac]` or `YYYY-MM-DD HH:MM:SS[.frac]`,
+/// with the same fraction rules as dump frame headers.
+impl FromStr for Timestamp {
+    type Err = ParseError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match crate::frame::parse_timestamp_prefix(s.as_bytes()) {
+            Some((ts, len)) if len == s.len() => Ok(ts),
+            _ => Err(ParseError::InvalidHeader(format!(
+                "invalid timestamp '{s}'"
+            ))),
+        }
+    }
+}
+
 impl fmt::Display for Timestamp {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         match self {
@@ -110,7 +322,8 @@ impl fmt::Display for Timestamp {
     }
 }
 
-#[derive(Debug, Clone)]
+/// Equality and hashing cover every field, timestamp included.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
 pub struct Frame {
     pub direction: Direction,
     pub byte_count: usize,
@@ -118,18 +331,141 @@ pub struct Frame {
     pub address: String,
     pub timestamp: Timestamp,
     pub content: Vec<u8>,
+    /// Input byte offset of the frame header, counted from the start of the
+    /// reader (after any `GrepFilter` stripping).
+    pub offset: u64,
 }
 
-#[derive(Debug, Clone)]
+impl Frame {
+    /// Frame header in dump format: `recv N bytes from tcp/addr at ts:` (no trailing newline).
+    pub fn header_line(&self) -> String {
+        format_header_line(
+            self.direction,
+            self.byte_count,
+            self.transport,
+            &self.address,
+            &self.timestamp,
+        )
+    }
+
+    /// True when the content length differs from the header's byte count:
+    /// the frame was cut at a boundary found by scanning, or at end of input.
+    pub fn byte_count_mismatch(&self) -> bool {
+        self.content.len() != self.byte_count
+    }
+}
+
+fn format_header_line(
+    direction: Direction,
+    byte_count: usize,
+    transport: Transport,
+    address: &str,
+    timestamp: &Timestamp,
+) -> String {
+    format!(
+        "{direction} {byte_count} bytes {} {transport}/{address} at {timestamp}:",
+        direction.preposition()
+    )
+}
+
+/// Equality and hashing cover every field, timestamp included; see
+/// `ParsedSipMessage::fingerprint()` to match retransmissions.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
 pub struct SipMessage {
     pub direction: Direction,
     pub transport: Transport,
     pub address: String,
+    /// When the first frame of the message was logged.
     pub timestamp: Timestamp,
+    /// When the last frame was logged, i.e. when the message fully arrived.
+    /// Equal to `timestamp` for single-frame messages.
+    pub last_timestamp: Timestamp,
     pub content: Vec<u8>,
     pub frame_count: usize,
 }
 
+impl SipMessage {
+    /// True for CRLF keepalive pings (RFC 5626 Section 4.4.1).
+    pub fn is_keepalive(&self) -> bool {
+        is_keepalive(&self.content)
+    }
+
+    /// Header line for writing this message back as a single dump frame.
+    pub fn header_line(&self) -> String {
+        format_header_line(
+            self.direction,
+            self.content.len(),
+            self.transport,
+            &self.address,
+            &self.timestamp,
+        )
+    }
+}
+
+/// Decode text that may start with a byte order mark.
+///
+/// UTF-16 LE and BE bodies (seen in some PIDF and EIDO XML) are decoded by
+/// their BOM, and a UTF-8 BOM is dropped. Anything else is read as UTF-8
+/// with invalid sequences replaced.
+pub fn decode_bom_text(data: &[u8]) -> Cow<'_, str> {
+    let utf16 = |rest: &[u8], unit: fn([u8; 2]) -> u16| {
+        let chunks = rest.chunks_exact(2);
+        let odd = !chunks.remainder().is_empty();
+        let units: Vec<u16> = chunks.map(|c| unit([c[0], c[1]])).collect();
+        let mut text = String::from_utf16_lossy(&units);
+        if odd {
+            text.push(char::REPLACEMENT_CHARACTER);
+        }
+        Cow::Owned(text)
+    };
+    if let Some(rest) = data.strip_prefix(b"\xEF\xBB\xBF") {
+        String::from_utf8_lossy(rest)
+    } else if let Some(rest) = data.strip_prefix(b"\xFF\xFE") {
+        utf16(rest, u16::from_le_bytes)
+    } else if let Some(rest) = data.strip_prefix(b"\xFE\xFF") {
+        utf16(rest, u16::from_be_bytes)
+    } else {
+        String::from_utf8_lossy(data)
+    }
+}
+
+/// Split a dump address into host and port.
+///
+/// IPv6 hosts are returned without brackets or `%zone` suffix:
+/// `[fe80::1%eth0]:5060` gives `("fe80::1", 5060)`. `None` when the port is
+/// missing or invalid.
+pub fn split_address(address: &str) -> Option<(&str, u16)> {
+    let (host, port) = match address.strip_prefix('[') {
+        Some(rest) => {
+            let (host, port) = rest.split_once("]:")?;
+            (host.split_once('%').map_or(host, |(h, _)| h), port)
+        }
+        None => address.rsplit_once(':')?,
+    };
+    if host.is_empty() {
+        return None;
+    }
+    Some((host, port.parse().ok()?))
+}
+
+/// The embedded address of an IPv4-mapped IPv6 host (`::ffff:10.0.0.1`).
+pub fn ipv4_mapped(host: &str) -> Option<Ipv4Addr> {
+    host.parse::<Ipv6Addr>().ok()?.to_ipv4_mapped()
+}
+
+/// Content made only of CRLF pairs, as sent by CRLF keepalive pings.
+pub(crate) fn is_keepalive(content: &[u8]) -> bool {
+    !content.is_empty()
+        && content.len().is_multiple_of(2)
+        && content.chunks_exact(2).all(|pair| pair == b"\r\n")
+}
+
+/// Content starting like a SigComp message (RFC 3320): first byte with its
+/// five high bits set, which no SIP start line has.
+pub(crate) fn is_sigcomp(content: &[u8]) -> bool {
+    content.first().is_some_and(|&b| b & 0xF8 == 0xF8)
+}
+
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub enum SipMessageType {
     Request { method: String, uri: String },
@@ -145,6 +481,14 @@ impl fmt::Display for SipMessageType {
     }
 }
 
+/// Lossy UTF-8 form of `to_bytes()`.
+impl fmt::Display for ParsedSipMessage {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.head())?;
+        f.write_str(&String::from_utf8_lossy(&self.body))
+    }
+}
+
 impl SipMessageType {
     pub fn summary(&self) -> Cow<'_, str> {
         match self {
@@ -154,6 +498,42 @@ impl SipMessageType {
     }
 }
 
+/// Headers covered by `ParsedSipMessage::fingerprint()`.
+const FINGERPRINT_HEADERS: &[&str] = &["Call-ID", "CSeq", "From", "To", "Via"];
+
+/// Compact header forms (RFC 3261 Section 7.3.3 and later extensions).
+const COMPACT_FORMS: &[(&str, &str)] = &[
+    ("Accept-Contact", "a"),
+    ("Allow-Events", "u"),
+    ("Call-ID", "i"),
+    ("Contact", "m"),
+    ("Content-Encoding", "e"),
+    ("Content-Length", "l"),
+    ("Content-Type", "c"),
+    ("Event", "o"),
+    ("From", "f"),
+    ("Identity", "y"),
+    ("Refer-To", "r"),
+    ("Referred-By", "b"),
+    ("Reject-Contact", "j"),
+    ("Request-Disposition", "d"),
+    ("Session-Expires", "x"),
+    ("Subject", "s"),
+    ("Supported", "k"),
+    ("To", "t"),
+    ("Via", "v"),
+];
+
+/// True when header `name` as it appears in a message is `wanted`, by full
+/// name (case-insensitive) or by compact form, whichever of them `wanted` is.
+pub(crate) fn header_name_matches(name: &str, wanted: &str) -> bool {
+    name.eq_ignore_ascii_case(wanted)
+        || COMPACT_FORMS.iter().any(|(full, compact)| {
+            (full.eq_ignore_ascii_case(wanted) && name.eq_ignore_ascii_case(compact))
+                || (compact.eq_ignore_ascii_case(wanted) && name.eq_ignore_ascii_case(full))
+        })
+}
+
 #[derive(Debug, Clone)]
 pub struct ParsedSipMessage {
     pub direction: Direction,
@@ -162,10 +542,69 @@ pub struct ParsedSipMessage {
     pub timestamp: Timestamp,
     pub message_type: SipMessageType,
     pub headers: Vec<(String, String)>,
+    /// Whitespace between each header's colon and its value, as captured, so
+    /// `to_bytes()` reproduces the original bytes. `None` when every header
+    /// used a single space, and after headers are added or removed.
+    pub header_spacing: Option<Vec<String>>,
     pub body: Vec<u8>,
     pub frame_count: usize,
 }
 
+/// A `name-addr` or `addr-spec` header value (RFC 3261 Section 25.1),
+/// as found in From, To, Contact and P-Asserted-Identity.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct NameAddr {
+    pub display_name: Option<String>,
+    pub uri: String,
+}
+
+/// One Via header value (RFC 3261 Section 20.42), e.g.
+/// `SIP/2.0/UDP 10.0.0.1:5060;branch=z9hG4bK776asdhds`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Via {
+    /// Transport from the sent-protocol, e.g. `UDP` or `TLS`.
+    pub transport: String,
+    /// `host[:port]` the request was sent from.
+    pub sent_by: String,
+    pub branch: Option<String>,
+}
+
+/// One Reason header value (RFC 3326), e.g.
+/// `Q.850;cause=16;text="Normal call clearing"`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ReasonInfo {
+    /// Protocol as received, usually `SIP` or `Q.850`.
+    pub protocol: String,
+    pub cause: Option<u16>,
+    pub text: Option<String>,
+}
+
+/// One authentication header value (RFC 3261 section 22), a challenge from
+/// WWW-Authenticate/Proxy-Authenticate or credentials from
+/// Authorization/Proxy-Authorization, e.g.
+/// `Digest realm="example.com", nonce="abc", algorithm=MD5`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AuthParams {
+    /// Scheme as received, usually `Digest`.
+    pub scheme: String,
+    /// Parameters in order, quoted values unquoted.
+    pub params: Vec<(String, String)>,
+}
+
+impl AuthParams {
+    /// Value of parameter `name` (case-insensitive).
+    pub fn param(&self, name: &str) -> Option<&str> {
+        self.params
+            .iter()
+            .find(|(k, _)| k.eq_ignore_ascii_case(name))
+            .map(|(_, v)| v.as_str())
+    }
+
+    pub fn realm(&self) -> Option<&str> {
+        self.param("realm")
+    }
+}
+
 #[derive(Debug, Clone)]
 pub struct MimePart {
     pub headers: Vec<(String, String)>,
@@ -195,6 +634,36 @@ impl MimePart {
     pub fn content_disposition(&self) -> Option<&str> {
         self.header_value("Content-Disposition")
     }
+
+    pub fn content_transfer_encoding(&self) -> Option<&str> {
+        self.header_value("Content-Transfer-Encoding")
+    }
+
+    /// Body with `Content-Transfer-Encoding: base64` undone.
+    ///
+    /// Other encodings (7bit, 8bit, binary) are returned as-is, as is a
+    /// base64 body that fails to decode.
+    pub fn decoded_body(&self) -> Cow<'_, [u8]> {
+        let is_base64 = self
+            .content_transfer_encoding()
+            .is_some_and(|e| e.trim().eq_ignore_ascii_case("base64"));
+        if !is_base64 {
+            return Cow::Borrowed(&self.body);
+        }
+        let encoded: Vec<u8> = self
+            .body
+            .iter()
+            .copied()
+            .filter(|b| !b.is_ascii_whitespace())
+            .collect();
+        match BASE64_STANDARD.decode(&encoded) {
+            Ok(decoded) => Cow::Owned(decoded),
+            Err(e) => {
+                debug!("invalid base64 MIME part: {e}");
+                Cow::Borrowed(&self.body)
+            }
+        }
+    }
 }
 
 impl ParsedSipMessage {
@@ -218,6 +687,57 @@ impl ParsedSipMessage {
         self.header_value("CSeq")
     }
 
+    pub fn is_request(&self) -> bool {
+        matches!(self.message_type, SipMessageType::Request { .. })
+    }
+
+    pub fn is_response(&self) -> bool {
+        matches!(self.message_type, SipMessageType::Response { .. })
+    }
+
+    pub fn status_code(&self) -> Option<u16> {
+        match &self.message_type {
+            SipMessageType::Response { code, .. } => Some(*code),
+            SipMessageType::Request { .. } => None,
+        }
+    }
+
+    /// Reason phrase as received; `None` for requests.
+    pub fn reason_phrase(&self) -> Option<&str> {
+        match &self.message_type {
+            SipMessageType::Response { reason, .. } => Some(reason),
+            SipMessageType::Request { .. } => None,
+        }
+    }
+
+    /// Replace a response's reason phrase with the canonical one for its code.
+    ///
+    /// Unknown codes and requests are left untouched. Returns whether the
+    /// reason changed.
+    pub fn canonicalize_reason(&mut self) -> bool {
+        if let SipMessageType::Response { code, reason } = &mut self.message_type {
+            if let Some(canonical) = crate::reasons::canonical_reason(*code) {
+                if reason != canonical {
+                    *reason = canonical.to_string();
+                    return true;
+                }
+            }
+        }
+        false
+    }
+
+    /// Literal request method; `None` for responses (see `method()` for CSeq-derived).
+    pub fn request_method(&self) -> Option<&str> {
+        match &self.message_type {
+            SipMessageType::Request { method, .. } => Some(method),
+            SipMessageType::Response { .. } => None,
+        }
+    }
+
+    /// Request-line method for requests, CSeq method for responses.
+    ///
+    /// The request line wins over CSeq: an ACK whose CSeq still says INVITE,
+    /// as some UAs send it, is reported as `ACK`.
     pub fn method(&self) -> Option<&str> {
         match &self.message_type {
             SipMessageType::Request { method, .. } => Some(method),
@@ -227,37 +747,158 @@ impl ParsedSipMessage {
         }
     }
 
+    /// True for ACK requests, whatever their CSeq method.
+    ///
+    /// An ACK carries the INVITE's CSeq number with method ACK and never gets
+    /// a response. The ACK for a non-2xx final response shares the INVITE's
+    /// branch and is hop-by-hop; the ACK for a 2xx is end-to-end, with a
+    /// branch of its own (RFC 3261 Sections 17.1.1.3 and 13.2.2.4).
+    pub fn is_ack(&self) -> bool {
+        self.request_method()
+            .is_some_and(|m| m.eq_ignore_ascii_case("ACK"))
+    }
+
     pub fn body_text(&self) -> Cow<'_, str> {
         String::from_utf8_lossy(&self.body)
     }
 
+    /// Body as text, decoding UTF-16 by its BOM; see [`decode_bom_text`].
+    pub fn body_text_decoded(&self) -> Cow<'_, str> {
+        decode_bom_text(&self.body)
+    }
+
     pub fn to_bytes(&self) -> Vec<u8> {
-        let mut out = Vec::new();
+        let mut out = self.head().into_bytes();
+        out.extend_from_slice(&self.body);
+        out
+    }
+
+    /// `to_bytes()` as a string, with invalid UTF-8 in the body replaced.
+    pub fn to_string_lossy(&self) -> String {
+        self.to_string()
+    }
+
+    /// Start line and headers, up to and including the blank line.
+    fn head(&self) -> String {
+        let mut out = match &self.message_type {
+            SipMessageType::Request { method, uri } => format!("{method} {uri} SIP/2.0\r\n"),
+            SipMessageType::Response { code, reason } => format!("SIP/2.0 {code} {reason}\r\n"),
+        };
+        let spacing = self
+            .header_spacing
+            .as_ref()
+            .filter(|spacing| spacing.len() == self.headers.len());
+        for (i, (name, value)) in self.headers.iter().enumerate() {
+            let sep = spacing.map_or(" ", |spacing| spacing[i].as_str());
+            out.push_str(&format!("{name}:{sep}{value}\r\n"));
+        }
+        out.push_str("\r\n");
+        out
+    }
+
+    /// Content hash identifying retransmissions of the same message.
+    ///
+    /// Covers the start line (method and URI, or status code), the Call-ID,
+    /// CSeq, From, To and Via headers, and the body. Timestamp, direction,
+    /// transport, address and other headers do not participate. The value is
+    /// stable across runs and builds.
+    pub fn fingerprint(&self) -> u64 {
+        let mut hasher = SipHasher13::new();
         match &self.message_type {
             SipMessageType::Request { method, uri } => {
-                out.extend_from_slice(format!("{method} {uri} SIP/2.0\r\n").as_bytes());
-            }
-            SipMessageType::Response { code, reason } => {
-                out.extend_from_slice(format!("SIP/2.0 {code} {reason}\r\n").as_bytes());
+                method.to_ascii_uppercase().hash(&mut hasher);
+                uri.hash(&mut hasher);
             }
+            SipMessageType::Response { code, .. } => code.hash(&mut hasher),
         }
-        for (name, value) in &self.headers {
-            out.extend_from_slice(format!("{name}: {value}\r\n").as_bytes());
-        }
-        out.extend_from_slice(b"\r\n");
-        if !self.body.is_empty() {
-            out.extend_from_slice(&self.body);
+        for &name in FINGERPRINT_HEADERS {
+            let values = self
+                .headers
+                .iter()
+                .filter(|(k, _)| header_name_matches(k, name));
+            for (_, value) in values {
+                value.trim().hash(&mut hasher);
+            }
+            hasher.write_u8(0);
         }
-        out
+        self.body.hash(&mut hasher);
+        hasher.finish()
     }
 
-    fn header_value(&self, name: &str) -> Option<&str> {
+    pub(crate) fn header_value(&self, name: &str) -> Option<&str> {
         let name_lower = name.to_ascii_lowercase();
         self.headers
             .iter()
             .find(|(k, _)| k.to_ascii_lowercase() == name_lower)
             .map(|(_, v)| v.as_str())
     }
+
+    /// Replace every `name` header (case-insensitive) with a single one.
+    ///
+    /// The new header takes the position of the first existing occurrence,
+    /// or is appended when there was none. Compact forms are not matched.
+    pub fn set_header(&mut self, name: &str, value: &str) -> &mut Self {
+        match self
+            .headers
+            .iter()
+            .position(|(k, _)| k.eq_ignore_ascii_case(name))
+        {
+            Some(first) => {
+                self.header_spacing = None;
+                self.headers[first] = (name.to_string(), value.to_string());
+                let mut index = 0;
+                self.headers.retain(|(k, _)| {
+                    index += 1;
+                    index - 1 == first || !k.eq_ignore_ascii_case(name)
+                });
+            }
+            None => self.headers.push((name.to_string(), value.to_string())),
+        }
+        self
+    }
+
+    /// True when a `name` header is present, under its full name
+    /// (case-insensitive) or its compact form.
+    pub fn has_header(&self, name: &str) -> bool {
+        self.headers
+            .iter()
+            .any(|(k, _)| header_name_matches(k, name))
+    }
+
+    /// Set the value of every `name` header, matched like `has_header()`.
+    ///
+    /// Unlike `set_header()`, each occurrence keeps its own name, compact or
+    /// not, and its position; nothing is added when none is present.
+    pub fn replace_header(&mut self, name: &str, value: &str) -> &mut Self {
+        for (k, v) in &mut self.headers {
+            if header_name_matches(k, name) {
+                *v = value.to_string();
+            }
+        }
+        self
+    }
+
+    /// Remove every `name` header (case-insensitive).
+    pub fn remove_header(&mut self, name: &str) -> &mut Self {
+        self.header_spacing = None;
+        self.headers.retain(|(k, _)| !k.eq_ignore_ascii_case(name));
+        self
+    }
+
+    /// Add a header before all others, as a proxy does with Via.
+    pub fn insert_header_front(&mut self, name: &str, value: &str) -> &mut Self {
+        self.header_spacing = None;
+        self.headers
+            .insert(0, (name.to_string(), value.to_string()));
+        self
+    }
+
+    /// Copy of this message with `set_header(name, value)` applied.
+    pub fn clone_with_header(&self, name: &str, value: &str) -> Self {
+        let mut msg = self.clone();
+        msg.set_header(name, value);
+        msg
+    }
 }
 
 #[cfg(test)]
@@ -284,11 +925,421 @@ mod tests {
                 .iter()
                 .map(|(k, v)| (k.to_string(), v.to_string()))
                 .collect(),
+            header_spacing: None,
             body: body.to_vec(),
             frame_count: 1,
         }
     }
 
+    fn udp_retransmission(sec: u8, content: &[u8]) -> SipMessage {
+        SipMessage {
+            direction: Direction::Recv,
+            transport: Transport::Udp,
+            address: "10.0.0.1:5060".into(),
+            timestamp: Timestamp::TimeOnly {
+                hour: 12,
+                min: 0,
+                sec,
+                usec: 0,
+            },
+            last_timestamp: Timestamp::TimeOnly {
+                hour: 12,
+                min: 0,
+                sec,
+                usec: 0,
+            },
+            content: content.to_vec(),
+            frame_count: 1,
+        }
+    }
+
+    #[test]
+    fn fingerprint_ignores_timestamp() {
+        let content = b"INVITE sip:bob@host SIP/2.0\r\n\
+            Via: SIP/2.0/UDP 10.0.0.1;branch=z9hG4bK1\r\n\
+            Call-ID: rt@host\r\n\
+            CSeq: 1 INVITE\r\n\
+            Content-Length: 3\r\n\r\nv=0";
+        let first = udp_retransmission(0, content);
+        let second = udp_retransmission(1, content);
+        assert_ne!(first, second);
+        assert_eq!(first, first.clone());
+
+        let a = first.parse().unwrap();
+        let b = second.parse().unwrap();
+        assert_eq!(a.fingerprint(), b.fingerprint());
+
+        let mut changed = b.clone();
+        changed.body = b"v=1".to_vec();
+        assert_ne!(a.fingerprint(), changed.fingerprint());
+        // Headers outside the key set do not participate
+        changed.body = a.body.clone();
+        changed.set_header("User-Agent", "retransmitter");
+        assert_eq!(a.fingerprint(), changed.fingerprint());
+    }
+
+    #[test]
+    fn fingerprint_matches_compact_forms_in_any_case() {
+        let full = udp_retransmission(
+            0,
+            b"OPTIONS sip:bob@host SIP/2.0\r\nCall-ID: c@host\r\n\r\n",
+        );
+        let compact = udp_retransmission(1, b"OPTIONS sip:bob@host SIP/2.0\r\nI: c@host\r\n\r\n");
+        let other = udp_retransmission(2, b"OPTIONS sip:bob@host SIP/2.0\r\nI: d@host\r\n\r\n");
+        let full = full.parse().unwrap().fingerprint();
+        assert_eq!(full, compact.parse().unwrap().fingerprint());
+        assert_ne!(full, other.parse().unwrap().fingerprint());
+    }
+
+    fn header_fixture() -> ParsedSipMessage {
+        make_parsed(
+            SipMessageType::Request {
+                method: "INVITE".into(),
+                uri: "sip:bob@host".into(),
+            },
+            vec![
+                ("Via", "SIP/2.0/UDP a"),
+                ("Via", "SIP/2.0/UDP b"),
+                ("Call-ID", "x1"),
+                ("Contact", "<sip:a@a>"),
+            ],
+            b"",
+        )
+    }
+
+    #[test]
+    fn split_address_forms() {
+        assert_eq!(split_address("10.0.0.1:5060"), Some(("10.0.0.1", 5060)));
+        assert_eq!(
+            split_address("[2001:db8::1]:5061"),
+            Some(("2001:db8::1", 5061))
+        );
+        assert_eq!(
+            split_address("[fe80::1%eth0]:5060"),
+            Some(("fe80::1", 5060))
+        );
+        assert_eq!(
+            split_address("[::ffff:10.0.0.1]:5060"),
+            Some(("::ffff:10.0.0.1", 5060))
+        );
+        assert_eq!(split_address("10.0.0.1"), None);
+        assert_eq!(split_address("[::1]"), None);
+        assert_eq!(split_address(":5060"), None);
+    }
+
+    #[test]
+    fn ipv4_mapped_hosts() {
+        assert_eq!(
+            ipv4_mapped("::ffff:10.0.0.1"),
+            Some(Ipv4Addr::new(10, 0, 0, 1))
+        );
+        assert_eq!(ipv4_mapped("2001:db8::1"), None);
+        assert_eq!(ipv4_mapped("10.0.0.1"), None);
+    }
+
+    #[test]
+    fn set_header_replaces_all_in_place() {
+        let mut msg = header_fixture();
+        msg.set_header("via", "SIP/2.0/UDP c")
+            .set_header("X-New", "1");
+        assert_eq!(
+            msg.to_bytes(),
+            b"INVITE sip:bob@host SIP/2.0\r\n\
+            via: SIP/2.0/UDP c\r\n\
+            Call-ID: x1\r\n\
+            Contact: <sip:a@a>\r\n\
+            X-New: 1\r\n\r\n"
+        );
+    }
+
+    #[test]
+    fn replace_header_keeps_names_and_positions() {
+        let mut msg = make_parsed(
+            SipMessageType::Request {
+                method: "INVITE".into(),
+                uri: "sip:bob@host".into(),
+            },
+            vec![
+                ("v", "SIP/2.0/UDP a"),
+                ("i", "x1"),
+                ("VIA", "SIP/2.0/UDP b"),
+                ("f", "<sip:a@a>"),
+            ],
+            b"",
+        );
+        assert!(msg.has_header("Call-ID"));
+        assert!(msg.has_header("v"));
+        assert!(!msg.has_header("To"));
+        msg.replace_header("call-id", "y2")
+            .replace_header("Via", "SIP/2.0/UDP c")
+            .replace_header("From", "<sip:b@b>")
+            .replace_header("To", "<sip:c@c>");
+        assert_eq!(
+            msg.to_bytes(),
+            b"INVITE sip:bob@host SIP/2.0\r\n\
+            v: SIP/2.0/UDP c\r\n\
+            i: y2\r\n\
+            VIA: SIP/2.0/UDP c\r\n\
+            f: <sip:b@b>\r\n\r\n"
+        );
+    }
+
+    #[test]
+    fn remove_header_removes_all() {
+        let mut msg = header_fixture();
+        msg.remove_header("VIA").remove_header("X-Absent");
+        assert_eq!(
+            msg.to_bytes(),
+            b"INVITE sip:bob@host SIP/2.0\r\nCall-ID: x1\r\nContact: <sip:a@a>\r\n\r\n"
+        );
+    }
+
+    #[test]
+    fn insert_header_front_prepends() {
+        let mut msg = header_fixture();
+        msg.insert_header_front("Via", "SIP/2.0/UDP proxy");
+        let bytes = msg.to_bytes();
+        assert!(bytes.starts_with(
+            b"INVITE sip:bob@host SIP/2.0\r\nVia: SIP/2.0/UDP proxy\r\nVia: SIP/2.0/UDP a\r\n"
+        ));
+    }
+
+    #[test]
+    fn clone_with_header_leaves_original() {
+        let msg = header_fixture();
+        let changed = msg.clone_with_header("Call-ID", "x2");
+        assert_eq!(msg.call_id(), Some("x1"));
+        assert_eq!(changed.call_id(), Some("x2"));
+        assert_eq!(changed.headers.len(), msg.headers.len());
+    }
+
+    fn mime_part(headers: &[(&str, &str)], body: &[u8]) -> MimePart {
+        MimePart {
+            headers: headers
+                .iter()
+                .map(|(k, v)| (k.to_string(), v.to_string()))
+                .collect(),
+            body: body.to_vec(),
+        }
+    }
+
+    #[test]
+    fn decoded_body_base64() {
+        let part = mime_part(
+            &[("Content-Transfer-Encoding", "BASE64")],
+            b"PEVJRE8+SU5DLTQy\r\nPC9FSURPPg==\r\n",
+        );
+        assert_eq!(&*part.decoded_body(), b"<EIDO>INC-42</EIDO>");
+    }
+
+    #[test]
+    fn decoded_body_passthrough() {
+        let part = mime_part(&[("Content-Transfer-Encoding", "8bit")], b"v=0");
+        assert!(matches!(part.decoded_body(), Cow::Borrowed(b"v=0")));
+        let part = mime_part(&[("Content-Transfer-Encoding", "base64")], b"not base64!");
+        assert_eq!(&*part.decoded_body(), b"not base64!");
+    }
+
+    #[test]
+    fn timestamp_from_str() {
+        let parse = |s: &str| s.parse::<Timestamp>().ok();
+        assert_eq!(parse("12:00:00.123"), Some(time_only(12, 0, 0, 123000)));
+        assert_eq!(parse("23:59:59"), Some(time_only(23, 59, 59, 0)));
+        assert_eq!(
+            parse("2026-02-01 10:00:00"),
+            Some(date_time(2026, 2, 1, 10, 0, 0))
+        );
+    }
+
+    #[test]
+    fn timestamp_from_str_rejects_garbage() {
+        for bad in [
+            "",
+            "12:00",
+            "12:00:00.",
+            "12:00:00.5x",
+            "12:00:00 ",
+            "2026-02-01",
+        ] {
+            assert!(bad.parse::<Timestamp>().is_err(), "{bad:?}");
+        }
+    }
+
+    #[test]
+    fn timestamp_display_round_trip() {
+        // Deterministic LCG so failures are reproducible without a proptest dependency
+        let mut state: u64 = 0x2545_f491_4f6c_dd1d;
+        let mut next = |bound: u64| {
+            state = state
+                .wrapping_mul(6_364_136_223_846_793_005)
+                .wrapping_add(1_442_695_040_888_963_407);
+            (state >> 33) % bound
+        };
+        for _ in 0..1000 {
+            let (hour, min, sec) = (next(24) as u8, next(60) as u8, next(60) as u8);
+            let usec = next(1_000_000) as u32;
+            let ts = if next(2) == 0 {
+                time_only(hour, min, sec, usec)
+            } else {
+                Timestamp::DateTime {
+                    year: next(10_000) as u16,
+                    month: next(12) as u8 + 1,
+                    day: next(28) as u8 + 1,
+                    hour,
+                    min,
+                    sec,
+                    usec,
+                }
+            };
+            assert_eq!(ts.to_string().parse::<Timestamp>().ok(), Some(ts), "{ts}");
+        }
+    }
+
+    fn time_only(hour: u8, min: u8, sec: u8, usec: u32) -> Timestamp {
+        Timestamp::TimeOnly {
+            hour,
+            min,
+            sec,
+            usec,
+        }
+    }
+
+    fn date_time(year: u16, month: u8, day: u8, hour: u8, min: u8, sec: u8) -> Timestamp {
+        Timestamp::DateTime {
+            year,
+            month,
+            day,
+            hour,
+            min,
+            sec,
+            usec: 0,
+        }
+    }
+
+    #[test]
+    fn duration_since_time_only() {
+        let a = time_only(12, 0, 0, 0);
+        let b = time_only(12, 0, 1, 500_000);
+        assert_eq!(b.duration_since(&a), Some(Duration::from_millis(1500)));
+        assert_eq!(a.duration_since(&a), Some(Duration::ZERO));
+        assert_eq!(a.duration_since(&b), None);
+    }
+
+    #[test]
+    fn duration_since_time_only_midnight_wrap() {
+        let before = time_only(23, 59, 59, 900_000);
+        let after = time_only(0, 0, 0, 100_000);
+        assert_eq!(
+            after.duration_since(&before),
+            Some(Duration::from_millis(200))
+        );
+    }
+
+    #[test]
+    fn duration_since_date_time_across_dates() {
+        let before = date_time(2025, 12, 31, 23, 59, 59);
+        let after = date_time(2026, 1, 1, 0, 0, 1);
+        assert_eq!(after.duration_since(&before), Some(Duration::from_secs(2)));
+        assert_eq!(before.duration_since(&after), None);
+
+        let feb = date_time(2024, 2, 28, 12, 0, 0);
+        let mar = date_time(2024, 3, 1, 12, 0, 0);
+        assert_eq!(
+            mar.duration_since(&feb),
+            Some(Duration::from_secs(2 * 86_400))
+        );
+    }
+
+    #[test]
+    fn shifted_crosses_midnight() {
+        let late = date_time(2025, 12, 31, 22, 30, 0);
+        assert_eq!(late.shifted(5 * 60), date_time(2026, 1, 1, 3, 30, 0));
+        assert_eq!(
+            date_time(2024, 3, 1, 1, 0, 0).shifted(-4 * 60),
+            date_time(2024, 2, 29, 21, 0, 0)
+        );
+        assert_eq!(
+            time_only(23, 15, 0, 250_000).shifted(90),
+            time_only(0, 45, 0, 250_000)
+        );
+        assert_eq!(time_only(0, 10, 0, 0).shifted(-30), time_only(23, 40, 0, 0));
+        assert_eq!(late.shifted(0), late);
+    }
+
+    #[test]
+    fn duration_since_mixed_variants() {
+        let a = time_only(12, 0, 0, 0);
+        let b = date_time(2026, 1, 1, 12, 0, 0);
+        assert_eq!(a.duration_since(&b), None);
+        assert_eq!(b.duration_since(&a), None);
+    }
+
+    #[test]
+    fn frame_header_line_matches_dump_format() {
+        let frame = Frame {
+            direction: Direction::Recv,
+            byte_count: 1440,
+            transport: Transport::Tcp,
+            address: "[2001:db8::1]:30046".into(),
+            timestamp: Timestamp::TimeOnly {
+                hour: 13,
+                min: 3,
+                sec: 21,
+                usec: 674883,
+            },
+            content: Vec::new(),
+            offset: 0,
+        };
+        let line = frame.header_line();
+        assert_eq!(
+            line,
+            "recv 1440 bytes from tcp/[2001:db8::1]:30046 at 13:03:21.674883:"
+        );
+
+        let wire = format!("{line}\n");
+        let (direction, byte_count, transport, address, timestamp, len) =
+            crate::frame::parse_frame_header(wire.as_bytes()).unwrap();
+        assert_eq!(direction, frame.direction);
+        assert_eq!(byte_count, frame.byte_count);
+        assert_eq!(transport, frame.transport);
+        assert_eq!(address, frame.address);
+        assert_eq!(timestamp, frame.timestamp);
+        assert_eq!(len, wire.len());
+    }
+
+    #[test]
+    fn sip_message_header_line_uses_content_length() {
+        let msg = SipMessage {
+            direction: Direction::Sent,
+            transport: Transport::Udp,
+            address: "10.0.0.1:5060".into(),
+            timestamp: Timestamp::DateTime {
+                year: 2026,
+                month: 2,
+                day: 1,
+                hour: 10,
+                min: 0,
+                sec: 0,
+                usec: 0,
+            },
+            last_timestamp: Timestamp::DateTime {
+                year: 2026,
+                month: 2,
+                day: 1,
+                hour: 10,
+                min: 0,
+                sec: 1,
+                usec: 0,
+            },
+            content: b"hello".to_vec(),
+            frame_count: 3,
+        };
+        assert_eq!(
+            msg.header_line(),
+            "sent 5 bytes to udp/10.0.0.1:5060 at 2026-02-01 10:00:00.000000:"
+        );
+    }
+
     #[test]
     fn to_bytes_request_no_body() {
         let msg = make_parsed(
@@ -321,6 +1372,25 @@ mod tests {
         assert!(bytes.ends_with(body));
     }
 
+    #[test]
+    fn display_matches_to_bytes() {
+        let msg = make_parsed(
+            SipMessageType::Response {
+                code: 200,
+                reason: "OK".into(),
+            },
+            vec![("Call-ID", "test"), ("CSeq", "1 INVITE")],
+            b"v=0\r\n\xff",
+        );
+        let text = msg.to_string();
+        assert!(text.starts_with("SIP/2.0 200 OK\r\n"));
+        assert!(text.contains("Call-ID: test\r\n"));
+        assert!(text.contains("CSeq: 1 INVITE\r\n"));
+        assert!(text.ends_with("\r\n\r\nv=0\r\n\u{FFFD}"));
+        assert_eq!(text, msg.to_string_lossy());
+        assert_eq!(text, String::from_utf8_lossy(&msg.to_bytes()));
+    }
+
     #[test]
     fn to_bytes_response() {
         let msg = make_parsed(
@@ -336,6 +1406,69 @@ mod tests {
         assert!(text.starts_with("SIP/2.0 200 OK\r\n"));
     }
 
+    #[test]
+    fn request_helpers() {
+        let msg = make_parsed(
+            SipMessageType::Request {
+                method: "INVITE".into(),
+                uri: "sip:host".into(),
+            },
+            vec![("CSeq", "1 INVITE")],
+            b"",
+        );
+        assert!(msg.is_request());
+        assert!(!msg.is_response());
+        assert_eq!(msg.status_code(), None);
+        assert_eq!(msg.request_method(), Some("INVITE"));
+    }
+
+    #[test]
+    fn response_helpers() {
+        let msg = make_parsed(
+            SipMessageType::Response {
+                code: 486,
+                reason: "Busy Here".into(),
+            },
+            vec![("CSeq", "1 INVITE")],
+            b"",
+        );
+        assert!(msg.is_response());
+        assert!(!msg.is_request());
+        assert_eq!(msg.status_code(), Some(486));
+        assert_eq!(msg.reason_phrase(), Some("Busy Here"));
+        assert_eq!(msg.request_method(), None);
+        assert_eq!(msg.method(), Some("INVITE"));
+    }
+
+    #[test]
+    fn canonicalize_reason_known_code() {
+        let mut msg = make_parsed(
+            SipMessageType::Response {
+                code: 486,
+                reason: "User Busy (carrier 42)".into(),
+            },
+            vec![],
+            b"",
+        );
+        assert!(msg.canonicalize_reason());
+        assert_eq!(msg.reason_phrase(), Some("Busy Here"));
+        assert!(!msg.canonicalize_reason());
+    }
+
+    #[test]
+    fn canonicalize_reason_unknown_code() {
+        let mut msg = make_parsed(
+            SipMessageType::Response {
+                code: 299,
+                reason: "Carrier Special".into(),
+            },
+            vec![],
+            b"",
+        );
+        assert!(!msg.canonicalize_reason());
+        assert_eq!(msg.reason_phrase(), Some("Carrier Special"));
+    }
+
     #[test]
     fn body_text_valid_utf8() {
         let msg = make_parsed(
@@ -374,4 +1507,24 @@ mod tests {
         );
         assert!(msg.body_text().contains('\u{FFFD}'));
     }
+
+    #[test]
+    fn body_text_decoded_utf16le_bom() {
+        let mut body = vec![0xFF, 0xFE];
+        for unit in "<presence>é</presence>".encode_utf16() {
+            body.extend_from_slice(&unit.to_le_bytes());
+        }
+        let msg = make_parsed(
+            SipMessageType::Request {
+                method: "NOTIFY".into(),
+                uri: "sip:host".into(),
+            },
+            vec![],
+            &body,
+        );
+        assert_eq!(&*msg.body_text_decoded(), "<presence>é</presence>");
+        assert_eq!(decode_bom_text(b"\xFE\xFF\x00<\x00a"), "<a");
+        assert_eq!(decode_bom_text(b"\xEF\xBB\xBFv=0"), "v=0");
+        assert_eq!(decode_bom_text(b"v=0\xFF"), "v=0\u{FFFD}");
+    }
 }
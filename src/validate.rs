@@ -0,0 +1,243 @@
+use std::fmt;
+
+use crate::types::{ParsedSipMessage, Transport};
+
+/// An RFC 3261 conformance problem found by `ParsedSipMessage::validate()`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Violation {
+    /// A mandatory header is absent (Section 8.1.1, and 18.3 for
+    /// Content-Length on stream transports).
+    MissingHeader(&'static str),
+    /// CSeq has no parseable `number method` pair.
+    InvalidCSeq(String),
+    /// CSeq method differs from the request-line method (Section 8.1.1.5).
+    CSeqMethodMismatch { request: String, cseq: String },
+    /// Content-Length does not match the body length (Section 20.14).
+    ContentLengthMismatch { declared: usize, actual: usize },
+    /// Status code outside the three-digit 100-699 range (Section 25.1).
+    InvalidStatusCode(u16),
+}
+
+impl fmt::Display for Violation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Violation::MissingHeader(name) => write!(f, "missing {name} header"),
+            Violation::InvalidCSeq(value) => write!(f, "invalid CSeq '{value}'"),
+            Violation::CSeqMethodMismatch { request, cseq } => {
+                write!(
+                    f,
+                    "CSeq method {cseq} does not match request method {request}"
+                )
+            }
+            Violation::ContentLengthMismatch { declared, actual } => {
+                write!(f, "Content-Length {declared} but body is {actual} bytes")
+            }
+            Violation::InvalidStatusCode(code) => {
+                write!(f, "status code {code} outside 100-699")
+            }
+        }
+    }
+}
+
+/// Mandatory headers as (full name, compact form).
+const REQUIRED_HEADERS: &[(&str, Option<&str>)] = &[
+    ("Via", Some("v")),
+    ("From", Some("f")),
+    ("To", Some("t")),
+    ("Call-ID", Some("i")),
+    ("CSeq", None),
+];
+
+impl ParsedSipMessage {
+    /// Run RFC 3261 sanity checks; an empty list means the message passed.
+    pub fn validate(&self) -> Vec<Violation> {
+        let mut violations = Vec::new();
+
+        if let Some(code) = self.status_code() {
+            if !(100..=699).contains(&code) {
+                violations.push(Violation::InvalidStatusCode(code));
+            }
+        }
+
+        for &(name, compact) in REQUIRED_HEADERS {
+            let present = self.header_value(name).is_some()
+                || compact.is_some_and(|c| self.header_value(c).is_some());
+            if !present {
+                violations.push(Violation::MissingHeader(name));
+            }
+        }
+
+        if let Some(method) = self.request_method() {
+            if self.header_value("Max-Forwards").is_none() {
+                violations.push(Violation::MissingHeader("Max-Forwards"));
+            }
+            if let Some(cseq) = self.cseq() {
+                match parse_cseq_method(cseq) {
+                    Some(cseq_method) if cseq_method != method => {
+                        violations.push(Violation::CSeqMethodMismatch {
+                            request: method.to_string(),
+                            cseq: cseq_method.to_string(),
+                        });
+                    }
+                    Some(_) => {}
+                    None => violations.push(Violation::InvalidCSeq(cseq.to_string())),
+                }
+            }
+        } else if let Some(cseq) = self.cseq() {
+            if parse_cseq_method(cseq).is_none() {
+                violations.push(Violation::InvalidCSeq(cseq.to_string()));
+            }
+        }
+
+        match self.content_length() {
+            Some(declared) if declared != self.body.len() => {
+                violations.push(Violation::ContentLengthMismatch {
+                    declared,
+                    actual: self.body.len(),
+                });
+            }
+            Some(_) => {}
+            None if matches!(self.transport, Transport::Tcp | Transport::Tls) => {
+                violations.push(Violation::MissingHeader("Content-Length"));
+            }
+            None => {}
+        }
+
+        violations
+    }
+}
+
+fn parse_cseq_method(cseq: &str) -> Option<&str> {
+    let mut fields = cseq.split_whitespace();
+    fields.next()?.parse::<u32>().ok()?;
+    let method = fields.next()?;
+    fields.next().is_none().then_some(method)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::types::{Direction, SipMessage, Timestamp};
+
+    fn parse(transport: Transport, content: &[u8]) -> ParsedSipMessage {
+        SipMessage {
+            direction: Direction::Recv,
+            transport,
+            address: "10.0.0.1:5060".into(),
+            timestamp: Timestamp::TimeOnly {
+                hour: 12,
+                min: 0,
+                sec: 0,
+                usec: 0,
+            },
+            last_timestamp: Timestamp::TimeOnly {
+                hour: 12,
+                min: 0,
+                sec: 0,
+                usec: 0,
+            },
+            content: content.to_vec(),
+            frame_count: 1,
+        }
+        .parse()
+        .unwrap()
+    }
+
+    #[test]
+    fn valid_request() {
+        let msg = parse(
+            Transport::Tcp,
+            b"INVITE sip:bob@host SIP/2.0\r\n\
+            v: SIP/2.0/TCP 10.0.0.1;branch=z9hG4bK1\r\n\
+            Max-Forwards: 70\r\n\
+            From: <sip:alice@host>;tag=1\r\n\
+            To: <sip:bob@host>\r\n\
+            Call-ID: ok@host\r\n\
+            CSeq: 1 INVITE\r\n\
+            Content-Length: 3\r\n\r\nabc",
+        );
+        assert_eq!(msg.validate(), []);
+    }
+
+    #[test]
+    fn missing_via() {
+        let msg = parse(
+            Transport::Udp,
+            b"OPTIONS sip:host SIP/2.0\r\n\
+            Max-Forwards: 70\r\n\
+            From: <sip:alice@host>;tag=1\r\n\
+            To: <sip:host>\r\n\
+            Call-ID: novia@host\r\n\
+            CSeq: 1 OPTIONS\r\n\r\n",
+        );
+        assert_eq!(msg.validate(), [Violation::MissingHeader("Via")]);
+    }
+
+    #[test]
+    fn cseq_method_mismatch() {
+        let msg = parse(
+            Transport::Udp,
+            b"BYE sip:host SIP/2.0\r\n\
+            Via: SIP/2.0/UDP 10.0.0.1;branch=z9hG4bK1\r\n\
+            Max-Forwards: 70\r\n\
+            From: <sip:alice@host>;tag=1\r\n\
+            To: <sip:host>;tag=2\r\n\
+            Call-ID: mismatch@host\r\n\
+            CSeq: 2 INVITE\r\n\
+            Content-Length: 0\r\n\r\n",
+        );
+        assert_eq!(
+            msg.validate(),
+            [Violation::CSeqMethodMismatch {
+                request: "BYE".into(),
+                cseq: "INVITE".into(),
+            }]
+        );
+    }
+
+    #[test]
+    fn response_checks() {
+        let msg = parse(
+            Transport::Tls,
+            b"SIP/2.0 200 OK\r\n\
+            Via: SIP/2.0/TLS 10.0.0.1;branch=z9hG4bK1\r\n\
+            From: <sip:alice@host>;tag=1\r\n\
+            To: <sip:bob@host>;tag=2\r\n\
+            Call-ID: resp@host\r\n\
+            CSeq: one INVITE\r\n\r\n",
+        );
+        assert_eq!(
+            msg.validate(),
+            [
+                Violation::InvalidCSeq("one INVITE".into()),
+                Violation::MissingHeader("Content-Length"),
+            ]
+        );
+    }
+
+    #[test]
+    fn status_code_out_of_range() {
+        for (line, code) in [("SIP/2.0 99 Weird", 99), ("SIP/2.0 700 Nope", 700)] {
+            let content = format!(
+                "{line}\r\n\
+                Via: SIP/2.0/UDP 10.0.0.1;branch=z9hG4bK1\r\n\
+                From: <sip:alice@host>;tag=1\r\n\
+                To: <sip:bob@host>;tag=2\r\n\
+                Call-ID: code@host\r\n\
+                CSeq: 1 INVITE\r\n\r\n"
+            );
+            let msg = parse(Transport::Udp, content.as_bytes());
+            assert_eq!(msg.status_code(), Some(code));
+            assert_eq!(msg.validate(), [Violation::InvalidStatusCode(code)]);
+        }
+    }
+
+    #[test]
+    fn violation_display() {
+        let v = Violation::ContentLengthMismatch {
+            declared: 10,
+            actual: 4,
+        };
+        assert_eq!(v.to_string(), "Content-Length 10 but body is 4 bytes");
+    }
+}
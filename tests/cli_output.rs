@@ -0,0 +1,550 @@
+use std::path::PathBuf;
+use std::process::Command;
+
+const DUMP: &[u8] = b"recv 52 bytes from udp/10.0.0.1:5060 at 00:00:00.000000:\n\
+    OPTIONS sip:a@host SIP/2.0\r\nContent-Length: 0\r\n\r\n\x0B\n\
+    recv 53 bytes from udp/10.0.0.1:5060 at 00:00:01.000000:\n\
+    NOTIFY sip:a@host SIP/2.0\r\nContent-Length: 0\r\n\r\n\x0B\n";
+
+fn temp_path(name: &str) -> PathBuf {
+    std::env::temp_dir().join(format!("sofia-cli-{}-{name}", std::process::id()))
+}
+
+fn parser() -> Command {
+    Command::new(env!("CARGO_BIN_EXE_freeswitch-sofia-trace-parser"))
+}
+
+fn udp_frame(direction: &str, content: &str) -> String {
+    let prep = if direction == "recv" { "from" } else { "to" };
+    format!(
+        "{direction} {} bytes {prep} udp/10.0.0.1:5060 at 00:00:00.000000:\n{content}\x0B\n",
+        content.len()
+    )
+}
+
+#[test]
+fn output_file_matches_stdout() {
+    let input = temp_path("in.dump");
+    let output = temp_path("out.txt");
+    std::fs::write(&input, DUMP).unwrap();
+
+    for mode in [
+        &["--all-methods"][..],
+        &["--all-methods", "--full"],
+        &["--stats"],
+    ] {
+        let stdout = parser().args(mode).arg(&input).output().unwrap();
+        assert!(stdout.status.success());
+        assert!(!stdout.stdout.is_empty());
+
+        let to_file = parser()
+            .args(mode)
+            .arg("--output")
+            .arg(&output)
+            .arg(&input)
+            .output()
+            .unwrap();
+        assert!(to_file.status.success());
+        assert!(to_file.stdout.is_empty());
+        assert_eq!(std::fs::read(&output).unwrap(), stdout.stdout, "{mode:?}");
+    }
+
+    std::fs::remove_file(&input).unwrap();
+    std::fs::remove_file(&output).unwrap();
+}
+
+#[test]
+fn stats_json() {
+    let input = temp_path("stats.dump");
+    let dump = [
+        udp_frame(
+            "recv",
+            "INVITE sip:b@host SIP/2.0\r\nCall-ID: j1\r\nCSeq: 1 INVITE\r\n\r\n",
+        ),
+        udp_frame(
+            "sent",
+            "SIP/2.0 100 Trying\r\nCall-ID: j1\r\nCSeq: 1 INVITE\r\n\r\n",
+        ),
+        udp_frame(
+            "sent",
+            "SIP/2.0 200 OK\r\nCall-ID: j1\r\nCSeq: 1 INVITE\r\n\r\n",
+        ),
+        udp_frame(
+            "recv",
+            "BYE sip:b@host SIP/2.0\r\nCall-ID: j1\r\nCSeq: 2 BYE\r\n\r\n",
+        ),
+        udp_frame(
+            "sent",
+            "SIP/2.0 200 OK\r\nCall-ID: j1\r\nCSeq: 3 IN\"V\\\r\n\r\n",
+        ),
+        udp_frame(
+            "recv",
+            "OPTIONS sip:b@host SIP/2.0\r\nCSeq: 1 OPTIONS\r\n\r\n",
+        ),
+        udp_frame("recv", "garbage without a line ending"),
+    ]
+    .concat();
+    std::fs::write(&input, dump).unwrap();
+
+    let out = parser()
+        .args(["--stats", "--json"])
+        .arg(&input)
+        .output()
+        .unwrap();
+    assert!(out.status.success());
+    let stats: serde_json::Value = serde_json::from_slice(&out.stdout).unwrap();
+    assert_eq!(
+        stats,
+        serde_json::json!({
+            "total": 7,
+            "matched": 5,
+            "errors": 1,
+            "sigcomp": 0,
+            "recv": 2,
+            "sent": 3,
+            "methods": {"BYE": 1, "INVITE": 3, "IN\"V\\": 1},
+            "response_codes": {"100": 1, "200": 2},
+            "transports": {"udp": {"frames": 5, "messages": 5}},
+        })
+    );
+
+    std::fs::remove_file(&input).unwrap();
+}
+
+#[test]
+fn stats_json_schema_lists_every_key() {
+    let input = temp_path("schema.dump");
+    std::fs::write(
+        &input,
+        udp_frame(
+            "recv",
+            "NOTIFY sip:b@host SIP/2.0\r\nCall-ID: s1\r\nCSeq: 1 NOTIFY\r\n\r\n",
+        ),
+    )
+    .unwrap();
+    let out = parser()
+        .args(["--stats", "--json", "--list-call-ids", "--dedup"])
+        .arg(&input)
+        .output()
+        .unwrap();
+    assert!(out.status.success());
+    let stats: serde_json::Value = serde_json::from_slice(&out.stdout).unwrap();
+    std::fs::remove_file(&input).unwrap();
+    // Optional keys are checked against the schema too
+    assert!(stats.get("call_ids").is_some());
+    assert!(stats.get("retransmissions_suppressed").is_some());
+
+    let out = parser().arg("--print-json-schema").output().unwrap();
+    assert!(out.status.success());
+    let schema: serde_json::Value = serde_json::from_slice(&out.stdout).unwrap();
+    assert_eq!(
+        schema["required"],
+        serde_json::json!([
+            "total",
+            "matched",
+            "errors",
+            "sigcomp",
+            "recv",
+            "sent",
+            "methods",
+            "response_codes",
+            "transports"
+        ])
+    );
+    for key in stats.as_object().unwrap().keys() {
+        assert!(
+            schema["properties"].get(key).is_some(),
+            "{key} not in schema"
+        );
+    }
+}
+
+/// Classic pcap with one Ethernet/IPv4/UDP datagram from 10.0.0.1:5060 to 10.0.0.2:5060.
+fn udp_pcap(payload: &[u8]) -> Vec<u8> {
+    let udp_len = 8 + payload.len() as u16;
+    let mut ip = vec![
+        0x45, 0, 0, 0, 0, 0, 0x40, 0, 64, 17, 0, 0, 10, 0, 0, 1, 10, 0, 0, 2,
+    ];
+    ip[2..4].copy_from_slice(&(20 + udp_len).to_be_bytes());
+    ip.extend_from_slice(&[0x13, 0xc4, 0x13, 0xc4]);
+    ip.extend_from_slice(&udp_len.to_be_bytes());
+    ip.extend_from_slice(&[0, 0]);
+    ip.extend_from_slice(payload);
+    let mut packet = vec![0; 12];
+    packet.extend_from_slice(&[0x08, 0x00]);
+    packet.extend_from_slice(&ip);
+
+    let mut pcap = vec![0xd4, 0xc3, 0xb2, 0xa1, 2, 0, 4, 0, 0, 0, 0, 0, 0, 0, 0, 0];
+    pcap.extend_from_slice(&[0xff, 0xff, 0, 0, 1, 0, 0, 0]);
+    pcap.extend_from_slice(&1_704_164_645u32.to_le_bytes());
+    pcap.extend_from_slice(&0u32.to_le_bytes());
+    pcap.extend_from_slice(&(packet.len() as u32).to_le_bytes());
+    pcap.extend_from_slice(&(packet.len() as u32).to_le_bytes());
+    pcap.extend_from_slice(&packet);
+    pcap
+}
+
+#[test]
+fn pcap_input_udp_options() {
+    let input = temp_path("options.pcap");
+    let options = b"OPTIONS sip:a@host SIP/2.0\r\nCall-ID: pcap1\r\nContent-Length: 0\r\n\r\n";
+    std::fs::write(&input, udp_pcap(options)).unwrap();
+
+    let out = parser()
+        .args(["--input-format", "pcap", "--all-methods", "--full"])
+        .args(["--pcap-local", "10.0.0.2"])
+        .arg(&input)
+        .output()
+        .unwrap();
+    assert!(out.status.success());
+    let stdout = String::from_utf8(out.stdout).unwrap();
+    assert!(stdout.contains("recv"), "{stdout}");
+    assert!(stdout.contains("udp/10.0.0.1:5060"), "{stdout}");
+    assert!(stdout.contains("Call-ID: pcap1"), "{stdout}");
+
+    let sent = parser()
+        .args(["--input-format", "pcap", "--all-methods", "-d", "sent"])
+        .args(["--pcap-local", ":5060"])
+        .arg(&input)
+        .output()
+        .unwrap();
+    assert!(sent.status.success());
+    assert!(String::from_utf8(sent.stdout)
+        .unwrap()
+        .contains("10.0.0.2:5060"));
+
+    let misuse = parser()
+        .args(["--pcap-port", "5080"])
+        .arg(&input)
+        .output()
+        .unwrap();
+    assert_eq!(misuse.status.code(), Some(2));
+
+    std::fs::remove_file(&input).unwrap();
+}
+
+#[test]
+fn split_by_call_id_writes_one_dump_per_dialog() {
+    let input = temp_path("split.dump");
+    let dir = temp_path("split");
+    let dump = [
+        udp_frame(
+            "recv",
+            "INVITE sip:b@host SIP/2.0\r\nCall-ID: a/b\r\nCSeq: 1 INVITE\r\n\r\n",
+        ),
+        udp_frame(
+            "recv",
+            "INVITE sip:b@host SIP/2.0\r\nCall-ID: a:b\r\nCSeq: 1 INVITE\r\n\r\n",
+        ),
+        udp_frame(
+            "sent",
+            "SIP/2.0 200 OK\r\nCall-ID: a/b\r\nCSeq: 1 INVITE\r\n\r\n",
+        ),
+        udp_frame(
+            "recv",
+            "INVITE sip:b@host SIP/2.0\r\nCall-ID: other\r\nCSeq: 1 INVITE\r\n\r\n",
+        ),
+    ]
+    .concat();
+    std::fs::write(&input, dump).unwrap();
+
+    let out = parser()
+        .args(["--call-id", "^a"])
+        .arg("--split-by-call-id")
+        .arg(&dir)
+        .arg(&input)
+        .output()
+        .unwrap();
+    assert!(out.status.success());
+
+    let mut names: Vec<String> = std::fs::read_dir(&dir)
+        .unwrap()
+        .map(|e| e.unwrap().file_name().into_string().unwrap())
+        .collect();
+    names.sort();
+    assert_eq!(names, ["a_b-2.dump", "a_b.dump"]);
+
+    let frames = |name: &str| {
+        let dump = std::fs::read_to_string(dir.join(name)).unwrap();
+        assert!(!dump.contains("Call-ID: other"), "{dump}");
+        dump.matches(" bytes ").count()
+    };
+    assert_eq!(frames("a_b.dump"), 2);
+    assert_eq!(frames("a_b-2.dump"), 1);
+    assert!(String::from_utf8(out.stdout)
+        .unwrap()
+        .contains("a_b-2.dump"));
+
+    std::fs::remove_file(&input).unwrap();
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn summary_frame_and_byte_columns() {
+    let input = temp_path("columns.dump");
+    let head = "MESSAGE sip:a@host SIP/2.0\r\nCall-ID: cols\r\nContent-Length: 5\r\n\r\n";
+    let dump = format!(
+        "recv {} bytes from tcp/10.0.0.1:5060 at 00:00:00.000000:\n{head}\x0B\n\
+         recv 5 bytes from tcp/10.0.0.1:5060 at 00:00:00.100000:\nhello\x0B\n",
+        head.len()
+    );
+    std::fs::write(&input, dump).unwrap();
+
+    let run = |args: &[&str]| {
+        let out = parser().args(args).arg(&input).output().unwrap();
+        assert!(out.status.success());
+        String::from_utf8(out.stdout).unwrap()
+    };
+    let plain = run(&[]);
+    assert!(plain.trim_end().ends_with(" cols"), "{plain}");
+    let extra = run(&["--show-frames", "--show-bytes"]);
+    assert_eq!(
+        extra,
+        format!("{} frames=2 bytes={}\n", plain.trim_end(), head.len() + 5)
+    );
+
+    std::fs::remove_file(&input).unwrap();
+}
+
+#[test]
+fn swap_direction_relabels_but_filters_on_dump_direction() {
+    let input = temp_path("swap.dump");
+    let dump = [
+        udp_frame(
+            "recv",
+            "INVITE sip:b@host SIP/2.0\r\nCall-ID: s1\r\nCSeq: 1 INVITE\r\n\r\n",
+        ),
+        udp_frame(
+            "sent",
+            "SIP/2.0 200 OK\r\nCall-ID: s1\r\nCSeq: 1 INVITE\r\n\r\n",
+        ),
+    ]
+    .concat();
+    std::fs::write(&input, dump).unwrap();
+
+    let run = |args: &[&str]| {
+        let out = parser().args(args).arg(&input).output().unwrap();
+        assert!(out.status.success());
+        String::from_utf8(out.stdout).unwrap()
+    };
+
+    let swapped = run(&["--swap-direction"]);
+    let lines: Vec<&str> = swapped.lines().collect();
+    assert!(
+        lines[0].contains(" sent ") && lines[0].contains("INVITE"),
+        "{swapped}"
+    );
+    assert!(
+        lines[1].contains(" recv ") && lines[1].contains("200 OK"),
+        "{swapped}"
+    );
+
+    let full = run(&["--swap-direction", "--full", "-m", "INVITE"]);
+    assert!(full.starts_with("sent to udp/10.0.0.1:5060"), "{full}");
+
+    // -d keeps the dump's own meaning: recv is still the INVITE
+    let filtered = run(&["--swap-direction", "-d", "recv"]);
+    assert_eq!(filtered.lines().count(), 1);
+    assert!(
+        filtered.contains(" sent ") && filtered.contains("INVITE"),
+        "{filtered}"
+    );
+
+    // Exported dumps keep the original direction
+    let exported = run(&["--swap-direction", "--export-dump", "-m", "INVITE"]);
+    assert!(exported.starts_with("recv "), "{exported}");
+
+    std::fs::remove_file(&input).unwrap();
+}
+
+/// Split CSV text into records per RFC 4180: quoted fields may hold commas,
+/// doubled quotes and line breaks.
+fn parse_csv(text: &str) -> Vec<Vec<String>> {
+    let mut records = Vec::new();
+    let mut record = Vec::new();
+    let mut field = String::new();
+    let mut quoted = false;
+    let mut chars = text.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '"' if quoted && chars.peek() == Some(&'"') => {
+                field.push('"');
+                chars.next();
+            }
+            '"' => quoted = !quoted,
+            ',' if !quoted => record.push(std::mem::take(&mut field)),
+            '\n' if !quoted => {
+                record.push(std::mem::take(&mut field));
+                records.push(std::mem::take(&mut record));
+            }
+            c => field.push(c),
+        }
+    }
+    records
+}
+
+#[test]
+fn csv_rows_round_trip() {
+    let input = temp_path("csv.dump");
+    let dump = [
+        udp_frame(
+            "recv",
+            "INVITE sip:b@host SIP/2.0\r\nCall-ID: c1\r\nCSeq: 1 INVITE\r\nContent-Length: 0\r\n\r\n",
+        ),
+        udp_frame(
+            "sent",
+            "SIP/2.0 486 Busy, \"really\" Here\r\nCall-ID: c1\r\nCSeq: 1 INVITE\r\n\r\n",
+        ),
+    ]
+    .concat();
+    std::fs::write(&input, dump).unwrap();
+
+    let out = parser().arg("--csv").arg(&input).output().unwrap();
+    assert!(out.status.success());
+    let records = parse_csv(&String::from_utf8(out.stdout).unwrap());
+    assert_eq!(records.len(), 3);
+    let header = &records[0];
+    assert_eq!(header.len(), 12);
+    assert_eq!(header[0], "timestamp");
+    let column = |row: &Vec<String>, name: &str| {
+        let i = header.iter().position(|h| h == name).unwrap();
+        row[i].clone()
+    };
+
+    let invite = &records[1];
+    assert_eq!(invite.len(), 12);
+    assert_eq!(column(invite, "type"), "request");
+    assert_eq!(column(invite, "method"), "INVITE");
+    assert_eq!(column(invite, "status"), "");
+    assert_eq!(column(invite, "content_length"), "0");
+    assert_eq!(column(invite, "frame_count"), "1");
+
+    let busy = &records[2];
+    assert_eq!(busy.len(), 12);
+    assert_eq!(column(busy, "direction"), "sent");
+    assert_eq!(column(busy, "address"), "10.0.0.1:5060");
+    assert_eq!(column(busy, "status"), "486");
+    assert_eq!(column(busy, "reason"), "Busy, \"really\" Here");
+    assert_eq!(column(busy, "call_id"), "c1");
+    assert_eq!(column(busy, "cseq"), "1 INVITE");
+    assert_eq!(column(busy, "content_length"), "");
+
+    std::fs::remove_file(&input).unwrap();
+}
+
+#[test]
+fn quiet_exit_codes_tell_errors_from_no_match() {
+    let input = temp_path("quiet.dump");
+    std::fs::write(
+        &input,
+        udp_frame("recv", "NOTIFY sip:a@host SIP/2.0\r\nCall-ID: q1\r\n\r\n"),
+    )
+    .unwrap();
+    let code = |args: &[&str], path: &std::path::Path| {
+        let out = parser()
+            .arg("--quiet")
+            .args(args)
+            .arg(path)
+            .output()
+            .unwrap();
+        assert!(out.stdout.is_empty());
+        out.status.code()
+    };
+    assert_eq!(code(&["--call-id", "q1"], &input), Some(0));
+    assert_eq!(code(&["--call-id", "other"], &input), Some(1));
+    assert_eq!(code(&[], &temp_path("missing.dump")), Some(2));
+
+    std::fs::remove_file(&input).unwrap();
+}
+
+#[test]
+fn strip_ansi_reads_colored_grep_output() {
+    let input = temp_path("ansi.dump");
+    let frame = udp_frame(
+        "recv",
+        "OPTIONS sip:a@host SIP/2.0\r\nCall-ID: ansi1\r\nCSeq: 1 OPTIONS\r\n\r\n",
+    );
+    let colored = frame
+        .replacen("recv", "\x1b[01;31m\x1b[Krecv\x1b[m\x1b[K", 1)
+        .replacen("ansi1", "\x1b[01;31m\x1b[Kansi1\x1b[m\x1b[K", 1);
+    std::fs::write(&input, colored).unwrap();
+
+    let run = |args: &[&str]| {
+        let out = parser()
+            .args(["--all-methods", "--list-call-ids"])
+            .args(args)
+            .arg(&input)
+            .output()
+            .unwrap();
+        assert!(out.status.success());
+        String::from_utf8(out.stdout).unwrap()
+    };
+    assert_eq!(run(&[]), "");
+    assert_eq!(run(&["--strip-ansi"]), "ansi1\n");
+
+    std::fs::remove_file(&input).unwrap();
+}
+
+#[test]
+fn grep_prefix_stripping_is_opt_in() {
+    let input = temp_path("prefix.dump");
+    let frame = udp_frame(
+        "recv",
+        "MESSAGE sip:a@host SIP/2.0\r\nCall-ID: gp1\r\nContent-Length: 7\r\n\r\n12:body",
+    );
+    std::fs::write(&input, &frame).unwrap();
+    let out = parser().args(["--body"]).arg(&input).output().unwrap();
+    assert!(out.status.success());
+    assert!(String::from_utf8(out.stdout).unwrap().contains("12:body"));
+
+    let prefixed: String = frame
+        .split_inclusive('\n')
+        .enumerate()
+        .map(|(i, line)| format!("sofia.log:{}:{line}", i + 1))
+        .collect();
+    std::fs::write(&input, prefixed).unwrap();
+    let out = parser()
+        .args(["--strip-grep-prefix", "--list-call-ids"])
+        .arg(&input)
+        .output()
+        .unwrap();
+    assert!(out.status.success());
+    assert_eq!(String::from_utf8(out.stdout).unwrap(), "gp1\n");
+
+    std::fs::remove_file(&input).unwrap();
+}
+
+#[test]
+fn merge_interleaves_files_by_timestamp() {
+    let frame = |sec: u8, call_id: &str| {
+        let content = format!("NOTIFY sip:a@host SIP/2.0\r\nCall-ID: {call_id}\r\n\r\n");
+        format!(
+            "recv {} bytes from udp/10.0.0.1:5060 at 00:00:0{sec}.000000:\n{content}\x0B\n",
+            content.len()
+        )
+    };
+    let first = temp_path("merge-a.dump");
+    let second = temp_path("merge-b.dump");
+    std::fs::write(&first, frame(0, "m0") + &frame(2, "m2")).unwrap();
+    std::fs::write(&second, frame(1, "m1") + &frame(3, "m3")).unwrap();
+
+    let call_ids = |merge: bool| {
+        let mut cmd = parser();
+        if merge {
+            cmd.arg("--merge");
+        }
+        let out = cmd
+            .args(["--list-call-ids"])
+            .arg(&first)
+            .arg(&second)
+            .output()
+            .unwrap();
+        assert!(out.status.success());
+        String::from_utf8(out.stdout).unwrap()
+    };
+    assert_eq!(call_ids(false), "m0\nm2\nm1\nm3\n");
+    assert_eq!(call_ids(true), "m0\nm1\nm2\nm3\n");
+
+    std::fs::remove_file(&first).unwrap();
+    std::fs::remove_file(&second).unwrap();
+}
@@ -88,7 +88,7 @@ fn esinet1_v4_tcp() {
     let mismatches: Vec<_> = frames
         .iter()
         .enumerate()
-        .filter(|(_, f)| f.byte_count != f.content.len())
+        .filter(|(_, f)| f.byte_count_mismatch())
         .collect();
     eprintln!(
         "  byte_count mismatches: {}/{}",
@@ -128,10 +128,7 @@ fn esinet1_v4_udp() {
     );
 
     // UDP frames should have byte_count == content.len() (no reassembly needed)
-    let mismatches = frames
-        .iter()
-        .filter(|f| f.byte_count != f.content.len())
-        .count();
+    let mismatches = frames.iter().filter(|f| f.byte_count_mismatch()).count();
     eprintln!("  byte_count mismatches: {}/{}", mismatches, frames.len());
 }
 
@@ -289,6 +286,7 @@ fn tcp_has_multiframe_sequences() {
 }
 
 #[test]
+#[allow(clippy::unnecessary_sort_by)]
 fn byte_count_distribution() {
     let frames = parse_sample("esinet1-v4-tcp.dump.20");
     if frames.is_empty() {
@@ -355,7 +353,7 @@ fn file_concatenation_two_dumps() {
     // Verify zero byte_count mismatches in concatenated parse
     let mismatches = combined_frames
         .iter()
-        .filter(|f| f.byte_count != f.content.len())
+        .filter(|f| f.byte_count_mismatch())
         .count();
     eprintln!("  byte_count mismatches: {mismatches}/{combined_count}");
     assert_eq!(
@@ -384,7 +382,7 @@ fn esinet1_v4_tcp_150() {
     let mismatches: Vec<_> = frames
         .iter()
         .enumerate()
-        .filter(|(_, f)| f.byte_count != f.content.len())
+        .filter(|(_, f)| f.byte_count_mismatch())
         .collect();
 
     eprintln!(
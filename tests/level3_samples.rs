@@ -101,6 +101,7 @@ fn tcp_all_messages_parse() {
 }
 
 #[test]
+#[allow(clippy::unnecessary_sort_by)]
 fn tcp_method_distribution() {
     let result = parse_file("esinet1-v4-tcp.dump.20");
     if result.total == 0 {
@@ -148,6 +149,7 @@ fn udp_all_messages_parse() {
 }
 
 #[test]
+#[allow(clippy::unnecessary_sort_by)]
 fn tls_v6_all_messages_parse() {
     let result = parse_file("esinet1-v6-tls.dump.180");
     if result.total == 0 {
@@ -268,6 +270,7 @@ fn response_method_extraction() {
 }
 
 #[test]
+#[allow(clippy::unnecessary_sort_by)]
 fn tcp_multipart_bodies() {
     let result = parse_file("esinet1-v4-tcp.dump.20");
     if result.total == 0 {
@@ -0,0 +1,82 @@
+//! Method-only filtering can run on the raw message without allocating,
+//! where a full parse allocates for every header.
+
+use std::alloc::{GlobalAlloc, Layout, System};
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+use freeswitch_sofia_trace_parser::{Direction, SipMessage, Timestamp, Transport};
+
+struct CountingAlloc;
+
+static ALLOCATIONS: AtomicUsize = AtomicUsize::new(0);
+
+unsafe impl GlobalAlloc for CountingAlloc {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        ALLOCATIONS.fetch_add(1, Ordering::Relaxed);
+        unsafe { System.alloc(layout) }
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        unsafe { System.dealloc(ptr, layout) }
+    }
+}
+
+#[global_allocator]
+static GLOBAL: CountingAlloc = CountingAlloc;
+
+fn allocations_during(f: impl FnOnce()) -> usize {
+    let before = ALLOCATIONS.load(Ordering::Relaxed);
+    f();
+    ALLOCATIONS.load(Ordering::Relaxed) - before
+}
+
+#[test]
+fn method_filter_without_parse_does_not_allocate() {
+    let messages: Vec<SipMessage> = [
+        &b"OPTIONS sip:a@host SIP/2.0\r\nVia: SIP/2.0/UDP h;branch=z9hG4bK1\r\nCall-ID: o1\r\nCSeq: 1 OPTIONS\r\nContent-Length: 0\r\n\r\n"[..],
+        b"SIP/2.0 200 OK\r\nVia: SIP/2.0/UDP h;branch=z9hG4bK1\r\nCall-ID: o1\r\nCSeq: 1 OPTIONS\r\nContent-Length: 0\r\n\r\n",
+        b"INVITE sip:b@host SIP/2.0\r\nVia: SIP/2.0/UDP h;branch=z9hG4bK2\r\nCall-ID: i1\r\nCSeq: 1 INVITE\r\nContent-Length: 0\r\n\r\n",
+    ]
+    .iter()
+    .map(|content| SipMessage {
+        direction: Direction::Recv,
+        transport: Transport::Udp,
+        address: "10.0.0.1:5060".into(),
+        timestamp: Timestamp::TimeOnly {
+            hour: 0,
+            min: 0,
+            sec: 0,
+            usec: 0,
+        },
+        last_timestamp: Timestamp::TimeOnly {
+            hour: 0,
+            min: 0,
+            sec: 0,
+            usec: 0,
+        },
+        content: content.to_vec(),
+        frame_count: 1,
+    })
+    .collect();
+
+    let mut peeked = 0;
+    let peek = allocations_during(|| {
+        peeked = messages
+            .iter()
+            .filter(|m| m.peek_method() == Some("INVITE"))
+            .count();
+    });
+    let mut parsed = 0;
+    let parse = allocations_during(|| {
+        parsed = messages
+            .iter()
+            .filter(|m| m.parse().unwrap().method() == Some("INVITE"))
+            .count();
+    });
+
+    assert_eq!(peeked, 1);
+    assert_eq!(parsed, 1);
+    assert_eq!(peek, 0);
+    // At least one String per header name and value, per message
+    assert!(parse >= messages.len() * 10, "{parse}");
+}